@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek};
+use std::path::{Component, Path, PathBuf};
+
+use flate2::read::GzDecoder;
+
+use crate::filter::FileFilter;
+use crate::provider::{too_large, FileProvider, Listed};
+
+/// Whether a path names an archive which can be scanned like a directory
+pub fn is_archive<P: AsRef<Path>>(path: P) -> bool {
+    let name = path.as_ref().to_string_lossy().to_ascii_lowercase();
+    [".zip", ".tar", ".tar.gz", ".tgz"]
+        .iter()
+        .any(|extension| name.ends_with(extension))
+}
+
+/**
+The files of a zip archive or tarball, read into memory so they can be checked without
+extracting them.
+
+Files are keyed by their path in the archive. CI artifacts usually hold a single directory like
+`build/`, so when every file is in the same top-level directory it's stripped off, and the
+archive is scanned as that directory would be. Entries which would extract outside the
+archive, like `../outside.html`, are left out.
+ */
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Archive {
+    /// Where the archive was read from, or nothing for one built in memory
+    pub path: PathBuf,
+    pub files: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+/// Whether an archive entry's path stays inside the directory it's extracted to
+fn is_enclosed(path: &Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+impl Archive {
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Archive> {
+        let path = path.as_ref();
+        Archive::read(path, BufReader::new(File::open(path)?))
+    }
+    /**
+    Read an archive which is already in memory, like one dropped into a browser, as if it had
+    been opened from a file with this `name`.
+    */
+    pub fn from_bytes<P: AsRef<Path>>(name: P, contents: &[u8]) -> std::io::Result<Archive> {
+        Archive::read(name.as_ref(), Cursor::new(contents))
+    }
+    /// Read an archive of the kind its `name` says
+    fn read<R: Read + Seek>(name: &Path, reader: R) -> std::io::Result<Archive> {
+        let lowercase = name.to_string_lossy().to_ascii_lowercase();
+        let mut archive = if lowercase.ends_with(".zip") {
+            Archive::read_zip(reader)?
+        } else if lowercase.ends_with(".tar") {
+            Archive::read_tar(reader)?
+        } else {
+            Archive::read_tar(GzDecoder::new(reader))?
+        };
+        archive.strip_top_level();
+        archive.path = name.to_path_buf();
+        Ok(archive)
+    }
+    pub fn read_tar<R: Read>(reader: R) -> std::io::Result<Archive> {
+        let mut archive = Archive::default();
+        for entry in tar::Archive::new(reader).entries()? {
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let path = entry.path()?.components().collect::<PathBuf>();
+            if !is_enclosed(&path) {
+                continue;
+            }
+            let mut contents = vec![];
+            entry.read_to_end(&mut contents)?;
+            archive.files.insert(path, contents);
+        }
+        Ok(archive)
+    }
+    pub fn read_zip<R: Read + Seek>(reader: R) -> std::io::Result<Archive> {
+        let mut zip = zip::ZipArchive::new(reader).map_err(std::io::Error::other)?;
+        let mut archive = Archive::default();
+        for i in 0..zip.len() {
+            let mut entry = zip.by_index(i).map_err(std::io::Error::other)?;
+            let Some(path) = entry.enclosed_name().filter(|_| entry.is_file()) else {
+                continue;
+            };
+            let mut contents = vec![];
+            entry.read_to_end(&mut contents)?;
+            archive.files.insert(path, contents);
+        }
+        Ok(archive)
+    }
+    /// Strip the directory every file is in, if they're all in the same one
+    fn strip_top_level(&mut self) {
+        let top_level = |path: &Path| match path.components().next() {
+            Some(Component::Normal(top)) if path.components().nth(1).is_some() => {
+                Some(PathBuf::from(top))
+            }
+            _ => None,
+        };
+        let Some(first) = self.files.keys().next().and_then(|path| top_level(path)) else {
+            return;
+        };
+        if self
+            .files
+            .keys()
+            .all(|path| top_level(path).as_ref() == Some(&first))
+        {
+            self.files = std::mem::take(&mut self.files)
+                .into_iter()
+                .map(|(path, contents)| {
+                    (path.strip_prefix(&first).unwrap().to_path_buf(), contents)
+                })
+                .collect();
+        }
+    }
+}
+
+/// The files of an archive, listed in order of their paths
+impl FileProvider for Archive {
+    fn root(&self) -> &Path {
+        &self.path
+    }
+    fn list(&self, filter: &FileFilter, visit: &mut dyn FnMut(Listed) -> bool) {
+        for path in self.files.keys() {
+            if !filter.within_depth(path)
+                || !path
+                    .ancestors()
+                    .skip(1)
+                    .all(|directory| filter.walks(directory))
+            {
+                continue;
+            }
+            if !visit(Ok(path.clone())) {
+                return;
+            }
+        }
+    }
+    fn read(&self, path: &Path, max_size: Option<u64>) -> std::io::Result<Vec<u8>> {
+        let contents = self
+            .files
+            .get(path)
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotFound))?;
+        if let Some(max_size) = max_size.filter(|max_size| contents.len() as u64 > *max_size) {
+            return Err(too_large(max_size));
+        }
+        Ok(contents.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tarball(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(vec![]);
+        for (path, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, contents.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_is_archive() {
+        assert!(is_archive("site.zip"));
+        assert!(is_archive("build/docs.TAR.GZ"));
+        assert!(is_archive("docs.tgz"));
+        assert!(!is_archive("docs"));
+        assert!(!is_archive("docs.gz"));
+    }
+
+    #[test]
+    fn test_read_tar() {
+        let mut archive = Archive::read_tar(
+            tarball(&[
+                ("build/index.html", "<a href=guide/>"),
+                ("build/guide/index.html", ""),
+            ])
+            .as_slice(),
+        )
+        .unwrap();
+        archive.strip_top_level();
+        assert_eq!(
+            archive.files.keys().collect::<Vec<_>>(),
+            vec![Path::new("guide/index.html"), Path::new("index.html")]
+        );
+        assert_eq!(archive.files[Path::new("index.html")], b"<a href=guide/>");
+
+        let mut archive =
+            Archive::read_tar(tarball(&[("index.html", ""), ("guide/index.html", "")]).as_slice())
+                .unwrap();
+        archive.strip_top_level();
+        assert!(archive.files.contains_key(Path::new("guide/index.html")));
+    }
+
+    #[test]
+    fn test_read_zip() {
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+        let options = zip::write::SimpleFileOptions::default();
+        zip.add_directory("site/", options).unwrap();
+        zip.start_file("site/index.html", options).unwrap();
+        std::io::Write::write_all(&mut zip, b"<h1 id=top>").unwrap();
+        zip.start_file("../outside.html", options).unwrap();
+        let contents = zip.finish().unwrap().into_inner();
+        let archive = Archive::read_zip(std::io::Cursor::new(contents)).unwrap();
+        assert_eq!(
+            archive.files,
+            BTreeMap::from([(PathBuf::from("site/index.html"), b"<h1 id=top>".to_vec())])
+        );
+        assert_eq!(
+            archive
+                .read_to_string(Path::new("site/index.html"), None)
+                .unwrap(),
+            "<h1 id=top>"
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::tree::{BrokenLink, EscapingLink, ExternalLink, LoopbackLink};
+
+/**
+A single known-broken link, identified by the document it appears in and its `href`.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema)]
+pub struct BaselineEntry {
+    pub source: PathBuf,
+    pub href: String,
+}
+
+impl From<&BrokenLink> for BaselineEntry {
+    fn from(broken: &BrokenLink) -> BaselineEntry {
+        BaselineEntry {
+            source: broken.source.clone(),
+            href: broken.href.clone(),
+        }
+    }
+}
+
+impl From<&LoopbackLink> for BaselineEntry {
+    fn from(loopback: &LoopbackLink) -> BaselineEntry {
+        BaselineEntry {
+            source: loopback.source.clone(),
+            href: loopback.href.clone(),
+        }
+    }
+}
+
+impl From<&EscapingLink> for BaselineEntry {
+    fn from(escaping: &EscapingLink) -> BaselineEntry {
+        BaselineEntry {
+            source: escaping.source.clone(),
+            href: escaping.href.clone(),
+        }
+    }
+}
+
+impl From<&ExternalLink> for BaselineEntry {
+    fn from(external: &ExternalLink) -> BaselineEntry {
+        BaselineEntry {
+            source: external.source.clone(),
+            href: external.href.clone(),
+        }
+    }
+}
+
+/**
+A record of links which were already broken when the baseline was taken.
+
+Links in the baseline are not reported as failures, so that the tool can be adopted on a site
+with existing broken links and only new breakages fail the check.
+ */
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(transparent)]
+pub struct Baseline(BTreeSet<BaselineEntry>);
+
+impl Baseline {
+    /// Load a baseline file. A file that doesn't exist yet is treated as an empty baseline.
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Baseline> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Baseline::default());
+        }
+        let contents = std::fs::read_to_string(path)?;
+        serde_json::from_str(&contents)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let mut contents = serde_json::to_string_pretty(self)?;
+        contents.push('\n');
+        std::fs::write(path, contents)
+    }
+    pub fn contains<E: Into<BaselineEntry>>(&self, entry: E) -> bool {
+        self.0.contains(&entry.into())
+    }
+}
+
+impl<E: Into<BaselineEntry>> FromIterator<E> for Baseline {
+    fn from_iter<I: IntoIterator<Item = E>>(iter: I) -> Baseline {
+        Baseline(iter.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<E: Into<BaselineEntry>> Extend<E> for Baseline {
+    fn extend<I: IntoIterator<Item = E>>(&mut self, iter: I) {
+        self.0.extend(iter.into_iter().map(Into::into));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tree::HtmlFileLink;
+
+    fn broken(source: &str, href: &str) -> BrokenLink {
+        BrokenLink {
+            source: source.into(),
+            href: href.into(),
+            link: HtmlFileLink::new(href),
+            missing_fragment: false,
+            similar_ids: vec![],
+            attribute: None,
+            text: None,
+        }
+    }
+
+    #[test]
+    fn test_baseline_contains() {
+        let baseline: Baseline = [broken("foo", "bar"), broken("foo", "baz")]
+            .iter()
+            .collect();
+        assert!(baseline.contains(&broken("foo", "bar")));
+        assert!(baseline.contains(&broken("foo", "baz")));
+        assert!(!baseline.contains(&broken("bar", "bar")));
+        assert!(!baseline.contains(&broken("foo", "qux")));
+    }
+
+    #[test]
+    fn test_baseline_roundtrip() {
+        let baseline: Baseline = [broken("foo", "bar#baz")].iter().collect();
+        let json = serde_json::to_string(&baseline).unwrap();
+        assert_eq!(json, r##"[{"source":"foo","href":"bar#baz"}]"##);
+        assert_eq!(serde_json::from_str::<Baseline>(&json).unwrap(), baseline);
+    }
+}
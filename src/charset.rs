@@ -0,0 +1,82 @@
+use std::sync::LazyLock;
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use regex::bytes::Regex;
+
+/// How far into a document a `<meta charset>` is looked for, as browsers do
+const PRESCAN_LENGTH: usize = 1024;
+
+/// A `<meta charset="...">` or `<meta http-equiv="Content-Type" content="...; charset=...">`
+static META_CHARSET: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)<meta\s[^>]*charset\s*=\s*["']?\s*([a-z0-9_.:-]+)"#)
+        .expect("The charset regex is valid")
+});
+
+/// The encoding a document declares with a `<meta>` tag near its start, if it declares one
+fn meta_encoding(document: &[u8]) -> Option<&'static Encoding> {
+    let start = &document[..document.len().min(PRESCAN_LENGTH)];
+    let label = META_CHARSET.captures(start)?.get(1)?.as_bytes();
+    match Encoding::for_label(label)? {
+        // A declaration which could be read as ASCII can't be in UTF-16
+        encoding if encoding == UTF_16LE || encoding == UTF_16BE => Some(UTF_8),
+        encoding => Some(encoding),
+    }
+}
+
+/**
+Decode an HTML document in the encoding it's in: the one given by its byte order mark, or else
+by a `<meta charset>` near its start, or else UTF-8.
+
+Documents which aren't valid in their encoding fail with `ErrorKind::InvalidData`.
+*/
+pub fn decode_html(document: &[u8]) -> std::io::Result<String> {
+    let (encoding, bom_length) = Encoding::for_bom(document)
+        .or_else(|| meta_encoding(document).map(|encoding| (encoding, 0)))
+        .unwrap_or((UTF_8, 0));
+    encoding
+        .decode_without_bom_handling_and_without_replacement(&document[bom_length..])
+        .map(|decoded| decoded.into_owned())
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("not valid {}", encoding.name()),
+            )
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decode_html() {
+        assert_eq!(
+            decode_html("<p>café</p>".as_bytes()).unwrap(),
+            "<p>café</p>"
+        );
+        assert_eq!(
+            decode_html(b"<meta charset=\"iso-8859-1\"><p>caf\xe9</p>").unwrap(),
+            "<meta charset=\"iso-8859-1\"><p>café</p>"
+        );
+        assert_eq!(
+            decode_html(
+                b"<META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=windows-1252\">\x93"
+            )
+            .unwrap(),
+            "<META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=windows-1252\">\u{201c}"
+        );
+        let utf16: Vec<u8> = [0xff, 0xfe]
+            .into_iter()
+            .chain("<p id=\"é\">".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        assert_eq!(decode_html(&utf16).unwrap(), "<p id=\"é\">");
+        assert_eq!(
+            decode_html(b"\xef\xbb\xbf<p>").unwrap(),
+            "<p>",
+            "A UTF-8 byte order mark is dropped"
+        );
+        let err = decode_html(b"<p>caf\xe9</p>").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "not valid UTF-8");
+    }
+}
@@ -0,0 +1,414 @@
+use crate::filter::FileFilter;
+use crate::progress::Progress;
+use crate::provider::FileProvider;
+use crate::report::{Failure, Report};
+use crate::routes::VirtualRoutes;
+use crate::rules::{Rule, Rules, Severity};
+use crate::tree::{
+    missing_return_links, BrokenLink, EscapingLink, Hooks, HtmlFileLink, HtmlFiles, LoopbackLink,
+    NonPortableLink, Probing, Scan,
+};
+
+/**
+What a `CheckOptions::resolve` hook made of a broken link.
+*/
+pub enum Resolution {
+    /// It leads somewhere after all
+    Found,
+    /// It's broken, whatever the rest of the check would make of it
+    Broken,
+    /// It's checked further like any other broken link
+    Unresolved,
+}
+
+/// Looks at a broken link before it's checked further, as `CheckOptions::resolve` does
+pub type ResolveHook<'a> = Box<dyn FnMut(&mut BrokenLink) -> Resolution + 'a>;
+
+/// Looks for the target of a broken link outside the scan, as `CheckOptions::find_elsewhere` does
+pub type FindHook<'a> = Box<dyn FnMut(&BrokenLink) -> std::io::Result<bool> + 'a>;
+
+/**
+How a scan is checked, besides what can be told from its documents alone.
+
+The defaults check nothing but the documents, as `check_site` does. The binary fills in the
+rest from its arguments, like hooks which look for links on disk.
+*/
+#[derive(Default)]
+pub struct CheckOptions<'a> {
+    /// Whether links to redirect stubs are followed to where the stubs redirect
+    pub follow_redirects: bool,
+    /// Routes which the site's scripts serve, so links to them aren't broken
+    pub virtual_routes: VirtualRoutes,
+    /// Whether links to local servers are fine
+    pub allow_localhost: bool,
+    /// How many redirect stubs a link may go through before the chain is reported, 1 by default
+    pub max_redirect_hops: Option<usize>,
+    /// The `srcset` variants every image needs, like `2x`
+    pub srcset_variants: Vec<String>,
+    /// How links are resolved to the documents they lead to
+    pub probing: Probing,
+    /// Looks at each broken link first, and may change where it leads, like to a rendered
+    /// source file or to a document under the site root
+    pub resolve: Option<ResolveHook<'a>>,
+    /// Looks for the target of a broken link outside the scan, like on disk
+    pub find_elsewhere: Option<FindHook<'a>>,
+    /// Closures told about each document and link as they're checked, and which may leave
+    /// links out, for rules of the integrator's own
+    pub hooks: Hooks<'a>,
+}
+
+/**
+What a check found wrong. The broken, loopback and escaping links are kept apart from the rest,
+so that a baseline can be applied to them.
+*/
+#[derive(Debug, Default)]
+pub struct Findings {
+    pub broken_links: Vec<BrokenLink>,
+    pub loopback_links: Vec<LoopbackLink>,
+    pub escaping_links: Vec<EscapingLink>,
+    /// Everything else
+    pub failures: Vec<Failure>,
+}
+
+impl Findings {
+    pub fn into_failures(self) -> Vec<Failure> {
+        let Findings {
+            broken_links,
+            loopback_links,
+            escaping_links,
+            mut failures,
+        } = self;
+        failures.extend(broken_links.into_iter().map(Failure::from));
+        failures.extend(loopback_links.into_iter().map(Failure::from));
+        failures.extend(escaping_links.into_iter().map(Failure::from));
+        failures
+    }
+}
+
+/**
+Settle which of the links a scan couldn't find are really broken, and gather them with
+everything else wrong with the scan.
+
+The findings are taken out of the scan, leaving its documents, link counts, external links and
+source links to the caller. Links are looked for with `contains`, and `files`, if the documents
+were kept, tell which links only differ by case from a file and which ids a missing fragment
+may have meant.
+*/
+pub fn check_scan<F: Fn(&HtmlFileLink) -> bool>(
+    scan: &mut Scan,
+    files: Option<&HtmlFiles>,
+    contains: F,
+    rules: &Rules,
+    options: &mut CheckOptions,
+) -> std::io::Result<Findings> {
+    // Fragments which are off aren't looked for at all, even in documents outside the scan
+    let check_fragments = rules.severity(Rule::MissingFragment) != Severity::Off;
+    let check_portability = rules.severity(Rule::NonPortableLink) != Severity::Off;
+    let mut broken_links: Vec<BrokenLink> = vec![];
+    let mut non_portable_links = std::mem::take(&mut scan.non_portable_links);
+    for mut broken in std::mem::take(&mut scan.broken_links) {
+        if options.virtual_routes.contains(&broken.link) {
+            continue;
+        }
+        if let Some(resolve) = &mut options.resolve {
+            match resolve(&mut broken) {
+                Resolution::Found => continue,
+                Resolution::Broken => {
+                    broken_links.push(broken);
+                    continue;
+                }
+                Resolution::Unresolved => {}
+            }
+        }
+        if broken.missing_fragment && !check_fragments {
+            continue;
+        }
+        // Such links are found on case-insensitive filesystems, but break elsewhere
+        let case_match = files
+            .filter(|_| check_portability && !broken.missing_fragment)
+            .and_then(|files| files.case_insensitive_match(&broken.link.path));
+        if let Some(file) = case_match {
+            non_portable_links.push(NonPortableLink {
+                source: broken.source.clone(),
+                href: broken.href.clone(),
+                problem: format!("differs only by case from {}", file.display()),
+            });
+            continue;
+        }
+        // Links to the document itself are always scanned, as are documents found without the
+        // fragment, so there's nothing more to look for elsewhere
+        let found = match &mut options.find_elsewhere {
+            _ if broken.link.path == broken.source || broken.missing_fragment => false,
+            Some(find_elsewhere) => find_elsewhere(&broken)?,
+            None => false,
+        };
+        if broken.missing_fragment && options.virtual_routes.contains_hashbang(&broken.link) {
+            continue;
+        }
+        if !found {
+            if let (true, Some(files)) = (broken.missing_fragment, files) {
+                broken.similar_ids = files.similar_ids(&broken.link);
+            }
+            broken_links.push(broken);
+        }
+    }
+    let loopback_links = match options.allow_localhost {
+        true => vec![],
+        false => std::mem::take(&mut scan.loopback_links),
+    };
+    let mut failures: Vec<Failure> = vec![];
+    failures.extend(
+        std::mem::take(&mut scan.contact_links)
+            .into_iter()
+            .map(Failure::from),
+    );
+    failures.extend(
+        std::mem::take(&mut scan.placeholder_links)
+            .into_iter()
+            .map(Failure::from),
+    );
+    failures.extend(non_portable_links.into_iter().map(Failure::from));
+    failures.extend(
+        std::mem::take(&mut scan.duplicate_ids)
+            .into_iter()
+            .map(Failure::from),
+    );
+    failures.extend(
+        std::mem::take(&mut scan.broken_id_references)
+            .into_iter()
+            .map(Failure::from),
+    );
+    failures.extend(
+        std::mem::take(&mut scan.toc_omissions)
+            .into_iter()
+            .map(Failure::from),
+    );
+    failures.extend(
+        std::mem::take(&mut scan.duplicate_documents)
+            .into_iter()
+            .map(Failure::from),
+    );
+    let max_redirect_hops = options.max_redirect_hops.unwrap_or(1);
+    failures.extend(
+        std::mem::take(&mut scan.redirect_chains)
+            .into_iter()
+            .filter(|chain| chain.circular || chain.hops.len() > max_redirect_hops)
+            .map(Failure::from),
+    );
+    for srcset in std::mem::take(&mut scan.srcsets) {
+        let missing = srcset.missing_variants(&options.srcset_variants, |link| {
+            contains(&HtmlFileLink {
+                path: link.path.clone(),
+                fragment: None,
+                base: link.base,
+            })
+        });
+        failures.extend(missing.into_iter().map(Failure::from));
+    }
+    let alternates = std::mem::take(&mut scan.alternates);
+    let return_links = missing_return_links(alternates, &options.probing, &contains);
+    failures.extend(return_links.into_iter().map(Failure::from));
+    Ok(Findings {
+        broken_links,
+        loopback_links,
+        escaping_links: std::mem::take(&mut scan.escaping_links),
+        failures,
+    })
+}
+
+/**
+Check a site held in memory, like an archive dropped into a browser or handed over by a
+Node.js script, with nothing but the providers: no files on disk outside them, no network and
+no threads.
+
+The documents are scanned as a directory is by default, and everything which can be told from
+the site alone is reported, each failure as severe as the `rules` say. External links aren't
+requested, and links which lead out of the site are reported as broken rather than looked for
+on disk, unless the `options` have a hook to look for them.
+*/
+pub fn check_site<P: FileProvider>(
+    providers: &[P],
+    filter: &FileFilter,
+    rules: &Rules,
+    mut options: CheckOptions,
+) -> std::io::Result<Report> {
+    let progress = Progress::default();
+    let mut read_errors = vec![];
+    let errors = Some(&mut read_errors);
+    let mut files = HtmlFiles::new_with(providers, filter, 1, &progress, errors, &options.hooks)?;
+    files.set_probing(options.probing.clone());
+    let follow_redirects = options.follow_redirects;
+    let mut scan = files.scan_with(follow_redirects, 1, &progress, &options.hooks);
+    let contains = |link: &HtmlFileLink| match follow_redirects {
+        true => files.contains_following_redirects(link),
+        false => files.contains(link),
+    };
+    let findings = check_scan(&mut scan, Some(&files), contains, rules, &mut options)?;
+    let mut failures = findings.into_failures();
+    failures.extend(read_errors.into_iter().map(Failure::from));
+    Ok(Report {
+        checked_links: Some(scan.links.internal),
+        ..Report::with_rules(failures, rules)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use super::*;
+    use crate::archive::Archive;
+    use crate::html::HtmlInfo;
+
+    fn zip(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+        let options = zip::write::SimpleFileOptions::default();
+        for (path, contents) in files {
+            zip.start_file(*path, options).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_check_site() {
+        let contents = zip(&[
+            (
+                "site/index.html",
+                "<a href=guide/#install>Install</a> <a href=missing.html>Gone</a> \
+                 <a href=http://localhost:8000/>Dev</a> <a href=logo.svg>Logo</a>",
+            ),
+            ("site/guide/index.html", "<h2 id=instal>Install</h2>"),
+            ("site/logo.svg", "<svg></svg>"),
+        ]);
+        let archive = Archive::from_bytes("site.zip", &contents).unwrap();
+        let report = check_site(
+            &[archive],
+            &FileFilter::default(),
+            &Rules::default(),
+            CheckOptions::default(),
+        )
+        .unwrap();
+        let failures: Vec<(&str, &str, Option<&str>)> = (report.failures.iter())
+            .map(|failure| {
+                let source = failure.source().to_str().unwrap();
+                (failure.kind(), source, failure.href())
+            })
+            .collect();
+        assert_eq!(
+            failures,
+            vec![
+                ("Missing fragment", "index.html", Some("guide/#install")),
+                (
+                    "Local server link",
+                    "index.html",
+                    Some("http://localhost:8000/")
+                ),
+                ("Broken link", "index.html", Some("missing.html")),
+            ]
+        );
+        assert!(matches!(
+            &report.failures[0],
+            Failure::MissingFragment { similar_ids, .. } if similar_ids == &["instal"]
+        ));
+        assert_eq!(report.checked_links, Some(3));
+
+        // The kind of archive is told by its name
+        assert!(Archive::from_bytes("site.tar.gz", &contents).is_err());
+    }
+
+    #[test]
+    fn test_check_options() {
+        let contents = zip(&[(
+            "index.html",
+            "<a href=readme.md>Source</a> <a href=api/index.html>API</a> \
+             <a href=gone.html>Gone</a> <a href=http://localhost:8000/>Dev</a>",
+        )]);
+        let archive = Archive::from_bytes("site.zip", &contents).unwrap();
+        let mut looked_for = vec![];
+        let options = CheckOptions {
+            allow_localhost: true,
+            resolve: Some(Box::new(|broken: &mut BrokenLink| {
+                match broken.link.path.extension().is_some_and(|ext| ext == "md") {
+                    true => Resolution::Found,
+                    false => Resolution::Unresolved,
+                }
+            })),
+            find_elsewhere: Some(Box::new(|broken: &BrokenLink| {
+                looked_for.push(broken.href.clone());
+                Ok(broken.href.starts_with("api/"))
+            })),
+            ..CheckOptions::default()
+        };
+        let report = check_site(
+            &[archive],
+            &FileFilter::default(),
+            &Rules::default(),
+            options,
+        )
+        .unwrap();
+        let hrefs: Vec<Option<&str>> = report.failures.iter().map(Failure::href).collect();
+        assert_eq!(hrefs, vec![Some("gone.html")]);
+        assert_eq!(looked_for, vec!["api/index.html", "gone.html"]);
+    }
+
+    #[test]
+    fn test_check_hooks() {
+        let contents = zip(&[
+            (
+                "index.html",
+                "<a href=logo.png>Logo</a> <a href=assets/icon.png>Icon</a> \
+                 <a href=drafts/next.html>Next</a> <a href=gone.html>Gone</a> \
+                 <a href=../drafts/up.html>Up</a> <a href=http://localhost:8000/drafts/>Dev</a> \
+                 <link rel=alternate hreflang=fr href=drafts/fr.html>",
+            ),
+            ("logo.png", ""),
+            ("assets/icon.png", ""),
+        ]);
+        let archive = Archive::from_bytes("site.zip", &contents).unwrap();
+        // A rule of the integrator's own, that every linked image lives under /assets
+        let misplaced = std::sync::Mutex::new(vec![]);
+        let checked = std::sync::Mutex::new(vec![]);
+        let options = CheckOptions {
+            hooks: Hooks {
+                on_file_parsed: Some(Box::new(|path: &std::path::Path, info: &HtmlInfo| {
+                    let images = (info.relative_hrefs.iter())
+                        .filter(|href| href.ends_with(".png") && !href.starts_with("assets/"))
+                        .map(|href| format!("{}: {}", path.display(), &**href));
+                    misplaced.lock().unwrap().extend(images);
+                })),
+                on_link_checked: Some(Box::new(|_: &std::path::Path, href: &str, found| {
+                    checked.lock().unwrap().push((href.to_string(), found));
+                })),
+                link_filter: Some(Box::new(|_: &std::path::Path, href: &str| {
+                    !href.contains("drafts/")
+                })),
+            },
+            ..CheckOptions::default()
+        };
+        let report = check_site(
+            &[archive],
+            &FileFilter::default(),
+            &Rules::default(),
+            options,
+        )
+        .unwrap();
+        // Links the filter leaves out aren't reported by any rule
+        let hrefs: Vec<Option<&str>> = report.failures.iter().map(Failure::href).collect();
+        assert_eq!(hrefs, vec![Some("gone.html")]);
+        assert_eq!(
+            misplaced.into_inner().unwrap(),
+            vec!["index.html: logo.png"]
+        );
+        let mut checked = checked.into_inner().unwrap();
+        checked.sort();
+        assert_eq!(
+            checked,
+            vec![
+                ("assets/icon.png".to_string(), true),
+                ("gone.html".to_string(), false),
+                ("logo.png".to_string(), true),
+            ]
+        );
+    }
+}
@@ -0,0 +1,638 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::external::{Nofollow, StatusPolicy};
+use crate::rewrite::RewriteConfig;
+use crate::routes::RouteConfig;
+use crate::rules::{FailOn, Rule, Severity};
+use crate::tree::{FragmentMatch, TrailingSlash};
+
+/// Where the config file is looked for when `--config` isn't given
+pub const DEFAULT_CONFIG_PATH: &str = "relative-link-check.toml";
+
+/// Every key a config file may contain
+const KEYS: &[&str] = &[
+    "directories",
+    "base",
+    "site-root",
+    "baseline",
+    "allow-localhost",
+    "base-url",
+    "deny-url-prefixes",
+    "allow-url-prefixes",
+    "check-external",
+    "net-jobs",
+    "host-delay",
+    "respect-robots",
+    "headers",
+    "auth",
+    "proxy",
+    "status-codes",
+    "check-external-fragments",
+    "offline",
+    "external-only-domains",
+    "external-exclude-domains",
+    "external-cache",
+    "external-cache-ttl",
+    "nofollow",
+    "check-fragments",
+    "check-duplicate-ids",
+    "check-id-references",
+    "check-toc",
+    "check-portability",
+    "check-placeholder-links",
+    "check-duplicate-content",
+    "check-redirect-chains",
+    "max-redirect-hops",
+    "check-hreflang",
+    "check-source-links",
+    "map-source-links",
+    "srcset-variants",
+    "check-sitemap",
+    "check-feeds",
+    "data-dirs",
+    "data-keys",
+    "url-manifest",
+    "index-linked",
+    "follow-redirects",
+    "trailing-slash",
+    "default-documents",
+    "pretty-urls",
+    "fragment-match",
+    "extensions",
+    "include",
+    "exclude",
+    "extract",
+    "check-templates",
+    "check-styles",
+    "ignore-attribute",
+    "follow-symlinks",
+    "no-ignore",
+    "fs-jobs",
+    "max-file-size",
+    "max-depth",
+    "strict-io",
+    "low-memory",
+    "fail-on",
+    "source-tree",
+    "mount",
+    "map",
+    "rewrite",
+    "virtual-routes",
+    "rules",
+];
+
+/// The file written by `config init`, documenting every key
+pub const TEMPLATE: &str = r#"# Configuration for relative-link-check.
+# Options given on the command line take precedence over the ones in this file.
+# Paths are relative to the directory containing this file.
+
+# Directories of HTML documents to check (default: the current directory)
+# directories = ["public"]
+
+# Directory that links outside the checked directories are resolved against
+# base = "."
+
+# Directory that root-relative links like /assets/logo.png are resolved against, when it isn't
+# the checked directory
+# site-root = "build"
+
+# JSON file of known broken links which should not fail the check
+# baseline = "link-baseline.json"
+
+# Don't flag links to local servers like http://localhost:8000/
+# allow-localhost = false
+
+# The site's production URL. When it's served over https, external links to it over http are
+# reported
+# base-url = "https://example.com/"
+
+# Report external links starting with these prefixes, like the URLs of staging or preview
+# deployments
+# deny-url-prefixes = ["https://staging.example.com/"]
+
+# Never report external links starting with these prefixes for leading to the wrong environment
+# allow-url-prefixes = []
+
+# Never use the network, and refuse settings which need it, like check-external
+# offline = false
+
+# Request every http and https link to other sites, and report those which don't answer with a
+# success
+# check-external = false
+
+# How many hosts to request external links from at once, alongside the --fs-jobs threads
+# net-jobs = 8
+
+# Milliseconds to wait between requests to the same host
+# host-delay = 100
+
+# Don't request external links which the site's robots.txt disallows, and wait as long between
+# requests as its Crawl-delay asks
+# respect-robots = false
+
+# Headers to send with every external request, like a cookie or API key a site wants before it
+# answers with anything but 403
+# headers = ["Cookie: cf_clearance=..."]
+
+# Make external requests through a proxy, or through the one the ALL_PROXY, HTTPS_PROXY or
+# HTTP_PROXY environment variable names with "env"
+# proxy = "http://proxy.example.com:8080"
+
+# Only request external links to hosts matching these globs, or their subdomains
+# external-only-domains = ["example.com", "*.example.org"]
+
+# Don't request external links to hosts matching these globs, or their subdomains
+# external-exclude-domains = ["twitter.com", "linkedin.com"]
+
+# Also fetch the pages external links with a #fragment lead to, and report those whose fragment
+# isn't an id in the page
+# check-external-fragments = false
+
+# Keep what came of requesting external links in this file, and only request those which haven't
+# worked within external-cache-ttl again, like 12h or 7d
+# external-cache = ".rlc-cache.json"
+# external-cache-ttl = "1d"
+
+# Whether to "check" external links marked rel="nofollow", only "warn" when they're broken, or
+# "skip" them
+# nofollow = "check"
+
+# Whether links to missing #fragments are an "error", a "warn"ing, or "off" and not checked at
+# all, for sites whose ids are made by scripts. The linked files must still exist
+# check-fragments = "error"
+
+# Report documents which contain the same id more than once
+# check-duplicate-ids = false
+
+# Report <label for>, aria-labelledby, aria-describedby and headers attributes referring to ids
+# which aren't in the same document
+# check-id-references = false
+
+# Report h2 and h3 headings left out of a page's <nav class="toc"> table of contents
+# check-toc = false
+
+# Report links which may not lead to the same file on every common filesystem: those with
+# backslashes, names Windows doesn't allow, or names differing only by case from a file
+# check-portability = false
+
+# Report placeholder links left in while writing: empty hrefs, hrefs of a lone # and
+# javascript:void(0). Each is its own rule, empty-link, hash-link and javascript-link, so it can
+# be turned off under [rules]
+# check-placeholder-links = false
+
+# Report documents which are byte-identical copies of another document. JSON reports also
+# gather them into sets of identical documents, under "duplicates"
+# check-duplicate-content = false
+
+# Report <meta http-equiv="refresh"> redirect stubs which lead through more than
+# max-redirect-hops stubs before reaching a page, or around in a loop
+# check-redirect-chains = false
+# max-redirect-hops = 1
+
+# Report <link rel="alternate" hreflang> translations which don't link back to the page linking
+# to them
+# check-hreflang = false
+
+# Report links to Markdown, reStructuredText and AsciiDoc sources, like guide.md, which should
+# lead to the page rendered from them
+# check-source-links = false
+
+# Check links to sources as links to the pages rendered from them, as mapped by map, for hosts
+# which rewrite them. Only those whose page doesn't exist are reported
+# map-source-links = false
+
+# Report image srcsets which lack any of these variants, or whose image for one doesn't exist
+# srcset-variants = ["1x", "2x"]
+
+# Check that every URL in the sitemap.xml of each checked directory leads to a file. Set the
+# missing-from-sitemap rule to also report documents the sitemap leaves out
+# check-sitemap = false
+
+# Check that the internal links of every RSS and Atom feed, the .xml files whose items link to
+# pages or enclosures of the site, lead to a file
+# check-feeds = false
+
+# Also check the links in the .json, .yaml and .yml files of these directories, like the data
+# directory of a Hugo, Jekyll or Eleventy site, against the scanned site
+# data-dirs = ["_data"]
+
+# The keys whose values in data files are links, at any depth
+# data-keys = ["url", "href", "link"]
+
+# Check that every URL in this file, a list of the URLs the site has ever published with one
+# per line, still leads to a file. A redirect is suggested for each URL whose page seems to
+# have moved
+# url-manifest = "published-urls.txt"
+
+# Parse documents outside the checked directories (but within base) when they are linked to,
+# so that their #fragments are checked too
+# index-linked = false
+
+# Check the #fragments of links to <meta http-equiv="refresh"> redirect stubs in the document
+# they redirect to
+# follow-redirects = false
+
+# Whether a link to "guide" also finds "guide/index.html" ("lenient"), only links to "guide/" do
+# ("strict"), or links to directories need the trailing slash and links to files mustn't have
+# one ("require")
+# trailing-slash = "lenient"
+
+# What a link to a directory leads to, looked for in this order: names like "index.html" inside
+# it, or extensions like ".html" appended to the link so "about" leads to "about.html"
+# default-documents = ["index.html"]
+
+# Also let a link to "about" lead to "about.html", like GitHub Pages and many hosts do
+# pretty-urls = false
+
+# How a link's #fragment is compared with ids: "exact"ly, with "ignore-case", or as a "slug" so
+# #getting-started finds id="Getting Started"
+# fragment-match = "exact"
+
+# Extensions of the files to parse as HTML documents
+# extensions = ["html", "htm"]
+
+# Only parse files matching one of these globs, relative to the checked directories
+# include = ["**/*"]
+
+# Don't parse files or walk directories matching these globs. Always applied, even when more
+# are given on the command line
+# exclude = ["**/node_modules", "**/target"]
+
+# Also check the value of an attribute on every element matching a selector like a link, given
+# as SELECTOR@ATTRIBUTE
+# extract = ["div[data-href]@data-href", "iframe[src]@src"]
+
+# Also check the links and ids inside <template> and <noscript> elements, for sites which stamp
+# out templates with scripts
+# check-templates = false
+
+# Also check the url(...) references in style attributes and <style> elements, like background
+# images
+# check-styles = false
+
+# The attribute marking elements whose links, and those of everything inside them, are
+# placeholders or broken on purpose, so they aren't checked
+# ignore-attribute = "data-rlc-ignore"
+
+# Walk into symlinked directories. Symlinks back up the tree are skipped
+# follow-symlinks = false
+
+# Also walk files and directories matched by .gitignore and .ignore files in the checked
+# directories, which are skipped by default
+# no-ignore = false
+
+# How many documents to parse, and check the links of, at once (default: one per CPU)
+# fs-jobs = 8
+
+# Skip files larger than this many bytes with a warning, instead of reading them
+# max-file-size = 104857600
+
+# Only walk this many directories deep into the scanned directories, 0 for just the documents
+# directly in them
+# max-depth = 2
+
+# Abort on the first file or directory which can't be read, instead of reporting it and
+# carrying on
+# strict-io = false
+
+# Keep only the valid link targets in memory and parse every document twice, for very large
+# sites. Can't be combined with source-tree
+# low-memory = false
+
+# Exit with an error when there are failures ("error"), when there are failures or warnings
+# ("warning"), or never ("none")
+# fail-on = "error"
+
+# Markdown source tree the checked HTML was generated from. When given, also check that every
+# source link made it into the output and vice versa
+# source-tree = "docs"
+
+# The URL path each checked directory is deployed at, so links between directories deployed
+# side by side resolve as they do on the site. Root-relative links are then resolved against
+# the mounted directories
+# [mount]
+# "build/docs" = "/docs"
+
+# Environment variables holding the credentials to send with external requests to each host and
+# its subdomains: as they are if they name their scheme, like "Basic dXNlcjpwYXNz", or else as
+# a bearer token
+# [auth]
+# "docs.example.com" = "DOCS_TOKEN"
+
+# Status codes external links may answer with which count as a success, only as a warning, or as
+# broken, for every host and for each host and its subdomains. Redirects are followed and
+# succeed, and other codes succeed below 400, unless listed. A link is as bad as the worst status
+# on the way to it
+# [status-codes]
+# ok = [429]
+# warn = [301]
+# error = [204]
+# [status-codes.hosts."twitter.com"]
+# ok = [403]
+
+# How the generator maps source extensions to output extensions
+# [map]
+# md = "html"
+
+# Rules rewriting every href before it's resolved, for sites whose URLs are changed when they're
+# deployed. Each replaces the first match of its regular expression, in turn, and failures show
+# the rewritten href
+# [[rewrite]]
+# pattern = "^/v2/"
+# replacement = "/"
+
+# Links which a client-side router handles rather than leading to files, so they're never
+# broken. Routes are paths from the site root, matched exactly, by prefix or by a regular
+# expression, and also match #!/route fragments
+# [virtual-routes]
+# paths = ["/app/settings"]
+# prefixes = ["/app/"]
+# patterns = ["^/users/[0-9]+$"]
+
+# Whether each rule is an error, a warning which doesn't fail the check, or off. Every rule is
+# an error by default, except duplicate-id, missing-from-toc and duplicate-content. Rules given
+# with --rule take precedence
+# [rules]
+# missing-fragment = "warn"
+"#;
+
+/**
+Settings read from a config file, with the same meaning as the command line options.
+*/
+#[derive(Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Config {
+    #[serde(default)]
+    pub directories: Vec<PathBuf>,
+    pub base: Option<PathBuf>,
+    pub site_root: Option<PathBuf>,
+    pub baseline: Option<PathBuf>,
+    #[serde(default)]
+    pub allow_localhost: bool,
+    pub base_url: Option<String>,
+    #[serde(default)]
+    pub deny_url_prefixes: Vec<String>,
+    #[serde(default)]
+    pub allow_url_prefixes: Vec<String>,
+    #[serde(default)]
+    pub check_external: bool,
+    #[serde(alias = "max-concurrency")]
+    pub net_jobs: Option<usize>,
+    pub host_delay: Option<u64>,
+    #[serde(default)]
+    pub respect_robots: bool,
+    #[serde(default)]
+    pub headers: Vec<String>,
+    #[serde(default)]
+    pub auth: BTreeMap<String, String>,
+    pub proxy: Option<String>,
+    #[serde(default)]
+    pub status_codes: StatusPolicy,
+    #[serde(default)]
+    pub check_external_fragments: bool,
+    #[serde(default)]
+    pub offline: bool,
+    #[serde(default)]
+    pub external_only_domains: Vec<String>,
+    #[serde(default)]
+    pub external_exclude_domains: Vec<String>,
+    pub external_cache: Option<PathBuf>,
+    pub external_cache_ttl: Option<String>,
+    pub nofollow: Option<Nofollow>,
+    pub check_fragments: Option<Severity>,
+    #[serde(default)]
+    pub check_duplicate_ids: bool,
+    #[serde(default)]
+    pub check_id_references: bool,
+    #[serde(default)]
+    pub check_toc: bool,
+    #[serde(default)]
+    pub check_portability: bool,
+    #[serde(default)]
+    pub check_placeholder_links: bool,
+    #[serde(default)]
+    pub check_duplicate_content: bool,
+    #[serde(default)]
+    pub check_redirect_chains: bool,
+    pub max_redirect_hops: Option<usize>,
+    #[serde(default)]
+    pub check_hreflang: bool,
+    #[serde(default)]
+    pub check_source_links: bool,
+    #[serde(default)]
+    pub map_source_links: bool,
+    #[serde(default)]
+    pub srcset_variants: Vec<String>,
+    #[serde(default)]
+    pub check_sitemap: bool,
+    #[serde(default)]
+    pub check_feeds: bool,
+    #[serde(default)]
+    pub data_dirs: Vec<PathBuf>,
+    #[serde(default)]
+    pub data_keys: Vec<String>,
+    pub url_manifest: Option<PathBuf>,
+    #[serde(default)]
+    pub index_linked: bool,
+    #[serde(default)]
+    pub follow_redirects: bool,
+    pub trailing_slash: Option<TrailingSlash>,
+    #[serde(default)]
+    pub default_documents: Vec<String>,
+    #[serde(default)]
+    pub pretty_urls: bool,
+    pub fragment_match: Option<FragmentMatch>,
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default)]
+    pub extract: Vec<String>,
+    #[serde(default)]
+    pub check_templates: bool,
+    #[serde(default)]
+    pub check_styles: bool,
+    pub ignore_attribute: Option<String>,
+    #[serde(default)]
+    pub follow_symlinks: bool,
+    #[serde(default)]
+    pub no_ignore: bool,
+    pub fs_jobs: Option<usize>,
+    pub max_file_size: Option<u64>,
+    pub max_depth: Option<usize>,
+    #[serde(default)]
+    pub strict_io: bool,
+    #[serde(default)]
+    pub low_memory: bool,
+    pub fail_on: Option<FailOn>,
+    pub source_tree: Option<PathBuf>,
+    #[serde(default)]
+    pub mount: BTreeMap<PathBuf, String>,
+    #[serde(default)]
+    pub map: BTreeMap<String, String>,
+    #[serde(default)]
+    pub rewrite: Vec<RewriteConfig>,
+    #[serde(default)]
+    pub virtual_routes: RouteConfig,
+    #[serde(default)]
+    pub rules: BTreeMap<Rule, Severity>,
+    /// The keys given under their old names, with their new ones, to warn about
+    #[serde(skip)]
+    pub renamed_keys: Vec<(&'static str, &'static str)>,
+}
+
+/// Keys which were renamed, with their new names. They're still read under their old ones
+const RENAMED_KEYS: &[(&str, &str)] = &[("max-concurrency", "net-jobs")];
+
+/// The known key most similar to `key`, if any is similar enough to be a likely typo
+fn suggest(key: &str) -> Option<&'static str> {
+    KEYS.iter()
+        .map(|candidate| (strsim::jaro(key, candidate), *candidate))
+        .filter(|(similarity, _)| *similarity > 0.7)
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, candidate)| candidate)
+}
+
+impl Config {
+    /**
+    Parse a config file, rejecting any keys which aren't part of the schema.
+    */
+    pub fn parse(contents: &str) -> Result<Config, String> {
+        let table: toml::Table = toml::from_str(contents).map_err(|err| err.to_string())?;
+        let errors: Vec<String> = table
+            .keys()
+            .filter(|key| {
+                !KEYS.contains(&key.as_str()) && !RENAMED_KEYS.iter().any(|(old, _)| old == key)
+            })
+            .map(|key| match suggest(key) {
+                Some(suggestion) => format!("unknown key `{key}`, did you mean `{suggestion}`?"),
+                None => format!("unknown key `{key}`, expected one of {}", KEYS.join(", ")),
+            })
+            .collect();
+        if !errors.is_empty() {
+            return Err(errors.join("\n"));
+        }
+        let mut config: Config = toml::from_str(contents).map_err(|err| err.to_string())?;
+        config.renamed_keys = (RENAMED_KEYS.iter().copied())
+            .filter(|(old, _)| table.contains_key(*old))
+            .collect();
+        Ok(config)
+    }
+    /// Read and parse a config file, resolving the paths in it against its directory
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, String> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let mut config = Config::parse(&contents)?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for directory in config
+            .directories
+            .iter_mut()
+            .chain(config.data_dirs.iter_mut())
+        {
+            *directory = dir.join(&directory);
+        }
+        config.mount = std::mem::take(&mut config.mount)
+            .into_iter()
+            .map(|(directory, prefix)| (dir.join(directory), prefix))
+            .collect();
+        for path in [
+            &mut config.base,
+            &mut config.site_root,
+            &mut config.baseline,
+            &mut config.url_manifest,
+            &mut config.external_cache,
+            &mut config.source_tree,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *path = dir.join(&path);
+        }
+        Ok(config)
+    }
+    /// Write the commented template to `path`
+    pub fn init<P: AsRef<Path>>(path: P) -> std::io::Result<()> {
+        std::fs::write(path, TEMPLATE)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse(
+            r#"
+directories = ["public"]
+allow-localhost = true
+source-tree = "docs"
+
+[map]
+rst = "html"
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            Config {
+                directories: vec!["public".into()],
+                allow_localhost: true,
+                source_tree: Some("docs".into()),
+                map: BTreeMap::from([("rst".into(), "html".into())]),
+                ..Config::default()
+            }
+        );
+        let renamed = Config::parse("max-concurrency = 4").unwrap();
+        assert_eq!(renamed.net_jobs, Some(4));
+        assert_eq!(renamed.renamed_keys, vec![("max-concurrency", "net-jobs")]);
+        assert!(Config::parse("allow-localhost = \"yes\"")
+            .unwrap_err()
+            .contains("invalid type"));
+    }
+
+    #[test]
+    fn test_parse_unknown_keys() {
+        assert_eq!(
+            Config::parse("alow-localhost = true").unwrap_err(),
+            "unknown key `alow-localhost`, did you mean `allow-localhost`?"
+        );
+        assert_eq!(
+            Config::parse("sourcetree = \"docs\"").unwrap_err(),
+            "unknown key `sourcetree`, did you mean `source-tree`?"
+        );
+        assert!(Config::parse("xyzzy = 1")
+            .unwrap_err()
+            .starts_with("unknown key `xyzzy`, expected one of directories, base"));
+    }
+
+    #[test]
+    fn test_template() {
+        assert_eq!(Config::parse(TEMPLATE), Ok(Config::default()));
+        let uncommented: String = TEMPLATE
+            .lines()
+            .filter_map(|line| line.strip_prefix("# "))
+            .filter(|line| line.contains(" = ") || line.starts_with('['))
+            .map(|line| format!("{line}\n"))
+            .collect();
+        let config = Config::parse(&uncommented).unwrap();
+        assert_eq!(config.directories, vec![PathBuf::from("public")]);
+        assert_eq!(config.map.get("md").map(String::as_str), Some("html"));
+        assert_eq!(config.rewrite[0].pattern, "^/v2/");
+        assert_eq!(
+            config.rules.get(&Rule::MissingFragment),
+            Some(&Severity::Warn)
+        );
+        for key in KEYS {
+            assert!(
+                uncommented.contains(&format!("{key} "))
+                    || uncommented.contains(&format!("[{key}]"))
+            );
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use crate::filter::FileFilter;
+use crate::intern::Interned;
+use crate::markdown::MarkdownInfo;
+use crate::provider::FileProvider;
+use crate::tree::{resolve_document_link, resolved_links, HtmlFileLink, HtmlFiles, ReadError};
+
+/**
+All the Markdown files in a source tree, keyed by their path relative to the source directory.
+
+Files and directories which can't be read are recorded in `errors` and skipped. Without
+`errors`, the first one aborts the walk instead.
+*/
+#[derive(Debug)]
+pub struct SourceFiles(pub HashMap<PathBuf, MarkdownInfo>);
+impl SourceFiles {
+    pub fn new<P: FileProvider>(
+        provider: &P,
+        mut errors: Option<&mut Vec<ReadError>>,
+    ) -> std::io::Result<SourceFiles> {
+        // Every source is compared with its output, even if it's ignored
+        let mut filter = FileFilter::new(&["md".to_string()], &[], &[])
+            .expect("There are no globs to be invalid");
+        filter.ignore_files = false;
+        let mut map = HashMap::new();
+        let mut failed = Ok(());
+        provider.list(&filter, &mut |file| {
+            let parsed = match file {
+                Ok(path) if !filter.has_extension(&path) => return true,
+                Ok(path) => match provider.read_to_string(&path, None) {
+                    Ok(contents) => Ok((path, MarkdownInfo::parse(&contents))),
+                    Err(err) => Err((path, err)),
+                },
+                Err(err) => Err(err),
+            };
+            match (parsed, errors.as_deref_mut()) {
+                (Ok((path, info)), _) => {
+                    map.insert(path, info);
+                }
+                (Err((path, err)), Some(errors)) => errors.push(ReadError::new(path, &err)),
+                (Err((_, err)), None) => failed = Err(err),
+            }
+            failed.is_ok()
+        });
+        failed?;
+        Ok(SourceFiles(map))
+    }
+}
+
+/**
+The rules a site generator uses to turn source paths into output paths.
+
+Each rule maps a source extension to an output extension, like `md=html`.
+Source files named `README` or `index` become the `index` document of their directory.
+*/
+#[derive(Debug, Clone)]
+pub struct PathMapping(Vec<(String, String)>);
+
+impl Default for PathMapping {
+    fn default() -> PathMapping {
+        PathMapping(vec![("md".to_string(), "html".to_string())])
+    }
+}
+
+impl PathMapping {
+    pub fn new(rules: Vec<(String, String)>) -> PathMapping {
+        if rules.is_empty() {
+            PathMapping::default()
+        } else {
+            PathMapping(rules)
+        }
+    }
+    /// Parse a `from=to` extension mapping rule
+    pub fn parse_rule(rule: &str) -> Result<(String, String), String> {
+        match rule.split_once('=') {
+            Some((from, to)) if !from.is_empty() && !to.is_empty() => {
+                Ok((from.to_string(), to.to_string()))
+            }
+            _ => Err(format!("expected a rule like md=html, got {rule:?}")),
+        }
+    }
+    pub fn map_path<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(OsStr::to_str);
+        let Some((_, to)) = self
+            .0
+            .iter()
+            .find(|(from, _)| Some(from.as_str()) == extension)
+        else {
+            return path.to_path_buf();
+        };
+        let stem = path.file_stem().and_then(OsStr::to_str).unwrap_or("");
+        if stem.eq_ignore_ascii_case("readme") || stem == "index" {
+            path.with_file_name(format!("index.{to}"))
+        } else {
+            path.with_extension(to)
+        }
+    }
+    /// Map the path of a resolved link, keeping any `#fragment`
+    pub fn rendered_link(&self, link: &HtmlFileLink) -> HtmlFileLink {
+        HtmlFileLink {
+            path: self.map_path(&link.path),
+            fragment: link.fragment.clone(),
+            base: link.base,
+        }
+    }
+    /// Map the path of a relative `href`, keeping any `#fragment`
+    pub fn map_href(&self, href: &str) -> String {
+        let (path, fragment) = match href.split_once('#') {
+            Some((path, fragment)) => (path, Some(fragment)),
+            None => (href, None),
+        };
+        if path.is_empty() {
+            return href.to_string();
+        }
+        let mapped = self.map_path(path).to_string_lossy().to_string();
+        match fragment {
+            Some(fragment) => format!("{mapped}#{fragment}"),
+            None => mapped,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Inconsistency {
+    /// The source file has no corresponding output document
+    NoOutput,
+    /// A link in the source file (given as written) doesn't appear in the output document
+    DroppedLink(String),
+    /// A link in the output document (given as written) doesn't appear in the source file
+    ExtraLink(String),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ConsistencyIssue {
+    pub source: PathBuf,
+    pub output: PathBuf,
+    pub inconsistency: Inconsistency,
+}
+
+/// `dir/index.html` and `dir/` are the same document, so compare them the same way
+fn canonical(mut link: HtmlFileLink) -> HtmlFileLink {
+    if link.path.file_name() == Some(OsStr::new("index.html")) {
+        link.path.pop();
+    }
+    link
+}
+
+/**
+Check that every link in the source tree made it into the output tree, and vice versa.
+*/
+pub fn check_consistency(
+    sources: &SourceFiles,
+    outputs: &HtmlFiles,
+    mapping: &PathMapping,
+) -> Vec<ConsistencyIssue> {
+    let mut issues = vec![];
+    for (source, markdown) in sources.0.iter() {
+        let output = mapping.map_path(source);
+        let Some(html) = outputs.get(&output) else {
+            issues.push(ConsistencyIssue {
+                source: source.clone(),
+                output,
+                inconsistency: Inconsistency::NoOutput,
+            });
+            continue;
+        };
+        let base = output.parent().expect("No parent");
+        let source_links: Vec<(&String, HtmlFileLink)> = markdown
+            .relative_hrefs
+            .iter()
+            .map(|href| {
+                let link = resolve_document_link(&output, base, &mapping.map_href(href));
+                (href, canonical(link))
+            })
+            .collect();
+        let output_links: Vec<(&Interned, HtmlFileLink)> = resolved_links(&output, html)
+            .map(|(href, link)| (href, canonical(link)))
+            .collect();
+
+        let output_set: HashSet<&HtmlFileLink> = output_links.iter().map(|(_, l)| l).collect();
+        let source_set: HashSet<&HtmlFileLink> = source_links.iter().map(|(_, l)| l).collect();
+        for (href, link) in source_links.iter() {
+            if !output_set.contains(link) {
+                issues.push(ConsistencyIssue {
+                    source: source.clone(),
+                    output: output.clone(),
+                    inconsistency: Inconsistency::DroppedLink(href.to_string()),
+                });
+            }
+        }
+        for (href, link) in output_links.iter() {
+            if !source_set.contains(link) {
+                issues.push(ConsistencyIssue {
+                    source: source.clone(),
+                    output: output.clone(),
+                    inconsistency: Inconsistency::ExtraLink(href.to_string()),
+                });
+            }
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::html::HtmlInfo;
+    use crate::tree::{FragmentMatch, Probing};
+
+    #[test]
+    fn test_path_mapping() {
+        let mapping = PathMapping::default();
+        assert_eq!(
+            mapping.map_path("guide/intro.md"),
+            PathBuf::from("guide/intro.html")
+        );
+        assert_eq!(
+            mapping.map_path("guide/README.md"),
+            PathBuf::from("guide/index.html")
+        );
+        assert_eq!(mapping.map_path("index.md"), PathBuf::from("index.html"));
+        assert_eq!(mapping.map_path("image.png"), PathBuf::from("image.png"));
+        assert_eq!(mapping.map_href("intro.md#setup"), "intro.html#setup");
+        assert_eq!(mapping.map_href("#setup"), "#setup");
+        assert_eq!(
+            PathMapping::parse_rule("rst=htm"),
+            Ok(("rst".to_string(), "htm".to_string()))
+        );
+        assert!(PathMapping::parse_rule("rst").is_err());
+    }
+
+    #[test]
+    fn test_check_consistency() {
+        let sources = SourceFiles(HashMap::from([
+            (
+                PathBuf::from("README.md"),
+                MarkdownInfo::parse(
+                    "[a](guide/intro.md) [b](guide/README.md#top) [c](gone.md) [d](#usage)",
+                ),
+            ),
+            (
+                PathBuf::from("guide/intro.md"),
+                MarkdownInfo::parse("[home](../README.md)"),
+            ),
+            (PathBuf::from("orphan.md"), MarkdownInfo::parse("")),
+        ]));
+        let outputs = HtmlFiles(
+            HashMap::from([
+                (
+                    PathBuf::from("index.html"),
+                    HtmlInfo::parse(
+                        r##"<a href="guide/intro.html"/><a href="guide/#top"/><a href="extra.html"/><a href="#usage"/>"##,
+                    ),
+                ),
+                (
+                    PathBuf::from("guide/intro.html"),
+                    HtmlInfo::parse(r#"<a href="../index.html"/>"#),
+                ),
+            ]),
+            HashMap::new(),
+            Probing::default(),
+            FragmentMatch::default(),
+        );
+        let mut issues = check_consistency(&sources, &outputs, &PathMapping::default());
+        issues.sort_by_key(|issue| issue.source.clone());
+        assert_eq!(
+            issues,
+            vec![
+                ConsistencyIssue {
+                    source: "README.md".into(),
+                    output: "index.html".into(),
+                    inconsistency: Inconsistency::DroppedLink("gone.md".into()),
+                },
+                ConsistencyIssue {
+                    source: "README.md".into(),
+                    output: "index.html".into(),
+                    inconsistency: Inconsistency::ExtraLink("extra.html".into()),
+                },
+                ConsistencyIssue {
+                    source: "orphan.md".into(),
+                    output: "orphan.html".into(),
+                    inconsistency: Inconsistency::NoOutput,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,200 @@
+use percent_encoding::percent_decode_str;
+
+/// The most digits a phone number has, by the E.164 numbering plan
+const MAX_PHONE_DIGITS: usize = 15;
+
+/// The fewest digits a phone number has, counting short numbers like emergency services
+const MIN_PHONE_DIGITS: usize = 3;
+
+/// The rest of a URL after `scheme:`, which is matched ignoring case
+fn strip_scheme<'a>(href: &'a str, scheme: &str) -> Option<&'a str> {
+    let (prefix, rest) = href.split_at_checked(scheme.len() + 1)?;
+    let name = prefix.strip_suffix(':')?;
+    name.eq_ignore_ascii_case(scheme).then_some(rest)
+}
+
+/// Decode the `%XX` escapes of part of a URL, rejecting any `%` which doesn't start one
+fn decode(part: &str) -> Result<String, String> {
+    let bytes = part.as_bytes();
+    for (i, _) in part.match_indices('%') {
+        let escape = bytes.get(i + 1..i + 3);
+        if !escape.is_some_and(|escape| escape.iter().all(u8::is_ascii_hexdigit)) {
+            return Err(format!("invalid percent escape in {part:?}"));
+        }
+    }
+    percent_decode_str(part)
+        .decode_utf8()
+        .map(|decoded| decoded.into_owned())
+        .map_err(|_| format!("{part:?} is not valid UTF-8"))
+}
+
+/// Whether a domain is a dotted list of letters, digits and hyphens, or an address in brackets
+fn is_domain(domain: &str) -> bool {
+    if let Some(literal) = domain.strip_prefix('[') {
+        return literal.ends_with(']') && literal.len() > 1;
+    }
+    !domain.is_empty()
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+        })
+}
+
+/// Whether the part of an address before the `@` is a dotted list of atoms, or quoted
+fn is_local_part(local: &str) -> bool {
+    if local.len() >= 2 && local.starts_with('"') && local.ends_with('"') {
+        return true;
+    }
+    const SPECIALS: &str = "!#$%&'*+-/=?^_`{|}~";
+    !local.is_empty()
+        && local.split('.').all(|atom| {
+            !atom.is_empty()
+                && atom
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || SPECIALS.contains(c))
+        })
+}
+
+fn check_address(address: &str) -> Result<(), String> {
+    let (local, domain) = address
+        .rsplit_once('@')
+        .ok_or_else(|| format!("{address:?} has no @"))?;
+    if !is_local_part(local) {
+        return Err(format!("{address:?} has an invalid name before the @"));
+    }
+    if !is_domain(domain) {
+        return Err(format!("{address:?} has an invalid domain"));
+    }
+    Ok(())
+}
+
+/**
+Check the structure of a `mailto:` link as RFC 6068 gives it: a comma separated list of
+addresses, then optionally `?` and `&` separated `name=value` header fields, like
+`mailto:someone@example.com?subject=Hello`.
+*/
+pub fn check_mailto(href: &str) -> Result<(), String> {
+    let rest = strip_scheme(href, "mailto").ok_or("not a mailto: link")?;
+    let (to, headers) = match rest.split_once('?') {
+        Some((to, headers)) => (to, Some(headers)),
+        None => (rest, None),
+    };
+    let mut has_address = false;
+    if !to.is_empty() {
+        for address in decode(to)?.split(',') {
+            check_address(address.trim())?;
+            has_address = true;
+        }
+    }
+    for header in headers.into_iter().flat_map(|headers| headers.split('&')) {
+        let (name, value) = header
+            .split_once('=')
+            .filter(|(name, _)| !name.is_empty())
+            .ok_or_else(|| format!("header {header:?} is not a name=value pair"))?;
+        let value = decode(value)?;
+        if name.eq_ignore_ascii_case("to") {
+            for address in value.split(',').filter(|a| !a.trim().is_empty()) {
+                check_address(address.trim())?;
+                has_address = true;
+            }
+        }
+        decode(name)?;
+    }
+    match has_address {
+        true => Ok(()),
+        false => Err("no address to send to".to_string()),
+    }
+}
+
+/**
+Check that a `tel:` link is a plausible phone number, like `tel:+1-201-555-0123`: digits and the
+visual separators `-.()` and spaces, with an optional leading `+`, then any `;` parameters.
+*/
+pub fn check_tel(href: &str) -> Result<(), String> {
+    let rest = strip_scheme(href, "tel").ok_or("not a tel: link")?;
+    let rest = decode(rest)?;
+    let number = rest.split(';').next().unwrap_or_default();
+    let digits = number.strip_prefix('+').unwrap_or(number);
+    if let Some(c) = digits
+        .chars()
+        .find(|c| !(c.is_ascii_digit() || "-.() ".contains(*c)))
+    {
+        return Err(format!("{number:?} contains {c:?}"));
+    }
+    let count = digits.chars().filter(char::is_ascii_digit).count();
+    if !(MIN_PHONE_DIGITS..=MAX_PHONE_DIGITS).contains(&count) {
+        return Err(format!("{number:?} has {count} digits"));
+    }
+    Ok(())
+}
+
+/// Check a `mailto:` or `tel:` link, or nothing for any other link
+pub fn check_contact_link(href: &str) -> Option<Result<(), String>> {
+    if strip_scheme(href, "mailto").is_some() {
+        Some(check_mailto(href))
+    } else if strip_scheme(href, "tel").is_some() {
+        Some(check_tel(href))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check_mailto() {
+        for href in [
+            "mailto:someone@example.com",
+            "MAILTO:someone@example.com",
+            "mailto:a@example.com,b@example.org",
+            "mailto:first.last+tag@sub.example.co.uk?subject=Hi%20there&body=Thanks",
+            "mailto:?to=someone@example.com&subject=Hi",
+            "mailto:%22not%20ok%22@example.com",
+            "mailto:root@[192.0.2.1]",
+        ] {
+            assert_eq!(check_mailto(href), Ok(()), "{href}");
+        }
+        for href in [
+            "mailto:",
+            "mailto:someone",
+            "mailto:someone@",
+            "mailto:@example.com",
+            "mailto:some one@example.com",
+            "mailto:someone@example..com",
+            "mailto:someone@example.com?subject",
+            "mailto:someone@example.com?=x",
+            "mailto:someone@example.com?subject=100%",
+            "mailto:?subject=Hi",
+        ] {
+            assert!(check_mailto(href).is_err(), "{href}");
+        }
+    }
+
+    #[test]
+    fn test_check_tel() {
+        for href in [
+            "tel:+1-201-555-0123",
+            "tel:+44 (0)20 7946 0958",
+            "tel:112",
+            "tel:555.0123;ext=42",
+            "tel:+1%20201%20555%200123",
+        ] {
+            assert_eq!(check_tel(href), Ok(()), "{href}");
+        }
+        for href in [
+            "tel:",
+            "tel:12",
+            "tel:+1-800-FLOWERS",
+            "tel:+1234567890123456",
+            "tel:555+0123",
+        ] {
+            assert!(check_tel(href).is_err(), "{href}");
+        }
+        assert_eq!(check_contact_link("https://example.com/"), None);
+        assert!(check_contact_link("tel:abc").is_some_and(|result| result.is_err()));
+    }
+}
@@ -0,0 +1,168 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+/**
+The URLs referenced by a stylesheet or an inline `style` attribute: those given with `url(...)`
+and the quoted ones of `@import` rules, like `background-image: url("images/hero.png")`.
+
+The text is tokenized just enough to know where URLs are, so comments, strings and escapes
+don't hide URLs or make up new ones. Escapes in URLs are decoded, as browsers do.
+*/
+pub fn css_urls(css: &str) -> Vec<String> {
+    let mut urls = vec![];
+    let mut chars = css.chars().peekable();
+    let mut after_import = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                skip_comment(&mut chars);
+            }
+            '"' | '\'' => {
+                let string = read_string(&mut chars, c);
+                if after_import {
+                    urls.push(string);
+                }
+                after_import = false;
+            }
+            '\\' => {
+                read_escape(&mut chars);
+                after_import = false;
+            }
+            c if is_name_char(c) || c == '@' => {
+                let mut name = String::from(c);
+                while let Some(&c) = chars.peek().filter(|c| is_name_char(**c)) {
+                    name.push(c);
+                    chars.next();
+                }
+                after_import = name.eq_ignore_ascii_case("@import");
+                if name.eq_ignore_ascii_case("url") && chars.peek() == Some(&'(') {
+                    chars.next();
+                    urls.extend(read_url(&mut chars));
+                }
+            }
+            c if c.is_whitespace() => {}
+            _ => after_import = false,
+        }
+    }
+    urls
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_' || !c.is_ascii()
+}
+
+fn skip_comment(chars: &mut Peekable<Chars>) {
+    while let Some(c) = chars.next() {
+        if c == '*' && chars.peek() == Some(&'/') {
+            chars.next();
+            return;
+        }
+    }
+}
+
+/// Decode an escape after its backslash, like `\)` or `\28 `
+fn read_escape(chars: &mut Peekable<Chars>) -> Option<char> {
+    let mut hex = String::new();
+    while let Some(&c) = chars
+        .peek()
+        .filter(|c| c.is_ascii_hexdigit() && hex.len() < 6)
+    {
+        hex.push(c);
+        chars.next();
+    }
+    if hex.is_empty() {
+        return chars.next();
+    }
+    // A single whitespace ends a hex escape, and is part of it
+    if chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+    let code = u32::from_str_radix(&hex, 16).ok()?;
+    Some(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER))
+}
+
+/// Read a string after its opening quote, up to the closing quote or the end of the line
+fn read_string(chars: &mut Peekable<Chars>, quote: char) -> String {
+    let mut string = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c == quote => break,
+            '\n' => break,
+            '\\' if chars.peek() == Some(&'\n') => {
+                chars.next();
+            }
+            '\\' => string.extend(read_escape(chars)),
+            c => string.push(c),
+        }
+    }
+    string
+}
+
+/// Read the URL of a `url(` up to its closing parenthesis, which may be quoted or not
+fn read_url(chars: &mut Peekable<Chars>) -> Option<String> {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+    let url = match chars.peek() {
+        Some(&quote @ ('"' | '\'')) => {
+            chars.next();
+            read_string(chars, quote)
+        }
+        _ => {
+            let mut url = String::new();
+            while let Some(c) = chars.next() {
+                match c {
+                    ')' => return (!url.trim_end().is_empty()).then(|| url.trim_end().into()),
+                    '\\' => url.extend(read_escape(chars)),
+                    c => url.push(c),
+                }
+            }
+            url
+        }
+    };
+    // The rest of a quoted url, up to its closing parenthesis
+    for c in chars.by_ref() {
+        if c == ')' {
+            break;
+        }
+    }
+    (!url.trim().is_empty()).then(|| url.trim().to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_css_urls() {
+        assert_eq!(
+            css_urls(
+                r#"
+@import "base.css";
+@import url('theme.css') screen;
+/* background: url(commented.png) */
+.hero { background-image: URL( images/hero.png ); }
+.icon { background: url("icons/a\"b.svg#arrow") no-repeat, url(data:image/png;base64,AAAA); }
+.quote::before { content: "url(not-a-url.png)"; }
+.escaped { background: url(my\ image\29 .png); }
+.empty { background: url(); }
+.font { font-family: "Open Sans"; }"#
+            ),
+            vec![
+                "base.css",
+                "theme.css",
+                "images/hero.png",
+                "icons/a\"b.svg#arrow",
+                "data:image/png;base64,AAAA",
+                "my image).png",
+            ]
+        );
+        assert_eq!(
+            css_urls("background:url(/img/bg.jpg);color:red"),
+            vec!["/img/bg.jpg"]
+        );
+        assert_eq!(css_urls("a { b: url(unterminated"), vec!["unterminated"]);
+        assert!(css_urls("myurl(x.png)").is_empty());
+    }
+}
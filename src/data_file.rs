@@ -0,0 +1,212 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+use url::Url;
+use walkdir::WalkDir;
+
+use crate::rewrite::{rewrite_href, Rewrite};
+use crate::tree::{resolve_link, HtmlFileLink, ReadError};
+
+/// The keys whose values are checked like links unless others are given
+pub const DEFAULT_DATA_KEYS: &[&str] = &["url", "href", "link"];
+
+/// Parse a data file as its extension says, or nothing if it isn't one
+fn parse(path: &Path, contents: &str) -> Option<Result<Value, String>> {
+    match path.extension()?.to_str()? {
+        "json" => Some(serde_json::from_str(contents).map_err(|err| err.to_string())),
+        "yaml" | "yml" => Some(serde_yaml_ng::from_str(contents).map_err(|err| err.to_string())),
+        _ => None,
+    }
+}
+
+/**
+The strings found under any of the `keys` in a data file, at any depth, like the `url` of each
+entry of a navigation menu. A key may also hold a list of them.
+*/
+pub fn data_links(value: &Value, keys: &[String]) -> Vec<String> {
+    let mut links = vec![];
+    collect_links(value, keys, &mut links);
+    links
+}
+
+fn collect_links(value: &Value, keys: &[String], links: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                if keys.contains(key) {
+                    match value {
+                        Value::String(link) => links.push(link.clone()),
+                        Value::Array(items) => links.extend(
+                            items
+                                .iter()
+                                .filter_map(|item| item.as_str())
+                                .map(String::from),
+                        ),
+                        _ => {}
+                    }
+                }
+                collect_links(value, keys, links);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                collect_links(item, keys, links);
+            }
+        }
+        _ => {}
+    }
+}
+
+/**
+Where a link of a data file leads on the site, or nothing for one that can't be checked, like an
+external URL or a bare `#fragment`.
+
+Data files aren't pages of the site, so their links are resolved against its root whether
+they're written root-relative like `/docs/` or not, though only the former get the `Root` base.
+*/
+pub fn internal_link(link: &str) -> Option<HtmlFileLink> {
+    if link.is_empty()
+        || link.starts_with('#')
+        || link.starts_with("//")
+        || Url::parse(link) != Err(url::ParseError::RelativeUrlWithoutBase)
+    {
+        return None;
+    }
+    Some(resolve_link("", link))
+}
+
+/**
+Read the links of every `.json`, `.yaml` and `.yml` file in the `directories`, rewritten by the
+`rewrites` rules like those of documents are. Each file is keyed by its path as given.
+
+Files which can't be read or parsed are recorded in `errors` and skipped. Without `errors`, the
+first one aborts instead.
+*/
+pub fn load_data_files(
+    directories: &[PathBuf],
+    keys: &[String],
+    rewrites: &[Rewrite],
+    mut errors: Option<&mut Vec<ReadError>>,
+) -> std::io::Result<Vec<(PathBuf, Vec<String>)>> {
+    let mut files = vec![];
+    for directory in directories {
+        for entry in WalkDir::new(directory).sort_by_file_name() {
+            let parsed = match entry {
+                Ok(entry) if entry.file_type().is_file() => {
+                    let path = entry.into_path();
+                    let value = std::fs::read_to_string(&path).and_then(|contents| {
+                        parse(&path, &contents).transpose().map_err(|err| {
+                            std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+                        })
+                    });
+                    match value {
+                        Ok(value) => Ok(value.map(|value| (path, value))),
+                        Err(err) => Err((path, err)),
+                    }
+                }
+                Ok(_) => Ok(None),
+                Err(err) => {
+                    let path = err.path().unwrap_or(directory).to_path_buf();
+                    Err((path, std::io::Error::from(err)))
+                }
+            };
+            match (parsed, errors.as_deref_mut()) {
+                (Ok(Some((path, value))), _) => {
+                    let links = data_links(&value, keys)
+                        .iter()
+                        .map(|link| rewrite_href(rewrites, link))
+                        .collect();
+                    files.push((path, links));
+                }
+                (Ok(None), _) => {}
+                (Err((path, err)), Some(errors)) => errors.push(ReadError::new(path, &err)),
+                (Err((_, err)), None) => return Err(err),
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// The links of a data file, as written, which `contains` can't find
+pub fn broken_data_links<F: Fn(&HtmlFileLink) -> bool>(
+    links: &[String],
+    contains: F,
+) -> Vec<String> {
+    links
+        .iter()
+        .filter(|link| internal_link(link).is_some_and(|internal| !contains(&internal)))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tree::LinkBase;
+
+    #[test]
+    fn test_data_links() {
+        let keys: Vec<String> = DEFAULT_DATA_KEYS.iter().map(|k| k.to_string()).collect();
+        let yaml = parse(
+            Path::new("_data/navigation.yml"),
+            r##"
+main:
+  - title: Guide
+    url: /guide/
+    children:
+      - { title: Setup, url: "/guide/setup.html#linux" }
+  - title: Blog
+    href: https://example.com/blog/
+footer:
+  link: [about.html, "#top"]
+  text: not/a/link.html
+"##,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            data_links(&yaml, &keys),
+            vec![
+                "about.html",
+                "#top",
+                "/guide/setup.html#linux",
+                "/guide/",
+                "https://example.com/blog/"
+            ]
+        );
+        let json = parse(
+            Path::new("data/menu.json"),
+            r#"[{"name": "Docs", "target": "/docs/"}, {"name": "Home", "url": "/"}]"#,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(data_links(&json, &["target".into()]), vec!["/docs/"]);
+        assert!(parse(Path::new("data/broken.json"), "{").unwrap().is_err());
+        assert!(parse(Path::new("data/notes.txt"), "").is_none());
+    }
+
+    #[test]
+    fn test_broken_data_links() {
+        let links: Vec<String> = [
+            "/guide/",
+            "guide/setup.html#linux",
+            "/gone.html",
+            "https://example.com/",
+            "mailto:someone@example.com",
+            "#top",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        let exists = |link: &HtmlFileLink| link.path.starts_with("guide");
+        assert_eq!(broken_data_links(&links, exists), vec!["/gone.html"]);
+        assert_eq!(
+            internal_link("/guide/setup.html#linux"),
+            Some(HtmlFileLink {
+                path: "guide/setup.html".into(),
+                fragment: Some("linux".into()),
+                base: LinkBase::Root,
+            })
+        );
+    }
+}
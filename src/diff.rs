@@ -0,0 +1,131 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+use crate::tree::{resolved_links, HtmlFileLink, HtmlFiles, LinkBase};
+
+/**
+A file of an old build of the site, or a `#fragment` of one which the old build linked to,
+which a new build no longer has, as `rlc diff` reports them.
+
+The documents of the old build which linked there break when the new build replaces it, and so
+may links from other sites, even if the new build is consistent on its own.
+*/
+#[derive(Debug, PartialEq, Eq)]
+pub struct VanishedTarget {
+    /// The file, relative to the scanned directory of the old build
+    pub path: PathBuf,
+    /// The id linked to in the file, if only that has gone
+    pub fragment: Option<String>,
+    /// The documents of the old build which linked there
+    pub linked_from: Vec<PathBuf>,
+}
+
+/**
+The files of the `old` build missing from the `new` one, and the fragments linked to in the old
+build missing from files the new one still has, sorted by path.
+
+Links which were already broken in the old build aren't counted. Ids nothing linked to aren't
+reported either, since any of them could have been renamed without harm.
+*/
+pub fn vanished_targets(
+    old: &HtmlFiles,
+    new: &HtmlFiles,
+    follow_redirects: bool,
+) -> Vec<VanishedTarget> {
+    let mut linked_from: BTreeMap<(PathBuf, Option<String>), BTreeSet<PathBuf>> = BTreeMap::new();
+    for path in old.paths() {
+        linked_from.entry((path.clone(), None)).or_default();
+    }
+    for (source, info) in old.0.iter() {
+        for (_, link) in resolved_links(source, info) {
+            let Some(path) = old.file(&link.path).filter(|_| old.contains(&link)) else {
+                continue;
+            };
+            let mut add = |fragment| {
+                (linked_from.entry((path.clone(), fragment)).or_default()).insert(source.clone())
+            };
+            add(None);
+            if link.fragment.is_some() {
+                add(link.fragment);
+            }
+        }
+    }
+    let contains = |link: &HtmlFileLink| match follow_redirects {
+        true => new.contains_following_redirects(link),
+        false => new.contains(link),
+    };
+    let mut vanished: Vec<VanishedTarget> = vec![];
+    for ((path, fragment), sources) in linked_from {
+        // A file's own entry comes before its fragments, which aren't worth reporting as well
+        if fragment.is_some() && vanished.last().is_some_and(|last| last.path == path) {
+            continue;
+        }
+        let link = HtmlFileLink {
+            path: path.clone(),
+            fragment: fragment.clone(),
+            base: LinkBase::Document,
+        };
+        if !contains(&link) {
+            vanished.push(VanishedTarget {
+                path,
+                fragment,
+                linked_from: sources.into_iter().collect(),
+            });
+        }
+    }
+    vanished
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::html::HtmlInfo;
+
+    fn files(documents: &[(&str, &str)]) -> HtmlFiles {
+        let mut files = HtmlFiles::default();
+        for (path, document) in documents {
+            files.insert(path.into(), HtmlInfo::parse(document));
+        }
+        files
+    }
+
+    #[test]
+    fn test_vanished_targets() {
+        let old = files(&[
+            (
+                "index.html",
+                r##"<a href="guide/"/><a href="api.html#parse"/><a href="api.html#gone"/><a href="old.html#top"/>"##,
+            ),
+            ("guide/index.html", r#"<a href="../old.html"/>"#),
+            ("api.html", r#"<h2 id="parse"/><h2 id="unlinked"/>"#),
+            ("old.html", r#"<h1 id="top"/>"#),
+            ("unlinked.html", ""),
+        ]);
+        let new = files(&[
+            ("index.html", r#"<a href="guide/"/>"#),
+            ("guide/index.html", ""),
+            ("api.html", r#"<h2 id="parsing"/>"#),
+        ]);
+        let vanished = vanished_targets(&old, &new, false);
+        assert_eq!(
+            vanished
+                .iter()
+                .map(|target| (
+                    target.path.to_str().unwrap(),
+                    target.fragment.as_deref(),
+                    target.linked_from.len()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("api.html", Some("parse"), 1),
+                ("old.html", None, 2),
+                ("unlinked.html", None, 0),
+            ]
+        );
+        assert_eq!(
+            vanished[1].linked_from,
+            vec![PathBuf::from("guide/index.html"), "index.html".into()]
+        );
+        assert_eq!(vanished_targets(&old, &old, false), vec![]);
+    }
+}
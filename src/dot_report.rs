@@ -0,0 +1,204 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use crate::report::{Failure, Report};
+
+/**
+The scanned documents and the files each of them links to, drawn by `--format dot` and
+`rlc graph`.
+
+Links are kept without their `#fragment`, so a document linking to another several times is a
+single edge.
+ */
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LinkGraph {
+    pub documents: BTreeSet<PathBuf>,
+    pub links: BTreeSet<(PathBuf, PathBuf)>,
+}
+
+/// Quote a path as a Graphviz id
+fn quote(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    format!("\"{}\"", path.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The document a link to `path` leads to, so links to `guide/` and `guide/index.html` meet
+fn node(path: &Path) -> PathBuf {
+    let text = path.to_string_lossy();
+    if text.is_empty() || text.ends_with('/') {
+        path.join("index.html")
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// The links of the failures which are about an internal link, and the color to draw them in
+fn broken_links(report: &Report) -> BTreeMap<(PathBuf, PathBuf), &'static str> {
+    let mut broken = BTreeMap::new();
+    let failures = report.warnings.iter().map(|failure| (failure, "orange"));
+    for (failure, color) in failures.chain(report.failures.iter().map(|f| (f, "red"))) {
+        if let Failure::BrokenLink { source, link, .. }
+        | Failure::MissingFragment { source, link, .. } = failure
+        {
+            broken.insert((source.clone(), node(&link.path)), color);
+        }
+    }
+    broken
+}
+
+/// The documents of a report's graph, and its links apart from the broken ones
+fn graph(report: &Report) -> (BTreeSet<PathBuf>, BTreeSet<(PathBuf, PathBuf)>) {
+    let graph = report.graph.clone().unwrap_or_default();
+    let broken = broken_links(report);
+    let links = graph
+        .links
+        .iter()
+        .map(|(source, target)| (source.clone(), node(target)))
+        .filter(|link| !broken.contains_key(link))
+        .collect();
+    (graph.documents, links)
+}
+
+/**
+Render a report as a Graphviz graph of the documents and their internal links, with broken
+links in red, or orange if they're only warnings.
+
+The graph is only known to the run which checked the documents, so a merged report draws just
+its broken links.
+*/
+pub fn render(report: &Report) -> String {
+    let (documents, links) = graph(report);
+    let mut dot = String::from("digraph links {\n    node [shape=box];\n");
+    for document in documents.iter() {
+        writeln!(dot, "    {};", quote(document)).unwrap();
+    }
+    for (source, target) in links.iter() {
+        writeln!(dot, "    {} -> {};", quote(source), quote(target)).unwrap();
+    }
+    for ((source, target), color) in broken_links(report).iter() {
+        writeln!(
+            dot,
+            "    {} -> {} [color={color}];",
+            quote(source),
+            quote(target)
+        )
+        .unwrap();
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/**
+Render the same graph as `render` as a Mermaid flowchart, which Markdown previews on most code
+hosts draw. Each file is a node numbered in order of appearance and labelled with its path.
+*/
+pub fn render_mermaid(report: &Report) -> String {
+    let (documents, links) = graph(report);
+    let broken = broken_links(report);
+    let mut nodes: BTreeMap<PathBuf, usize> = BTreeMap::new();
+    let mut mermaid = String::from("flowchart LR\n");
+    let mut id = |path: &Path, mermaid: &mut String| {
+        let next = nodes.len();
+        let id = *nodes.entry(path.to_path_buf()).or_insert(next);
+        if id == next {
+            let label = path.to_string_lossy().replace('"', "#quot;");
+            writeln!(mermaid, "    n{id}[\"{label}\"]").unwrap();
+        }
+        id
+    };
+    for document in documents.iter() {
+        id(document, &mut mermaid);
+    }
+    let mut styles = vec![];
+    let edges = (links.iter().map(|link| (link, None)))
+        .chain(broken.iter().map(|(link, color)| (link, Some(color))));
+    for (i, ((source, target), color)) in edges.enumerate() {
+        let source = id(source, &mut mermaid);
+        let target = id(target, &mut mermaid);
+        writeln!(mermaid, "    n{source} --> n{target}").unwrap();
+        if let Some(color) = color {
+            styles.push(format!("    linkStyle {i} stroke:{color}\n"));
+        }
+    }
+    mermaid.extend(styles);
+    mermaid
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tree::HtmlFileLink;
+
+    #[test]
+    fn test_render() {
+        let mut report = Report::new(vec![Failure::BrokenLink {
+            source: "index.html".into(),
+            href: "gone.html".into(),
+            link: HtmlFileLink::new("gone.html"),
+            attribute: None,
+            text: None,
+        }]);
+        report.warnings.push(Failure::MissingFragment {
+            source: "index.html".into(),
+            href: "guide/#setup".into(),
+            link: HtmlFileLink::new("guide/#setup"),
+            similar_ids: vec![],
+            attribute: None,
+            text: None,
+        });
+        let link = |source: &str, target: &str| (PathBuf::from(source), PathBuf::from(target));
+        report.graph = Some(LinkGraph {
+            documents: ["index.html", "guide/index.html", "say \"hi\".html"]
+                .map(PathBuf::from)
+                .into(),
+            links: [
+                link("index.html", "gone.html"),
+                link("index.html", "guide/"),
+                link("guide/index.html", ""),
+                link("guide/index.html", "index.html"),
+            ]
+            .into(),
+        });
+        assert_eq!(
+            render(&report),
+            r#"digraph links {
+    node [shape=box];
+    "guide/index.html";
+    "index.html";
+    "say \"hi\".html";
+    "guide/index.html" -> "index.html";
+    "index.html" -> "gone.html" [color=red];
+    "index.html" -> "guide/index.html" [color=orange];
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_render_mermaid() {
+        let mut report = Report::new(vec![Failure::BrokenLink {
+            source: "index.html".into(),
+            href: "gone.html".into(),
+            link: HtmlFileLink::new("gone.html"),
+            attribute: None,
+            text: None,
+        }]);
+        let link = |source: &str, target: &str| (PathBuf::from(source), PathBuf::from(target));
+        report.graph = Some(LinkGraph {
+            documents: ["index.html", "say \"hi\".html"].map(PathBuf::from).into(),
+            links: [link("index.html", "gone.html"), link("say \"hi\".html", "")].into(),
+        });
+        assert_eq!(
+            render_mermaid(&report),
+            r#"flowchart LR
+    n0["index.html"]
+    n1["say #quot;hi#quot;.html"]
+    n1 --> n0
+    n2["gone.html"]
+    n0 --> n2
+    linkStyle 1 stroke:red
+"#
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::external::is_web_url;
+use crate::html::{HtmlInfo, Ids};
+use crate::tree::resolved_links;
+
+/**
+How `rlc list` writes the links it finds.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DumpFormat {
+    /// A JSON array of links
+    Json,
+    /// A CSV table with a header row
+    Csv,
+}
+
+/**
+What kind of URL a link is given by.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinkKind {
+    /// A relative or root-relative link to a file of the site
+    Internal,
+    /// An http or https link to another site
+    External,
+    /// Any other absolute URL, like `mailto:` or `tel:`
+    Other,
+}
+
+impl LinkKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            LinkKind::Internal => "internal",
+            LinkKind::External => "external",
+            LinkKind::Other => "other",
+        }
+    }
+}
+
+/**
+A link as it was extracted from a document, without checking where it leads.
+
+Internal links are resolved like they are for checking, so `target` is the file they lead to
+relative to the scanned directory, and their `fragment` is dropped if the document says
+`no-fragments`.
+ */
+#[derive(Debug, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct DumpedLink {
+    /// The document the link is in, relative to the scanned directory
+    pub source: PathBuf,
+    /// The link as it was written in the document
+    pub href: String,
+    pub kind: LinkKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fragment: Option<String>,
+}
+
+/// Every link in a document, internal links first, as `rlc list` lists them
+pub fn document_links(file_path: &Path, info: &HtmlInfo) -> Vec<DumpedLink> {
+    let internal = resolved_links(file_path, info).map(|(href, link)| DumpedLink {
+        source: file_path.to_path_buf(),
+        href: href.to_string(),
+        kind: LinkKind::Internal,
+        target: Some(link.path),
+        fragment: link.fragment,
+    });
+    let external = info.external_hrefs.iter().map(|href| DumpedLink {
+        source: file_path.to_path_buf(),
+        href: href.clone(),
+        kind: match is_web_url(href) {
+            true => LinkKind::External,
+            false => LinkKind::Other,
+        },
+        target: None,
+        fragment: href
+            .split_once('#')
+            .map(|(_, fragment)| fragment.to_string()),
+    });
+    internal.chain(external).collect()
+}
+
+/// Write the links in a dump `format`
+pub fn render(links: &[DumpedLink], format: DumpFormat) -> String {
+    match format {
+        DumpFormat::Json => {
+            serde_json::to_string_pretty(links).expect("Links are always valid JSON") + "\n"
+        }
+        DumpFormat::Csv => {
+            let mut csv = String::from("source,href,kind,target,fragment\n");
+            for link in links {
+                let target = link
+                    .target
+                    .as_ref()
+                    .map(|target| target.to_string_lossy())
+                    .unwrap_or_default();
+                let fields = [
+                    &link.source.to_string_lossy(),
+                    link.href.as_str(),
+                    link.kind.as_str(),
+                    &target,
+                    link.fragment.as_deref().unwrap_or_default(),
+                ];
+                let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+                csv.push_str(&row.join(","));
+                csv.push('\n');
+            }
+            csv
+        }
+    }
+}
+
+/**
+A file of the site which links can lead to, with the `#fragment`s they can lead to in it, as
+`rlc targets` lists them for editors to complete links from.
+*/
+#[derive(Debug, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct LinkTarget {
+    /// The file, relative to the scanned directory
+    pub path: PathBuf,
+    /// The ids in the file, if it's a document or an SVG image, each once in the order they
+    /// first appear
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fragments: Vec<String>,
+}
+
+impl LinkTarget {
+    pub fn new(path: PathBuf, ids: &Ids) -> LinkTarget {
+        let mut seen = HashSet::new();
+        let fragments = ids
+            .iter()
+            .filter(|id| seen.insert(*id))
+            .map(|id| id.to_string())
+            .collect();
+        LinkTarget { path, fragments }
+    }
+}
+
+/// Write link targets as a JSON array, or else a line for each file and each of its fragments
+pub fn render_targets(targets: &[LinkTarget], json: bool) -> String {
+    if json {
+        return serde_json::to_string_pretty(targets).expect("Targets are always valid JSON")
+            + "\n";
+    }
+    let mut lines = String::new();
+    for LinkTarget { path, fragments } in targets {
+        let path = path.to_string_lossy();
+        lines.push_str(&format!("{path}\n"));
+        for fragment in fragments {
+            lines.push_str(&format!("{path}#{fragment}\n"));
+        }
+    }
+    lines
+}
+
+/// Quote a CSV field if it has a comma, quote or line break in it
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_dump_links() {
+        let info = HtmlInfo::parse(
+            r#"
+<a href="../guide.html#setup, part 1">a</a>
+<a href="https://example.com/#top">b</a>
+<a href="mailto:someone@example.com">c</a>"#,
+        );
+        let links = document_links(Path::new("docs/index.html"), &info);
+        assert_eq!(
+            links
+                .iter()
+                .map(|link| (link.kind, link.target.as_deref(), link.fragment.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    LinkKind::Internal,
+                    Some(Path::new("guide.html")),
+                    Some("setup, part 1")
+                ),
+                (LinkKind::External, None, Some("top")),
+                (LinkKind::Other, None, None),
+            ]
+        );
+        assert_eq!(
+            render(&links, DumpFormat::Csv),
+            r#"source,href,kind,target,fragment
+docs/index.html,"../guide.html#setup, part 1",internal,guide.html,"setup, part 1"
+docs/index.html,https://example.com/#top,external,,top
+docs/index.html,mailto:someone@example.com,other,,
+"#
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&render(&links, DumpFormat::Json)).unwrap();
+        assert_eq!(json[2]["kind"], "other");
+        assert!(json[2].get("target").is_none());
+    }
+
+    #[test]
+    fn test_link_targets() {
+        let info = HtmlInfo::parse(r#"<h1 id="top"/><h2 id="setup"/><p id="top"/>"#);
+        let targets = [
+            LinkTarget::new("guide.html".into(), &info.ids),
+            LinkTarget::new("manual.pdf".into(), &Ids::default()),
+        ];
+        assert_eq!(targets[0].fragments, vec!["top", "setup"]);
+        assert_eq!(
+            render_targets(&targets, false),
+            "guide.html\nguide.html#top\nguide.html#setup\nmanual.pdf\n"
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&render_targets(&targets, true)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {"path": "guide.html", "fragments": ["top", "setup"]},
+                {"path": "manual.pdf"},
+            ])
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use url::Url;
+
+use crate::html::with_scheme;
+
+/// Parse a `--base-url`, the site's production URL, which must be an http or https URL
+pub fn parse_base_url(base_url: &str) -> Result<Url, String> {
+    let url = Url::parse(base_url).map_err(|err| format!("{base_url:?}: {err}"))?;
+    match (url.scheme(), url.host()) {
+        ("http" | "https", Some(_)) => Ok(url),
+        _ => Err(format!("expected an http or https URL, got {base_url:?}")),
+    }
+}
+
+/**
+The URLs of the site's own environments: where it's published, and the prefixes of staging or
+preview deployments which published documents mustn't link to.
+
+Links starting with one of the `allow` prefixes are never flagged, for the odd deliberate link
+to an older deployment.
+*/
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct EnvironmentUrls {
+    pub base_url: Option<Url>,
+    pub deny: Vec<String>,
+    pub allow: Vec<String>,
+}
+
+impl EnvironmentUrls {
+    /// Whether there's nothing to check links against
+    pub fn is_empty(&self) -> bool {
+        self.base_url.is_none() && self.deny.is_empty()
+    }
+    /**
+    What's wrong with an external link to one of the site's environments, if anything: it leads
+    to the production site over http when that's served over https, or to a denied prefix.
+    */
+    pub fn check_url(&self, href: &str) -> Option<String> {
+        // Protocol-relative links are matched as the https URLs they load, but only links which
+        // are written with http are judged for it
+        let url = with_scheme(href);
+        if self
+            .allow
+            .iter()
+            .any(|prefix| url.starts_with(prefix.as_str()))
+        {
+            return None;
+        }
+        if let Some(prefix) = self
+            .deny
+            .iter()
+            .find(|prefix| url.starts_with(prefix.as_str()))
+        {
+            return Some(format!("{prefix} isn't a production URL"));
+        }
+        let base_url = self.base_url.as_ref()?;
+        let url = Url::parse(href).ok()?;
+        (base_url.scheme() == "https" && url.scheme() == "http" && url.host() == base_url.host())
+            .then(|| "the production site is served over https".to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check_url() {
+        let urls = EnvironmentUrls {
+            base_url: Some(parse_base_url("https://example.com/docs/").unwrap()),
+            deny: vec![
+                "https://staging.example.com/".into(),
+                "https://deploy-preview-".into(),
+            ],
+            allow: vec!["http://example.com/legacy/".into()],
+        };
+        assert_eq!(urls.check_url("https://example.com/docs/guide.html"), None);
+        assert_eq!(urls.check_url("http://other.org/"), None);
+        assert_eq!(urls.check_url("http://example.com/legacy/page.html"), None);
+        assert_eq!(
+            urls.check_url("http://EXAMPLE.com/docs/guide.html")
+                .unwrap(),
+            "the production site is served over https"
+        );
+        assert_eq!(
+            urls.check_url("https://staging.example.com/docs/").unwrap(),
+            "https://staging.example.com/ isn't a production URL"
+        );
+        assert_eq!(
+            urls.check_url("//staging.example.com/docs/").unwrap(),
+            "https://staging.example.com/ isn't a production URL"
+        );
+        assert_eq!(urls.check_url("//example.com/docs/guide.html"), None);
+        assert_eq!(
+            urls.check_url("https://deploy-preview-42--example.netlify.app/")
+                .unwrap(),
+            "https://deploy-preview- isn't a production URL"
+        );
+        assert!(EnvironmentUrls::default().is_empty());
+        assert!(parse_base_url("ftp://example.com/").is_err());
+        assert!(parse_base_url("example.com").is_err());
+    }
+}
@@ -0,0 +1,206 @@
+use std::path::{Path, PathBuf};
+
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+use crate::filter::FileFilter;
+use crate::provider::FileProvider;
+use crate::site_path::site_key;
+use crate::tree::{HtmlFileLink, LinkBase, ReadError};
+
+/// Whether a file may be a feed
+fn is_xml(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "xml")
+}
+
+/**
+The links in an RSS or Atom feed: the `<link>` of each item or entry, and the URL of any
+enclosures like podcast episodes.
+
+A feed names the site it belongs to with its own `<link>`s. Links to the same hosts, or
+root-relative ones like `/posts/hello.html`, are internal to the site.
+ */
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Feed {
+    pub links: Vec<String>,
+    /// The hosts of the site the feed belongs to
+    pub hosts: Vec<String>,
+}
+
+impl Feed {
+    /// Parse a feed, or nothing if the document is some other kind of XML
+    pub fn parse(contents: &str) -> Result<Option<Feed>, String> {
+        let document = roxmltree::Document::parse(contents).map_err(|err| err.to_string())?;
+        let root = document.root_element();
+        let is_atom = root.has_tag_name("feed");
+        if !is_atom && !root.has_tag_name("rss") && !root.has_tag_name("RDF") {
+            return Ok(None);
+        }
+        let mut feed = Feed::default();
+        for node in root.descendants().filter(|node| node.is_element()) {
+            let name = node.tag_name().name();
+            let url = match (name, is_atom) {
+                ("link", true) => node.attribute("href"),
+                ("link", false) => node.attribute("href").or(node.text()),
+                ("enclosure", false) => node.attribute("url"),
+                _ => None,
+            };
+            let Some(url) = url.map(str::trim).filter(|url| !url.is_empty()) else {
+                continue;
+            };
+            let in_item = node
+                .ancestors()
+                .any(|ancestor| ancestor.has_tag_name("item") || ancestor.has_tag_name("entry"));
+            if in_item {
+                feed.links.push(url.to_string());
+            } else if let Some(host) = Url::parse(url)
+                .ok()
+                .and_then(|url| url.host_str().map(String::from))
+            {
+                feed.hosts.push(host);
+            }
+        }
+        Ok(Some(feed))
+    }
+    /// Where an internal link of the feed leads on the site, or nothing for an external one
+    pub fn internal_link(&self, link: &str) -> Option<HtmlFileLink> {
+        let url = match Url::parse(link) {
+            Ok(url)
+                if url
+                    .host_str()
+                    .is_some_and(|host| self.hosts.iter().any(|h| h == host)) =>
+            {
+                url
+            }
+            Ok(_) => return None,
+            Err(_) if link.starts_with('/') && !link.starts_with("//") => {
+                Url::parse("internal:///").ok()?.join(link).ok()?
+            }
+            Err(_) => return None,
+        };
+        let path = percent_decode_str(url.path()).decode_utf8().ok()?;
+        Some(HtmlFileLink {
+            path: PathBuf::from(path.trim_start_matches('/')),
+            fragment: url.fragment().map(String::from),
+            base: LinkBase::Root,
+        })
+    }
+}
+
+/**
+Read every `.xml` file of the providers which is a feed, keyed like documents are.
+
+Files which can't be read or aren't well-formed XML are recorded in `errors` and skipped.
+Without `errors`, the first one aborts instead.
+*/
+pub fn load_feeds<P: FileProvider>(
+    providers: &[P],
+    filter: &FileFilter,
+    mut errors: Option<&mut Vec<ReadError>>,
+) -> std::io::Result<Vec<(PathBuf, Feed)>> {
+    let mut feeds = vec![];
+    for provider in providers {
+        let prefix = filter.url_prefix(provider.root());
+        let mut files = vec![];
+        provider.list(filter, &mut |file| {
+            match file {
+                Ok(path) if is_xml(&path) => files.push(path),
+                _ => {}
+            }
+            true
+        });
+        for path in files {
+            let feed = provider
+                .read_to_string(&path, filter.max_file_size)
+                .and_then(|contents| {
+                    Feed::parse(&contents)
+                        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+                });
+            match feed {
+                Ok(Some(feed)) => feeds.push((site_key(prefix, path), feed)),
+                Ok(None) => {}
+                Err(err) => ReadError::record(site_key(prefix, path), err, errors.as_deref_mut())?,
+            }
+        }
+    }
+    Ok(feeds)
+}
+
+/// The internal links of a feed, as written, which `contains` can't find
+pub fn broken_feed_links<F: Fn(&HtmlFileLink) -> bool>(feed: &Feed, contains: F) -> Vec<String> {
+    feed.links
+        .iter()
+        .filter(|link| {
+            feed.internal_link(link)
+                .is_some_and(|internal| !contains(&internal))
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_rss() {
+        let feed = Feed::parse(
+            r#"<?xml version="1.0"?>
+<rss version="2.0"><channel>
+  <title>Blog</title>
+  <link>https://example.com/</link>
+  <item><link>https://example.com/posts/hello.html</link></item>
+  <item>
+    <link> /posts/episode-1.html </link>
+    <enclosure url="https://cdn.example.net/episode-1.mp3" type="audio/mpeg" length="1"/>
+  </item>
+</channel></rss>"#,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            feed,
+            Feed {
+                links: vec![
+                    "https://example.com/posts/hello.html".into(),
+                    "/posts/episode-1.html".into(),
+                    "https://cdn.example.net/episode-1.mp3".into(),
+                ],
+                hosts: vec!["example.com".into()],
+            }
+        );
+        assert_eq!(
+            feed.internal_link("https://example.com/posts/caf%C3%A9.html#top"),
+            Some(HtmlFileLink {
+                path: "posts/café.html".into(),
+                fragment: Some("top".into()),
+                base: LinkBase::Root,
+            })
+        );
+        assert_eq!(
+            feed.internal_link("/posts/episode-1.html"),
+            Some(HtmlFileLink::new("/posts/episode-1.html"))
+        );
+        assert_eq!(feed.internal_link("https://cdn.example.net/a.mp3"), None);
+        assert_eq!(feed.internal_link("posts/relative.html"), None);
+    }
+
+    #[test]
+    fn test_parse_atom() {
+        let feed = Feed::parse(
+            r#"<feed xmlns="http://www.w3.org/2005/Atom">
+  <link rel="self" href="https://example.com/atom.xml"/>
+  <entry>
+    <link href="https://example.com/posts/hello.html"/>
+    <link rel="enclosure" href="https://example.com/files/hello.pdf"/>
+  </entry>
+</feed>"#,
+        )
+        .unwrap()
+        .unwrap();
+        let broken = broken_feed_links(&feed, |link| link.path == Path::new("posts/hello.html"));
+        assert_eq!(broken, vec!["https://example.com/files/hello.pdf"]);
+        assert_eq!(Feed::parse("<urlset/>"), Ok(None));
+        assert!(Feed::parse("<rss>").is_err());
+    }
+}
@@ -0,0 +1,277 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use url::Url;
+
+use crate::html::ParseOptions;
+
+/**
+Which files in the scanned directories are parsed as HTML documents.
+
+Files are parsed if they have one of the `extensions`, match one of the `include` globs (if
+there are any), and match none of the `exclude` globs. Globs are matched against paths
+relative to the scanned directory. Excluded directories aren't walked at all.
+
+Files and directories matched by `.gitignore` and `.ignore` files in the scanned directories
+are skipped too, unless `ignore_files` is off. Hidden files are not skipped.
+
+Symlinked files are always parsed, but symlinked directories are only walked into with
+`follow_symlinks`. Documents found through a symlink are keyed by their path through the link.
+
+Documents are parsed as the `parse` options say. Files larger than `max_file_size` bytes are
+skipped rather than read, as are files more than `max_depth` directories down.
+
+Files in a directory with one of the `mounts` are keyed by their path under its URL prefix
+rather than relative to the directory, though globs are still matched against the latter.
+ */
+#[derive(Debug, Clone)]
+pub struct FileFilter {
+    extensions: Vec<String>,
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    pub follow_symlinks: bool,
+    /// Whether to skip files matched by `.gitignore` and `.ignore` files
+    pub ignore_files: bool,
+    pub parse: ParseOptions,
+    pub max_file_size: Option<u64>,
+    /// How many directories deep to walk, 0 for just the files directly in the scanned directory
+    pub max_depth: Option<usize>,
+    pub mounts: Vec<Mount>,
+}
+
+impl Default for FileFilter {
+    fn default() -> FileFilter {
+        FileFilter {
+            extensions: vec!["html".to_string()],
+            include: None,
+            exclude: GlobSet::empty(),
+            follow_symlinks: false,
+            ignore_files: true,
+            parse: ParseOptions::default(),
+            max_file_size: None,
+            max_depth: None,
+            mounts: vec![],
+        }
+    }
+}
+
+/**
+A scanned directory and the URL path it's deployed at, given on the command line as
+`DIRECTORY=URL_PREFIX` like `build/docs=/docs`.
+
+Mounting directories side by side lets links between them, like `/blog/post.html` or
+`../blog/post.html` from the docs, resolve the way they do on the deployed site.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mount {
+    pub directory: PathBuf,
+    /// The URL path without its leading slash, empty for a directory deployed at `/`
+    pub prefix: PathBuf,
+}
+
+impl Mount {
+    pub fn new<P: Into<PathBuf>>(directory: P, prefix: &str) -> Mount {
+        Mount {
+            directory: directory.into(),
+            prefix: PathBuf::from(prefix.trim_matches('/')),
+        }
+    }
+    pub fn parse(mount: &str) -> Result<Mount, String> {
+        let (directory, prefix) = mount
+            .rsplit_once('=')
+            .filter(|(directory, prefix)| !directory.is_empty() && prefix.starts_with('/'))
+            .ok_or_else(|| format!("expected <DIRECTORY>=/<URL_PREFIX>, got {mount:?}"))?;
+        Ok(Mount::new(directory, prefix))
+    }
+}
+
+fn glob_set(globs: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob).map_err(|err| err.to_string())?);
+    }
+    builder.build().map_err(|err| err.to_string())
+}
+
+impl FileFilter {
+    pub fn new(
+        extensions: &[String],
+        include: &[String],
+        exclude: &[String],
+    ) -> Result<FileFilter, String> {
+        let mut filter = FileFilter::default();
+        if !extensions.is_empty() {
+            filter.extensions = extensions
+                .iter()
+                .map(|extension| extension.trim_start_matches('.').to_string())
+                .collect();
+        }
+        if !include.is_empty() {
+            filter.include = Some(glob_set(include)?);
+        }
+        filter.exclude = glob_set(exclude)?;
+        Ok(filter)
+    }
+    /// Whether a file has one of the extensions of documents to parse
+    pub fn has_extension(&self, path: &Path) -> bool {
+        let extension = path.extension().and_then(OsStr::to_str);
+        self.extensions
+            .iter()
+            .any(|allowed| Some(allowed.as_str()) == extension)
+    }
+    /// Whether a file, relative to the scanned directory, should be parsed
+    pub fn is_document(&self, path: &Path) -> bool {
+        self.has_extension(path)
+            && self
+                .include
+                .as_ref()
+                .is_none_or(|include| include.is_match(path))
+            && !self.exclude.is_match(path)
+    }
+    /// Whether a directory, relative to the scanned directory, should be walked
+    pub fn walks(&self, path: &Path) -> bool {
+        !self.exclude.is_match(path)
+    }
+    /// Whether a file, relative to the scanned directory, is within `max_depth`
+    pub fn within_depth(&self, path: &Path) -> bool {
+        self.max_depth
+            .is_none_or(|max_depth| path.components().count() <= max_depth + 1)
+    }
+    /// The URL prefix the files of a scanned directory are keyed under, empty if it isn't mounted
+    pub fn url_prefix(&self, directory: &Path) -> &Path {
+        self.mounts
+            .iter()
+            .find(|mount| mount.directory == directory)
+            .map_or(Path::new(""), |mount| mount.prefix.as_path())
+    }
+}
+
+/**
+Which hosts external links are requested from, given as globs like `*.example.com`.
+
+A host is requested from if it matches one of the `only` globs (if there are any) and none of
+the `exclude` globs. A host matches a glob if it or any of its parent domains does, so
+`twitter.com` also covers `mobile.twitter.com`. Hosts are matched ignoring case.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct DomainFilter {
+    only: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl DomainFilter {
+    pub fn new(only: &[String], exclude: &[String]) -> Result<DomainFilter, String> {
+        let lowercase = |globs: &[String]| -> Vec<String> {
+            globs.iter().map(|glob| glob.to_ascii_lowercase()).collect()
+        };
+        Ok(DomainFilter {
+            only: match only.is_empty() {
+                true => None,
+                false => Some(glob_set(&lowercase(only))?),
+            },
+            exclude: glob_set(&lowercase(exclude))?,
+        })
+    }
+    /// Whether the host of a URL, or any of its parent domains, matches some glob of a set
+    fn matches(globs: &GlobSet, host: &str) -> bool {
+        let mut domain = host;
+        loop {
+            if globs.is_match(domain) {
+                return true;
+            }
+            match domain.split_once('.') {
+                Some((_, parent)) => domain = parent,
+                None => return false,
+            }
+        }
+    }
+    /// Whether external links to a URL should be requested
+    pub fn allows(&self, url: &str) -> bool {
+        let Some(host) = Url::parse(url)
+            .ok()
+            .and_then(|url| Some(url.host_str()?.trim_end_matches('.').to_ascii_lowercase()))
+        else {
+            return true;
+        };
+        (self.only.as_ref()).is_none_or(|only| DomainFilter::matches(only, &host))
+            && !DomainFilter::matches(&self.exclude, &host)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_file_filter() {
+        let filter = FileFilter::default();
+        assert!(filter.is_document(Path::new("guide/index.html")));
+        assert!(!filter.is_document(Path::new("guide/index.htm")));
+
+        let strings = |strings: &[&str]| strings.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let filter = FileFilter::new(
+            &strings(&["html", ".htm"]),
+            &strings(&["docs/**"]),
+            &strings(&["**/node_modules", "**/*.min.html"]),
+        )
+        .unwrap();
+        assert!(filter.is_document(Path::new("docs/index.html")));
+        assert!(filter.is_document(Path::new("docs/old/page.htm")));
+        assert!(!filter.is_document(Path::new("docs/page.xhtml")));
+        assert!(!filter.is_document(Path::new("blog/index.html")));
+        assert!(!filter.is_document(Path::new("docs/bundle.min.html")));
+        assert!(!filter.walks(Path::new("docs/node_modules")));
+        assert!(filter.walks(Path::new("docs/vendor")));
+
+        assert!(FileFilter::new(&[], &strings(&["docs/[unclosed"]), &[]).is_err());
+    }
+
+    #[test]
+    fn test_mount() {
+        assert_eq!(
+            Mount::parse("build/docs=/docs/"),
+            Ok(Mount::new("build/docs", "docs"))
+        );
+        assert_eq!(Mount::parse("public=/"), Ok(Mount::new("public", "")));
+        assert!(Mount::parse("docs").is_err());
+        assert!(Mount::parse("docs=docs").is_err());
+        assert!(Mount::parse("=/docs").is_err());
+
+        let filter = FileFilter {
+            mounts: vec![Mount::new("build/blog", "/blog")],
+            ..FileFilter::default()
+        };
+        assert_eq!(
+            filter.url_prefix(Path::new("build/blog/")),
+            Path::new("blog")
+        );
+        assert_eq!(filter.url_prefix(Path::new("build/docs")), Path::new(""));
+    }
+
+    #[test]
+    fn test_domain_filter() {
+        let strings = |strings: &[&str]| strings.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let filter = DomainFilter::default();
+        assert!(filter.allows("https://twitter.com/rustlang"));
+
+        let filter = DomainFilter::new(&[], &strings(&["twitter.com", "*.Facebook.com"])).unwrap();
+        assert!(!filter.allows("https://twitter.com/rustlang"));
+        assert!(!filter.allows("https://mobile.TWITTER.com./rustlang"));
+        assert!(!filter.allows("https://www.facebook.com/"));
+        assert!(filter.allows("https://nottwitter.com/"));
+
+        let filter = DomainFilter::new(
+            &strings(&["example.com", "example-*.org"]),
+            &strings(&["legacy.example.com"]),
+        )
+        .unwrap();
+        assert!(filter.allows("https://example.com/"));
+        assert!(filter.allows("https://docs.example.com/guide"));
+        assert!(filter.allows("https://example-shop.org/"));
+        assert!(!filter.allows("https://legacy.example.com/"));
+        assert!(!filter.allows("https://github.com/"));
+
+        assert!(DomainFilter::new(&strings(&["[unclosed"]), &[]).is_err());
+    }
+}
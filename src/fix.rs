@@ -0,0 +1,130 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::report::{Failure, Report};
+
+/**
+The `href` which would fix a broken link, if there's an obvious one: the most similar id for a
+missing fragment, or the rendered page for a link to a source file.
+*/
+pub fn suggested_href(failure: &Failure) -> Option<String> {
+    match failure {
+        Failure::MissingFragment {
+            href, similar_ids, ..
+        } => {
+            let id = similar_ids.first()?;
+            let path = href.split_once('#').map_or(href.as_str(), |(path, _)| path);
+            Some(format!("{path}#{id}"))
+        }
+        Failure::SourceFileLink { rendered, .. } => rendered.clone(),
+        _ => None,
+    }
+}
+
+/**
+Replace the first quoted `href` in `contents` with `replacement`, or nothing if it isn't there.
+*/
+pub fn replace_href(contents: &str, href: &str, replacement: &str) -> Option<String> {
+    ['"', '\''].iter().find_map(|quote| {
+        let needle = format!("{quote}{href}{quote}");
+        contents
+            .contains(&needle)
+            .then(|| contents.replacen(&needle, &format!("{quote}{replacement}{quote}"), 1))
+    })
+}
+
+/**
+A link `rlc fix` rewrites, in a document found on disk.
+*/
+#[derive(Debug, PartialEq, Eq)]
+pub struct Fix {
+    /// The document on disk
+    pub file: PathBuf,
+    pub href: String,
+    pub replacement: String,
+}
+
+/**
+The fixes suggested for the failures of a report, in the documents found in one of the `roots`.
+*/
+pub fn suggested_fixes(report: &Report, roots: &[PathBuf]) -> Vec<Fix> {
+    let failures = report.failures.iter().chain(report.warnings.iter());
+    failures
+        .filter_map(|failure| {
+            let replacement = suggested_href(failure)?;
+            let file = roots
+                .iter()
+                .map(|root| root.join(failure.source()))
+                .find(|path| path.is_file())?;
+            Some(Fix {
+                file,
+                href: failure.href()?.to_string(),
+                replacement,
+            })
+        })
+        .collect()
+}
+
+/**
+Rewrite the links of each fix in its document, returning the fixes which were made. Links which
+aren't written as a quoted attribute are left be.
+*/
+pub fn apply_fixes(fixes: Vec<Fix>) -> std::io::Result<Vec<Fix>> {
+    let mut by_file: BTreeMap<PathBuf, Vec<Fix>> = BTreeMap::new();
+    for fix in fixes {
+        by_file.entry(fix.file.clone()).or_default().push(fix);
+    }
+    let mut applied = vec![];
+    for (file, fixes) in by_file {
+        let mut contents = std::fs::read_to_string(&file)?;
+        let mut changed = false;
+        for fix in fixes {
+            if let Some(fixed) = replace_href(&contents, &fix.href, &fix.replacement) {
+                contents = fixed;
+                changed = true;
+                applied.push(fix);
+            }
+        }
+        if changed {
+            std::fs::write(&file, contents)?;
+        }
+    }
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tree::HtmlFileLink;
+
+    #[test]
+    fn test_suggested_href() {
+        let failure = Failure::MissingFragment {
+            source: "index.html".into(),
+            href: "guide.html#setup".into(),
+            link: HtmlFileLink::new("guide.html#setup"),
+            similar_ids: vec!["set-up".into()],
+            attribute: None,
+            text: None,
+        };
+        assert_eq!(suggested_href(&failure), Some("guide.html#set-up".into()));
+        let failure = Failure::BrokenLink {
+            source: "index.html".into(),
+            href: "gone.html".into(),
+            link: HtmlFileLink::new("gone.html"),
+            attribute: None,
+            text: None,
+        };
+        assert_eq!(suggested_href(&failure), None);
+    }
+
+    #[test]
+    fn test_replace_href() {
+        let contents = "<p>\n<a href=\"guide.html#setup\">Setup</a>\n<a href='guide.html#setup'>";
+        assert_eq!(
+            replace_href(contents, "guide.html#setup", "guide.html#set-up").unwrap(),
+            "<p>\n<a href=\"guide.html#set-up\">Setup</a>\n<a href='guide.html#setup'>"
+        );
+        assert_eq!(replace_href(contents, "guide.html", "other.html"), None);
+    }
+}
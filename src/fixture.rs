@@ -0,0 +1,32 @@
+/*!
+Files on disk for the tests which need them.
+*/
+use std::path::{Path, PathBuf};
+
+use tempfile::TempDir;
+
+/**
+A directory for one test to write its files in, which no other test shares. It's deleted with
+everything in it when dropped, even if the test fails.
+ */
+pub struct Fixture {
+    pub path: PathBuf,
+    _dir: TempDir,
+}
+
+impl Fixture {
+    pub fn new() -> Fixture {
+        let dir = tempfile::Builder::new().prefix("rlc-").tempdir().unwrap();
+        Fixture {
+            path: dir.path().to_path_buf(),
+            _dir: dir,
+        }
+    }
+    /// Write a file at a path relative to the directory, making its parent directories
+    pub fn write<P: AsRef<Path>, C: AsRef<[u8]>>(&self, path: P, contents: C) -> PathBuf {
+        let path = self.path.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+}
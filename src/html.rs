@@ -1,48 +1,736 @@
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
 use std::path::Path;
 
-use scraper::{Html, Selector};
+use html5ever::driver::ParseOpts;
+use html5ever::tendril::TendrilSink;
+use html5ever::tree_builder::TreeBuilderOpts;
+use schemars::JsonSchema;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
 use url::Url;
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::charset::decode_html;
+use crate::css::css_urls;
+use crate::intern::Interned;
+use crate::rewrite::{rewrite_href, Rewrite};
+
 /**
 The relevant contents of an HTML document.
 
 Currently we only care about:
-* The `href` attributes of any link tags, split into absolute and relative URLs
+* The `href` attributes of any `<a>` and `<link>` tags and the URLs in Open Graph and Twitter
+  card meta tags, split into absolute and relative URLs
+* The SVG files and fragments referenced by `<use>` tags, and by `<object>` and `<iframe>` tags
+  which embed an SVG file
+* The `action` of any `<form>`, the `formaction` of any `<button>` or `<input>`, the
+  `poster` of any `<video>` and the `href` of any image map `<area>`, and which of the links
+  came from them
+* Which of the `<a>` and `<area>` hrefs are marked `rel="nofollow"`
+* The text of the first `<a>` with each `href`, so failures can say which link they're about
+* The `href` of any `<a>` which is only a placeholder, like `#`
 * Any `id` attributes on any tags
+* The ids referenced by `<label for>`, `aria-labelledby`, `aria-describedby` and `headers`
+  attributes, which should be in the same document
+* Any `<meta name="relative-link-check">` directives
+* The target of a `<meta http-equiv="refresh">` redirect, which is also checked like a link
+* The ids of any `h2` and `h3` headings left out of the document's `<nav class="toc">`
+* The `srcset` attributes of any `img` tags
+* The `hreflang` and `href` of any `<link rel="alternate">` translations of the document
+* A hash of the whole document, to spot byte-identical copies
+* Any extra links picked out by the `--extract` selectors
+* The `url(...)` references in `style` attributes and `<style>` elements, if
+  `ParseOptions::styles` says so
+
+Every `href` is rewritten by the `ParseOptions::rewrites` rules as it's found.
+
+Links and ids inside `<template>` and `<noscript>` elements are left out, since they aren't
+part of the page as it's shown, unless `ParseOptions::templates` says otherwise. Links on or
+inside elements with the `ParseOptions::ignore_attribute` are left out too, but their ids aren't.
  */
 #[derive(Debug)]
 pub struct HtmlInfo {
-    pub relative_hrefs: Vec<String>,
+    pub relative_hrefs: Vec<Interned>,
     pub external_hrefs: Vec<String>,
-    pub ids: Vec<String>,
+    pub nofollow_hrefs: Vec<String>,
+    /// The attribute each link which isn't an `href` was found in
+    pub link_attributes: HashMap<String, LinkAttribute>,
+    /// The text of the first `<a>` with each `href`, when it has any
+    pub link_texts: HashMap<String, String>,
+    /// The `href` of each `<a>` which leads nowhere, as it's written
+    pub placeholder_hrefs: Vec<String>,
+    pub ids: Ids,
+    /// The attribute and id of each reference to an id of the document itself
+    pub id_references: Vec<(String, String)>,
+    pub directives: Directives,
+    pub redirect: Option<String>,
+    pub headings_missing_from_toc: Vec<String>,
+    pub srcsets: Vec<String>,
+    /// The language and `href` of each alternate version of the document
+    pub alternates: Vec<(String, String)>,
+    pub content_hash: (u64, u64),
+}
+
+/**
+The attribute a link was found in, for links which aren't the `href` of an `<a>` or `<link>`,
+so failures about them can say what they are.
+*/
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinkAttribute {
+    /// The `action` a `<form>` is submitted to
+    Action,
+    /// The `formaction` a `<button>` or `<input>` submits its form to
+    Formaction,
+    /// The `poster` image of a `<video>`
+    Poster,
+    /// The `href` of an image map `<area>`
+    AreaHref,
+}
+
+impl LinkAttribute {
+    /// What a link from the attribute is called
+    pub fn noun(attribute: Option<LinkAttribute>) -> &'static str {
+        match attribute {
+            None => "link",
+            Some(LinkAttribute::Action) => "form action",
+            Some(LinkAttribute::Formaction) => "button form action",
+            Some(LinkAttribute::Poster) => "video poster",
+            Some(LinkAttribute::AreaHref) => "image map link",
+        }
+    }
+}
+
+/**
+The ids in a document or SVG image, in the order they appear, along with a set of them built
+as they're parsed so a `#fragment` can be looked up without scanning every id, which matters
+on API references with tens of thousands of anchors.
+*/
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Ids {
+    list: Vec<Interned>,
+    set: HashSet<Interned>,
+}
+
+impl Ids {
+    /// Whether any of the ids is `id`
+    pub fn contains(&self, id: &str) -> bool {
+        self.set.contains(id)
+    }
+}
+
+impl Deref for Ids {
+    type Target = [Interned];
+    fn deref(&self) -> &[Interned] {
+        &self.list
+    }
+}
+
+impl<'a> IntoIterator for &'a Ids {
+    type Item = &'a Interned;
+    type IntoIter = std::slice::Iter<'a, Interned>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.list.iter()
+    }
+}
+
+impl FromIterator<Interned> for Ids {
+    fn from_iter<I: IntoIterator<Item = Interned>>(ids: I) -> Ids {
+        let list: Vec<Interned> = ids.into_iter().collect();
+        let set = list.iter().cloned().collect();
+        Ids { list, set }
+    }
+}
+
+impl PartialEq<Vec<&str>> for Ids {
+    fn eq(&self, other: &Vec<&str>) -> bool {
+        self.list == *other
+    }
+}
+
+/// The Open Graph and Twitter card meta tags whose content is a URL, rather than text
+pub const META_URL_PROPERTIES: &[&str] = &[
+    "og:url",
+    "og:image",
+    "og:image:url",
+    "og:image:secure_url",
+    "og:video",
+    "og:video:url",
+    "og:video:secure_url",
+    "og:audio",
+    "og:audio:url",
+    "og:audio:secure_url",
+    "twitter:image",
+    "twitter:image:src",
+    "twitter:player",
+    "twitter:player:stream",
+];
+
+/**
+An extra place to find links in documents, given on the command line as `SELECTOR@ATTRIBUTE`.
+
+For sites whose navigation is driven by scripts, like `div[data-href]@data-href`, or to check
+inline frames with `iframe[src]@src`. The value of the attribute on every element matching
+the selector is checked like the `href` of a link.
+ */
+#[derive(Debug, Clone)]
+pub struct Extractor {
+    selector: Selector,
+    pub attribute: String,
+    /// The selector as it was written, for the streaming parser to parse its own way
+    #[cfg(feature = "streaming-parser")]
+    pub source: String,
+}
+
+impl Extractor {
+    pub fn parse(extractor: &str) -> Result<Extractor, String> {
+        let (selector, attribute) = extractor
+            .rsplit_once('@')
+            .filter(|(selector, attribute)| !selector.is_empty() && !attribute.is_empty())
+            .ok_or_else(|| format!("expected <SELECTOR>@<ATTRIBUTE>, got {extractor:?}"))?;
+        let parsed = Selector::parse(selector)
+            .map_err(|err| format!("invalid selector {selector:?}: {err}"))?;
+        Ok(Extractor {
+            selector: parsed,
+            attribute: attribute.to_string(),
+            #[cfg(feature = "streaming-parser")]
+            source: selector.to_string(),
+        })
+    }
+}
+
+/// The attribute which leaves the links of an element out unless another is given
+pub const DEFAULT_IGNORE_ATTRIBUTE: &str = "data-rlc-ignore";
+
+/// Check the name of an attribute given for `ParseOptions::ignore_attribute`, which both parsers
+/// put in a selector
+pub fn parse_attribute_name(name: &str) -> Result<String, String> {
+    let valid = name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    match valid {
+        true => Ok(name.to_ascii_lowercase()),
+        false => Err(format!("invalid attribute name {name:?}")),
+    }
+}
+
+/// Whether a file is an SVG image, whose ids can be linked to like a document's
+pub fn is_svg<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref()
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("svg"))
+}
+
+/// Whether a URL leads to an SVG image, ignoring its query and `#fragment`
+pub fn is_svg_url(url: &str) -> bool {
+    is_svg(url.split(['?', '#']).next().unwrap_or(url))
+}
+
+/**
+The ids in an SVG image, like the symbols of a sprite sheet. An image which isn't well-formed
+XML has none.
+*/
+pub fn parse_svg_ids(contents: &str, options: &ParseOptions) -> Ids {
+    // SVG files exported by editors often declare the SVG 1.1 doctype
+    let xml_options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    let Ok(document) = roxmltree::Document::parse_with_options(contents, xml_options) else {
+        return Ids::default();
+    };
+    document
+        .descendants()
+        .filter_map(|node| node.attribute("id"))
+        .map(|id| options.interned(id))
+        .collect()
+}
+
+/**
+How documents are parsed, besides looking for their usual links and ids.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Where else to look for links
+    pub extractors: Vec<Extractor>,
+    /**
+    Whether to look inside `<template>` and `<noscript>` elements too, for sites which stamp
+    out templates with scripts or are read without them. The contents of `<noscript>` are
+    only parsed as markup then, as they are by browsers with scripts turned off.
+    */
+    pub templates: bool,
+    /// Whether to look for `url(...)` references in `style` attributes and `<style>` elements
+    pub styles: bool,
+    /// How to rewrite every `href` before it's resolved
+    pub rewrites: Vec<Rewrite>,
+    /// The attribute marking elements whose links, and those of everything inside them, are
+    /// placeholders or broken on purpose, so they aren't checked
+    pub ignore_attribute: Option<String>,
+    /// Whether to stream through documents with `html_stream`, rather than building their tree
+    #[cfg(feature = "streaming-parser")]
+    pub streaming: bool,
+    /// Whether the documents are kept once they're checked, so that their hrefs and ids are
+    /// interned to share one copy between them
+    pub intern: bool,
+}
+
+impl ParseOptions {
+    /// An href or id of a document, interned if the documents are kept
+    pub(crate) fn interned(&self, string: &str) -> Interned {
+        match self.intern {
+            true => Interned::new(string),
+            false => Interned::unshared(string),
+        }
+    }
+    fn parse_document(&self, document: &str) -> Html {
+        if self.templates {
+            let options = ParseOpts {
+                tree_builder: TreeBuilderOpts {
+                    scripting_enabled: false,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            return html5ever::parse_document(Html::new_document(), options).one(document);
+        }
+        let mut html = Html::parse_document(document);
+        let template_selector = Selector::parse("template").unwrap();
+        // The template element stays, but not the contents it holds for scripts to stamp out
+        let contents: Vec<_> = html
+            .select(&template_selector)
+            .flat_map(|element| element.children().map(|child| child.id()))
+            .collect();
+        for id in contents {
+            if let Some(mut content) = html.tree.get_mut(id) {
+                content.detach();
+            }
+        }
+        html
+    }
+}
+
+/**
+The URL an href loads, which for a protocol-relative URL like `//cdn.example.com/style.css` is
+the absolute `https` URL it loads on a site served over https. Links keep the href as it's
+written, and only take the scheme when they're told apart and requested.
+*/
+pub fn with_scheme(href: &str) -> Cow<'_, str> {
+    match href.starts_with("//") {
+        true => Cow::Owned(format!("https:{href}")),
+        false => Cow::Borrowed(href),
+    }
+}
+
+/// Whether an href leads within the site, rather than being a URL of its own
+pub fn is_relative_href(href: &str) -> bool {
+    Url::parse(&with_scheme(href)) == Err(url::ParseError::RelativeUrlWithoutBase)
+}
+
+/**
+Parse the URL out of the content of a refresh meta tag, like `0; url=new/page.html`.
+*/
+pub fn parse_refresh(content: &str) -> Option<String> {
+    let (_, target) = content.split_once([';', ','])?;
+    let target = target.trim_start();
+    if !target.get(..3)?.eq_ignore_ascii_case("url") {
+        return None;
+    }
+    let target = target[3..].trim_start().strip_prefix('=')?.trim();
+    let target = target.trim_matches(|c| c == '"' || c == '\'');
+    (!target.is_empty()).then(|| target.to_string())
+}
+
+/**
+Split a `srcset` attribute into its image URLs and their descriptors, like `2x` or `640w`.
+
+A candidate without a descriptor is `1x`.
+*/
+pub fn parse_srcset(srcset: &str) -> Vec<(&str, &str)> {
+    srcset
+        .split(',')
+        .filter_map(|candidate| {
+            let mut parts = candidate.split_whitespace();
+            Some((parts.next()?, parts.next().unwrap_or("1x")))
+        })
+        .collect()
+}
+
+/**
+Per-page settings declared by the page author.
+
+A document can adjust how it is checked with a meta tag like
+`<meta name="relative-link-check" content="no-fragments, base=guide">`.
+The content is a comma or whitespace separated list of:
+* `skip`: don't check any of the links in this document
+* `no-fragments`: check that linked documents exist, but not their `#fragment`s
+* `base=<dir>`: resolve relative links against `<dir>` (relative to the scanned directory)
+  instead of the directory containing the document
+ */
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Directives {
+    pub skip: bool,
+    pub no_fragments: bool,
+    pub base: Option<String>,
+}
+
+impl Directives {
+    pub fn parse(content: &str) -> Directives {
+        let mut directives = Directives::default();
+        for token in content
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|token| !token.is_empty())
+        {
+            match token.split_once('=') {
+                Some(("base", base)) => directives.base = Some(base.to_string()),
+                None if token == "skip" => directives.skip = true,
+                None if token == "no-fragments" => directives.no_fragments = true,
+                _ => eprintln!("Ignoring unknown relative-link-check directive {token:?}"),
+            }
+        }
+        directives
+    }
+}
+
+/**
+Two independent hashes of a whole document, to spot byte-identical copies. The second tells
+apart the different documents which happen to share the first, as a few will on large sites.
+*/
+pub fn content_hash(document: &str) -> (u64, u64) {
+    let hash = |seed: u8| {
+        let mut hasher = DefaultHasher::new();
+        seed.hash(&mut hasher);
+        document.hash(&mut hasher);
+        hasher.finish()
+    };
+    (hash(0), hash(1))
+}
+
+/**
+An `<a>` which leads nowhere, usually left in while a page was being written.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placeholder {
+    /// `href=""`, which reloads the page
+    Empty,
+    /// `href="#"`, which scrolls to the top of the page
+    Hash,
+    /// `href="javascript:void(0)"`, which does nothing
+    Javascript,
+}
+
+impl Placeholder {
+    /// Which placeholder an `href` is, if it's one
+    pub fn of(href: &str) -> Option<Placeholder> {
+        let href = href.trim();
+        if href.is_empty() {
+            return Some(Placeholder::Empty);
+        }
+        if href == "#" {
+            return Some(Placeholder::Hash);
+        }
+        let script = href
+            .get(.."javascript:".len())
+            .filter(|scheme| scheme.eq_ignore_ascii_case("javascript:"))
+            .map(|scheme| &href[scheme.len()..])?;
+        let script: String = script
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != ';')
+            .collect();
+        matches!(script.as_str(), "" | "void(0)" | "void0" | "void(null)")
+            .then_some(Placeholder::Javascript)
+    }
+}
+
+/**
+The text of the first link with each `href` among `anchors`, with its whitespace collapsed.
+Links without any text, like those wrapping an image, are left out.
+*/
+pub fn link_texts<I: IntoIterator<Item = (String, String)>>(anchors: I) -> HashMap<String, String> {
+    let mut texts = HashMap::new();
+    for (href, text) in anchors {
+        let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+        if !text.is_empty() {
+            texts.entry(href).or_insert(text);
+        }
+    }
+    texts
+}
+
+/// The link in an attribute other than `href` of an element, like the `action` of a `<form>`
+pub fn attribute_url<'a>(
+    element: &str,
+    attr: impl Fn(&str) -> Option<&'a str>,
+) -> Option<(&'a str, LinkAttribute)> {
+    match element {
+        "form" => Some((attr("action")?, LinkAttribute::Action)),
+        "video" => Some((attr("poster")?, LinkAttribute::Poster)),
+        "area" => Some((attr("href")?, LinkAttribute::AreaHref)),
+        "button" | "input" => Some((attr("formaction")?, LinkAttribute::Formaction)),
+        _ => None,
+    }
+}
+
+/// The ids an element refers to with `for`, `aria-labelledby` and the like, with the attribute
+pub fn id_references<'a>(
+    element: &str,
+    attr: impl Fn(&str) -> Option<&'a str>,
+) -> Vec<(String, String)> {
+    ["for", "aria-labelledby", "aria-describedby", "headers"]
+        .into_iter()
+        .filter(|attribute| *attribute != "for" || element == "label")
+        .filter_map(|attribute| Some((attribute, attr(attribute)?)))
+        // All but `for` take a list of ids
+        .flat_map(|(attribute, ids)| {
+            ids.split_ascii_whitespace()
+                .map(move |id| (attribute.to_string(), id.to_string()))
+        })
+        .collect()
 }
 
 impl HtmlInfo {
-    pub fn parse_file<P: AsRef<Path>>(path: P) -> std::io::Result<HtmlInfo> {
-        let contents = std::fs::read_to_string(path)?;
-        Ok(Self::parse(&contents))
+    /// Every id which appears more than once in the document, with how many times it appears
+    pub fn duplicate_ids(&self) -> Vec<(&Interned, usize)> {
+        let mut counts: BTreeMap<&Interned, usize> = BTreeMap::new();
+        for id in self.ids.iter() {
+            *counts.entry(id).or_default() += 1;
+        }
+        counts.into_iter().filter(|(_, count)| *count > 1).collect()
     }
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn parse_file<P: AsRef<Path>>(
+        path: P,
+        options: &ParseOptions,
+    ) -> std::io::Result<HtmlInfo> {
+        let contents = decode_html(&std::fs::read(path)?)?;
+        Ok(Self::parse_with(&contents, options))
+    }
+    #[cfg(test)]
     pub fn parse(document: &str) -> HtmlInfo {
-        let document = Html::parse_document(document);
+        Self::parse_with(document, &ParseOptions::default())
+    }
+    /// Parse a document as the `options` say
+    pub fn parse_with(document: &str, options: &ParseOptions) -> HtmlInfo {
+        #[cfg(feature = "streaming-parser")]
+        if options.streaming {
+            if let Some(info) = crate::html_stream::parse(document, options) {
+                return info;
+            }
+        }
+        let content_hash = content_hash(document);
+        let html = options.parse_document(document);
+        // Detached templates are still in the tree, so only what's under the root is searched
+        let document = html.root_element();
+        let ignored: HashSet<_> = match &options.ignore_attribute {
+            Some(attribute) => {
+                let ignore_selector = Selector::parse(&format!("[{attribute}], [{attribute}] *"))
+                    .expect("The attribute name was checked");
+                document.select(&ignore_selector).map(|e| e.id()).collect()
+            }
+            None => HashSet::new(),
+        };
+        let checked = |element: &ElementRef| !ignored.contains(&element.id());
         // TODO img src
         // TODO other srcs
-        let link_selector = Selector::parse("a[href]").unwrap();
-        let (relative_hrefs, external_hrefs) = document
-            .select(&link_selector)
+        let link_selector = Selector::parse("a[href], link[href]").unwrap();
+        // Open Graph uses `property`, but Twitter cards are usually given a `name`
+        let meta_url_selector =
+            Selector::parse("meta[property][content], meta[name][content]").unwrap();
+        let meta_urls = document
+            .select(&meta_url_selector)
+            .filter(checked)
+            .filter(|element| {
+                let property = element
+                    .value()
+                    .attr("property")
+                    .or(element.value().attr("name"));
+                property.is_some_and(|property| META_URL_PROPERTIES.contains(&property))
+            })
+            .filter_map(|element| element.value().attr("content"));
+        // `<use>` is given an `xlink:href` by older SVG editors, so its `href` may be namespaced
+        let svg_selector = Selector::parse("use, object[data], iframe[src]").unwrap();
+        let svg_urls = document
+            .select(&svg_selector)
+            .filter(checked)
+            .filter_map(|element| match element.value().name() {
+                "use" => element
+                    .value()
+                    .attrs()
+                    .find_map(|(name, value)| (name == "href").then_some(value)),
+                "object" => element.value().attr("data").filter(|url| is_svg_url(url)),
+                _ => element.value().attr("src").filter(|url| is_svg_url(url)),
+            });
+        let attribute_selector = Selector::parse(
+            "form[action], button[formaction], input[formaction], video[poster], area[href]",
+        )
+        .unwrap();
+        let attribute_urls: Vec<(&str, LinkAttribute)> = document
+            .select(&attribute_selector)
+            .filter(checked)
+            .filter_map(|element| {
+                let element = element.value();
+                attribute_url(element.name(), |name| element.attr(name))
+            })
+            .collect();
+        let rewrite = |href: &str| rewrite_href(&options.rewrites, href);
+        let link_attributes = attribute_urls
+            .iter()
+            .map(|(url, attribute)| (rewrite(url), *attribute))
+            .collect();
+        let anchor_selector = Selector::parse("a[href]").unwrap();
+        let anchors: Vec<ElementRef> = document.select(&anchor_selector).filter(checked).collect();
+        let link_texts = link_texts(anchors.iter().map(|element| {
+            let href = element.value().attr("href").unwrap_or_default();
+            (rewrite(href), element.text().collect())
+        }));
+        let placeholder_hrefs = anchors
+            .iter()
             .filter_map(|element| element.value().attr("href"))
+            .filter(|href| Placeholder::of(href).is_some())
             .map(String::from)
-            .partition(|href| Url::parse(href) == Err(url::ParseError::RelativeUrlWithoutBase));
+            .collect();
+        let style_urls = match options.styles {
+            true => {
+                let style_selector = Selector::parse("[style], style").unwrap();
+                document
+                    .select(&style_selector)
+                    .filter(checked)
+                    .flat_map(|element| match element.value().name() {
+                        "style" => css_urls(&element.text().collect::<String>()),
+                        _ => css_urls(element.value().attr("style").unwrap_or_default()),
+                    })
+                    .collect()
+            }
+            false => vec![],
+        };
+        let refresh_selector = Selector::parse("meta[http-equiv][content]").unwrap();
+        let redirect = document
+            .select(&refresh_selector)
+            .filter(|element| {
+                element
+                    .value()
+                    .attr("http-equiv")
+                    .is_some_and(|equiv| equiv.eq_ignore_ascii_case("refresh"))
+            })
+            .filter_map(|element| element.value().attr("content"))
+            .find_map(parse_refresh)
+            .map(|redirect| rewrite(&redirect));
+        let (relative_hrefs, external_hrefs): (Vec<String>, _) = document
+            .select(&link_selector)
+            .filter(checked)
+            .filter_map(|element| element.value().attr("href"))
+            .chain(meta_urls)
+            .chain(svg_urls)
+            .chain(attribute_urls.iter().map(|(url, _)| *url))
+            .chain(options.extractors.iter().flat_map(|extractor| {
+                document
+                    .select(&extractor.selector)
+                    .filter(checked)
+                    .filter_map(|element| element.value().attr(&extractor.attribute))
+            }))
+            .map(rewrite)
+            .chain(style_urls.iter().map(|url| rewrite(url)))
+            .chain(redirect.clone())
+            .partition(|href| is_relative_href(href));
+        let nofollow_selector =
+            Selector::parse(r#"a[href][rel~="nofollow" i], area[href][rel~="nofollow" i]"#)
+                .unwrap();
+        let nofollow_hrefs = document
+            .select(&nofollow_selector)
+            .filter(checked)
+            .filter_map(|element| element.value().attr("href"))
+            .map(rewrite)
+            .collect();
 
         let id_selector = Selector::parse("*[id]").unwrap();
         let ids = document
             .select(&id_selector)
             .filter_map(|element| element.value().attr("id"))
+            .map(|id| options.interned(id))
+            .collect();
+        let id_reference_selector =
+            Selector::parse("label[for], [aria-labelledby], [aria-describedby], [headers]")
+                .unwrap();
+        let id_references = document
+            .select(&id_reference_selector)
+            .filter(checked)
+            .flat_map(|element| {
+                let element = element.value();
+                id_references(element.name(), |name| element.attr(name))
+            })
+            .collect();
+
+        // The fragments of a table of contents are also checked like any other link, so only
+        // the headings it leaves out need looking for here
+        let toc_selector = Selector::parse(r##"nav.toc a[href^="#"]"##).unwrap();
+        let toc: Vec<&str> = document
+            .select(&toc_selector)
+            .filter_map(|element| element.value().attr("href"))
+            .map(|href| &href[1..])
+            .collect();
+        let heading_selector = Selector::parse("h2[id], h3[id]").unwrap();
+        let headings_missing_from_toc = if toc.is_empty() {
+            vec![]
+        } else {
+            document
+                .select(&heading_selector)
+                .filter_map(|element| element.value().attr("id"))
+                .filter(|id| !toc.contains(id))
+                .map(String::from)
+                .collect()
+        };
+
+        let srcset_selector = Selector::parse("img[srcset]").unwrap();
+        let srcsets = document
+            .select(&srcset_selector)
+            .filter(checked)
+            .filter_map(|element| element.value().attr("srcset"))
             .map(String::from)
             .collect();
+
+        let alternate_selector =
+            Selector::parse(r#"link[rel~="alternate" i][hreflang][href]"#).unwrap();
+        let alternates = document
+            .select(&alternate_selector)
+            .filter(checked)
+            .filter_map(|element| {
+                let hreflang = element.value().attr("hreflang")?;
+                let href = element.value().attr("href")?;
+                Some((hreflang.to_string(), rewrite(href)))
+            })
+            .collect();
+
+        let meta_selector =
+            Selector::parse(r#"meta[name="relative-link-check"][content]"#).unwrap();
+        let directives = document
+            .select(&meta_selector)
+            .filter_map(|element| element.value().attr("content"))
+            .collect::<Vec<&str>>()
+            .join(",");
+        let directives = Directives::parse(&directives);
         HtmlInfo {
-            relative_hrefs,
+            relative_hrefs: (relative_hrefs.iter())
+                .map(|href| options.interned(href))
+                .collect(),
             external_hrefs,
+            nofollow_hrefs,
+            link_attributes,
+            link_texts,
+            placeholder_hrefs,
             ids,
+            id_references,
+            directives,
+            redirect,
+            headings_missing_from_toc,
+            srcsets,
+            alternates,
+            content_hash,
         }
     }
 }
@@ -50,6 +738,7 @@ impl HtmlInfo {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::rewrite::RewriteConfig;
     #[test]
     fn test_parse() {
         let html_info = HtmlInfo::parse(
@@ -67,5 +756,458 @@ mod test {
         );
         assert_eq!(html_info.external_hrefs, vec!["https://www.google.com"]);
         assert_eq!(html_info.ids, vec!["main", "url", "sub"]);
+        assert!(html_info.ids.contains("url"));
+        assert!(!html_info.ids.contains("ur"));
+        assert_eq!(html_info.duplicate_ids(), vec![]);
+    }
+
+    #[test]
+    fn test_placeholder() {
+        assert_eq!(Placeholder::of(" "), Some(Placeholder::Empty));
+        assert_eq!(Placeholder::of("#"), Some(Placeholder::Hash));
+        for href in [
+            "javascript:void(0)",
+            "javascript:void 0;",
+            "JAVASCRIPT:",
+            "javascript:;",
+        ] {
+            assert_eq!(
+                Placeholder::of(href),
+                Some(Placeholder::Javascript),
+                "{href}"
+            );
+        }
+        for href in ["#top", "javascript:history.back()", "java.html", "./"] {
+            assert_eq!(Placeholder::of(href), None, "{href}");
+        }
+    }
+
+    #[test]
+    fn test_parse_link_texts() {
+        let html_info = HtmlInfo::parse(
+            r#"
+<a href="setup/install.html"><b>Installation</b>
+    guide</a>
+<a href="setup/install.html">Install</a>
+<a href="logo.html"><img src="logo.png"></a>
+<link rel="stylesheet" href="style.css">"#,
+        );
+        assert_eq!(
+            html_info.link_texts,
+            HashMap::from([("setup/install.html".into(), "Installation guide".into())])
+        );
+    }
+
+    #[test]
+    fn test_parse_link_attributes() {
+        let html_info = HtmlInfo::parse(
+            r#"
+<form action="submit.html">
+    <button formaction="../preview.html">Preview</button>
+    <input type="submit" formaction="https://example.com/submit">
+</form>
+<video poster="poster.jpg"><source src="movie.mp4"></video>
+<form method="dialog"></form>
+<map name="regions">
+    <area shape="rect" coords="0,0,10,10" href="north.html#map" alt="North">
+    <area shape="rect" coords="0,10,10,20" href="https://example.com/south" rel="nofollow">
+    <area shape="default" nohref>
+</map>"#,
+        );
+        assert_eq!(
+            html_info.relative_hrefs,
+            vec![
+                "submit.html",
+                "../preview.html",
+                "poster.jpg",
+                "north.html#map"
+            ]
+        );
+        assert_eq!(
+            html_info.external_hrefs,
+            vec!["https://example.com/submit", "https://example.com/south"]
+        );
+        assert_eq!(html_info.nofollow_hrefs, vec!["https://example.com/south"]);
+        assert_eq!(
+            html_info.link_attributes.get("north.html#map"),
+            Some(&LinkAttribute::AreaHref)
+        );
+        assert_eq!(
+            html_info.link_attributes.get("submit.html"),
+            Some(&LinkAttribute::Action)
+        );
+        assert_eq!(
+            html_info.link_attributes.get("../preview.html"),
+            Some(&LinkAttribute::Formaction)
+        );
+        assert_eq!(
+            html_info.link_attributes.get("poster.jpg"),
+            Some(&LinkAttribute::Poster)
+        );
+    }
+
+    #[test]
+    fn test_parse_meta_urls() {
+        let html_info = HtmlInfo::parse(
+            r#"
+<head>
+    <link rel="canonical" href="/guide/">
+    <link rel="stylesheet" href="style.css">
+    <meta property="og:image" content="images/card.png">
+    <meta property="og:title" content="A title, not a URL">
+    <meta name="twitter:image" content="https://example.com/card.png">
+    <meta name="description" content="guide.html">
+</head>
+<a href="page.html">a</a>"#,
+        );
+        assert_eq!(
+            html_info.relative_hrefs,
+            vec!["/guide/", "style.css", "page.html", "images/card.png"]
+        );
+        assert_eq!(
+            html_info.external_hrefs,
+            vec!["https://example.com/card.png"]
+        );
+    }
+
+    #[test]
+    fn test_parse_svg_references() {
+        let html_info = HtmlInfo::parse(
+            r##"
+<svg><use href="sprite.svg#arrow"/><use xlink:href="icons.svg#close"/><use href="#inline"/></svg>
+<object data="diagram.svg#detail" type="image/svg+xml"></object>
+<object data="movie.swf"></object>
+<iframe src="chart.SVG#bar"></iframe>
+<iframe src="embed.html"></iframe>"##,
+        );
+        assert_eq!(
+            html_info.relative_hrefs,
+            vec![
+                "sprite.svg#arrow",
+                "icons.svg#close",
+                "#inline",
+                "diagram.svg#detail",
+                "chart.SVG#bar"
+            ]
+        );
+        assert_eq!(
+            parse_svg_ids(
+                r#"<?xml version="1.0"?>
+<!DOCTYPE svg PUBLIC "-//W3C//DTD SVG 1.1//EN" "http://www.w3.org/Graphics/SVG/1.1/DTD/svg11.dtd">
+<svg xmlns="http://www.w3.org/2000/svg" id="sprite">
+  <symbol id="arrow"><path d="M0 0"/></symbol>
+  <g><symbol id="close"/></g>
+</svg>"#,
+                &ParseOptions::default()
+            ),
+            vec!["sprite", "arrow", "close"]
+        );
+        assert!(parse_svg_ids("<svg id=broken>", &ParseOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn test_parse_extractors() {
+        let options = ParseOptions {
+            extractors: vec![
+                Extractor::parse("div[data-href]@data-href").unwrap(),
+                Extractor::parse("iframe[src]@src").unwrap(),
+            ],
+            ..ParseOptions::default()
+        };
+        let html_info = HtmlInfo::parse_with(
+            r#"
+<div class="card" data-href="guide.html#setup">Guide</div>
+<iframe src="https://example.com/embed"></iframe>
+<a href="index.html">Home</a>"#,
+            &options,
+        );
+        assert_eq!(
+            html_info.relative_hrefs,
+            vec!["index.html", "guide.html#setup"]
+        );
+        assert_eq!(html_info.external_hrefs, vec!["https://example.com/embed"]);
+        assert!(Extractor::parse("a[data-url]").is_err());
+        assert!(Extractor::parse("@href").is_err());
+        assert!(Extractor::parse("a[@href").is_err());
+    }
+
+    #[test]
+    fn test_parse_templates() {
+        let document = r#"
+<a href="index.html" id="top">Home</a>
+<template id="row"><a href="item.html" id="item">Item</a></template>
+<noscript><a href="no-js.html" id="fallback">Without scripts</a></noscript>"#;
+        let html_info = HtmlInfo::parse(document);
+        assert_eq!(html_info.relative_hrefs, vec!["index.html"]);
+        assert_eq!(html_info.ids, vec!["top", "row"]);
+        let options = ParseOptions {
+            templates: true,
+            ..ParseOptions::default()
+        };
+        let html_info = HtmlInfo::parse_with(document, &options);
+        assert_eq!(
+            html_info.relative_hrefs,
+            vec!["index.html", "item.html", "no-js.html"]
+        );
+        assert_eq!(html_info.ids, vec!["top", "row", "item", "fallback"]);
+    }
+
+    #[test]
+    fn test_parse_styles() {
+        let document = r#"
+<style>.hero { background: url("images/hero.png"); }</style>
+<div style="background-image: url(/img/bg.jpg)"></div>
+<p style="color: red">Text</p>"#;
+        assert!(HtmlInfo::parse(document).relative_hrefs.is_empty());
+        let options = ParseOptions {
+            styles: true,
+            ..ParseOptions::default()
+        };
+        let html_info = HtmlInfo::parse_with(document, &options);
+        assert_eq!(
+            html_info.relative_hrefs,
+            vec!["images/hero.png", "/img/bg.jpg"]
+        );
+    }
+
+    #[test]
+    fn test_parse_nofollow() {
+        let html_info = HtmlInfo::parse(
+            r#"
+<a href="https://example.com/ad" rel="sponsored NOFOLLOW">a</a>
+<a href="https://example.com/" rel="noopener">b</a>
+<link rel="nofollow" href="https://example.com/style.css">"#,
+        );
+        assert_eq!(html_info.nofollow_hrefs, vec!["https://example.com/ad"]);
+    }
+
+    #[test]
+    fn test_parse_protocol_relative() {
+        let html_info = HtmlInfo::parse(
+            r#"<link rel="stylesheet" href="//cdn.example.com/s.css">
+            <link rel="dns-prefetch" href="//fonts.example.com">
+            <a href="//example.com/ad" rel="nofollow">Ad</a><a href="/guide/">Guide</a>"#,
+        );
+        assert_eq!(html_info.relative_hrefs, vec![Interned::from("/guide/")]);
+        assert_eq!(
+            html_info.external_hrefs,
+            vec![
+                "//cdn.example.com/s.css",
+                "//fonts.example.com",
+                "//example.com/ad"
+            ]
+        );
+        assert_eq!(html_info.nofollow_hrefs, vec!["//example.com/ad"]);
+        assert_eq!(with_scheme("//example.com/ad"), "https://example.com/ad");
+        assert_eq!(with_scheme("/guide/"), "/guide/");
+    }
+
+    #[test]
+    fn test_duplicate_ids() {
+        let html_info = HtmlInfo::parse(
+            r#"<h2 id="b"/><h2 id="a"/><p id="b"/><p id="a"/><p id="b"/><p id="c"/>"#,
+        );
+        assert_eq!(
+            html_info.duplicate_ids(),
+            vec![(&"a".into(), 2), (&"b".into(), 3)]
+        );
+    }
+
+    #[test]
+    fn test_parse_ignore_attribute() {
+        let document = r#"
+<a href="checked.html" id="top">Checked</a>
+<a href="placeholder.html" data-rlc-ignore>Placeholder</a>
+<ul data-rlc-ignore>
+  <li id="example"><a href="example.html#gone">Example</a><img srcset="x.png 2x">
+  <li><form action="/search"></form><div data-href="spa/route"></div>
+</ul>
+<p><a href="after.html" data-skip>After</a>"#;
+        let extractor = Extractor::parse("div[data-href]@data-href").unwrap();
+        let options = ParseOptions {
+            extractors: vec![extractor],
+            ignore_attribute: Some("data-rlc-ignore".into()),
+            ..ParseOptions::default()
+        };
+        let html_info = HtmlInfo::parse_with(document, &options);
+        assert_eq!(html_info.relative_hrefs, vec!["checked.html", "after.html"]);
+        assert!(html_info.link_attributes.is_empty());
+        assert!(html_info.srcsets.is_empty());
+        assert_eq!(html_info.ids, vec!["top", "example"]);
+        let html_info = HtmlInfo::parse_with(
+            document,
+            &ParseOptions {
+                ignore_attribute: Some("data-skip".into()),
+                ..options
+            },
+        );
+        assert_eq!(
+            html_info.relative_hrefs,
+            vec![
+                "checked.html",
+                "placeholder.html",
+                "example.html#gone",
+                "/search",
+                "spa/route"
+            ]
+        );
+        assert_eq!(parse_attribute_name("Data-Skip"), Ok("data-skip".into()));
+        assert!(parse_attribute_name("a]").is_err());
+        assert!(parse_attribute_name("").is_err());
+    }
+
+    #[test]
+    fn test_parse_directives() {
+        let html_info = HtmlInfo::parse(
+            "
+<head>
+    <meta name=\"relative-link-check\" content=\"no-fragments, base=guide\">
+    <meta name=\"description\" content=\"skip\">
+</head>",
+        );
+        assert_eq!(
+            html_info.directives,
+            Directives {
+                skip: false,
+                no_fragments: true,
+                base: Some("guide".to_string()),
+            }
+        );
+        assert!(Directives::parse("skip").skip);
+        assert_eq!(Directives::parse(""), Directives::default());
+    }
+
+    #[test]
+    fn test_parse_redirect() {
+        let html_info = HtmlInfo::parse(
+            "
+<head>
+    <meta http-equiv=\"Refresh\" content=\"0; url=new/page.html#top\">
+</head>
+<a href=\"other.html\">a</a>",
+        );
+        assert_eq!(html_info.redirect, Some("new/page.html#top".to_string()));
+        assert_eq!(
+            html_info.relative_hrefs,
+            vec!["other.html", "new/page.html#top"]
+        );
+        assert_eq!(
+            parse_refresh("0;URL='moved.html'"),
+            Some("moved.html".into())
+        );
+        assert_eq!(
+            parse_refresh("5, url = https://example.com/"),
+            Some("https://example.com/".into())
+        );
+        assert_eq!(parse_refresh("5"), None);
+        assert_eq!(parse_refresh("0; url="), None);
+        assert_eq!(parse_refresh("0; target=x"), None);
+    }
+
+    #[test]
+    fn test_parse_srcset() {
+        let html_info =
+            HtmlInfo::parse(r#"<img src="a.png" srcset="a.png, a@2x.png 2x"><img src="b.png">"#);
+        assert_eq!(html_info.srcsets, vec!["a.png, a@2x.png 2x"]);
+        assert_eq!(
+            parse_srcset(&html_info.srcsets[0]),
+            vec![("a.png", "1x"), ("a@2x.png", "2x")]
+        );
+        assert_eq!(
+            parse_srcset(" small.jpg 480w,\n large.jpg  1080w ,"),
+            vec![("small.jpg", "480w"), ("large.jpg", "1080w")]
+        );
+    }
+
+    #[test]
+    fn test_parse_alternates() {
+        let html_info = HtmlInfo::parse(
+            r#"
+<head>
+    <link rel="alternate" hreflang="en" href="/en/page.html">
+    <link rel="Alternate" hreflang="fr" href="../fr/page.html">
+    <link rel="alternate" type="application/rss+xml" href="feed.xml">
+    <link rel="stylesheet" hreflang="de" href="style.css">
+</head>"#,
+        );
+        assert_eq!(
+            html_info.alternates,
+            vec![
+                ("en".to_string(), "/en/page.html".to_string()),
+                ("fr".to_string(), "../fr/page.html".to_string())
+            ]
+        );
+        assert_eq!(html_info.relative_hrefs.len(), 4);
+    }
+
+    #[test]
+    fn test_parse_rewrites() {
+        let options = ParseOptions {
+            rewrites: vec![Rewrite::new(&RewriteConfig {
+                pattern: "^https://example.com/v2/".into(),
+                replacement: "/".into(),
+            })
+            .unwrap()],
+            ..ParseOptions::default()
+        };
+        let html_info = HtmlInfo::parse_with(
+            r#"
+<a href="https://example.com/v2/guide.html" rel="nofollow">Guide</a>
+<form action="https://example.com/v2/search.html"></form>
+<a href="https://example.com/v3/">v3</a>"#,
+            &options,
+        );
+        assert_eq!(
+            html_info.relative_hrefs,
+            vec!["/guide.html", "/search.html"]
+        );
+        assert_eq!(html_info.external_hrefs, vec!["https://example.com/v3/"]);
+        assert_eq!(html_info.nofollow_hrefs, vec!["/guide.html"]);
+        assert_eq!(
+            html_info.link_attributes.get("/search.html"),
+            Some(&LinkAttribute::Action)
+        );
+    }
+
+    #[test]
+    fn test_parse_id_references() {
+        let html_info = HtmlInfo::parse(
+            r#"
+<label for="email">Email</label><input id="email" aria-describedby="email-hint">
+<div role="dialog" aria-labelledby="title subtitle"></div>
+<output for="a b"></output>
+<table><tr><th id="name">Name</th></tr><tr><td headers="name">rlc</td></tr></table>
+<template><label for="in-template"></label></template>"#,
+        );
+        let reference = |attribute: &str, id: &str| (attribute.to_string(), id.to_string());
+        assert_eq!(
+            html_info.id_references,
+            vec![
+                reference("for", "email"),
+                reference("aria-describedby", "email-hint"),
+                reference("aria-labelledby", "title"),
+                reference("aria-labelledby", "subtitle"),
+                reference("headers", "name"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_toc() {
+        let html_info = HtmlInfo::parse(
+            r##"
+<nav class="toc"><a href="#install">Install</a><a href="#usage">Usage</a></nav>
+<h2 id="install">Install</h2>
+<h3 id="from-source">From source</h3>
+<h2 id="usage">Usage</h2>
+<h4 id="details">Details</h4>
+<h2 id="faq">FAQ</h2>"##,
+        );
+        assert_eq!(
+            html_info.headings_missing_from_toc,
+            vec!["from-source", "faq"]
+        );
+        let html_info = HtmlInfo::parse(r#"<h2 id="install">Install</h2>"#);
+        assert!(html_info.headings_missing_from_toc.is_empty());
     }
 }
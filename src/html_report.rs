@@ -0,0 +1,205 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+#[cfg(not(target_arch = "wasm32"))]
+use url::Url;
+
+use crate::report::{Failure, Report};
+
+const STYLE: &str = "
+body { font-family: system-ui, sans-serif; margin: 2em auto; max-width: 60em; color: #222; }
+h1 { font-size: 1.6em; }
+h2 { font-size: 1.2em; margin-top: 2em; border-bottom: 1px solid #ddd; }
+table { border-collapse: collapse; }
+th, td { text-align: left; padding: 0.3em 1em 0.3em 0; vertical-align: top; }
+td.count { text-align: right; }
+code { background: #f3f3f3; padding: 0 0.2em; }
+.kind { color: #a00; white-space: nowrap; }
+.ok { color: #070; }
+";
+
+/// Escape text for use in HTML content or attribute values
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A `file://` URL for the document, if it can be found in one of the `roots`
+#[cfg(not(target_arch = "wasm32"))]
+fn file_url(roots: &[PathBuf], source: &Path) -> Option<String> {
+    roots
+        .iter()
+        .map(|root| root.join(source))
+        .find(|path| path.is_file())
+        .and_then(|path| path.canonicalize().ok())
+        .and_then(|path| Url::from_file_path(path).ok())
+        .map(String::from)
+}
+
+/// Documents checked from wasm32 have no files on disk to link to
+#[cfg(target_arch = "wasm32")]
+fn file_url(_roots: &[PathBuf], _source: &Path) -> Option<String> {
+    None
+}
+
+/**
+Render a report as a standalone web page.
+
+Failures are grouped by the document they were found in, after a summary of how many failures
+of each kind were found. Documents that can be found in one of the `roots` are linked so they
+can be opened straight from the report.
+ */
+pub fn render(report: &Report, roots: &[PathBuf]) -> String {
+    let mut by_source: BTreeMap<&Path, Vec<&Failure>> = BTreeMap::new();
+    let mut by_kind: BTreeMap<&str, usize> = BTreeMap::new();
+    for failure in report.failures.iter() {
+        by_source.entry(failure.source()).or_default().push(failure);
+        *by_kind.entry(failure.kind()).or_default() += 1;
+    }
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<title>Link check report</title>\n");
+    let _ = writeln!(html, "<style>{STYLE}</style>\n</head>\n<body>");
+    html.push_str("<h1>Link check report</h1>\n");
+    if report.failures.is_empty() {
+        html.push_str("<p class=\"ok\">No broken links found.</p>\n");
+        render_warnings(&mut html, report);
+        html.push_str("</body>\n</html>\n");
+        return html;
+    }
+
+    let _ = writeln!(
+        html,
+        "<p>{} failures in {} documents.</p>",
+        report.failures.len(),
+        by_source.len()
+    );
+    html.push_str("<table>\n<tr><th>Kind</th><th>Count</th></tr>\n");
+    for (kind, count) in by_kind.iter() {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td class=\"count\">{count}</td></tr>",
+            escape(kind)
+        );
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Documents</h2>\n<table>\n");
+    for (source, failures) in by_source.iter() {
+        let name = escape(&source.to_string_lossy());
+        let _ = writeln!(
+            html,
+            "<tr><td><a href=\"#{name}\">{name}</a></td><td class=\"count\">{}</td></tr>",
+            failures.len()
+        );
+    }
+    html.push_str("</table>\n");
+
+    for (source, failures) in by_source.iter() {
+        let name = escape(&source.to_string_lossy());
+        match file_url(roots, source) {
+            Some(url) => {
+                let _ = writeln!(
+                    html,
+                    "<h2 id=\"{name}\"><a href=\"{}\">{name}</a></h2>",
+                    escape(&url)
+                );
+            }
+            None => {
+                let _ = writeln!(html, "<h2 id=\"{name}\">{name}</h2>");
+            }
+        }
+        html.push_str("<table>\n");
+        for failure in failures {
+            let _ = writeln!(
+                html,
+                "<tr><td class=\"kind\">{}</td><td>{}</td></tr>",
+                escape(failure.kind()),
+                escape(&failure.to_string())
+            );
+        }
+        html.push_str("</table>\n");
+    }
+    render_warnings(&mut html, report);
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// List the warnings, which don't fail the check, after everything else
+fn render_warnings(html: &mut String, report: &Report) {
+    if report.warnings.is_empty() {
+        return;
+    }
+    let _ = writeln!(
+        html,
+        "<h2 id=\"warnings\">{} warnings</h2>",
+        report.warnings.len()
+    );
+    html.push_str("<table>\n");
+    for warning in report.warnings.iter() {
+        let _ = writeln!(
+            html,
+            "<tr><td class=\"kind\">{}</td><td>{}</td></tr>",
+            escape(warning.kind()),
+            escape(&warning.to_string())
+        );
+    }
+    html.push_str("</table>\n");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape(r#"<a href="x">&'</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&#39;&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let report = Report::new(vec![
+            Failure::LocalServerLink {
+                source: "a&b.html".into(),
+                href: "http://localhost/".into(),
+            },
+            Failure::LocalServerLink {
+                source: "a&b.html".into(),
+                href: "http://127.0.0.1/".into(),
+            },
+            Failure::NoOutput {
+                source: "c.md".into(),
+                output: "c.html".into(),
+            },
+        ]);
+        let html = render(&report, &[]);
+        assert!(html.contains("<p>3 failures in 2 documents.</p>"));
+        assert!(html.contains(r#"<tr><td>Local server link</td><td class="count">2</td></tr>"#));
+        assert!(html.contains(r#"<h2 id="a&amp;b.html">a&amp;b.html</h2>"#));
+        assert!(html.contains(r#"<h2 id="c.md">c.md</h2>"#));
+        assert!(render(&Report::default(), &[]).contains("No broken links found."));
+        let only_warnings = Report {
+            failures: vec![],
+            warnings: report.failures,
+            ..Report::default()
+        };
+        let html = render(&only_warnings, &[]);
+        assert!(html.contains("No broken links found."));
+        assert!(html.contains(r#"<h2 id="warnings">3 warnings</h2>"#));
+    }
+}
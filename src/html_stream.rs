@@ -0,0 +1,516 @@
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use lol_html::html_content::{Element, TextChunk};
+use lol_html::{end_tag, ElementContentHandlers, HandlerResult, HtmlRewriter, Selector, Settings};
+
+use crate::css::css_urls;
+use crate::html::{
+    attribute_url, content_hash, id_references, is_relative_href, is_svg_url, link_texts,
+    parse_refresh, Directives, Extractor, HtmlInfo, LinkAttribute, ParseOptions, Placeholder,
+    META_URL_PROPERTIES,
+};
+use crate::rewrite::rewrite_href;
+
+/// What a document holds, gathered as its elements stream past
+#[derive(Debug, Default)]
+struct Found {
+    links: Vec<String>,
+    meta_urls: Vec<String>,
+    svg_urls: Vec<String>,
+    attribute_urls: Vec<(String, LinkAttribute)>,
+    /// The links picked out by each of the extractors, in turn
+    extracted: Vec<Vec<String>>,
+    style_urls: Vec<String>,
+    redirect: Option<String>,
+    nofollow_hrefs: Vec<String>,
+    /// The `href` and text of each `<a>`, the text growing as it streams past
+    anchors: Vec<(String, String)>,
+    ids: Vec<String>,
+    id_references: Vec<(String, String)>,
+    toc: Vec<String>,
+    headings: Vec<String>,
+    srcsets: Vec<String>,
+    alternates: Vec<(String, String)>,
+    directives: Vec<String>,
+    /// The text of each `<noscript>`, to parse as markup when templates are
+    noscripts: Vec<String>,
+}
+
+/// Whether an extractor's selector can be matched by the streaming parser
+pub fn supports(extractor: &Extractor) -> Result<(), String> {
+    extractor
+        .source
+        .parse::<Selector>()
+        .map(|_| ())
+        .map_err(|err| {
+            format!(
+                "{:?} can't be used with --streaming-parser: {err}",
+                extractor.source
+            )
+        })
+}
+
+/**
+Parse a document like `HtmlInfo::parse_with`, but streaming through it with `lol_html` instead of
+building its tree first, which is several times faster and needs far less memory on large
+pages.
+
+Nothing is returned if the document can't be streamed through, so it's parsed the usual way.
+That includes documents with elements marked by the `ParseOptions::ignore_attribute`, as the end
+of an element isn't told apart when its end tag is left out.
+Extractors which the streaming parser can't match are rejected up front by `supports`.
+*/
+pub fn parse(document: &str, options: &ParseOptions) -> Option<HtmlInfo> {
+    let found = RefCell::new(Found {
+        extracted: vec![vec![]; options.extractors.len()],
+        ..Found::default()
+    });
+    stream(document, options, &found).ok()?;
+    let mut found = found.into_inner();
+    // Browsers with scripts turned off parse the contents of `<noscript>` as markup
+    for noscript in std::mem::take(&mut found.noscripts) {
+        let inner = RefCell::new(Found {
+            extracted: vec![vec![]; options.extractors.len()],
+            ..Found::default()
+        });
+        stream(&noscript, options, &inner).ok()?;
+        found.extend(inner.into_inner());
+    }
+    Some(found.into_info(document, options))
+}
+
+type Handler<'h> = (Cow<'static, Selector>, ElementContentHandlers<'h>);
+
+/// A handler for the elements matching `selector`, outside any hidden templates
+fn on_element<'h>(
+    selector: &str,
+    hidden: &'h Cell<usize>,
+    mut handler: impl FnMut(&Element) + 'h,
+) -> Handler<'h> {
+    let selector = selector.parse().expect("The selector is supported");
+    let handlers = ElementContentHandlers::default().element(move |element: &mut Element| {
+        if hidden.get() == 0 {
+            handler(element);
+        }
+        HandlerResult::Ok(())
+    });
+    (Cow::Owned(selector), handlers)
+}
+
+/// A handler for the whole text of the elements matching `selector`, which comes in chunks
+fn on_text<'h>(
+    selector: &str,
+    hidden: &'h Cell<usize>,
+    mut handler: impl FnMut(String) + 'h,
+) -> Handler<'h> {
+    let selector = selector.parse().expect("The selector is supported");
+    let mut text = String::new();
+    let handlers = ElementContentHandlers::default().text(move |chunk: &mut TextChunk| {
+        if hidden.get() == 0 {
+            text.push_str(chunk.as_str());
+            if chunk.last_in_text_node() {
+                handler(std::mem::take(&mut text));
+            }
+        }
+        HandlerResult::Ok(())
+    });
+    (Cow::Owned(selector), handlers)
+}
+
+/// A handler for each chunk of text inside the elements matching `selector`
+fn on_text_chunk<'h>(
+    selector: &str,
+    hidden: &'h Cell<usize>,
+    mut handler: impl FnMut(&str) + 'h,
+) -> Handler<'h> {
+    let selector = selector.parse().expect("The selector is supported");
+    let handlers = ElementContentHandlers::default().text(move |chunk: &mut TextChunk| {
+        if hidden.get() == 0 {
+            handler(chunk.as_str());
+        }
+        HandlerResult::Ok(())
+    });
+    (Cow::Owned(selector), handlers)
+}
+
+/// The attributes of an element, decoded
+fn attributes(element: &Element) -> HashMap<String, String> {
+    element
+        .attributes()
+        .iter()
+        .map(|attribute| (attribute.name(), attribute.value()))
+        .collect()
+}
+
+fn stream(document: &str, options: &ParseOptions, found: &RefCell<Found>) -> HandlerResult {
+    // How many templates the stream is inside, whose contents are left out like the DOM parser
+    // leaves them out
+    let hidden = Rc::new(Cell::new(0));
+    let hiding = hidden.clone();
+    let hidden: &Cell<usize> = &hidden;
+    let mut handlers = vec![
+        on_element("a[href], link[href]", hidden, |element| {
+            found
+                .borrow_mut()
+                .links
+                .extend(element.get_attribute("href"));
+        }),
+        on_element("a[href]", hidden, |element| {
+            let href = element.get_attribute("href").unwrap_or_default();
+            found.borrow_mut().anchors.push((href, String::new()));
+        }),
+        on_text_chunk("a[href]", hidden, |chunk| {
+            if let Some((_, text)) = found.borrow_mut().anchors.last_mut() {
+                text.push_str(chunk);
+            }
+        }),
+        on_element(
+            "meta[property][content], meta[name][content]",
+            hidden,
+            |element| {
+                let property = element
+                    .get_attribute("property")
+                    .or(element.get_attribute("name"));
+                if property.is_some_and(|property| META_URL_PROPERTIES.contains(&&*property)) {
+                    found
+                        .borrow_mut()
+                        .meta_urls
+                        .extend(element.get_attribute("content"));
+                }
+            },
+        ),
+        // `<use>` is given an `xlink:href` by older SVG editors
+        on_element("use, object[data], iframe[src]", hidden, |element| {
+            let url = match element.tag_name().as_str() {
+                "use" => element
+                    .attributes()
+                    .iter()
+                    .find(|attribute| attribute.name().rsplit(':').next() == Some("href"))
+                    .map(|attribute| attribute.value()),
+                "object" => element.get_attribute("data").filter(|url| is_svg_url(url)),
+                _ => element.get_attribute("src").filter(|url| is_svg_url(url)),
+            };
+            found.borrow_mut().svg_urls.extend(url);
+        }),
+        on_element(
+            "form[action], button[formaction], input[formaction], video[poster], area[href]",
+            hidden,
+            |element| {
+                let attributes = attributes(element);
+                let url = attribute_url(&element.tag_name(), |name| {
+                    attributes.get(name).map(String::as_str)
+                });
+                if let Some((url, attribute)) = url {
+                    found
+                        .borrow_mut()
+                        .attribute_urls
+                        .push((url.to_string(), attribute));
+                }
+            },
+        ),
+        on_element("meta[http-equiv][content]", hidden, |element| {
+            let refresh = element
+                .get_attribute("http-equiv")
+                .is_some_and(|equiv| equiv.eq_ignore_ascii_case("refresh"));
+            let mut found = found.borrow_mut();
+            if refresh && found.redirect.is_none() {
+                found.redirect = element
+                    .get_attribute("content")
+                    .and_then(|content| parse_refresh(&content));
+            }
+        }),
+        on_element(
+            r#"a[href][rel~="nofollow" i], area[href][rel~="nofollow" i]"#,
+            hidden,
+            |element| {
+                found
+                    .borrow_mut()
+                    .nofollow_hrefs
+                    .extend(element.get_attribute("href"));
+            },
+        ),
+        on_element("[id]", hidden, |element| {
+            (found.borrow_mut().ids).extend(element.get_attribute("id"));
+        }),
+        on_element(
+            "label[for], [aria-labelledby], [aria-describedby], [headers]",
+            hidden,
+            |element| {
+                let attributes = attributes(element);
+                let references = id_references(&element.tag_name(), |name| {
+                    attributes.get(name).map(String::as_str)
+                });
+                found.borrow_mut().id_references.extend(references);
+            },
+        ),
+        on_element(r##"nav.toc a[href^="#"]"##, hidden, |element| {
+            if let Some(href) = element.get_attribute("href") {
+                found.borrow_mut().toc.push(href[1..].to_string());
+            }
+        }),
+        on_element("h2[id], h3[id]", hidden, |element| {
+            found
+                .borrow_mut()
+                .headings
+                .extend(element.get_attribute("id"));
+        }),
+        on_element("img[srcset]", hidden, |element| {
+            found
+                .borrow_mut()
+                .srcsets
+                .extend(element.get_attribute("srcset"));
+        }),
+        on_element(
+            r#"link[rel~="alternate" i][hreflang][href]"#,
+            hidden,
+            |element| {
+                if let (Some(hreflang), Some(href)) = (
+                    element.get_attribute("hreflang"),
+                    element.get_attribute("href"),
+                ) {
+                    found.borrow_mut().alternates.push((hreflang, href));
+                }
+            },
+        ),
+        on_element(
+            r#"meta[name="relative-link-check"][content]"#,
+            hidden,
+            |element| {
+                found
+                    .borrow_mut()
+                    .directives
+                    .extend(element.get_attribute("content"));
+            },
+        ),
+    ];
+    for (index, extractor) in options.extractors.iter().enumerate() {
+        handlers.push(on_element(&extractor.source, hidden, move |element| {
+            found.borrow_mut().extracted[index].extend(element.get_attribute(&extractor.attribute));
+        }));
+    }
+    if options.styles {
+        handlers.push(on_element("[style]", hidden, |element| {
+            let style = element.get_attribute("style").unwrap_or_default();
+            found.borrow_mut().style_urls.extend(css_urls(&style));
+        }));
+        handlers.push(on_text("style", hidden, |style| {
+            found.borrow_mut().style_urls.extend(css_urls(&style));
+        }));
+    }
+    if options.templates {
+        handlers.push(on_text("noscript", hidden, |noscript| {
+            found.borrow_mut().noscripts.push(noscript);
+        }));
+    } else {
+        let selector = "template".parse().expect("The selector is supported");
+        let template = ElementContentHandlers::default().element(move |element: &mut Element| {
+            hiding.set(hiding.get() + 1);
+            let shown = hiding.clone();
+            element.on_end_tag(end_tag!(move |_| {
+                shown.set(shown.get() - 1);
+                Ok(())
+            }))
+        });
+        handlers.push((Cow::Owned(selector), template));
+    }
+    if let Some(attribute) = &options.ignore_attribute {
+        let selector = format!("[{attribute}]")
+            .parse()
+            .expect("The attribute name was checked");
+        let ignore = ElementContentHandlers::default()
+            .element(|_: &mut Element| Err("Some links are ignored".into()));
+        handlers.push((Cow::Owned(selector), ignore));
+    }
+    let settings = handlers
+        .into_iter()
+        .fold(Settings::new().with_strict(false), |settings, handler| {
+            settings.append_element_content_handler(handler)
+        });
+    let mut rewriter = HtmlRewriter::new(settings, |_: &[u8]| {});
+    rewriter.write(document.as_bytes())?;
+    rewriter.end()?;
+    Ok(())
+}
+
+impl Found {
+    fn extend(&mut self, other: Found) {
+        self.links.extend(other.links);
+        self.meta_urls.extend(other.meta_urls);
+        self.svg_urls.extend(other.svg_urls);
+        self.attribute_urls.extend(other.attribute_urls);
+        for (extracted, other) in self.extracted.iter_mut().zip(other.extracted) {
+            extracted.extend(other);
+        }
+        self.style_urls.extend(other.style_urls);
+        self.redirect = self.redirect.take().or(other.redirect);
+        self.nofollow_hrefs.extend(other.nofollow_hrefs);
+        self.anchors.extend(other.anchors);
+        self.ids.extend(other.ids);
+        self.id_references.extend(other.id_references);
+        self.toc.extend(other.toc);
+        self.headings.extend(other.headings);
+        self.srcsets.extend(other.srcsets);
+        self.alternates.extend(other.alternates);
+        self.directives.extend(other.directives);
+    }
+    /// What was found, put together as the DOM parser puts it together
+    fn into_info(self, document: &str, options: &ParseOptions) -> HtmlInfo {
+        let rewrite = |href: &str| rewrite_href(&options.rewrites, href);
+        let redirect = self.redirect.map(|redirect| rewrite(&redirect));
+        let link_attributes = self
+            .attribute_urls
+            .iter()
+            .map(|(url, attribute)| (rewrite(url), *attribute))
+            .collect();
+        let (relative_hrefs, external_hrefs): (Vec<String>, _) = self
+            .links
+            .iter()
+            .chain(self.meta_urls.iter())
+            .chain(self.svg_urls.iter())
+            .chain(self.attribute_urls.iter().map(|(url, _)| url))
+            .chain(self.extracted.iter().flatten())
+            .chain(self.style_urls.iter())
+            .map(|href| rewrite(href))
+            .chain(redirect.clone())
+            .partition(|href| is_relative_href(href));
+        let headings_missing_from_toc = if self.toc.is_empty() {
+            vec![]
+        } else {
+            self.headings
+                .into_iter()
+                .filter(|id| !self.toc.contains(id))
+                .collect()
+        };
+        HtmlInfo {
+            relative_hrefs: (relative_hrefs.iter())
+                .map(|href| options.interned(href))
+                .collect(),
+            external_hrefs,
+            nofollow_hrefs: self
+                .nofollow_hrefs
+                .iter()
+                .map(|href| rewrite(href))
+                .collect(),
+            link_attributes,
+            placeholder_hrefs: (self.anchors.iter())
+                .filter(|(href, _)| Placeholder::of(href).is_some())
+                .map(|(href, _)| href.clone())
+                .collect(),
+            link_texts: link_texts(
+                self.anchors
+                    .into_iter()
+                    .map(|(href, text)| (rewrite(&href), text)),
+            ),
+            ids: self.ids.iter().map(|id| options.interned(id)).collect(),
+            id_references: self.id_references,
+            directives: Directives::parse(&self.directives.join(",")),
+            redirect,
+            headings_missing_from_toc,
+            srcsets: self.srcsets,
+            alternates: self
+                .alternates
+                .into_iter()
+                .map(|(hreflang, href)| (hreflang, rewrite(&href)))
+                .collect(),
+            content_hash: content_hash(document),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A document using everything the parsers look for
+    const DOCUMENT: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="relative-link-check" content="no-fragments">
+<meta property="og:image" content="/images/card.png">
+<meta name="twitter:image" content="https://example.com/card.png">
+<meta name="description" content="not/a/link.html">
+<link rel="stylesheet" href="style.css">
+<link rel="preconnect" href="//cdn.example.com">
+<link rel="alternate" hreflang="fr" href="../fr/index.html">
+<style>.hero { background: url(hero.png) }</style>
+</head>
+<body>
+<nav class="toc"><a href="#install">Install</a></nav>
+<h2 id="install">Install</h2>
+<h3 id="usage">Usage</h3>
+<a href="guide.html#setup"><b>Setup</b>
+  guide</a><a href="guide.html#setup">Again</a>
+<a href="https://example.com/" rel="External NOFOLLOW">Example</a>
+<a href="#">Soon</a><a href=" javascript:void(0); ">Menu</a>
+<div data-href="spa/route" style="background-image: url('bg.jpg')"></div>
+<svg><use xlink:href="sprite.svg#icon"></use><use href="#local"></use></svg>
+<object data="diagram.svg"></object><iframe src="embed.html"></iframe>
+<form action="search.html"><button formaction="preview.html">Preview</button></form>
+<video poster="poster.jpg"></video>
+<map name="m"><area href="north.html" rel="nofollow"></map>
+<img srcset="small.png 1x, large.png 2x">
+<label for="email">Email</label><input id="email" aria-describedby="hint missing">
+<template><a href="template.html">Stamped</a><p id="in-template"></p></template>
+<noscript><a href="noscript.html">No scripts</a></noscript>
+<p id="install">Again</p>
+</body>
+</html>"##;
+
+    fn assert_same(options: &ParseOptions) {
+        let tree = HtmlInfo::parse_with(DOCUMENT, options);
+        let streamed = parse(DOCUMENT, options).unwrap();
+        assert_eq!(streamed.relative_hrefs, tree.relative_hrefs);
+        assert_eq!(streamed.external_hrefs, tree.external_hrefs);
+        assert_eq!(streamed.nofollow_hrefs, tree.nofollow_hrefs);
+        assert_eq!(streamed.link_attributes, tree.link_attributes);
+        assert_eq!(streamed.link_texts, tree.link_texts);
+        assert_eq!(streamed.placeholder_hrefs, tree.placeholder_hrefs);
+        assert_eq!(streamed.ids, tree.ids);
+        assert_eq!(streamed.id_references, tree.id_references);
+        assert_eq!(streamed.directives, tree.directives);
+        assert_eq!(streamed.redirect, tree.redirect);
+        assert_eq!(
+            streamed.headings_missing_from_toc,
+            tree.headings_missing_from_toc
+        );
+        assert_eq!(streamed.srcsets, tree.srcsets);
+        assert_eq!(streamed.alternates, tree.alternates);
+        assert_eq!(streamed.content_hash, tree.content_hash);
+    }
+
+    #[test]
+    fn test_parse_like_the_tree() {
+        assert_same(&ParseOptions::default());
+        let extractor = Extractor::parse("div[data-href]@data-href").unwrap();
+        assert_same(&ParseOptions {
+            extractors: vec![extractor],
+            styles: true,
+            ..ParseOptions::default()
+        });
+        let templates = ParseOptions {
+            templates: true,
+            ..ParseOptions::default()
+        };
+        let streamed = parse(DOCUMENT, &templates).unwrap();
+        assert!(streamed.relative_hrefs.contains(&"template.html".into()));
+        assert!(streamed.relative_hrefs.contains(&"noscript.html".into()));
+        assert!(streamed.ids.contains("in-template"));
+        let ignoring = ParseOptions {
+            ignore_attribute: Some("data-rlc-ignore".into()),
+            ..ParseOptions::default()
+        };
+        assert_same(&ignoring);
+        let document = r#"<a href="a.html"><p data-rlc-ignore><a href="b.html">"#;
+        assert!(parse(document, &ignoring).is_none());
+    }
+
+    #[test]
+    fn test_supports() {
+        assert!(supports(&Extractor::parse("div[data-href]@data-href").unwrap()).is_ok());
+        assert!(supports(&Extractor::parse("h2 + a@href").unwrap()).is_err());
+    }
+}
@@ -0,0 +1,258 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::baseline::{Baseline, BaselineEntry};
+use crate::fix::{replace_href, suggested_href};
+use crate::report::{Failure, Report};
+use crate::text_report::describe_plain;
+
+/// The keys the triage answers to, shown at the bottom of the screen
+const HELP: &str = "↑/↓ move  e edit  i ignore  f fix  q quit";
+
+/// The line an `href` is written on, counting from 1, preferring it quoted as an attribute
+pub fn href_line(contents: &str, href: &str) -> Option<usize> {
+    let offset = [format!("\"{href}\""), format!("'{href}'"), href.to_string()]
+        .iter()
+        .find_map(|needle| contents.find(needle.as_str()))?;
+    Some(contents[..offset].matches('\n').count() + 1)
+}
+
+/// Whether a failure is about a broken link, which the baseline can ignore
+fn is_broken_link(failure: &Failure) -> bool {
+    matches!(
+        failure,
+        Failure::BrokenLink { .. }
+            | Failure::MissingFragment { .. }
+            | Failure::LocalServerLink { .. }
+            | Failure::EscapingLink { .. }
+            | Failure::BrokenExternalLink { .. }
+            | Failure::BrokenNofollowLink { .. }
+    )
+}
+
+/// A failure being triaged, and what has been done about it
+struct Entry<'a> {
+    failure: &'a Failure,
+    /// The file the failure was found in, if it's on disk in one of the scanned directories
+    file: Option<PathBuf>,
+    done: Option<String>,
+}
+
+/**
+The state of the triage: the broken links of each document, and which of them is selected.
+*/
+struct Triage<'a> {
+    entries: Vec<Entry<'a>>,
+    selected: usize,
+    baseline: Option<PathBuf>,
+    status: String,
+}
+
+impl<'a> Triage<'a> {
+    fn new(report: &'a Report, roots: &[PathBuf], baseline: Option<&Path>) -> Triage<'a> {
+        let mut failures: Vec<&Failure> = report
+            .failures
+            .iter()
+            .chain(report.warnings.iter())
+            .filter(|failure| is_broken_link(failure))
+            .collect();
+        failures.sort();
+        let entries = failures
+            .into_iter()
+            .map(|failure| Entry {
+                file: roots
+                    .iter()
+                    .map(|root| root.join(failure.source()))
+                    .find(|path| path.is_file()),
+                failure,
+                done: None,
+            })
+            .collect();
+        Triage {
+            entries,
+            selected: 0,
+            baseline: baseline.map(Path::to_path_buf),
+            status: HELP.to_string(),
+        }
+    }
+    fn select(&mut self, step: isize) {
+        let last = self.entries.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(step).min(last);
+    }
+    fn entry(&mut self) -> Option<&mut Entry<'a>> {
+        self.entries.get_mut(self.selected)
+    }
+    /// The rows of the list, a heading for each document followed by its failures, and the row
+    /// of the selected failure
+    fn rows(&self) -> (Vec<ListItem<'_>>, usize) {
+        let mut rows = vec![];
+        let mut selected_row = 0;
+        let mut source = None;
+        for (i, entry) in self.entries.iter().enumerate() {
+            if source != Some(entry.failure.source()) {
+                source = Some(entry.failure.source());
+                rows.push(ListItem::new(Line::from(
+                    entry.failure.source().display().to_string().bold(),
+                )));
+            }
+            if i == self.selected {
+                selected_row = rows.len();
+            }
+            let mut line = format!("  {}", describe_plain(entry.failure));
+            if let Some(done) = &entry.done {
+                line = format!("{line} [{done}]");
+            }
+            let style = match entry.done {
+                Some(_) => Style::new().add_modifier(Modifier::DIM),
+                None => Style::new(),
+            };
+            rows.push(ListItem::new(line).style(style));
+        }
+        (rows, selected_row)
+    }
+    fn draw(&self, frame: &mut Frame) {
+        let [list_area, status_area] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+        let (rows, selected_row) = self.rows();
+        let list = List::new(rows).highlight_style(Style::new().reversed());
+        let mut state = ListState::default().with_selected(Some(selected_row));
+        frame.render_stateful_widget(list, list_area, &mut state);
+        frame.render_widget(Paragraph::new(self.status.as_str()), status_area);
+    }
+    /// Open the selected failure's document in `$VISUAL` or `$EDITOR`, at the line of the link
+    fn edit(&mut self, terminal: &mut DefaultTerminal) -> std::io::Result<()> {
+        let Some(entry) = self.entry() else {
+            return Ok(());
+        };
+        let Some(file) = entry.file.clone() else {
+            self.status = "the document isn't in a scanned directory".into();
+            return Ok(());
+        };
+        let href = entry.failure.href().unwrap_or_default().to_string();
+        let line = std::fs::read_to_string(&file)
+            .ok()
+            .and_then(|contents| href_line(&contents, &href))
+            .unwrap_or(1);
+        let editor = std::env::var("VISUAL")
+            .or_else(|_| std::env::var("EDITOR"))
+            .unwrap_or_else(|_| "vi".into());
+        let mut words = editor.split_whitespace();
+        let program = words.next().unwrap_or("vi");
+        ratatui::restore();
+        let status = Command::new(program)
+            .args(words)
+            .arg(format!("+{line}"))
+            .arg(&file)
+            .status();
+        *terminal = ratatui::init();
+        self.status = match status {
+            Ok(status) if status.success() => HELP.to_string(),
+            Ok(status) => format!("{program} exited with {status}"),
+            Err(err) => format!("couldn't run {program}: {err}"),
+        };
+        Ok(())
+    }
+    /// Add the selected failure's link to the baseline, so it doesn't fail the check again
+    fn ignore(&mut self) -> std::io::Result<()> {
+        let Some(path) = self.baseline.clone() else {
+            self.status = "give a --baseline file to record ignored links in".into();
+            return Ok(());
+        };
+        let Some(entry) = self.entry() else {
+            return Ok(());
+        };
+        let Some(href) = entry.failure.href() else {
+            return Ok(());
+        };
+        let mut baseline = Baseline::load(&path)?;
+        baseline.extend([BaselineEntry {
+            source: entry.failure.source().to_path_buf(),
+            href: href.to_string(),
+        }]);
+        baseline.save(&path)?;
+        entry.done = Some("ignored".into());
+        self.status = format!("recorded in {}", path.display());
+        Ok(())
+    }
+    /// Rewrite the selected failure's link in its document to the suggested `href`
+    fn fix(&mut self) -> std::io::Result<()> {
+        let Some(entry) = self.entry() else {
+            return Ok(());
+        };
+        let (Some(replacement), Some(href)) = (suggested_href(entry.failure), entry.failure.href())
+        else {
+            self.status = "no fix to suggest for this link".into();
+            return Ok(());
+        };
+        let Some(file) = &entry.file else {
+            self.status = "the document isn't in a scanned directory".into();
+            return Ok(());
+        };
+        let contents = std::fs::read_to_string(file)?;
+        let Some(fixed) = replace_href(&contents, href, &replacement) else {
+            self.status = format!("{href:?} isn't written as a quoted attribute");
+            return Ok(());
+        };
+        std::fs::write(file, fixed)?;
+        entry.done = Some(format!("fixed to {replacement}"));
+        self.status = HELP.to_string();
+        Ok(())
+    }
+}
+
+/**
+Triage the failures of a report which are about links in a terminal interface, grouped by the
+document they're in. Each can be opened in an editor, recorded in the `baseline` to ignore it
+from then on, or fixed with a suggested `href`. Documents are looked for in the `roots`.
+*/
+pub fn triage(report: &Report, roots: &[PathBuf], baseline: Option<&Path>) -> std::io::Result<()> {
+    let mut triage = Triage::new(report, roots, baseline);
+    if triage.entries.is_empty() {
+        println!("No broken links to triage");
+        return Ok(());
+    }
+    let mut terminal = ratatui::init();
+    let result = run(&mut triage, &mut terminal);
+    ratatui::restore();
+    result
+}
+
+fn run(triage: &mut Triage, terminal: &mut DefaultTerminal) -> std::io::Result<()> {
+    loop {
+        terminal.draw(|frame| triage.draw(frame))?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Up | KeyCode::Char('k') => triage.select(-1),
+            KeyCode::Down | KeyCode::Char('j') => triage.select(1),
+            KeyCode::Enter | KeyCode::Char('e') => triage.edit(terminal)?,
+            KeyCode::Char('i') => triage.ignore()?,
+            KeyCode::Char('f') => triage.fix()?,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_href_line() {
+        let contents = "<p>\n<a href=\"guide.html#setup\">Setup</a>\n<a href='guide.html#setup'>";
+        assert_eq!(href_line(contents, "guide.html#setup"), Some(2));
+        assert_eq!(href_line(contents, "gone.html"), None);
+    }
+}
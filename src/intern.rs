@@ -0,0 +1,274 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::{Arc, LazyLock, Mutex, MutexGuard};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/**
+A string kept once for as long as any document has it, however many documents it's found in.
+
+Large sites repeat the same hrefs and ids in every document, like the links of a navigation
+bar, so the parsed documents share one copy of each instead of allocating their own. An
+interned string derefs to a `str` and compares equal to one, so it can be used much like a
+`String`.
+
+The interner lets go of a string along with its last copy, so a long-running server doesn't
+keep the strings of every version of every document it has parsed.
+*/
+#[derive(Clone)]
+pub struct Interned {
+    string: Arc<str>,
+    /// Whether the interner holds the string too, rather than this being a copy of its own
+    shared: bool,
+}
+
+impl Interned {
+    pub fn new(string: &str) -> Interned {
+        INTERNER.intern(string)
+    }
+    /// A copy of its own, for documents which are dropped as soon as they're checked and so
+    /// would only pay for the interner's locking
+    pub fn unshared(string: &str) -> Interned {
+        Interned {
+            string: Arc::from(string),
+            shared: false,
+        }
+    }
+}
+
+impl Drop for Interned {
+    fn drop(&mut self) {
+        if self.shared {
+            INTERNER.release(std::mem::take(&mut self.string));
+        }
+    }
+}
+
+impl PartialEq for Interned {
+    fn eq(&self, other: &Interned) -> bool {
+        *self.string == *other.string
+    }
+}
+
+impl Eq for Interned {}
+
+impl PartialOrd for Interned {
+    fn partial_cmp(&self, other: &Interned) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Interned {
+    fn cmp(&self, other: &Interned) -> Ordering {
+        self.string.cmp(&other.string)
+    }
+}
+
+/// Hashed as a `str`, so sets of interned strings can be searched with one
+impl Hash for Interned {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.string.hash(state)
+    }
+}
+
+impl Deref for Interned {
+    type Target = str;
+    fn deref(&self) -> &str {
+        &self.string
+    }
+}
+
+impl Borrow<str> for Interned {
+    fn borrow(&self) -> &str {
+        &self.string
+    }
+}
+
+impl AsRef<str> for Interned {
+    fn as_ref(&self) -> &str {
+        &self.string
+    }
+}
+
+impl From<&str> for Interned {
+    fn from(string: &str) -> Interned {
+        Interned::new(string)
+    }
+}
+
+impl From<String> for Interned {
+    fn from(string: String) -> Interned {
+        Interned::new(&string)
+    }
+}
+
+impl PartialEq<str> for Interned {
+    fn eq(&self, other: &str) -> bool {
+        &*self.string == other
+    }
+}
+
+impl PartialEq<&str> for Interned {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.string == *other
+    }
+}
+
+impl PartialEq<String> for Interned {
+    fn eq(&self, other: &String) -> bool {
+        &*self.string == other
+    }
+}
+
+impl fmt::Debug for Interned {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&*self.string, f)
+    }
+}
+
+impl fmt::Display for Interned {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.string)
+    }
+}
+
+/// How many separately locked sets the interner spreads its strings over, so the threads
+/// parsing documents rarely wait on each other
+const SHARDS: usize = 16;
+
+/**
+Every string interned and still in use, and how many bytes interning them has saved.
+*/
+struct Interner {
+    shards: [Mutex<Shard>; SHARDS],
+}
+
+#[derive(Default)]
+struct Shard {
+    strings: HashSet<Arc<str>>,
+    /// The bytes of every string interned, counting each time it was
+    interned_bytes: usize,
+    /// The bytes actually kept, counting each string once, even if it's been let go of since
+    kept_bytes: usize,
+}
+
+static INTERNER: LazyLock<Interner> = LazyLock::new(|| Interner {
+    shards: Default::default(),
+});
+
+impl Interner {
+    fn shard(&self, string: &str) -> MutexGuard<'_, Shard> {
+        let mut hasher = DefaultHasher::new();
+        string.hash(&mut hasher);
+        self.shards[hasher.finish() as usize % SHARDS]
+            .lock()
+            .unwrap()
+    }
+    fn intern(&self, string: &str) -> Interned {
+        let mut shard = self.shard(string);
+        shard.interned_bytes += string.len();
+        let string = match shard.strings.get(string) {
+            Some(interned) => interned.clone(),
+            None => {
+                let interned: Arc<str> = Arc::from(string);
+                shard.kept_bytes += string.len();
+                shard.strings.insert(interned.clone());
+                interned
+            }
+        };
+        Interned {
+            string,
+            shared: true,
+        }
+    }
+    /**
+    Let go of a string once this copy is the last besides the interner's own. The copy is
+    dropped before the shard is unlocked, so that two last copies dropped at once can't both
+    leave the string to the other.
+    */
+    fn release(&self, string: Arc<str>) {
+        let mut shard = self.shard(&string);
+        if Arc::strong_count(&string) == 2 {
+            shard.strings.remove(&*string);
+        }
+        drop(string);
+    }
+}
+
+/**
+How many strings have been interned and how much memory that saved, for `--stats`.
+*/
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct InternerStats {
+    /// How many different strings are kept
+    pub strings: usize,
+    /// The bytes of the strings kept, including those let go of since
+    pub kept_bytes: usize,
+    /// The bytes separate copies of every string found would have taken
+    pub interned_bytes: usize,
+}
+
+impl InternerStats {
+    /// The interner's totals so far
+    pub fn current() -> InternerStats {
+        let mut stats = InternerStats::default();
+        for shard in &INTERNER.shards {
+            let shard = shard.lock().unwrap();
+            stats.strings += shard.strings.len();
+            stats.kept_bytes += shard.kept_bytes;
+            stats.interned_bytes += shard.interned_bytes;
+        }
+        stats
+    }
+    /// The bytes saved by keeping a single copy of each string
+    pub fn saved_bytes(&self) -> usize {
+        self.interned_bytes - self.kept_bytes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_interned() {
+        let before = InternerStats::current();
+        let first = Interned::new("rlc-test-interned");
+        let second = Interned::from("rlc-test-interned".to_string());
+        assert!(Arc::ptr_eq(&first.string, &second.string));
+        assert_eq!(first, "rlc-test-interned");
+        assert_eq!(first.len(), 17);
+        assert_eq!(format!("{first:?}"), r#""rlc-test-interned""#);
+        let strings: HashSet<Interned> = [first].into();
+        assert!(strings.contains("rlc-test-interned"));
+        let after = InternerStats::current();
+        // Other tests intern strings at the same time, so only check what these two added
+        assert!(kept("rlc-test-interned"));
+        assert!(after.interned_bytes >= before.interned_bytes + 34);
+        assert!(after.saved_bytes() >= before.saved_bytes() + 17);
+    }
+
+    fn kept(string: &str) -> bool {
+        INTERNER.shard(string).strings.contains(string)
+    }
+
+    #[test]
+    fn test_released() {
+        let first = Interned::new("rlc-test-released");
+        let second = first.clone();
+        drop(first);
+        assert!(kept("rlc-test-released"));
+        drop(second);
+        assert!(!kept("rlc-test-released"));
+
+        let unshared = Interned::unshared("rlc-test-unshared");
+        assert!(!kept("rlc-test-unshared"));
+        assert_eq!(unshared, Interned::new("rlc-test-unshared"));
+    }
+}
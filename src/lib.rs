@@ -0,0 +1,47 @@
+pub mod archive;
+pub mod baseline;
+pub mod charset;
+pub mod check;
+pub mod consistency;
+pub mod contact;
+pub mod css;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod data_file;
+pub mod diff;
+pub mod dot_report;
+pub mod dump;
+pub mod environment;
+pub mod external;
+pub mod feed;
+pub mod filter;
+pub mod fix;
+#[cfg(test)]
+mod fixture;
+pub mod html;
+pub mod html_report;
+#[cfg(feature = "streaming-parser")]
+pub mod html_stream;
+pub mod intern;
+pub mod manifest;
+pub mod markdown;
+pub mod portability;
+pub mod progress;
+pub mod provider;
+pub mod recheck;
+pub mod report;
+pub mod rewrite;
+pub mod robots;
+pub mod routes;
+pub mod rules;
+pub mod schema;
+pub mod shard;
+pub mod sink;
+pub mod site_path;
+pub mod sitemap;
+pub mod stats;
+pub mod streaming;
+pub mod text_report;
+pub mod tree;
+pub mod url_cache;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
@@ -0,0 +1,510 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use clap::ValueEnum;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+    PublishDiagnostics,
+};
+use lsp_types::request::{Completion, Request as _};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionTextEdit,
+    Diagnostic, DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, NumberOrString, Position, PublishDiagnosticsParams, Range,
+    ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit, Url,
+};
+
+use crate::consistency::PathMapping;
+use crate::filter::FileFilter;
+use crate::html::HtmlInfo;
+use crate::markdown::MarkdownInfo;
+use crate::report::Failure;
+use crate::rules::{Rules, Severity};
+use crate::scanned_path;
+use crate::site_path::site_key;
+use crate::text_report::describe_plain;
+use crate::tree::{
+    document_broken_links, resolve_document_link, BrokenLink, Hooks, HtmlFileLink, HtmlFiles,
+};
+
+/// The characters which start a link, after which its target is completed
+const TRIGGER_CHARACTERS: &[&str] = &["\"", "'", "(", "/", "#"];
+
+/// The position of a byte `offset` in `text`, in the UTF-16 code units LSP counts in
+pub fn position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..].encode_utf16().count() as u32,
+    }
+}
+
+/// The byte offset of a `position` in `text`, clamped to the end of its line
+pub fn offset(text: &str, position: Position) -> usize {
+    let line_start: usize = text
+        .split_inclusive('\n')
+        .take(position.line as usize)
+        .map(str::len)
+        .sum();
+    let line = text[line_start..].split('\n').next().unwrap_or("");
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= position.character as usize {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    line_start + line.len()
+}
+
+/// Whether a character may delimit a link target as written in HTML or Markdown
+fn is_delimiter(c: char) -> bool {
+    matches!(c, '"' | '\'' | '(' | ')' | '<' | '>' | ':') || c.is_whitespace()
+}
+
+/// Every place `href` is written in `text` as a whole link target
+pub fn href_ranges(text: &str, href: &str) -> Vec<Range> {
+    text.match_indices(href)
+        .filter(|(start, _)| {
+            let end = start + href.len();
+            text[..*start].chars().next_back().is_none_or(is_delimiter)
+                && text[end..].chars().next().is_none_or(is_delimiter)
+        })
+        .map(|(start, _)| Range {
+            start: position(text, start),
+            end: position(text, start + href.len()),
+        })
+        .collect()
+}
+
+/// A path from the directory `from` to the file `to`, both relative to the scanned directory
+pub fn relative_path(from: &Path, to: &Path) -> String {
+    let from: Vec<Component> = from.components().collect();
+    let to: Vec<Component> = to.components().collect();
+    let common = from
+        .iter()
+        .zip(to.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut segments = vec![".."; from.len() - common];
+    segments.extend(to[common..].iter().filter_map(|c| c.as_os_str().to_str()));
+    segments.join("/")
+}
+
+/**
+What is known about the site while editing it: the scanned files, with the documents open in the
+editor as they currently are.
+*/
+pub struct Workspace {
+    pub files: HtmlFiles,
+    pub directories: Vec<PathBuf>,
+    /// Where Markdown sources are kept, if not among the documents
+    pub source_tree: Option<PathBuf>,
+    pub mapping: PathMapping,
+    pub filter: FileFilter,
+    pub rules: Rules,
+    pub follow_redirects: bool,
+    /// The text of each open document
+    pub open: HashMap<Url, String>,
+}
+
+/// An open document, as the site sees it
+enum Document {
+    /// An HTML document, keyed like scanned documents are
+    Html(PathBuf),
+    /// A Markdown source, keyed by the document it's rendered to
+    Markdown(PathBuf),
+}
+
+impl Workspace {
+    /// Which document of the site an editor's file is, if it's one
+    fn document(&self, uri: &Url) -> Option<Document> {
+        let file = uri.to_file_path().ok()?;
+        let extension = file.extension().and_then(|e| e.to_str());
+        if matches!(extension, Some("md" | "markdown")) {
+            let source = self.source_tree.as_ref().and_then(|tree| {
+                file.canonicalize()
+                    .ok()?
+                    .strip_prefix(tree.canonicalize().ok()?)
+                    .ok()
+                    .map(Path::to_path_buf)
+            });
+            let path = match source {
+                Some(path) => path,
+                None => {
+                    let (directory, path) = scanned_path(&self.directories, &file).ok()??;
+                    site_key(self.filter.url_prefix(directory), path)
+                }
+            };
+            return Some(Document::Markdown(self.mapping.map_path(path)));
+        }
+        let (directory, path) = scanned_path(&self.directories, &file).ok()??;
+        self.filter
+            .is_document(&path)
+            .then(|| Document::Html(site_key(self.filter.url_prefix(directory), path)))
+    }
+    fn contains(&self, link: &HtmlFileLink) -> bool {
+        match self.follow_redirects {
+            true => self.files.contains_following_redirects(link),
+            false => self.files.contains(link),
+        }
+    }
+    /// The broken links of a document with the given `text`, whose ids are indexed first
+    fn broken_links(&mut self, document: &Document, text: &str) -> Vec<BrokenLink> {
+        match document {
+            Document::Html(path) => {
+                let info = HtmlInfo::parse_with(text, &self.filter.parse);
+                self.files.insert(path.clone(), info);
+                let info = self.files.get(path).expect("Just inserted");
+                document_broken_links(path, info, &Hooks::default(), |link| self.contains(link))
+            }
+            Document::Markdown(output) => {
+                let base = output.parent().unwrap_or(Path::new(""));
+                MarkdownInfo::parse(text)
+                    .relative_hrefs
+                    .into_iter()
+                    .filter_map(|href| {
+                        let link =
+                            resolve_document_link(output, base, &self.mapping.map_href(&href));
+                        if self.contains(&link) {
+                            return None;
+                        }
+                        let missing_fragment = link.fragment.is_some()
+                            && self.contains(&HtmlFileLink {
+                                path: link.path.clone(),
+                                fragment: None,
+                                base: link.base,
+                            });
+                        Some(BrokenLink {
+                            source: output.clone(),
+                            href,
+                            similar_ids: vec![],
+                            link,
+                            missing_fragment,
+                            attribute: None,
+                            text: None,
+                        })
+                    })
+                    .collect()
+            }
+        }
+    }
+    /// The diagnostics of a document with the given `text`, at each place a broken link is
+    pub fn diagnostics(&mut self, uri: &Url, text: &str) -> Vec<Diagnostic> {
+        let Some(document) = self.document(uri) else {
+            return vec![];
+        };
+        let mut diagnostics = vec![];
+        for mut broken in self.broken_links(&document, text) {
+            if broken.missing_fragment {
+                broken.similar_ids = self.files.similar_ids(&broken.link);
+            }
+            let href = broken.href.clone();
+            let failure = Failure::from(broken);
+            let rule = (failure.rule().to_possible_value().unwrap())
+                .get_name()
+                .to_string();
+            let severity = match self.rules.severity(failure.rule()) {
+                Severity::Error => DiagnosticSeverity::ERROR,
+                Severity::Warn => DiagnosticSeverity::WARNING,
+                Severity::Off => continue,
+            };
+            for range in href_ranges(text, &href) {
+                diagnostics.push(Diagnostic {
+                    range,
+                    severity: Some(severity),
+                    code: Some(NumberOrString::String(rule.clone())),
+                    source: Some("rlc".into()),
+                    message: describe_plain(&failure),
+                    ..Diagnostic::default()
+                });
+            }
+        }
+        diagnostics
+    }
+    /**
+    The link targets to complete the one being written at `position`: the files of the site
+    relative to the document, or after a `#`, the ids of the document it leads to.
+    */
+    pub fn completions(&self, uri: &Url, text: &str, position: Position) -> Vec<CompletionItem> {
+        let output = match self.document(uri) {
+            Some(Document::Html(path) | Document::Markdown(path)) => path,
+            None => return vec![],
+        };
+        let end = offset(text, position);
+        let start = text[..end]
+            .rfind(is_delimiter)
+            .map_or(0, |i| i + text[i..].chars().next().unwrap().len_utf8());
+        let typed = &text[start..end];
+        let directory = output.parent().unwrap_or(Path::new(""));
+        let item = |label: String, from: usize, kind: CompletionItemKind| CompletionItem {
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                range: Range {
+                    start: self::position(text, from),
+                    end: position,
+                },
+                new_text: label.clone(),
+            })),
+            label,
+            kind: Some(kind),
+            ..CompletionItem::default()
+        };
+        if let Some((path, fragment)) = typed.split_once('#') {
+            let link = resolve_document_link(&output, directory, &self.mapping.map_href(path));
+            let from = end - fragment.len();
+            return self
+                .files
+                .ids(&link.path)
+                .into_iter()
+                .flatten()
+                .filter(|id| id.starts_with(fragment))
+                .map(|id| item(id.to_string(), from, CompletionItemKind::REFERENCE))
+                .collect();
+        }
+        let mut paths: Vec<String> = self
+            .files
+            .paths()
+            .filter(|path| **path != output)
+            .map(|path| relative_path(directory, path))
+            .filter(|path| path.starts_with(typed))
+            .collect();
+        paths.sort();
+        paths
+            .into_iter()
+            .map(|path| item(path, start, CompletionItemKind::FILE))
+            .collect()
+    }
+}
+
+fn to_io_error<E: std::fmt::Display>(err: E) -> std::io::Error {
+    std::io::Error::other(err.to_string())
+}
+
+/// Tell the editor the diagnostics of a document
+fn publish(connection: &Connection, uri: Url, diagnostics: Vec<Diagnostic>) -> std::io::Result<()> {
+    let params = PublishDiagnosticsParams {
+        uri,
+        diagnostics,
+        version: None,
+    };
+    let notification = Notification::new(PublishDiagnostics::METHOD.into(), params);
+    connection
+        .sender
+        .send(Message::Notification(notification))
+        .map_err(to_io_error)
+}
+
+fn handle_request(
+    workspace: &Workspace,
+    connection: &Connection,
+    request: Request,
+) -> std::io::Result<()> {
+    let response = match request.method.as_str() {
+        Completion::METHOD => {
+            let (id, params): (_, CompletionParams) =
+                request.extract(Completion::METHOD).map_err(to_io_error)?;
+            let document = params.text_document_position;
+            let items = match workspace.open.get(&document.text_document.uri) {
+                Some(text) => {
+                    workspace.completions(&document.text_document.uri, text, document.position)
+                }
+                None => vec![],
+            };
+            Response::new_ok(id, items)
+        }
+        method => Response::new_err(
+            request.id,
+            ErrorCode::MethodNotFound as i32,
+            format!("unsupported method {method}"),
+        ),
+    };
+    connection
+        .sender
+        .send(Message::Response(response))
+        .map_err(to_io_error)
+}
+
+fn handle_notification(
+    workspace: &mut Workspace,
+    connection: &Connection,
+    notification: Notification,
+) -> std::io::Result<()> {
+    let (uri, text) = match notification.method.as_str() {
+        DidOpenTextDocument::METHOD => {
+            let params: DidOpenTextDocumentParams = notification
+                .extract(DidOpenTextDocument::METHOD)
+                .map_err(to_io_error)?;
+            (params.text_document.uri, params.text_document.text)
+        }
+        DidChangeTextDocument::METHOD => {
+            let params: DidChangeTextDocumentParams = notification
+                .extract(DidChangeTextDocument::METHOD)
+                .map_err(to_io_error)?;
+            // Documents are synced in full, so the last change is the whole text
+            let Some(change) = params.content_changes.into_iter().last() else {
+                return Ok(());
+            };
+            (params.text_document.uri, change.text)
+        }
+        DidCloseTextDocument::METHOD => {
+            let params: DidCloseTextDocumentParams = notification
+                .extract(DidCloseTextDocument::METHOD)
+                .map_err(to_io_error)?;
+            workspace.open.remove(&params.text_document.uri);
+            return publish(connection, params.text_document.uri, vec![]);
+        }
+        _ => return Ok(()),
+    };
+    let diagnostics = workspace.diagnostics(&uri, &text);
+    workspace.open.insert(uri.clone(), text);
+    publish(connection, uri, diagnostics)
+}
+
+/**
+Speak the Language Server Protocol on stdin and stdout until the editor shuts the server down,
+publishing the broken links of each open document as diagnostics and completing link targets.
+*/
+pub fn run(mut workspace: Workspace) -> std::io::Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(TRIGGER_CHARACTERS.iter().map(|c| c.to_string()).collect()),
+            ..CompletionOptions::default()
+        }),
+        ..ServerCapabilities::default()
+    };
+    connection
+        .initialize(serde_json::to_value(capabilities)?)
+        .map_err(to_io_error)?;
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request).map_err(to_io_error)? {
+                    break;
+                }
+                handle_request(&workspace, &connection, request)?;
+            }
+            Message::Notification(notification) => {
+                handle_notification(&mut workspace, &connection, notification)?;
+            }
+            Message::Response(_) => {}
+        }
+    }
+    drop(connection);
+    io_threads.join()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixture::Fixture;
+    use crate::progress::Progress;
+    use crate::rules::Rule;
+
+    #[test]
+    fn test_position() {
+        let text = "<p>é\n<a href=\"guide.html\">";
+        let start = text.find("guide").unwrap();
+        let position = position(text, start);
+        assert_eq!(position, Position::new(1, 9));
+        assert_eq!(offset(text, position), start);
+        assert_eq!(offset(text, Position::new(0, 2)), 2);
+        assert_eq!(offset(text, Position::new(0, 9)), "<p>é".len());
+    }
+
+    #[test]
+    fn test_href_ranges() {
+        let text = "[Guide](guide.html)\n<a href='guide.html'>\n<a href='guide.html#setup'>";
+        assert_eq!(
+            href_ranges(text, "guide.html"),
+            vec![
+                Range::new(Position::new(0, 8), Position::new(0, 18)),
+                Range::new(Position::new(1, 9), Position::new(1, 19)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_relative_path() {
+        let path = Path::new("guide/setup.html");
+        assert_eq!(relative_path(Path::new("guide"), path), "setup.html");
+        assert_eq!(relative_path(Path::new(""), path), "guide/setup.html");
+        assert_eq!(
+            relative_path(Path::new("blog"), path),
+            "../guide/setup.html"
+        );
+    }
+
+    #[test]
+    fn test_workspace() {
+        let fixture = Fixture::new();
+        fixture.write(
+            "guide/setup.html",
+            r#"<h1 id="install">Install</h1><h2 id="upgrade">Upgrade</h2>"#,
+        );
+        let index = fixture.write("index.html", "");
+        let readme = fixture.write("README.md", "");
+        let filter = FileFilter::default();
+        let directories = vec![fixture.path.clone()];
+        let files = HtmlFiles::new(&directories, &filter, 1, &Progress::default(), None).unwrap();
+        let mut rules = Rules::default();
+        rules.set(Rule::MissingFragment, Severity::Warn);
+        let mut workspace = Workspace {
+            files,
+            directories,
+            source_tree: None,
+            mapping: PathMapping::default(),
+            filter,
+            rules,
+            follow_redirects: false,
+            open: HashMap::new(),
+        };
+        let uri = Url::from_file_path(index).unwrap();
+        let text = "<a href='guide/setup.html#instal'></a>\n<a href='gone.html'></a>";
+        let diagnostics: Vec<(Range, Option<DiagnosticSeverity>)> = workspace
+            .diagnostics(&uri, text)
+            .into_iter()
+            .map(|diagnostic| (diagnostic.range, diagnostic.severity))
+            .collect();
+        assert_eq!(
+            diagnostics,
+            vec![
+                (
+                    Range::new(Position::new(0, 9), Position::new(0, 32)),
+                    Some(DiagnosticSeverity::WARNING)
+                ),
+                (
+                    Range::new(Position::new(1, 9), Position::new(1, 18)),
+                    Some(DiagnosticSeverity::ERROR)
+                ),
+            ]
+        );
+        // Markdown links are checked against the documents they're rendered to
+        let readme = Url::from_file_path(readme).unwrap();
+        let diagnostics =
+            workspace.diagnostics(&readme, "[Setup](guide/setup.md)\n[Gone](gone.md)");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(1, 7), Position::new(1, 14))
+        );
+
+        let labels = |text: &str| -> Vec<String> {
+            let end = position(text, text.len());
+            workspace
+                .completions(&uri, text, end)
+                .into_iter()
+                .map(|item| item.label)
+                .collect()
+        };
+        assert_eq!(labels("<a href=\"gu"), vec!["guide/setup.html"]);
+        assert_eq!(
+            labels("<a href='guide/setup.html#"),
+            vec!["install", "upgrade"]
+        );
+        assert_eq!(labels("[Setup](guide/setup.html#up"), vec!["upgrade"]);
+    }
+}
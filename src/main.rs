@@ -1,24 +1,917 @@
 use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::Read,
     path::{Path, PathBuf},
     process::exit,
+    time::{Duration, Instant, SystemTime},
 };
 
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
+use url::Url;
 
-mod html;
-mod tree;
-use crate::tree::HtmlFiles;
+mod config;
+#[cfg(test)]
+mod fixture;
+#[cfg(feature = "interactive")]
+mod interactive;
+mod lsp;
+mod serve;
+use crate::archive::{is_archive, Archive};
+use crate::baseline::{Baseline, BaselineEntry};
+use crate::check::{check_scan, CheckOptions, Findings, Resolution};
+use crate::config::{Config, DEFAULT_CONFIG_PATH};
+use crate::consistency::{check_consistency, PathMapping, SourceFiles};
+use crate::data_file::{broken_data_links, load_data_files, DEFAULT_DATA_KEYS};
+use crate::dot_report::LinkGraph;
+use crate::dump::{document_links, DumpFormat, DumpedLink, LinkKind, LinkTarget};
+use crate::environment::{parse_base_url, EnvironmentUrls};
+use crate::external::{
+    go_offline, ExternalChecker, Header, HostAuth, Nofollow, ProxySetting, StatusPolicy, UrlStatus,
+    DEFAULT_HOST_DELAY, DEFAULT_NET_JOBS,
+};
+use crate::feed::{broken_feed_links, load_feeds};
+use crate::filter::{DomainFilter, FileFilter, Mount};
+use crate::fix::{apply_fixes, suggested_fixes, Fix};
+use crate::html::{parse_attribute_name, Extractor, HtmlInfo, Ids, DEFAULT_IGNORE_ATTRIBUTE};
+use crate::intern::InternerStats;
+use crate::lsp::Workspace;
+use crate::manifest::{unpublished_urls, UnpublishedUrl, UrlManifest};
+use crate::progress::{Progress, Verbosity};
+use crate::recheck::Recheck;
+use crate::report::{Failure, Format, Report};
+use crate::rewrite::Rewrite;
+use crate::routes::VirtualRoutes;
+use crate::rules::{FailOn, Rule, Rules, Severity};
+use crate::schema::Schema;
+use crate::serve::{serve, Checker};
+use crate::shard::Shard;
+use crate::sink::{write_atomically, Sink};
+use crate::site_path::site_key;
+use crate::sitemap::{check_sitemaps, Sitemap, SitemapIssue, SITEMAP_PATH};
+use crate::stats::{elapsed_ms, render_metrics, Stats, Timings};
+use crate::streaming::TargetIndex;
+use crate::text_report::{render_stats, Color, GroupBy};
+use crate::tree::{
+    document_external_links, is_source_file, resolve_root_link, walk_documents, BrokenLink,
+    ExternalLink, FragmentMatch, Hooks, HtmlFileLink, HtmlFiles, LinkedFiles, Probing, ReadError,
+    RootLink, Scan, SourceLink, TrailingSlash, PRETTY_URL_DOCUMENT, SOURCE_EXTENSIONS,
+};
+use crate::url_cache::{parse_ttl, UrlCache, DEFAULT_TTL};
+#[cfg(feature = "streaming-parser")]
+use rlc::html_stream;
+use rlc::{
+    archive, baseline, check, consistency, data_file, diff, dot_report, dump, environment,
+    external, feed, filter, fix, html, intern, manifest, markdown, progress, recheck, report,
+    rewrite, routes, rules, schema, shard, sink, site_path, sitemap, stats, streaming, text_report,
+    tree, url_cache,
+};
 
 #[derive(Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Without a subcommand, the site is checked as `rlc check` does
+    #[command(flatten)]
+    args: Args,
+}
+
+// The options of every subcommand which scans a site, so that they all find the same documents
+// and resolve links the same way. Not a doc comment, which clap would show as their description
+#[derive(clap::Args, Debug)]
 struct Args {
+    /// Print the JSON schema of a document the check reads or writes, and exit
+    #[arg(long, value_enum, exclusive = true)]
+    schema: Option<Schema>,
+
+    /// Print every link in the scanned documents as JSON or CSV, like `rlc list` does
+    #[arg(long, value_enum, value_name = "FORMAT", hide = true)]
+    dump_links: Option<DumpFormat>,
+
+    /// Config file to read options from (default relative-link-check.toml, if it exists)
+    #[arg(long)]
+    config: Option<PathBuf>,
+
     #[arg(short, long)]
     base: Option<PathBuf>,
 
+    /// Directory that root-relative links like /assets/logo.png are resolved against, when it
+    /// isn't the scanned directory, like build for a scan of build/docs
+    #[arg(long)]
+    site_root: Option<PathBuf>,
+
+    /// Scan DIRECTORY as deployed at URL_PREFIX, like build/docs=/docs, so links between
+    /// directories deployed side by side resolve as they do on the site. Root-relative links
+    /// are then resolved against the mounted directories. May be given several times
+    #[arg(long, value_name = "DIRECTORY=URL_PREFIX", value_parser = Mount::parse)]
+    mount: Vec<Mount>,
+
+    /// JSON file of known broken links which should not fail the check
+    #[arg(long)]
+    baseline: Option<PathBuf>,
+
+    /// Record all currently broken links in the --baseline file instead of checking
+    #[arg(long, requires = "baseline")]
+    update_baseline: bool,
+
+    /// Don't flag links to local servers like http://localhost:8000/
+    #[arg(long)]
+    allow_localhost: bool,
+
+    /// The site's production URL, like https://example.com/. When it's served over https,
+    /// external links to it over http are reported
+    #[arg(long, value_name = "URL", value_parser = parse_base_url)]
+    base_url: Option<Url>,
+
+    /// Report external links starting with this prefix, like the URL of a staging or preview
+    /// deployment. May be given several times
+    #[arg(long = "deny-url-prefix", value_name = "PREFIX")]
+    deny_url_prefixes: Vec<String>,
+
+    /// Never report external links starting with this prefix for leading to the wrong
+    /// environment, even over http or under a --deny-url-prefix. May be given several times
+    #[arg(long = "allow-url-prefix", value_name = "PREFIX")]
+    allow_url_prefixes: Vec<String>,
+
+    /// Request every http and https link to other sites, and report those which don't answer
+    /// with a success
+    #[arg(long)]
+    check_external: bool,
+
+    /// How many hosts to request external links from at once, on threads of their own beside
+    /// the --fs-jobs ones (default 8)
+    #[arg(long)]
+    net_jobs: Option<usize>,
+
+    /// The old name of --net-jobs, still accepted with a warning
+    #[arg(long, value_name = "NET_JOBS", hide = true)]
+    max_concurrency: Option<usize>,
+
+    /// Milliseconds to wait between requests to the same host (default 100)
+    #[arg(long, value_name = "MILLISECONDS")]
+    host_delay: Option<u64>,
+
+    /// Don't request external links which the site's robots.txt disallows, and wait as long
+    /// between requests as its Crawl-delay asks
+    #[arg(long)]
+    respect_robots: bool,
+
+    /// A header to send with every external request, like a cookie or API key a site wants
+    /// before it answers with anything but 403
+    #[arg(long = "header", value_name = "NAME: VALUE", value_parser = Header::parse)]
+    headers: Vec<Header>,
+
+    /// Send the credentials in an environment variable with external requests to a host and
+    /// its subdomains: as they are if they name their scheme, like "Basic dXNlcjpwYXNz", or
+    /// else as a bearer token
+    #[arg(long, value_name = "HOST=ENV_VAR", value_parser = HostAuth::parse)]
+    auth: Vec<HostAuth>,
+
+    /// Make external requests through a proxy, or through the one the ALL_PROXY, HTTPS_PROXY
+    /// or HTTP_PROXY environment variable names with "env"
+    #[arg(long, value_name = "URL", value_parser = ProxySetting::parse)]
+    proxy: Option<ProxySetting>,
+
+    /// Also fetch the pages external links with a #fragment lead to, and report those whose
+    /// fragment isn't an id in the page
+    #[arg(long)]
+    check_external_fragments: bool,
+
+    /// Keep what came of requesting external links in this file, and only request those which
+    /// haven't worked within --external-cache-ttl again
+    #[arg(long, value_name = "FILE")]
+    external_cache: Option<PathBuf>,
+
+    /// How long results in the --external-cache are trusted, like 12h or 7d (default 1d)
+    #[arg(long, value_name = "DURATION", value_parser = parse_ttl)]
+    external_cache_ttl: Option<Duration>,
+
+    /// Only request external links to hosts matching this glob, like *.example.com, or their
+    /// subdomains
+    #[arg(long, value_name = "GLOB", value_delimiter = ',')]
+    external_only_domains: Vec<String>,
+
+    /// Don't request external links to hosts matching this glob, like twitter.com, or their
+    /// subdomains
+    #[arg(long, value_name = "GLOB", value_delimiter = ',')]
+    external_exclude_domains: Vec<String>,
+
+    /// Whether to check external links marked rel="nofollow", only warn when they're broken,
+    /// or skip them (default check)
+    #[arg(long, value_enum)]
+    nofollow: Option<Nofollow>,
+
+    /// Whether links to missing #fragments are errors, warnings, or not checked at all, for
+    /// sites whose ids are made by scripts (default error). The linked files must still exist
+    #[arg(long, value_name = "SEVERITY")]
+    check_fragments: Option<Severity>,
+
+    /// Parse documents outside the scanned directories (but within --base) when they are linked
+    /// to, so that their #fragments are checked too
+    #[arg(long)]
+    index_linked: bool,
+
+    /// Check the #fragments of links to <meta http-equiv="refresh"> redirect stubs in the
+    /// document they redirect to
+    #[arg(long)]
+    follow_redirects: bool,
+
+    /// Whether a link to "guide" also finds guide/index.html (lenient), only links to "guide/"
+    /// do (strict), or links to directories need the trailing slash and links to files mustn't
+    /// have one (require) (default lenient)
+    #[arg(long, value_enum)]
+    trailing_slash: Option<TrailingSlash>,
+
+    /// What a link to a directory leads to, looked for in the order given: names like index.html
+    /// inside it, or extensions like .html appended to the link (default index.html)
+    #[arg(long = "default-document", value_name = "NAME")]
+    default_documents: Vec<String>,
+
+    /// Also let a link to "about" lead to about.html, like GitHub Pages and many hosts do
+    #[arg(long)]
+    pretty_urls: bool,
+
+    /// How a link's #fragment is compared with ids: exactly, ignoring case, or as slugs so
+    /// #getting-started finds id="Getting Started" (default exact)
+    #[arg(long, value_enum)]
+    fragment_match: Option<FragmentMatch>,
+
+    /// Extensions of the files to parse as HTML documents (default html)
+    #[arg(long, value_delimiter = ',')]
+    extensions: Vec<String>,
+
+    /// Only parse files matching this glob, relative to the scanned directory
+    #[arg(long)]
+    include: Vec<String>,
+
+    /// Don't parse files or walk directories matching this glob, like **/node_modules
+    #[arg(long)]
+    exclude: Vec<String>,
+
+    /// Also check the value of ATTRIBUTE on every element matching SELECTOR like a link, like
+    /// div[data-href]@data-href or iframe[src]@src. May be given several times
+    #[arg(long, value_name = "SELECTOR@ATTRIBUTE", value_parser = Extractor::parse)]
+    extract: Vec<Extractor>,
+
+    /// Also check the links and ids inside <template> and <noscript> elements, for sites which
+    /// stamp out templates with scripts
+    #[arg(long)]
+    check_templates: bool,
+
+    /// Also check the url(...) references in style attributes and <style> elements, like
+    /// background images
+    #[arg(long)]
+    check_styles: bool,
+
+    /// The attribute marking elements whose links, and those of everything inside them, are
+    /// placeholders or broken on purpose, so they aren't checked (default data-rlc-ignore)
+    #[arg(long, value_name = "NAME", value_parser = parse_attribute_name)]
+    ignore_attribute: Option<String>,
+
+    /// Walk into symlinked directories. Symlinks back up the tree are skipped
+    #[arg(long)]
+    follow_symlinks: bool,
+
+    /// Also walk files and directories matched by .gitignore and .ignore files in the scanned
+    /// directories, which are skipped by default
+    #[arg(long)]
+    no_ignore: bool,
+
+    /// How many documents to parse, and check the links of, at once (default: one per CPU)
+    #[arg(long)]
+    fs_jobs: Option<usize>,
+
+    /// Skip files larger than this many bytes, like generated dumps, with a file-too-large
+    /// warning instead of reading them. Links to skipped documents aren't broken, but their ids
+    /// aren't known
+    #[arg(long, value_name = "BYTES")]
+    max_file_size: Option<u64>,
+
+    /// Only walk this many directories deep into the scanned directories, so 0 parses just the
+    /// documents directly in them. Links to files further down are only checked for existence
+    #[arg(long, value_name = "N")]
+    max_depth: Option<usize>,
+
+    /// Parse documents by streaming through them instead of building their whole tree, which is
+    /// faster and needs less memory on large pages
+    #[cfg(feature = "streaming-parser")]
+    #[arg(long)]
+    streaming_parser: bool,
+
+    /// Never use the network, and refuse options which need it, like --check-external, --serve
+    /// and webhook outputs
+    #[arg(long)]
+    offline: bool,
+
+    /// Keep an index of the site in memory and check single documents sent to POST /check on
+    /// this address, like 127.0.0.1:7070, answering with their failures as JSON. For editors to
+    /// check a document as it's edited without scanning the site each time
+    #[arg(long, value_name = "ADDRESS", conflicts_with = "update_baseline")]
+    serve: Option<String>,
+
+    /// Triage the broken links in a terminal interface instead of reporting them, opening each in
+    /// $EDITOR, recording it in the --baseline file or applying a suggested fix
+    #[cfg(feature = "interactive")]
+    #[arg(long, conflicts_with = "update_baseline")]
+    interactive: bool,
+
+    /// Abort on the first file or directory which can't be read, instead of reporting it and
+    /// carrying on
+    #[arg(long)]
+    strict_io: bool,
+
+    /// Keep only the valid link targets in memory and parse every document twice, for very
+    /// large sites
+    #[arg(long, conflicts_with = "source_tree")]
+    low_memory: bool,
+
+    /// Check only this document, against the link targets of the scanned directories it's in.
+    /// May be given several times, and documents given among the directories are added too
+    #[arg(long, group = "document", conflicts_with_all = ["source_tree", "low_memory"])]
+    file: Vec<PathBuf>,
+
+    /// Check only a document read from stdin, as if it were at PATH in the scanned directory
+    #[arg(
+        long,
+        value_name = "PATH",
+        group = "document",
+        conflicts_with_all = ["source_tree", "low_memory"]
+    )]
+    stdin: Option<PathBuf>,
+
+    /// Check only the documents among the paths read from stdin, one per line, like the output
+    /// of git diff --name-only. Paths which aren't documents in the scanned directories, or no
+    /// longer exist, are skipped
+    #[arg(
+        long,
+        group = "document",
+        conflicts_with_all = ["source_tree", "low_memory"]
+    )]
+    changed_only: bool,
+
+    /// Check the --file, --stdin or --changed-only documents against the link targets saved by --save-index,
+    /// instead of scanning the directories again
+    #[arg(long, requires = "document")]
+    index: Option<PathBuf>,
+
+    /// Save the link targets of the scanned directories to this file, for later runs with
+    /// --index
+    #[arg(long)]
+    save_index: Option<PathBuf>,
+
+    /// Report documents which contain the same id more than once
+    #[arg(long)]
+    check_duplicate_ids: bool,
+
+    /// Report <label for>, aria-labelledby, aria-describedby and headers attributes referring
+    /// to ids which aren't in the same document
+    #[arg(long)]
+    check_id_references: bool,
+
+    /// Report h2 and h3 headings left out of a page's <nav class="toc"> table of contents
+    #[arg(long)]
+    check_toc: bool,
+
+    /// Report links which may not lead to the same file on every common filesystem: those with
+    /// backslashes, names Windows doesn't allow, or names differing only by case from a file
+    #[arg(long)]
+    check_portability: bool,
+
+    /// Report placeholder links left in while writing: href="", href="#" and
+    /// href="javascript:void(0)". Each is its own rule, empty-link, hash-link and
+    /// javascript-link, so it can be turned off with --rule
+    #[arg(long)]
+    check_placeholder_links: bool,
+
+    /// Report documents which are byte-identical copies of another document. JSON reports also
+    /// gather them into sets of identical documents, under "duplicates"
+    #[arg(long)]
+    check_duplicate_content: bool,
+
+    /// Report <meta http-equiv="refresh"> redirect stubs which lead through more than
+    /// --max-redirect-hops stubs before reaching a page, or around in a loop
+    #[arg(long)]
+    check_redirect_chains: bool,
+
+    /// How many redirects a stub may take to reach a page, for --check-redirect-chains
+    /// (default 1)
+    #[arg(long, value_name = "N")]
+    max_redirect_hops: Option<usize>,
+
+    /// Report <link rel="alternate" hreflang> translations which don't link back to the page
+    /// linking to them
+    #[arg(long)]
+    check_hreflang: bool,
+
+    /// Report links to Markdown, reStructuredText and AsciiDoc sources, like guide.md, which
+    /// should lead to the page rendered from them
+    #[arg(long)]
+    check_source_links: bool,
+
+    /// Check links to sources as links to the pages rendered from them, as mapped by --map,
+    /// for hosts which rewrite them. Only those whose page doesn't exist are reported
+    #[arg(long)]
+    map_source_links: bool,
+
+    /// Report image srcsets which lack any of these variants, like 1x,2x or 480w,1080w, or whose
+    /// image for one doesn't exist
+    #[arg(long, value_delimiter = ',')]
+    srcset_variants: Vec<String>,
+
+    /// Check that every URL in the sitemap.xml of each scanned directory leads to a file. Set
+    /// the missing-from-sitemap rule to also report documents the sitemap leaves out
+    #[arg(long)]
+    check_sitemap: bool,
+
+    /// Check that the internal links of every RSS and Atom feed, the .xml files whose items
+    /// link to pages or enclosures of the site, lead to a file
+    #[arg(long)]
+    check_feeds: bool,
+
+    /// Also check the links in the .json, .yaml and .yml files of this directory, like the data
+    /// directory of a Hugo, Jekyll or Eleventy site, against the scanned site. May be given
+    /// several times
+    #[arg(long = "data-dir", value_name = "DIR")]
+    data_dirs: Vec<PathBuf>,
+
+    /// The keys whose values in data files are links, at any depth (default url, href, link).
+    /// May be given several times
+    #[arg(long = "data-key", value_name = "KEY")]
+    data_keys: Vec<String>,
+
+    /// Check that every URL in this file, a list of the URLs the site has ever published with one
+    /// per line, still leads to a file, so links to them from other sites don't break. A
+    /// redirect is suggested for each URL whose page seems to have moved
+    #[arg(long, value_name = "FILE")]
+    url_manifest: Option<PathBuf>,
+
+    /// Set a rule to error, warn or off, like missing-fragment=warn. Warnings are reported but
+    /// don't fail the check. May be given several times
+    #[arg(long = "rule", value_name = "RULE=SEVERITY", value_parser = Rules::parse_setting)]
+    rule_settings: Vec<(Rule, Severity)>,
+
+    /// Exit with an error when there are failures (error), when there are failures or warnings
+    /// (warning), or never (none) (default error)
+    #[arg(long, value_enum, global = true)]
+    fail_on: Option<FailOn>,
+
+    /// Markdown source tree the scanned HTML was generated from. When given, also check that
+    /// every source link made it into the output and vice versa
+    #[arg(long)]
+    source_tree: Option<PathBuf>,
+
+    /// How the generator maps source extensions to output extensions, for --source-tree and
+    /// --map-source-links (default md=html, and rst=html and adoc=html for links to sources)
+    #[arg(long = "map", value_parser = PathMapping::parse_rule)]
+    mappings: Vec<(String, String)>,
+
+    /// Only report the failures of an earlier JSON report which are still failing, requesting
+    /// only its broken external links again
+    #[arg(long, value_name = "REPORT", conflicts_with = "update_baseline")]
+    recheck: Option<PathBuf>,
+
+    /// Only report failures in this share of the documents, like 2/5 for the second of five jobs
+    #[arg(long, value_parser = Shard::parse, conflicts_with = "update_baseline")]
+    shard: Option<Shard>,
+
+    /// How to print the failures, for outputs which don't name a format
+    #[arg(long, value_enum, default_value_t, global = true)]
+    format: Format,
+
+    /// Where to send the report (default stdout), like report.txt, json=report.json, text=- for
+    /// stdout or webhook=<URL> to POST a summary. May be given several times. Files are replaced
+    /// atomically, and unless text also goes to stdout the summary line is printed to stderr
+    #[arg(long = "output", global = true)]
+    outputs: Vec<String>,
+
+    /// When to color the report printed to stdout. Auto colors it for terminals, unless
+    /// NO_COLOR is set
+    #[arg(long, value_enum, default_value_t, global = true)]
+    color: Color,
+
+    /// Whether the text report lists failures under the document they're in, or lists each
+    /// broken link target once under the documents linking to it
+    #[arg(long, value_enum, default_value_t, global = true)]
+    group_by: GroupBy,
+
+    /// Log every document as it's parsed
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Print nothing but the report, not even a progress bar
+    #[arg(short, long)]
+    quiet: bool,
+
+    /// Print nothing at all when there is nothing to report, instead of a line saying so
+    #[arg(long)]
+    quiet_success: bool,
+
+    /// Print totals of the documents, links and failures to stderr, with how long each phase
+    /// took and how much memory sharing repeated hrefs and ids saved, and add them to JSON
+    /// reports
+    #[arg(long)]
+    stats: bool,
+
+    /// Write the same totals to this file in the text format of Prometheus, for its node
+    /// exporter's textfile collector
+    #[arg(long, value_name = "PATH")]
+    metrics_file: Option<PathBuf>,
+
+    /// Directories of documents to check, or zip archives and tarballs of them. Documents
+    /// given here are checked as with --file
     #[arg()]
     directories: Vec<PathBuf>,
+
+    /// Only set by the config file
+    #[arg(skip)]
+    virtual_routes: VirtualRoutes,
+
+    /// Only set by the config file
+    #[arg(skip)]
+    rewrites: Vec<Rewrite>,
+
+    /// Only set by the config file
+    #[arg(skip)]
+    status_codes: StatusPolicy,
+
+    /// Only set by the subcommand
+    #[arg(skip)]
+    mode: Mode,
+}
+
+/**
+What is done with the scanned site, as the subcommand says.
+*/
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    #[default]
+    Check,
+    List {
+        internal: bool,
+        external: bool,
+        format: DumpFormat,
+    },
+    Graph {
+        mermaid: bool,
+    },
+    Fix {
+        dry_run: bool,
+    },
+    Lsp,
+    Targets {
+        json: bool,
+    },
+    Diff,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check the links of the scanned documents, which is also what rlc does without a subcommand
+    Check(Box<Args>),
+    /// Print every link in the scanned documents with where it resolves to, without checking any
+    /// of them
+    List(ListArgs),
+    /// Draw the scanned documents and the links between them, with broken links in color
+    Graph(GraphArgs),
+    /// Rewrite broken links which have an obvious fix, like a missing fragment to the most similar
+    /// id, in the scanned documents
+    Fix(FixArgs),
+    /// Answer an editor over the Language Server Protocol on stdin and stdout, with the broken
+    /// links of open HTML and Markdown documents and completions for link targets
+    Lsp(Box<Args>),
+    /// Print every file links can lead to, and every id in it links can lead to as a #fragment,
+    /// for editors to complete links from
+    Targets(TargetsArgs),
+    /// Compare an old build of the site with a new one, given as the two directories or archives
+    /// in that order, and report the files and linked #fragments the new build has lost, with
+    /// the old documents which linked to them
+    Diff(Box<Args>),
+    /// Work with JSON reports written by --format json
+    #[command(subcommand)]
+    Report(ReportCommand),
+    /// Work with config files
+    #[command(subcommand)]
+    Config(ConfigCommand),
+}
+
+#[derive(clap::Args, Debug)]
+struct ListArgs {
+    /// Only list links to files of the site, along with any --external ones
+    #[arg(long)]
+    internal: bool,
+
+    /// Only list http and https links to other sites, along with any --internal ones
+    #[arg(long)]
+    external: bool,
+
+    /// Write a CSV table with a header row instead of a JSON array
+    #[arg(long)]
+    csv: bool,
+
+    #[command(flatten)]
+    args: Box<Args>,
+}
+
+#[derive(clap::Args, Debug)]
+struct GraphArgs {
+    /// Write the graph in Graphviz's DOT language, which is the default
+    #[arg(long)]
+    dot: bool,
+
+    /// Write the graph as a Mermaid flowchart instead
+    #[arg(long, conflicts_with = "dot")]
+    mermaid: bool,
+
+    #[command(flatten)]
+    args: Box<Args>,
+}
+
+#[derive(clap::Args, Debug)]
+struct TargetsArgs {
+    /// Write a JSON array of files with their fragments instead of a line for each target
+    #[arg(long)]
+    json: bool,
+
+    #[command(flatten)]
+    args: Box<Args>,
+}
+
+#[derive(clap::Args, Debug)]
+struct FixArgs {
+    /// Print the links which would be rewritten without changing any document
+    #[arg(long)]
+    dry_run: bool,
+
+    #[command(flatten)]
+    args: Box<Args>,
+}
+
+impl Command {
+    /// The options of a subcommand which scans a site, with its mode
+    fn scan_args(self) -> Option<Args> {
+        Some(match self {
+            Command::Check(args) => *args,
+            Command::List(ListArgs {
+                internal,
+                external,
+                csv,
+                args,
+            }) => Args {
+                mode: Mode::List {
+                    internal,
+                    external,
+                    format: match csv {
+                        true => DumpFormat::Csv,
+                        false => DumpFormat::Json,
+                    },
+                },
+                ..*args
+            },
+            Command::Graph(GraphArgs { mermaid, args, .. }) => Args {
+                mode: Mode::Graph { mermaid },
+                ..*args
+            },
+            Command::Fix(FixArgs { dry_run, args }) => Args {
+                mode: Mode::Fix { dry_run },
+                ..*args
+            },
+            Command::Lsp(args) => Args {
+                mode: Mode::Lsp,
+                ..*args
+            },
+            Command::Targets(TargetsArgs { json, args }) => Args {
+                mode: Mode::Targets { json },
+                ..*args
+            },
+            Command::Diff(args) => Args {
+                mode: Mode::Diff,
+                ..*args
+            },
+            Command::Report(_) | Command::Config(_) => return None,
+        })
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Write a commented config file documenting every option
+    Init {
+        #[arg(default_value = DEFAULT_CONFIG_PATH)]
+        path: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ReportCommand {
+    /// Combine the reports of several --shard jobs into one
+    Merge {
+        #[arg(required = true)]
+        reports: Vec<PathBuf>,
+    },
 }
 
 impl Args {
+    /// Fill in any options not given on the command line from the config file
+    pub fn apply_config(&mut self) {
+        let path = match &self.config {
+            Some(path) => path.clone(),
+            None if Path::new(DEFAULT_CONFIG_PATH).is_file() => PathBuf::from(DEFAULT_CONFIG_PATH),
+            None => return,
+        };
+        let config = match Config::load(&path) {
+            Ok(config) => config,
+            Err(err) => {
+                eprintln!("Invalid config file {path:?}:\n{err}");
+                exit(1)
+            }
+        };
+        for (old, new) in &config.renamed_keys {
+            eprintln!("`{old}` in config file {path:?} was renamed to `{new}`");
+        }
+        if self.directories.is_empty() {
+            self.directories = config.directories;
+        }
+        self.base = self.base.take().or(config.base);
+        self.site_root = self.site_root.take().or(config.site_root);
+        if self.mount.is_empty() {
+            self.mount = config
+                .mount
+                .into_iter()
+                .map(|(directory, prefix)| Mount::new(directory, &prefix))
+                .collect();
+        }
+        self.baseline = self.baseline.take().or(config.baseline);
+        self.allow_localhost |= config.allow_localhost;
+        if self.base_url.is_none() {
+            match config.base_url.as_deref().map(parse_base_url) {
+                Some(Err(err)) => {
+                    eprintln!("Invalid base-url in config file {path:?}: {err}");
+                    exit(1)
+                }
+                base_url => self.base_url = base_url.and_then(Result::ok),
+            }
+        }
+        self.deny_url_prefixes.extend(config.deny_url_prefixes);
+        self.allow_url_prefixes.extend(config.allow_url_prefixes);
+        self.check_external |= config.check_external;
+        self.net_jobs = self.net_jobs.or(config.net_jobs);
+        self.host_delay = self.host_delay.or(config.host_delay);
+        self.respect_robots |= config.respect_robots;
+        match config.headers.iter().map(|h| Header::parse(h)).collect() {
+            // Headers given on the command line come last, so they replace the config's
+            Ok(headers) => self.headers = [headers, std::mem::take(&mut self.headers)].concat(),
+            Err(err) => {
+                eprintln!("Invalid headers in config file {path:?}: {err}");
+                exit(1)
+            }
+        }
+        if self.external_only_domains.is_empty() {
+            self.external_only_domains = config.external_only_domains;
+        }
+        (self.external_exclude_domains).extend(config.external_exclude_domains);
+        self.status_codes = config.status_codes;
+        self.check_external_fragments |= config.check_external_fragments;
+        self.external_cache = self.external_cache.take().or(config.external_cache);
+        if self.external_cache_ttl.is_none() {
+            match config.external_cache_ttl.as_deref().map(parse_ttl) {
+                Some(Err(err)) => {
+                    eprintln!("Invalid external-cache-ttl in config file {path:?}: {err}");
+                    exit(1)
+                }
+                ttl => self.external_cache_ttl = ttl.and_then(Result::ok),
+            }
+        }
+        if self.auth.is_empty() {
+            self.auth = (config.auth.iter())
+                .map(|(host, variable)| HostAuth::new(host, variable))
+                .collect();
+        }
+        if self.proxy.is_none() {
+            match config.proxy.as_deref().map(ProxySetting::parse) {
+                Some(Err(err)) => {
+                    eprintln!("Invalid proxy in config file {path:?}: {err}");
+                    exit(1)
+                }
+                proxy => self.proxy = proxy.and_then(Result::ok),
+            }
+        }
+        self.nofollow = self.nofollow.or(config.nofollow);
+        self.check_fragments = self.check_fragments.or(config.check_fragments);
+        self.check_duplicate_ids |= config.check_duplicate_ids;
+        self.check_id_references |= config.check_id_references;
+        self.check_toc |= config.check_toc;
+        self.check_portability |= config.check_portability;
+        self.check_placeholder_links |= config.check_placeholder_links;
+        self.check_duplicate_content |= config.check_duplicate_content;
+        self.check_redirect_chains |= config.check_redirect_chains;
+        self.max_redirect_hops = self.max_redirect_hops.or(config.max_redirect_hops);
+        self.check_hreflang |= config.check_hreflang;
+        self.check_source_links |= config.check_source_links;
+        self.map_source_links |= config.map_source_links;
+        self.check_sitemap |= config.check_sitemap;
+        self.check_feeds |= config.check_feeds;
+        self.data_dirs.extend(config.data_dirs);
+        self.url_manifest = self.url_manifest.take().or(config.url_manifest);
+        if self.data_keys.is_empty() {
+            self.data_keys = config.data_keys;
+        }
+        if self.srcset_variants.is_empty() {
+            self.srcset_variants = config.srcset_variants;
+        }
+        self.index_linked |= config.index_linked;
+        self.follow_redirects |= config.follow_redirects;
+        self.trailing_slash = self.trailing_slash.or(config.trailing_slash);
+        if self.default_documents.is_empty() {
+            self.default_documents = config.default_documents;
+        }
+        self.pretty_urls |= config.pretty_urls;
+        self.fragment_match = self.fragment_match.or(config.fragment_match);
+        self.low_memory |= config.low_memory;
+        self.strict_io |= config.strict_io;
+        self.fs_jobs = self.fs_jobs.or(config.fs_jobs);
+        self.max_file_size = self.max_file_size.or(config.max_file_size);
+        self.max_depth = self.max_depth.or(config.max_depth);
+        self.source_tree = self.source_tree.take().or(config.source_tree);
+        if self.mappings.is_empty() {
+            self.mappings = config.map.into_iter().collect();
+        }
+        if self.extensions.is_empty() {
+            self.extensions = config.extensions;
+        }
+        if self.include.is_empty() {
+            self.include = config.include;
+        }
+        self.exclude.extend(config.exclude);
+        if self.extract.is_empty() {
+            match config.extract.iter().map(|e| Extractor::parse(e)).collect() {
+                Ok(extract) => self.extract = extract,
+                Err(err) => {
+                    eprintln!("Invalid extract in config file {path:?}: {err}");
+                    exit(1)
+                }
+            }
+        }
+        match config.rewrite.iter().map(Rewrite::new).collect() {
+            Ok(rewrites) => self.rewrites = rewrites,
+            Err(err) => {
+                eprintln!("Invalid rewrite pattern in config file {path:?}: {err}");
+                exit(1)
+            }
+        }
+        match VirtualRoutes::new(&config.virtual_routes) {
+            Ok(routes) => self.virtual_routes = routes,
+            Err(err) => {
+                eprintln!("Invalid virtual-routes pattern in config file {path:?}: {err}");
+                exit(1)
+            }
+        }
+        self.check_templates |= config.check_templates;
+        self.check_styles |= config.check_styles;
+        if self.ignore_attribute.is_none() {
+            match config.ignore_attribute.as_deref().map(parse_attribute_name) {
+                Some(Err(err)) => {
+                    eprintln!("Invalid ignore-attribute in config file {path:?}: {err}");
+                    exit(1)
+                }
+                ignore_attribute => self.ignore_attribute = ignore_attribute.and_then(Result::ok),
+            }
+        }
+        self.follow_symlinks |= config.follow_symlinks;
+        self.no_ignore |= config.no_ignore;
+        // Later settings win, so the command line overrides the config file
+        self.fail_on = self.fail_on.or(config.fail_on);
+        let cli_settings = std::mem::take(&mut self.rule_settings);
+        self.rule_settings = config.rules.into_iter().chain(cli_settings).collect();
+        self.offline |= config.offline;
+        if self.low_memory && self.source_tree.is_some() {
+            eprintln!("low-memory can't be combined with source-tree");
+            exit(1)
+        }
+    }
+    /// Exit if --offline is given with options which need the network, and otherwise make
+    /// sure nothing can reach it for the rest of the run
+    pub fn go_offline(&self) {
+        if !self.offline {
+            return;
+        }
+        let webhook = self
+            .sinks()
+            .iter()
+            .any(|sink| matches!(sink, Sink::Webhook(_)));
+        let needs_network = [
+            (self.check_external, "check-external"),
+            (self.serve.is_some(), "serve"),
+            (webhook, "webhook outputs"),
+        ];
+        for (given, option) in needs_network {
+            if given {
+                eprintln!("offline can't be combined with {option}");
+                exit(1)
+            }
+        }
+        go_offline();
+    }
     pub fn base_dir(&self) -> std::io::Result<PathBuf> {
         let current_dir = std::env::current_dir()?;
         let base_dir = self
@@ -28,12 +921,308 @@ impl Args {
             .canonicalize()?;
         Ok(base_dir)
     }
+    pub fn sinks(&self) -> Vec<Sink> {
+        if self.outputs.is_empty() {
+            return vec![Sink::Stdout(self.format)];
+        }
+        match self
+            .outputs
+            .iter()
+            .map(|spec| Sink::parse(spec, self.format))
+            .collect()
+        {
+            Ok(sinks) => sinks,
+            Err(err) => {
+                eprintln!("Invalid --output: {err}");
+                exit(1)
+            }
+        }
+    }
+    /// The severity of every rule, from the --check-* flags and then any --rule settings
+    pub fn rules(&self) -> Rules {
+        let mut rules = Rules::default();
+        let flags = [
+            (self.allow_localhost, Rule::LocalServerLink, Severity::Off),
+            (self.check_duplicate_ids, Rule::DuplicateId, Severity::Error),
+            (
+                self.check_id_references,
+                Rule::BrokenIdReference,
+                Severity::Error,
+            ),
+            (self.check_toc, Rule::MissingFromToc, Severity::Error),
+            (
+                self.check_portability,
+                Rule::NonPortableLink,
+                Severity::Error,
+            ),
+            (
+                self.check_duplicate_content,
+                Rule::DuplicateContent,
+                Severity::Error,
+            ),
+            (
+                self.check_redirect_chains,
+                Rule::RedirectChain,
+                Severity::Error,
+            ),
+            (
+                self.check_hreflang,
+                Rule::MissingReturnLink,
+                Severity::Error,
+            ),
+            (
+                self.check_source_links,
+                Rule::SourceFileLink,
+                Severity::Error,
+            ),
+        ];
+        for (flag, rule, severity) in flags {
+            if flag {
+                rules.set(rule, severity);
+            }
+        }
+        if self.check_placeholder_links {
+            for rule in [Rule::EmptyLink, Rule::HashLink, Rule::JavascriptLink] {
+                rules.set(rule, Severity::Error);
+            }
+        }
+        if let Some(severity) = self.check_fragments {
+            rules.set(Rule::MissingFragment, severity);
+        }
+        match self.nofollow.unwrap_or_default() {
+            Nofollow::Check => {}
+            Nofollow::Warn => rules.set(Rule::BrokenNofollowLink, Severity::Warn),
+            Nofollow::Skip => rules.set(Rule::BrokenNofollowLink, Severity::Off),
+        }
+        for (rule, severity) in self.rule_settings.iter() {
+            rules.set(*rule, *severity);
+        }
+        rules
+    }
+    /// Which files links may lead to besides the ones they name
+    pub fn probing(&self) -> Probing {
+        let mut probing = Probing {
+            trailing_slash: self.trailing_slash.unwrap_or_default(),
+            ..Probing::default()
+        };
+        if !self.default_documents.is_empty() {
+            probing.default_documents = self.default_documents.clone();
+        }
+        if self.pretty_urls
+            && !probing
+                .default_documents
+                .iter()
+                .any(|d| d == PRETTY_URL_DOCUMENT)
+        {
+            probing
+                .default_documents
+                .push(PRETTY_URL_DOCUMENT.to_string());
+        }
+        probing
+    }
+    /// Whether root-relative links are resolved, against the site root or the mounts
+    pub fn resolves_root_links(&self) -> bool {
+        self.site_root.is_some() || !self.mount.is_empty()
+    }
+    /**
+    The path of each scanned directory within the site root, for those inside it. Documents in
+    mounted directories are already keyed by their path within the site.
+    */
+    pub fn site_prefixes(&self, directories: &[PathBuf]) -> std::io::Result<Vec<PathBuf>> {
+        if !self.mount.is_empty() {
+            return Ok(vec![PathBuf::new()]);
+        }
+        let Some(site_root) = &self.site_root else {
+            return Ok(vec![]);
+        };
+        let site_root = site_root.canonicalize()?;
+        let mut prefixes = vec![];
+        for directory in directories {
+            if let Ok(prefix) = directory.canonicalize()?.strip_prefix(&site_root) {
+                prefixes.push(prefix.to_path_buf());
+            }
+        }
+        Ok(prefixes)
+    }
+    /// How broken links are resolved, for the scanned `directories` under the `base_dir`
+    pub fn link_resolver(
+        &self,
+        base_dir: &Path,
+        directories: &[PathBuf],
+    ) -> std::io::Result<LinkResolver> {
+        // Sources are rendered as --map says, or as HTML if it doesn't mention their extension
+        let source_mapping = PathMapping::new(
+            self.mappings
+                .iter()
+                .cloned()
+                .chain(
+                    SOURCE_EXTENSIONS
+                        .iter()
+                        .map(|extension| (extension.to_string(), "html".to_string())),
+                )
+                .collect(),
+        );
+        Ok(LinkResolver {
+            base_dir: base_dir.to_path_buf(),
+            map_source_links: self.map_source_links,
+            source_mapping,
+            site_root: self.site_root.clone(),
+            site_prefixes: match self.resolves_root_links() {
+                true => Some(self.site_prefixes(directories)?),
+                false => None,
+            },
+            probing: self.probing(),
+        })
+    }
+    /**
+    How a scan is checked, by a whole check and by --serve alike, with broken links resolved by
+    the `resolver` and links looked up with `contains`.
+    */
+    pub fn check_options<'a, F: Fn(&HtmlFileLink) -> bool + 'a>(
+        &self,
+        resolver: &'a LinkResolver,
+        contains: F,
+    ) -> CheckOptions<'a> {
+        CheckOptions {
+            follow_redirects: self.follow_redirects,
+            virtual_routes: self.virtual_routes.clone(),
+            allow_localhost: self.allow_localhost,
+            max_redirect_hops: self.max_redirect_hops,
+            srcset_variants: self.srcset_variants.clone(),
+            probing: self.probing(),
+            resolve: Some(Box::new(move |broken: &mut BrokenLink| {
+                resolver.resolve(broken, &contains)
+            })),
+            find_elsewhere: Some(Box::new(|broken: &BrokenLink| {
+                Ok(file_exists(&resolver.base_dir, &broken.link.path))
+            })),
+            ..CheckOptions::default()
+        }
+    }
+    /// Where to send a report, leaving out printing it as text if --quiet-success hushes it
+    pub fn report_sinks(&self, report: &Report) -> Vec<Sink> {
+        let mut sinks = self.sinks();
+        if self.quiet_success && report.is_clean() {
+            sinks.retain(|sink| sink != &Sink::Stdout(Format::Text));
+        }
+        sinks
+    }
+    pub fn verbosity(&self) -> Verbosity {
+        match (self.quiet, self.verbose) {
+            (true, _) => Verbosity::Quiet,
+            (_, true) => Verbosity::Verbose,
+            _ => Verbosity::Normal,
+        }
+    }
+    /// Progress is only drawn while the reports are meant for people and going to a terminal
+    pub fn progress(&self) -> Progress {
+        let show_bar = self.sinks().iter().all(Sink::is_human_readable);
+        Progress::new(self.verbosity(), show_bar)
+    }
+    /**
+    The documents given by --file, --stdin or --changed-only, relative to the scanned directory
+    each is in. Nothing is returned when the whole directories should be checked instead.
+    */
+    pub fn selected_documents(
+        &self,
+        directories: &[PathBuf],
+        filter: &FileFilter,
+    ) -> std::io::Result<Option<Vec<(PathBuf, HtmlInfo)>>> {
+        if let Some(path) = &self.stdin {
+            let mut document = String::new();
+            std::io::stdin().read_to_string(&mut document)?;
+            let info = HtmlInfo::parse_with(&document, &filter.parse);
+            return Ok(Some(vec![(path.clone(), info)]));
+        }
+        if self.changed_only {
+            let mut documents = vec![];
+            for line in std::io::stdin().lines() {
+                let file = PathBuf::from(line?.trim());
+                // Deleted files are listed as changed too
+                if !file.is_file() {
+                    continue;
+                }
+                if let Some((directory, path)) = scanned_path(directories, &file)? {
+                    if filter.is_document(&path) {
+                        let info = HtmlInfo::parse_file(&file, &filter.parse)?;
+                        documents.push((site_key(filter.url_prefix(directory), path), info));
+                    }
+                }
+            }
+            return Ok(Some(documents));
+        }
+        if self.file.is_empty() {
+            return Ok(None);
+        }
+        let mut documents = vec![];
+        for file in self.file.iter() {
+            let Some((directory, path)) = scanned_path(directories, file)? else {
+                eprintln!("{file:?} is not in any of the scanned directories");
+                exit(1)
+            };
+            let info = HtmlInfo::parse_file(file, &filter.parse)?;
+            documents.push((site_key(filter.url_prefix(directory), path), info));
+        }
+        Ok(Some(documents))
+    }
+    pub fn file_filter(&self) -> FileFilter {
+        match FileFilter::new(&self.extensions, &self.include, &self.exclude) {
+            Ok(mut filter) => {
+                filter.follow_symlinks = self.follow_symlinks;
+                filter.ignore_files = !self.no_ignore;
+                filter.parse.extractors = self.extract.clone();
+                filter.parse.templates = self.check_templates;
+                filter.parse.styles = self.check_styles;
+                filter.parse.rewrites = self.rewrites.clone();
+                filter.parse.ignore_attribute = Some(
+                    (self.ignore_attribute.clone()).unwrap_or(DEFAULT_IGNORE_ATTRIBUTE.to_string()),
+                );
+                #[cfg(feature = "streaming-parser")]
+                {
+                    filter.parse.streaming = self.streaming_parser;
+                    if self.streaming_parser {
+                        for extractor in self.extract.iter() {
+                            if let Err(err) = html_stream::supports(extractor) {
+                                eprintln!("Invalid --extract: {err}");
+                                exit(1)
+                            }
+                        }
+                    }
+                }
+                filter.max_file_size = self.max_file_size;
+                filter.max_depth = self.max_depth;
+                filter.mounts = self.mount.clone();
+                filter
+            }
+            Err(err) => {
+                eprintln!("Invalid glob: {err}");
+                exit(1)
+            }
+        }
+    }
     pub fn resolve_directories(&mut self) -> std::io::Result<&[PathBuf]> {
         let current_dir = std::env::current_dir()?;
+        // Documents given among the directories are checked like --file ones, against the
+        // directories given with them, or else the current directory
+        let (files, directories): (Vec<PathBuf>, Vec<PathBuf>) =
+            std::mem::take(&mut self.directories)
+                .into_iter()
+                .partition(|path| path.is_file() && !is_archive(path));
+        self.file.extend(files);
+        self.directories = directories;
+        for mount in self.mount.iter() {
+            if !self.directories.contains(&mount.directory) {
+                self.directories.push(mount.directory.clone());
+            }
+        }
         if self.directories.is_empty() {
             self.directories.push(current_dir.clone());
         }
-        let nondirs: Vec<&PathBuf> = self.directories.iter().filter(|d| !d.is_dir()).collect();
+        let nondirs: Vec<&PathBuf> = self
+            .directories
+            .iter()
+            .filter(|d| !(d.is_dir() || d.is_file() && is_archive(d)))
+            .collect();
         if !nondirs.is_empty() {
             for nondir in nondirs {
                 eprintln!("Directory {:?} does not exist", nondir);
@@ -44,18 +1233,905 @@ impl Args {
     }
 }
 
+/// The scanned directory a file is in, and its path relative to that directory
+fn scanned_path<'a>(
+    directories: &'a [PathBuf],
+    file: &Path,
+) -> std::io::Result<Option<(&'a PathBuf, PathBuf)>> {
+    let canonical = file.canonicalize()?;
+    for directory in directories {
+        if let Ok(path) = canonical.strip_prefix(directory.canonicalize()?) {
+            return Ok(Some((directory, path.to_path_buf())));
+        }
+    }
+    Ok(None)
+}
+
 pub fn file_exists(base_dir: &Path, path: &Path) -> bool {
     base_dir.join(path).is_file()
 }
 
+/**
+Resolves broken links as the arguments say: to the documents source files are rendered to, and
+root links against the site root or the mounts. A whole check and --serve both resolve them so,
+and tell the same links broken.
+*/
+pub struct LinkResolver {
+    /// Where links which lead out of the scanned directories are looked for
+    base_dir: PathBuf,
+    map_source_links: bool,
+    /// How source files are rendered
+    pub source_mapping: PathMapping,
+    site_root: Option<PathBuf>,
+    /// The path of each scanned directory within the site, if root links are resolved
+    pub site_prefixes: Option<Vec<PathBuf>>,
+    probing: Probing,
+}
+
+impl LinkResolver {
+    /// What a broken link leads to after all, with links looked up with `contains`
+    pub fn resolve<F: Fn(&HtmlFileLink) -> bool>(
+        &self,
+        broken: &mut BrokenLink,
+        contains: F,
+    ) -> Resolution {
+        if self.map_source_links
+            && is_source_file(&broken.link.path)
+            && contains(&self.source_mapping.rendered_link(&broken.link))
+        {
+            return Resolution::Found;
+        }
+        let Some(site_prefixes) = &self.site_prefixes else {
+            return Resolution::Unresolved;
+        };
+        match resolve_root_link(&broken.link, site_prefixes) {
+            Some(RootLink::Scanned(link)) => {
+                if contains(&link) {
+                    return Resolution::Found;
+                }
+                broken.missing_fragment = link.fragment.is_some()
+                    && contains(&HtmlFileLink {
+                        path: link.path.clone(),
+                        fragment: None,
+                        base: link.base,
+                    });
+                broken.link = link;
+                Resolution::Unresolved
+            }
+            Some(RootLink::Outside(path)) => {
+                match self.site_root.as_ref().is_some_and(|site_root| {
+                    (self.probing.candidates(&path).iter())
+                        .any(|candidate| file_exists(site_root, candidate))
+                }) {
+                    true => Resolution::Found,
+                    false => Resolution::Broken,
+                }
+            }
+            None => Resolution::Unresolved,
+        }
+    }
+    /// The links to source files which aren't mapped to a document which exists
+    pub fn source_file_links<F: Fn(&HtmlFileLink) -> bool>(
+        &self,
+        source_links: Vec<SourceLink>,
+        contains: F,
+    ) -> Vec<Failure> {
+        let mut failures = vec![];
+        for SourceLink { source, href, link } in source_links {
+            let rendered = self.source_mapping.rendered_link(&link);
+            if self.map_source_links && contains(&rendered) {
+                continue;
+            }
+            let exists = contains(&HtmlFileLink {
+                path: rendered.path,
+                fragment: None,
+                base: rendered.base,
+            });
+            failures.push(Failure::SourceFileLink {
+                rendered: exists.then(|| self.source_mapping.map_href(&href)),
+                source,
+                href,
+            });
+        }
+        failures
+    }
+}
+
+/// Apply the fixes `rlc fix` suggests for a report's failures, or only print them for a dry run
+fn fix_links(report: &Report, roots: &[PathBuf], dry_run: bool) -> std::io::Result<()> {
+    let fixes = suggested_fixes(report, roots);
+    let fixes = match dry_run {
+        true => fixes,
+        false => apply_fixes(fixes)?,
+    };
+    for Fix {
+        file,
+        href,
+        replacement,
+    } in fixes.iter()
+    {
+        println!("{}: {href} → {replacement}", file.display());
+    }
+    let verb = if dry_run { "Would fix" } else { "Fixed" };
+    match fixes.len() {
+        1 => println!("{verb} 1 link"),
+        n => println!("{verb} {n} links"),
+    }
+    Ok(())
+}
+
 pub fn main() -> std::io::Result<()> {
-    let mut args = Args::parse();
+    let Cli { command, args } = Cli::parse();
+    if let Some(Command::Config(ConfigCommand::Init { path })) = &command {
+        if path.exists() {
+            eprintln!("{path:?} already exists");
+            exit(1)
+        }
+        Config::init(path)?;
+        println!("Wrote {path:?}");
+        return Ok(());
+    }
+    if let Some(Command::Report(ReportCommand::Merge { reports })) = &command {
+        args.go_offline();
+        let mut report = Report::merge(
+            reports
+                .iter()
+                .map(Report::load)
+                .collect::<std::io::Result<Vec<Report>>>()?,
+        );
+        report.set_status(args.fail_on.unwrap_or_default());
+        let roots = [std::env::current_dir()?];
+        let sinks = args.report_sinks(&report);
+        report.write(&sinks, &roots, args.color.enabled(), args.group_by)?;
+        if report.failed(args.fail_on.unwrap_or_default()) {
+            exit(1)
+        }
+        return Ok(());
+    }
+    let mut args = command.and_then(Command::scan_args).unwrap_or(args);
+    if let Some(schema) = args.schema {
+        print!("{}", schema.render());
+        return Ok(());
+    }
+    if let Some(format) = args.dump_links {
+        args.mode = Mode::List {
+            internal: false,
+            external: false,
+            format,
+        };
+    }
+    if let Some(jobs) = args.max_concurrency.take() {
+        eprintln!("--max-concurrency was renamed to --net-jobs, and will stop being accepted");
+        args.net_jobs = args.net_jobs.or(Some(jobs));
+    }
+    args.apply_config();
+    args.go_offline();
+    let started = Instant::now();
     let base_dir = args.base_dir()?;
-    let files = HtmlFiles::new(args.resolve_directories()?)?;
-    for link in files.missing_file_links() {
-        if !file_exists(&base_dir, &link.path) {
-            println!("Failed {link:?} in {base_dir:?}");
+    let (archives, directories): (Vec<PathBuf>, Vec<PathBuf>) = args
+        .resolve_directories()?
+        .iter()
+        .cloned()
+        .partition(|path| path.is_file());
+    if !archives.is_empty()
+        && (args.low_memory || !args.file.is_empty() || args.stdin.is_some() || args.changed_only)
+    {
+        eprintln!("Archives can't be checked with --low-memory, --file, --stdin or --changed-only");
+        exit(1)
+    }
+    // Clap only sees --file itself, not the documents given among the directories
+    if !args.file.is_empty()
+        && (args.low_memory
+            || args.source_tree.is_some()
+            || args.stdin.is_some()
+            || args.changed_only)
+    {
+        eprintln!("Documents can't be checked with --low-memory, --source-tree, --stdin or --changed-only");
+        exit(1)
+    }
+    let filter = args.file_filter();
+    let fs_jobs = args.fs_jobs.unwrap_or_else(|| {
+        std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+    });
+    let progress = args.progress();
+    let archives = archives
+        .iter()
+        .map(Archive::open)
+        .collect::<std::io::Result<Vec<Archive>>>()?;
+    let mut read_errors: Vec<ReadError> = vec![];
+    if args.mode == Mode::Diff {
+        let [old, new] = args.directories.as_slice() else {
+            eprintln!("rlc diff compares two builds, given as <OLD> <NEW>");
+            exit(1)
+        };
+        let mut scan = |build: &PathBuf| -> std::io::Result<HtmlFiles> {
+            let errors = (!args.strict_io).then_some(&mut read_errors);
+            let mut files = match build.is_file() {
+                true => HtmlFiles::new(
+                    &[Archive::open(build)?],
+                    &filter,
+                    fs_jobs,
+                    &progress,
+                    errors,
+                )?,
+                false => HtmlFiles::new(
+                    std::slice::from_ref(build),
+                    &filter,
+                    fs_jobs,
+                    &progress,
+                    errors,
+                )?,
+            };
+            files.set_probing(args.probing());
+            files.set_fragment_match(args.fragment_match.unwrap_or_default());
+            Ok(files)
+        };
+        let (old_files, new_files) = (scan(old)?, scan(new)?);
+        progress.finish();
+        let mut failures: Vec<Failure> = read_errors.into_iter().map(Failure::from).collect();
+        failures.extend(
+            diff::vanished_targets(&old_files, &new_files, args.follow_redirects)
+                .into_iter()
+                .map(Failure::from),
+        );
+        let mut report = Report::with_rules(failures, &args.rules());
+        report.set_status(args.fail_on.unwrap_or_default());
+        let sinks = args.report_sinks(&report);
+        report.write(
+            &sinks,
+            std::slice::from_ref(old),
+            args.color.enabled(),
+            args.group_by,
+        )?;
+        if report.failed(args.fail_on.unwrap_or_default()) {
+            exit(1)
+        }
+        return Ok(());
+    }
+    if let Some(address) = &args.serve {
+        let mut index = TargetIndex::new(
+            &directories,
+            &filter,
+            fs_jobs,
+            &progress,
+            (!args.strict_io).then_some(&mut read_errors),
+            args.fragment_match.unwrap_or_default(),
+        )?;
+        index.set_probing(args.probing());
+        progress.finish();
+        for error in read_errors {
+            eprintln!("{}", Failure::from(error));
+        }
+        let resolver = args.link_resolver(&base_dir, &directories)?;
+        let checker = Checker {
+            index,
+            directories,
+            filter,
+            rules: args.rules(),
+            args: &args,
+            resolver,
+        };
+        return serve(address, checker);
+    }
+    if args.mode == Mode::Lsp {
+        // Stdout is the editor's, so nothing but the protocol may be written to it
+        let mut errors = (!args.strict_io).then_some(&mut read_errors);
+        let mut files = HtmlFiles::new(
+            &directories,
+            &filter,
+            fs_jobs,
+            &progress,
+            errors.as_deref_mut(),
+        )?;
+        files.extend(HtmlFiles::new(
+            &archives, &filter, fs_jobs, &progress, errors,
+        )?);
+        files.set_probing(args.probing());
+        files.set_fragment_match(args.fragment_match.unwrap_or_default());
+        progress.finish();
+        for error in read_errors {
+            eprintln!("{}", Failure::from(error));
+        }
+        return lsp::run(Workspace {
+            files,
+            directories,
+            source_tree: args.source_tree.clone(),
+            mapping: PathMapping::new(args.mappings.clone()),
+            filter,
+            rules: args.rules(),
+            follow_redirects: args.follow_redirects,
+            open: HashMap::new(),
+        });
+    }
+    if let Mode::List {
+        internal,
+        external,
+        format,
+    } = args.mode
+    {
+        let mut documents: BTreeMap<PathBuf, Vec<DumpedLink>> = BTreeMap::new();
+        let mut errors = (!args.strict_io).then_some(&mut read_errors);
+        let mut visit = |path: PathBuf, info: HtmlInfo| {
+            documents.insert(path.clone(), document_links(&path, &info));
+        };
+        walk_documents(
+            &directories,
+            &filter,
+            fs_jobs,
+            &progress,
+            errors.as_deref_mut(),
+            &mut visit,
+        )?;
+        walk_documents(&archives, &filter, fs_jobs, &progress, errors, &mut visit)?;
+        progress.finish();
+        for error in read_errors {
+            eprintln!("{}", Failure::from(error));
+        }
+        // Without --internal or --external, every link is listed
+        let listed = |kind: LinkKind| match kind {
+            LinkKind::Internal => internal || !external,
+            LinkKind::External => external || !internal,
+            LinkKind::Other => !internal && !external,
+        };
+        let links: Vec<DumpedLink> = (documents.into_values().flatten())
+            .filter(|link| listed(link.kind))
+            .collect();
+        print!("{}", dump::render(&links, format));
+        return Ok(());
+    }
+    if let Mode::Targets { json } = args.mode {
+        let mut targets: BTreeMap<PathBuf, Ids> = BTreeMap::new();
+        let mut errors = (!args.strict_io).then_some(&mut read_errors);
+        let mut visit = |path: PathBuf, info: HtmlInfo| {
+            targets.insert(path, info.ids);
+        };
+        let mut other_files = walk_documents(
+            &directories,
+            &filter,
+            fs_jobs,
+            &progress,
+            errors.as_deref_mut(),
+            &mut visit,
+        )?;
+        other_files.extend(walk_documents(
+            &archives, &filter, fs_jobs, &progress, errors, &mut visit,
+        )?);
+        targets.extend(other_files);
+        progress.finish();
+        for error in read_errors {
+            eprintln!("{}", Failure::from(error));
+        }
+        let targets: Vec<LinkTarget> = (targets.into_iter())
+            .map(|(path, ids)| LinkTarget::new(path, &ids))
+            .collect();
+        print!("{}", dump::render_targets(&targets, json));
+        return Ok(());
+    }
+    let rules = args.rules();
+    let external_rule = |link: &ExternalLink| match link.nofollow {
+        true => Rule::BrokenNofollowLink,
+        false => Rule::BrokenExternalLink,
+    };
+    let domains = args.check_external.then(|| {
+        DomainFilter::new(&args.external_only_domains, &args.external_exclude_domains)
+            .unwrap_or_else(|err| {
+                eprintln!("Invalid domain glob: {err}");
+                exit(1)
+            })
+    });
+    let recheck = args.recheck.as_ref().map(Recheck::load).transpose()?;
+    // There's no point requesting links whose failures wouldn't be reported
+    let requested = |link: &ExternalLink| {
+        domains
+            .as_ref()
+            .is_some_and(|domains| domains.allows(&link.href))
+            && rules.severity(external_rule(link)) != Severity::Off
+            && (recheck.as_ref())
+                .is_none_or(|recheck| recheck.contains_link(&link.source, &link.href))
+    };
+    let mut checker = ExternalChecker::new(
+        args.net_jobs.unwrap_or(DEFAULT_NET_JOBS),
+        Duration::from_millis(args.host_delay.unwrap_or(DEFAULT_HOST_DELAY)),
+        args.proxy.clone(),
+    );
+    checker.respect_robots = args.respect_robots;
+    checker.headers = args.headers.clone();
+    checker.status_codes = args.status_codes.clone();
+    checker.check_fragments = args.check_external_fragments;
+    for auth in &args.auth {
+        match auth.authorization() {
+            Ok(authorization) => checker
+                .authorizations
+                .push((auth.host.clone(), authorization)),
+            Err(err) => {
+                eprintln!("{err}");
+                exit(1)
+            }
+        }
+    }
+    let now = SystemTime::now();
+    let ttl = args.external_cache_ttl.unwrap_or(DEFAULT_TTL);
+    let cache_path = args.external_cache.as_ref().filter(|_| args.check_external);
+    let mut url_cache = cache_path.map(|path| {
+        UrlCache::load(path).unwrap_or_else(|err| {
+            eprintln!("Ignoring unreadable external cache {path:?}: {err}");
+            UrlCache::default()
+        })
+    });
+    // Slow hosts mustn't hold up the local checks, nor the local checks the requests, so the
+    // external links are requested on --net-jobs threads of their own, from as soon as the
+    // documents linking to them are parsed
+    let requesting = Instant::now();
+    let requests = args.check_external.then(|| checker.spawn(progress.clone()));
+    let request = |link: &ExternalLink| {
+        let cached =
+            (url_cache.as_ref()).is_some_and(|cache| cache.get(&link.href, now, ttl).is_some());
+        if let Some(requests) = requests.as_ref().filter(|_| requested(link) && !cached) {
+            requests.request(link.href.clone());
+        }
+    };
+    let mut hooks = Hooks::default();
+    // Which documents a shard reports on depends on all of them, so its URLs wait for the walk
+    if requests.is_some() && args.shard.is_none() {
+        hooks.on_file_parsed = Some(Box::new(|path: &Path, info: &HtmlInfo| {
+            document_external_links(path, info).iter().for_each(request)
+        }));
+    }
+    let errors = (!args.strict_io).then_some(&mut read_errors);
+    let mut timings = Timings::default();
+    let scanning = Instant::now();
+    let (files, index, scan) =
+        if let Some(documents) = args.selected_documents(&directories, &filter)? {
+            let fragment_match = args.fragment_match.unwrap_or_default();
+            let mut index = match &args.index {
+                Some(index) => TargetIndex::load(index)?,
+                None => TargetIndex::new(
+                    &directories,
+                    &filter,
+                    fs_jobs,
+                    &progress,
+                    errors,
+                    fragment_match,
+                )?,
+            };
+            if index.fragment_match() != fragment_match {
+                let name =
+                    |mode: FragmentMatch| mode.to_possible_value().unwrap().get_name().to_string();
+                eprintln!(
+                    "The index was saved with --fragment-match {}, save it again to check with {}",
+                    name(index.fragment_match()),
+                    name(fragment_match)
+                );
+                exit(1)
+            }
+            index.set_probing(args.probing());
+            // The documents may have changed since they were indexed
+            for (path, info) in documents.iter() {
+                index.insert(path.clone(), info);
+            }
+            timings.parse_ms = elapsed_ms(scanning);
+            let mut scan = Scan::default();
+            for (path, info) in documents {
+                let contains = |link: &HtmlFileLink| index.check(link, args.follow_redirects);
+                scan.add_document(path, info, &hooks, contains);
+            }
+            (None, Some(index), scan)
+        } else if args.low_memory {
+            progress.start_pass("indexing");
+            let mut index = TargetIndex::new(
+                &directories,
+                &filter,
+                fs_jobs,
+                &progress,
+                errors,
+                args.fragment_match.unwrap_or_default(),
+            )?;
+            index.set_probing(args.probing());
+            timings.parse_ms = elapsed_ms(scanning);
+            progress.start_pass("checking");
+            let scan = streaming::check(
+                &index,
+                &directories,
+                &filter,
+                fs_jobs,
+                args.follow_redirects,
+                &progress,
+                !args.strict_io,
+                &hooks,
+            )?;
+            (None, Some(index), scan)
+        } else {
+            let mut errors = errors;
+            let mut files = HtmlFiles::new_with(
+                &directories,
+                &filter,
+                fs_jobs,
+                &progress,
+                errors.as_deref_mut(),
+                &hooks,
+            )?;
+            files.extend(HtmlFiles::new_with(
+                &archives, &filter, fs_jobs, &progress, errors, &hooks,
+            )?);
+            files.set_probing(args.probing());
+            files.set_fragment_match(args.fragment_match.unwrap_or_default());
+            timings.parse_ms = elapsed_ms(scanning);
+            let scan = files.scan(args.follow_redirects, fs_jobs, &progress);
+            (Some(files), None, scan)
+        };
+    drop(hooks);
+    if let Some(path) = &args.save_index {
+        match (&files, &index) {
+            (_, Some(index)) => index.save(path)?,
+            (Some(files), None) => TargetIndex::from(files).save(path)?,
+            (None, None) => {}
         }
     }
+    let contains = |link: &HtmlFileLink| match (&files, &index) {
+        (Some(files), _) if args.follow_redirects => files.contains_following_redirects(link),
+        (Some(files), _) => files.contains(link),
+        (_, Some(index)) if args.follow_redirects => index.contains_following_redirects(link),
+        (_, Some(index)) => index.contains(link),
+        (None, None) => false,
+    };
+    let resolver = args.link_resolver(&base_dir, &directories)?;
+    let mut scan = scan;
+    if let Some(site_prefixes) = &resolver.site_prefixes {
+        scan.alternates.retain_mut(|alternate| {
+            match resolve_root_link(&alternate.link, site_prefixes) {
+                Some(RootLink::Scanned(link)) => alternate.link = link,
+                Some(RootLink::Outside(_)) => return false,
+                None => {}
+            }
+            true
+        });
+    }
+    let external_links = std::mem::take(&mut scan.external_links);
+    let mut linked = args.index_linked.then(|| {
+        let mut linked = LinkedFiles::new(&base_dir, filter.clone());
+        linked.set_probing(args.probing());
+        linked
+    });
+    let environment_urls = EnvironmentUrls {
+        base_url: args.base_url.clone(),
+        deny: args.deny_url_prefixes.clone(),
+        allow: args.allow_url_prefixes.clone(),
+    };
+    let environment_links: Vec<Failure> = match environment_urls.is_empty() {
+        true => vec![],
+        false => external_links
+            .iter()
+            .filter_map(|link| {
+                Some(Failure::EnvironmentLink {
+                    source: link.source.clone(),
+                    href: link.href.clone(),
+                    problem: environment_urls.check_url(&link.href)?,
+                })
+            })
+            .collect(),
+    };
+    let mut external_links: Vec<ExternalLink> = external_links
+        .into_iter()
+        .filter(|link| requested(link))
+        .collect();
+    if let Some(shard) = &args.shard {
+        // Only request the URLs this shard reports on, so shards don't repeat each other's work
+        let documents = shard.select(scan.documents.iter());
+        external_links.retain(|link| documents.contains(&link.source));
+        external_links.iter().for_each(request);
+    }
+    let mut cached_urls = BTreeMap::new();
+    if let Some(url_cache) = &url_cache {
+        for link in &external_links {
+            if let Some(status) = url_cache.get(&link.href, now, ttl) {
+                cached_urls.insert(link.href.clone(), status.clone());
+            }
+        }
+        progress.log(|| {
+            format!(
+                "Reusing the cached results of {} external URLs",
+                cached_urls.len()
+            )
+        });
+    }
+    // Fragments which are off aren't looked for at all, even in documents outside the scan
+    let check_fragments = rules.severity(Rule::MissingFragment) != Severity::Off;
+    let findings = {
+        let mut options = args.check_options(&resolver, contains);
+        // Files linked from the site are looked for in an index of them, with their ids
+        if let Some(linked) = &mut linked {
+            options.find_elsewhere = Some(Box::new(|broken: &BrokenLink| {
+                let link = HtmlFileLink {
+                    path: broken.link.path.clone(),
+                    fragment: broken.link.fragment.clone().filter(|_| check_fragments),
+                    base: broken.link.base,
+                };
+                linked.contains(&link, (!args.strict_io).then_some(&mut read_errors))
+            }));
+        }
+        check_scan(&mut scan, files.as_ref(), contains, &rules, &mut options)?
+    };
+    let Findings {
+        broken_links,
+        loopback_links,
+        escaping_links,
+        mut failures,
+    } = findings;
+    let Scan {
+        documents,
+        source_links,
+        links,
+        ..
+    } = scan;
+    failures.extend(environment_links);
+    failures.extend(resolver.source_file_links(source_links, contains));
+    // Unreadable files and sitemaps aren't part of any shard, so every shard reports them
+    let mut unsharded: Vec<Failure> = vec![];
+    unsharded.extend(read_errors.into_iter().map(Failure::from));
+    if args.check_sitemap {
+        let mut sitemaps = vec![];
+        for directory in directories.iter() {
+            match Sitemap::load_site(directory) {
+                Ok(sitemap) => sitemaps.push((directory.clone(), sitemap)),
+                Err(err) if args.strict_io => return Err(err),
+                Err(err) => unsharded.push(Failure::ReadError {
+                    source: directory.join(SITEMAP_PATH),
+                    message: err.to_string(),
+                }),
+            }
+        }
+        let all_documents: HashSet<&Path> = documents.iter().map(PathBuf::as_path).collect();
+        let mut issues = check_sitemaps(&sitemaps, &all_documents);
+        if let Some(shard) = &args.shard {
+            let documents = shard.select(documents.iter());
+            issues.retain(|issue| match issue {
+                SitemapIssue::Unlisted(document) => documents.contains(document),
+                SitemapIssue::BrokenUrl(_) => true,
+            });
+        }
+        unsharded.extend(issues.into_iter().map(Failure::from));
+    }
+    if args.check_feeds {
+        let mut feed_errors = vec![];
+        let mut feeds = load_feeds(
+            &directories,
+            &filter,
+            (!args.strict_io).then_some(&mut feed_errors),
+        )?;
+        feeds.extend(load_feeds(
+            &archives,
+            &filter,
+            (!args.strict_io).then_some(&mut feed_errors),
+        )?);
+        unsharded.extend(feed_errors.into_iter().map(Failure::from));
+        for (source, feed) in feeds {
+            unsharded.extend(broken_feed_links(&feed, contains).into_iter().map(|url| {
+                Failure::BrokenFeedLink {
+                    source: source.clone(),
+                    url,
+                }
+            }));
+        }
+    }
+    if !args.data_dirs.is_empty() {
+        let keys = match args.data_keys.is_empty() {
+            true => DEFAULT_DATA_KEYS
+                .iter()
+                .map(|key| key.to_string())
+                .collect(),
+            false => args.data_keys.clone(),
+        };
+        let mut data_errors = vec![];
+        let data_files = load_data_files(
+            &args.data_dirs,
+            &keys,
+            &args.rewrites,
+            (!args.strict_io).then_some(&mut data_errors),
+        )?;
+        unsharded.extend(data_errors.into_iter().map(Failure::from));
+        for (source, links) in data_files {
+            unsharded.extend(broken_data_links(&links, contains).into_iter().map(|href| {
+                Failure::BrokenDataLink {
+                    source: source.clone(),
+                    href,
+                }
+            }));
+        }
+    }
+    if let Some(path) = &args.url_manifest {
+        match UrlManifest::load(path) {
+            Ok(manifest) => {
+                unsharded.extend(
+                    unpublished_urls(&manifest, contains, &documents)
+                        .into_iter()
+                        .map(|UnpublishedUrl { url, redirect }| Failure::UnpublishedUrl {
+                            source: path.clone(),
+                            url,
+                            redirect,
+                        }),
+                );
+            }
+            Err(err) if args.strict_io => return Err(err),
+            Err(err) => unsharded.push(Failure::ReadError {
+                source: path.clone(),
+                message: err.to_string(),
+            }),
+        }
+    }
+    if let Some(source_tree) = &args.source_tree {
+        let mut source_errors = vec![];
+        let sources =
+            SourceFiles::new(source_tree, (!args.strict_io).then_some(&mut source_errors))?;
+        unsharded.extend(source_errors.into_iter().map(Failure::from));
+        let mapping = PathMapping::new(args.mappings.clone());
+        let files = files
+            .as_ref()
+            .expect("--source-tree conflicts with --low-memory");
+        let mut issues = check_consistency(&sources, files, &mapping);
+        if let Some(shard) = &args.shard {
+            let documents = shard.select(sources.0.keys());
+            issues.retain(|issue| documents.contains(&issue.source));
+        }
+        unsharded.extend(issues.into_iter().map(Failure::from));
+    }
+    timings.check_ms = elapsed_ms(scanning) - timings.parse_ms;
+    let mut external_urls = match requests {
+        Some(requests) => {
+            let urls = requests.finish();
+            timings.external_ms = elapsed_ms(requesting);
+            urls
+        }
+        None => BTreeMap::new(),
+    };
+    if let (Some(url_cache), Some(path)) = (&mut url_cache, cache_path) {
+        url_cache.update(&external_urls, now, ttl);
+        url_cache.save(path)?;
+    }
+    external_urls.extend(cached_urls);
+    external_links.retain(|link| external_urls[&link.href].is_failure());
+    progress.finish();
+    progress.log(|| format!("Checked {} documents", documents.len()));
+
+    if args.update_baseline {
+        let baseline_path = args.baseline.as_ref().expect("--baseline is required");
+        let baseline: Baseline = broken_links
+            .iter()
+            .map(BaselineEntry::from)
+            .chain(loopback_links.iter().map(BaselineEntry::from))
+            .chain(escaping_links.iter().map(BaselineEntry::from))
+            .chain(external_links.iter().map(BaselineEntry::from))
+            .collect();
+        baseline.save(baseline_path)?;
+        println!(
+            "Recorded {} broken links in {baseline_path:?}",
+            broken_links.len() + loopback_links.len() + escaping_links.len() + external_links.len()
+        );
+        return Ok(());
+    }
+    let baseline = match &args.baseline {
+        Some(baseline_path) => Baseline::load(baseline_path)?,
+        None => Baseline::default(),
+    };
+    failures.extend(
+        broken_links
+            .into_iter()
+            .filter(|b| !baseline.contains(b))
+            .map(Failure::from),
+    );
+    failures.extend(
+        loopback_links
+            .into_iter()
+            .filter(|l| !baseline.contains(l))
+            .map(Failure::from),
+    );
+    failures.extend(
+        escaping_links
+            .into_iter()
+            .filter(|l| !baseline.contains(l))
+            .map(Failure::from),
+    );
+    failures.extend(
+        external_links
+            .into_iter()
+            .filter(|l| !baseline.contains(l))
+            .map(|link| {
+                let ExternalLink {
+                    source,
+                    href,
+                    nofollow,
+                } = link;
+                let status = &external_urls[&href];
+                let error = status.to_string();
+                if let UrlStatus::MissingFragment { .. } = status {
+                    Failure::MissingExternalFragment { source, href }
+                } else if !status.is_broken() {
+                    Failure::QuestionableExternalLink {
+                        source,
+                        href,
+                        error,
+                    }
+                } else if nofollow {
+                    Failure::BrokenNofollowLink {
+                        source,
+                        href,
+                        error,
+                    }
+                } else {
+                    Failure::BrokenExternalLink {
+                        source,
+                        href,
+                        error,
+                    }
+                }
+            }),
+    );
+    if let Some(shard) = &args.shard {
+        let documents = shard.select(documents.iter());
+        failures.retain(|failure| documents.contains(failure.source()));
+    }
+    failures.extend(unsharded);
+    if let Some(recheck) = &recheck {
+        failures.retain(|failure| recheck.contains(failure));
+    }
+    let mut report = Report {
+        external_urls,
+        checked_links: Some(
+            links.internal
+                + if args.check_external {
+                    links.external
+                } else {
+                    0
+                },
+        ),
+        ..Report::with_rules(failures, &rules)
+    };
+    report.set_status(args.fail_on.unwrap_or_default());
+    if args.stats || args.metrics_file.is_some() {
+        timings.total_ms = elapsed_ms(started);
+        let stats = Stats::new(
+            documents.len(),
+            &links,
+            &report,
+            timings,
+            InternerStats::current(),
+        );
+        if let Some(path) = &args.metrics_file {
+            // Written atomically, so the collector never reads half of them
+            write_atomically(path, &render_metrics(&stats))?;
+        }
+        report.stats = args.stats.then_some(stats);
+    }
+    #[cfg(feature = "interactive")]
+    if args.interactive {
+        return interactive::triage(&report, &directories, args.baseline.as_deref());
+    }
+    let sinks = args.sinks();
+    let graph = matches!(args.mode, Mode::Graph { .. });
+    if graph || sinks.iter().any(|sink| sink.format() == Some(Format::Dot)) {
+        report.graph = Some(LinkGraph {
+            documents: documents.into_iter().collect(),
+            links: links.edges.into_iter().collect(),
+        });
+    }
+    match args.mode {
+        Mode::Graph { mermaid: false } => {
+            print!("{}", dot_report::render(&report));
+            return Ok(());
+        }
+        Mode::Graph { mermaid: true } => {
+            print!("{}", dot_report::render_mermaid(&report));
+            return Ok(());
+        }
+        Mode::Fix { dry_run } => return fix_links(&report, &directories, dry_run),
+        Mode::Check | Mode::List { .. } | Mode::Lsp | Mode::Targets { .. } | Mode::Diff => {}
+    }
+    let sinks = args.report_sinks(&report);
+    report.write(&sinks, &directories, args.color.enabled(), args.group_by)?;
+    if let Some(stats) = &report.stats {
+        eprint!("{}", render_stats(stats));
+    }
+    if report.failed(args.fail_on.unwrap_or_default()) {
+        exit(1)
+    }
     Ok(())
 }
@@ -0,0 +1,182 @@
+use std::path::{Path, PathBuf};
+
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+use crate::tree::{HtmlFileLink, LinkBase};
+
+/**
+The URLs a site has ever published, read from a `--url-manifest` file with one URL per line.
+
+Entries are absolute URLs like `https://example.com/guide/`, whose host is ignored, or
+root-relative ones like `/guide/`. Blank lines and lines starting with `#` are skipped.
+*/
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct UrlManifest {
+    pub urls: Vec<String>,
+}
+
+impl UrlManifest {
+    pub fn parse(contents: &str) -> UrlManifest {
+        let urls = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(String::from)
+            .collect();
+        UrlManifest { urls }
+    }
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<UrlManifest> {
+        Ok(UrlManifest::parse(&std::fs::read_to_string(path)?))
+    }
+}
+
+/// Where a URL of the manifest leads on the site, or nothing if it isn't a URL at all
+fn manifest_link(url: &str) -> Option<HtmlFileLink> {
+    let url = match Url::parse(url) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => url,
+        Ok(_) => return None,
+        Err(_) if url.starts_with('/') && !url.starts_with("//") => {
+            Url::parse("internal:///").ok()?.join(url).ok()?
+        }
+        Err(_) => return None,
+    };
+    let path = percent_decode_str(url.path()).decode_utf8().ok()?;
+    Some(HtmlFileLink {
+        path: PathBuf::from(path.trim_start_matches('/')),
+        fragment: url.fragment().map(String::from),
+        base: LinkBase::Root,
+    })
+}
+
+/**
+A URL of the manifest which no longer leads to a file, with a redirect rule to add for it if a
+document of the site looks like where it went.
+*/
+#[derive(Debug, PartialEq, Eq)]
+pub struct UnpublishedUrl {
+    pub url: String,
+    pub redirect: Option<String>,
+}
+
+/// The name a page goes by in its URL: its file name without the extension, or its directory's
+/// name for an index page
+fn page_name(path: &Path) -> Option<&str> {
+    let path = match path.file_stem()? == "index" {
+        true => path.parent()?,
+        false => path,
+    };
+    path.file_stem()?.to_str()
+}
+
+/// The URL path of a document, with index pages given as their directory
+fn document_url(document: &Path) -> String {
+    let document = document.to_string_lossy().replace('\\', "/");
+    match document.strip_suffix("index.html") {
+        Some(directory) => format!("/{directory}"),
+        None => format!("/{document}"),
+    }
+}
+
+/**
+A redirect from a URL which no longer leads to a file to the document most likely to have
+replaced it, one with the same page name whose path is most alike, as a line for a
+`_redirects` file like Netlify's and Cloudflare Pages' read.
+*/
+fn suggest_redirect(link: &HtmlFileLink, documents: &[PathBuf]) -> Option<String> {
+    let name = page_name(&link.path)?;
+    let old = link.path.to_string_lossy();
+    let (_, document) = documents
+        .iter()
+        .filter(|document| page_name(document) == Some(name))
+        .map(|document| {
+            let similarity = strsim::jaro_winkler(&old, &document.to_string_lossy());
+            (similarity, document)
+        })
+        .max_by(|(a, a_document), (b, b_document)| {
+            a.total_cmp(b).then(b_document.cmp(a_document))
+        })?;
+    Some(format!("/{old} {} 301", document_url(document)))
+}
+
+/**
+The URLs of the manifest which `contains` can't find on the site, with a redirect to the most
+likely of the `documents` for each, if any is likely.
+*/
+pub fn unpublished_urls<F: Fn(&HtmlFileLink) -> bool>(
+    manifest: &UrlManifest,
+    contains: F,
+    documents: &[PathBuf],
+) -> Vec<UnpublishedUrl> {
+    manifest
+        .urls
+        .iter()
+        .filter_map(|url| match manifest_link(url) {
+            Some(link) if contains(&link) => None,
+            Some(link) => Some(UnpublishedUrl {
+                url: url.clone(),
+                redirect: suggest_redirect(&link, documents),
+            }),
+            None => Some(UnpublishedUrl {
+                url: url.clone(),
+                redirect: None,
+            }),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_unpublished_urls() {
+        let manifest = UrlManifest::parse(
+            "# Every URL we've published\n\
+            https://example.com/\n\
+            \n\
+            /guide/setup.html\n\
+            https://example.com/blog/hello.html\n\
+            /about/\n\
+            /gone.html\n\
+            mailto:someone@example.com\n",
+        );
+        assert_eq!(manifest.urls.len(), 6);
+        let documents: Vec<PathBuf> = vec![
+            "index.html".into(),
+            "docs/setup/index.html".into(),
+            "docs/install/setup.html".into(),
+            "posts/2024/hello.html".into(),
+            "about.html".into(),
+        ];
+        let contains = |link: &HtmlFileLink| {
+            documents.contains(&link.path) || documents.contains(&link.path.join("index.html"))
+        };
+        let unpublished = unpublished_urls(&manifest, contains, &documents);
+        assert_eq!(
+            unpublished,
+            vec![
+                UnpublishedUrl {
+                    url: "/guide/setup.html".into(),
+                    redirect: Some("/guide/setup.html /docs/setup/ 301".into()),
+                },
+                UnpublishedUrl {
+                    url: "https://example.com/blog/hello.html".into(),
+                    redirect: Some("/blog/hello.html /posts/2024/hello.html 301".into()),
+                },
+                UnpublishedUrl {
+                    url: "/about/".into(),
+                    redirect: Some("/about/ /about.html 301".into()),
+                },
+                UnpublishedUrl {
+                    url: "/gone.html".into(),
+                    redirect: None,
+                },
+                UnpublishedUrl {
+                    url: "mailto:someone@example.com".into(),
+                    redirect: None,
+                },
+            ]
+        );
+    }
+}
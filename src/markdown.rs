@@ -0,0 +1,47 @@
+use pulldown_cmark::{Event, Parser, Tag};
+use url::Url;
+
+/**
+The relevant contents of a Markdown document.
+
+Currently we only care about the destinations of any relative links.
+ */
+#[derive(Debug)]
+pub struct MarkdownInfo {
+    pub relative_hrefs: Vec<String>,
+}
+
+impl MarkdownInfo {
+    pub fn parse(document: &str) -> MarkdownInfo {
+        let relative_hrefs = Parser::new(document)
+            .filter_map(|event| match event {
+                Event::Start(Tag::Link { dest_url, .. }) => Some(dest_url.to_string()),
+                _ => None,
+            })
+            .filter(|href| Url::parse(href) == Err(url::ParseError::RelativeUrlWithoutBase))
+            .collect();
+        MarkdownInfo { relative_hrefs }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_parse() {
+        let markdown_info = MarkdownInfo::parse(
+            "
+# Title
+
+See [the guide](guide/intro.md#setup) or [Google](https://www.google.com).
+
+[ref]: ../other.md
+Also [a reference][ref].
+",
+        );
+        assert_eq!(
+            markdown_info.relative_hrefs,
+            vec!["guide/intro.md#setup", "../other.md"]
+        );
+    }
+}
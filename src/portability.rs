@@ -0,0 +1,87 @@
+use percent_encoding::percent_decode_str;
+
+/// Characters which Windows doesn't allow in file names, besides control characters
+const RESERVED_CHARACTERS: &[char] = &['<', '>', ':', '"', '|', '?', '*'];
+
+/// Device names which Windows doesn't allow as file names, whatever their extension
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// What keeps a single file or directory name from working on every common filesystem
+fn check_name(name: &str) -> Option<String> {
+    if let Some(c) = name
+        .chars()
+        .find(|c| RESERVED_CHARACTERS.contains(c) || c.is_control())
+    {
+        return Some(format!("{c:?} isn't allowed in file names on Windows"));
+    }
+    if name.ends_with(' ') || name.ends_with('.') {
+        return Some(format!("Windows drops the end of {name:?}"));
+    }
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    {
+        return Some(format!("{name:?} is a reserved name on Windows"));
+    }
+    None
+}
+
+/**
+What keeps the path of a relative `href` from leading to the same file on every common
+filesystem, if anything: backslashes used as separators, or names which Windows doesn't allow,
+once their `%XX` escapes are decoded.
+*/
+pub fn check_href(href: &str) -> Option<String> {
+    let path = href.split('#').next().unwrap_or(href);
+    if path.contains('\\') {
+        return Some("backslashes are only path separators on Windows".to_string());
+    }
+    path.split('/')
+        .filter(|segment| !matches!(*segment, "" | "." | ".."))
+        .find_map(|segment| check_name(&percent_decode_str(segment).decode_utf8_lossy()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check_href() {
+        for href in [
+            "guide/index.html#setup",
+            "../images/logo.png",
+            "./",
+            "console.html",
+            "com10.txt",
+            "a%20b.html",
+            "#top",
+        ] {
+            assert_eq!(check_href(href), None, "{href}");
+        }
+        for (href, problem) in [
+            (
+                "guide\\index.html",
+                "backslashes are only path separators on Windows",
+            ),
+            (
+                "notes/10:30.html",
+                "':' isn't allowed in file names on Windows",
+            ),
+            ("what%3F.html", "'?' isn't allowed in file names on Windows"),
+            ("draft%20/index.html", "Windows drops the end of \"draft \""),
+            ("page.html ", "Windows drops the end of \"page.html \""),
+            ("v1./", "Windows drops the end of \"v1.\""),
+            (
+                "docs/aux.html",
+                "\"aux.html\" is a reserved name on Windows",
+            ),
+            ("Nul", "\"Nul\" is a reserved name on Windows"),
+        ] {
+            assert_eq!(check_href(href), Some(problem.to_string()), "{href}");
+        }
+    }
+}
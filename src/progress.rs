@@ -0,0 +1,122 @@
+use std::io::IsTerminal;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+/**
+How much the check says about what it's doing, besides the report itself.
+*/
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Nothing but the report
+    Quiet,
+    /// A progress bar, when the report is going to a terminal
+    #[default]
+    Normal,
+    /// Also log every document as it's parsed
+    Verbose,
+}
+
+/**
+Tracks how many documents have been discovered and parsed, how many links checked, and how many
+external URLs have been queued and requested.
+
+The counts are drawn as a progress bar on stderr. Clones share the same counts, so the walker
+thread can count discoveries while the documents are visited elsewhere, and the external links
+can be requested while the documents are still being parsed.
+ */
+#[derive(Debug, Clone)]
+pub struct Progress {
+    bar: ProgressBar,
+    verbosity: Verbosity,
+    links: Arc<AtomicUsize>,
+    queued: Arc<AtomicUsize>,
+    urls: Arc<AtomicUsize>,
+}
+
+impl Default for Progress {
+    /// Progress which is never shown, for when nobody is watching
+    fn default() -> Progress {
+        Progress::new(Verbosity::Quiet, false)
+    }
+}
+
+impl Progress {
+    /// The bar is only drawn if `show_bar` and stdout and stderr are both terminals
+    pub fn new(verbosity: Verbosity, show_bar: bool) -> Progress {
+        let show_bar = show_bar
+            && verbosity != Verbosity::Quiet
+            && std::io::stdout().is_terminal()
+            && std::io::stderr().is_terminal();
+        let bar = if show_bar {
+            let bar = ProgressBar::with_draw_target(Some(0), ProgressDrawTarget::stderr());
+            bar.set_style(
+                ProgressStyle::with_template(
+                    "{spinner} {prefix}{pos}/{len} documents parsed, {msg}",
+                )
+                .unwrap(),
+            );
+            bar.set_message("0 links checked");
+            bar.enable_steady_tick(Duration::from_millis(100));
+            bar
+        } else {
+            ProgressBar::hidden()
+        };
+        Progress {
+            bar,
+            verbosity,
+            links: Arc::default(),
+            queued: Arc::default(),
+            urls: Arc::default(),
+        }
+    }
+    /// Start counting again for another pass over the same documents
+    pub fn start_pass(&self, name: &str) {
+        self.bar.set_prefix(format!("{name}: "));
+        self.bar.set_length(0);
+        self.bar.set_position(0);
+    }
+    pub fn discovered(&self) {
+        self.bar.inc_length(1);
+    }
+    pub fn parsed(&self, path: &Path) {
+        self.bar.inc(1);
+        self.log(|| format!("Parsed {path:?}"));
+    }
+    pub fn checked(&self, links: usize) {
+        self.links.fetch_add(links, Ordering::Relaxed);
+        self.show_counts();
+    }
+    pub fn queued(&self) {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        self.show_counts();
+    }
+    pub fn requested(&self) {
+        self.urls.fetch_add(1, Ordering::Relaxed);
+        self.show_counts();
+    }
+    fn show_counts(&self) {
+        let links = self.links.load(Ordering::Relaxed);
+        self.bar
+            .set_message(match self.queued.load(Ordering::Relaxed) {
+                0 => format!("{links} links checked"),
+                queued => {
+                    let urls = self.urls.load(Ordering::Relaxed);
+                    format!("{links} links checked, {urls}/{queued} external URLs requested")
+                }
+            });
+    }
+    /// Print a line to stderr if verbose, without tearing through the bar
+    pub fn log<F: FnOnce() -> String>(&self, message: F) {
+        if self.verbosity == Verbosity::Verbose {
+            let message = message();
+            self.bar.suspend(|| eprintln!("{message}"));
+        }
+    }
+    pub fn finish(&self) {
+        self.bar.finish_and_clear();
+    }
+}
@@ -0,0 +1,138 @@
+use std::io::{ErrorKind, Read};
+use std::path::{Path, PathBuf};
+
+use ignore::{DirEntry, WalkBuilder};
+
+use crate::filter::FileFilter;
+
+/// A file found by a provider, relative to its root, or the path which couldn't be read
+pub type Listed = Result<PathBuf, (PathBuf, std::io::Error)>;
+
+/**
+Somewhere the files of a scan come from, like a directory on disk or an archive in memory.
+
+Providers only list and read files. Which of them are documents, how they're keyed and how
+they're parsed is up to the scan, so every provider is checked the same way.
+ */
+pub trait FileProvider: Sync {
+    /// The path given for the provider, which mounts are matched against
+    fn root(&self) -> &Path;
+    /**
+    Pass every file to `visit`, relative to the root, leaving out directories which the filter
+    doesn't walk. Stops early once `visit` returns false.
+    */
+    fn list(&self, filter: &FileFilter, visit: &mut dyn FnMut(Listed) -> bool);
+    /**
+    Read a file, given relative to the root. Files larger than `max_size` bytes fail with
+    `ErrorKind::FileTooLarge` rather than being read into memory.
+    */
+    fn read(&self, path: &Path, max_size: Option<u64>) -> std::io::Result<Vec<u8>>;
+    /// Read a file which must be UTF-8, like `read` does
+    fn read_to_string(&self, path: &Path, max_size: Option<u64>) -> std::io::Result<String> {
+        String::from_utf8(self.read(path, max_size)?)
+            .map_err(|err| std::io::Error::new(ErrorKind::InvalidData, err))
+    }
+}
+
+/// The error for a file larger than the `max_size` it may be read at
+pub fn too_large(max_size: u64) -> std::io::Error {
+    std::io::Error::new(
+        ErrorKind::FileTooLarge,
+        format!("larger than the maximum file size of {max_size} bytes"),
+    )
+}
+
+fn is_dir(entry: &DirEntry) -> bool {
+    entry
+        .file_type()
+        .is_some_and(|file_type| file_type.is_dir())
+}
+
+/// Whether walking a directory failed because of a symlink back up the tree
+fn is_loop(err: &ignore::Error) -> bool {
+    match err {
+        ignore::Error::Loop { .. } => true,
+        ignore::Error::WithPath { err, .. } | ignore::Error::WithDepth { err, .. } => is_loop(err),
+        _ => false,
+    }
+}
+
+/// The path which couldn't be read while walking a directory, if it's known
+fn error_path(err: &ignore::Error) -> Option<&Path> {
+    match err {
+        ignore::Error::WithPath { path, .. } => Some(path),
+        ignore::Error::WithDepth { err, .. } => error_path(err),
+        _ => None,
+    }
+}
+
+/**
+A directory on disk.
+
+Files matched by `.gitignore` and `.ignore` files in the directory are skipped, unless the
+filter's `ignore_files` is off. Ignore files above it aren't read, since the generated site is
+often ignored itself.
+ */
+impl FileProvider for PathBuf {
+    fn root(&self) -> &Path {
+        self
+    }
+    fn list(&self, filter: &FileFilter, visit: &mut dyn FnMut(Listed) -> bool) {
+        let relative =
+            |path: &Path| -> PathBuf { path.strip_prefix(self).unwrap_or(path).to_path_buf() };
+        // The entry filter has to own what it uses
+        let (walk_filter, root) = (filter.clone(), self.clone());
+        let walker = WalkBuilder::new(self)
+            .follow_links(filter.follow_symlinks)
+            // The scanned directory itself is at depth 0, and the files directly in it at 1
+            .max_depth(filter.max_depth.map(|max_depth| max_depth + 1))
+            .hidden(false)
+            .parents(false)
+            .require_git(false)
+            .ignore(filter.ignore_files)
+            .git_ignore(filter.ignore_files)
+            .git_global(filter.ignore_files)
+            .git_exclude(filter.ignore_files)
+            .filter_entry(move |entry| {
+                !is_dir(entry)
+                    || walk_filter.walks(entry.path().strip_prefix(&root).unwrap_or(entry.path()))
+            })
+            .build();
+        for result in walker {
+            let file = match result {
+                Ok(entry) if is_dir(&entry) => continue,
+                Ok(entry) => Ok(relative(entry.path())),
+                // A symlink back up the tree, whose documents are walked anyway
+                Err(err) if is_loop(&err) => continue,
+                Err(err) => {
+                    let path = match relative(error_path(&err).unwrap_or(self)) {
+                        // The directory itself can't be read
+                        path if path.as_os_str().is_empty() => self.clone(),
+                        path => path,
+                    };
+                    let io_err = err.clone().into_io_error();
+                    Err((path, io_err.unwrap_or_else(|| std::io::Error::other(err))))
+                }
+            };
+            if !visit(file) {
+                return;
+            }
+        }
+    }
+    fn read(&self, path: &Path, max_size: Option<u64>) -> std::io::Result<Vec<u8>> {
+        let Some(max_size) = max_size else {
+            return std::fs::read(self.join(path));
+        };
+        let file = std::fs::File::open(self.join(path))?;
+        if file.metadata()?.len() > max_size {
+            return Err(too_large(max_size));
+        }
+        // The file may grow while it's read, so never read past the limit
+        let mut contents = vec![];
+        file.take(max_size + 1).read_to_end(&mut contents)?;
+        if contents.len() as u64 > max_size {
+            return Err(too_large(max_size));
+        }
+        Ok(contents)
+    }
+}
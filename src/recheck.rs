@@ -0,0 +1,72 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::report::{Failure, Report};
+
+/**
+The failures of an earlier report, given as `--recheck report.json`, so a run only reports
+those which are still failing. For checking again after fixing a batch of pages, or after
+external sites which were down come back.
+
+Every document is still scanned, since internal links are checked against all of them, but
+only the external links which failed are requested again. Failures which aren't about a link,
+like duplicate ids, are kept for the documents which had any before.
+ */
+#[derive(Debug, Default)]
+pub struct Recheck(HashSet<(PathBuf, Option<String>)>);
+
+impl Recheck {
+    pub fn new(report: &Report) -> Recheck {
+        Recheck(
+            report
+                .failures
+                .iter()
+                .chain(report.warnings.iter())
+                .map(|failure| {
+                    let href = failure.href().map(String::from);
+                    (failure.source().to_path_buf(), href)
+                })
+                .collect(),
+        )
+    }
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Recheck> {
+        Ok(Recheck::new(&Report::load(path)?))
+    }
+    /// Whether the link `href` in `source` failed before
+    pub fn contains_link(&self, source: &Path, href: &str) -> bool {
+        self.0
+            .contains(&(source.to_path_buf(), Some(href.to_string())))
+    }
+    /// Whether a failure was reported before, about the same link or document
+    pub fn contains(&self, failure: &Failure) -> bool {
+        let href = failure.href().map(String::from);
+        self.0.contains(&(failure.source().to_path_buf(), href))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_recheck() {
+        let broken = |source: &str, href: &str| Failure::LocalServerLink {
+            source: source.into(),
+            href: href.into(),
+        };
+        let duplicate = |source: &str, id: &str| Failure::DuplicateId {
+            source: source.into(),
+            id: id.into(),
+            count: 2,
+        };
+        let mut report = Report::new(vec![broken("a.html", "http://localhost/")]);
+        report.warnings.push(duplicate("b.html", "top"));
+        let recheck = Recheck::new(&report);
+        assert!(recheck.contains_link(Path::new("a.html"), "http://localhost/"));
+        assert!(!recheck.contains_link(Path::new("b.html"), "http://localhost/"));
+        assert!(recheck.contains(&broken("a.html", "http://localhost/")));
+        assert!(!recheck.contains(&broken("a.html", "http://localhost:8000/")));
+        assert!(recheck.contains(&duplicate("b.html", "main")));
+        assert!(!recheck.contains(&duplicate("a.html", "top")));
+    }
+}
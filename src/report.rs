@@ -0,0 +1,1260 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::consistency::{ConsistencyIssue, Inconsistency};
+use crate::diff::VanishedTarget;
+use crate::dot_report::LinkGraph;
+use crate::external::UrlStatus;
+use crate::html::{LinkAttribute, Placeholder};
+use crate::rules::{FailOn, Rule, Rules, Severity};
+use crate::sink::Sink;
+use crate::sitemap::{SitemapIssue, SITEMAP_PATH};
+use crate::stats::Stats;
+use crate::text_report::GroupBy;
+use crate::tree::{
+    Alternate, BrokenIdReference, BrokenLink, ContactLink, DuplicateDocument, DuplicateId,
+    EscapingLink, HtmlFileLink, LinkBase, LoopbackLink, MissingVariant, NonPortableLink,
+    PlaceholderLink, ReadError, RedirectChain, TocOmission,
+};
+
+/**
+Anything wrong with the checked site, which fails the check unless its rule is only a warning.
+*/
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Failure {
+    BrokenLink {
+        source: PathBuf,
+        href: String,
+        link: HtmlFileLink,
+        /// The attribute the link was found in, if it isn't an `href`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        attribute: Option<LinkAttribute>,
+        /// The text of the link, to find it by in the rendered page
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        text: Option<String>,
+    },
+    MissingFragment {
+        source: PathBuf,
+        href: String,
+        link: HtmlFileLink,
+        /// The ids in the linked document most like the missing fragment, best first
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        similar_ids: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        attribute: Option<LinkAttribute>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        text: Option<String>,
+    },
+    LocalServerLink {
+        source: PathBuf,
+        href: String,
+    },
+    EscapingLink {
+        source: PathBuf,
+        href: String,
+    },
+    NoOutput {
+        source: PathBuf,
+        output: PathBuf,
+    },
+    DroppedLink {
+        source: PathBuf,
+        output: PathBuf,
+        href: String,
+    },
+    ExtraLink {
+        source: PathBuf,
+        output: PathBuf,
+        href: String,
+    },
+    ReadError {
+        source: PathBuf,
+        message: String,
+    },
+    FileTooLarge {
+        source: PathBuf,
+        message: String,
+    },
+    DuplicateId {
+        source: PathBuf,
+        id: String,
+        count: usize,
+    },
+    BrokenIdReference {
+        source: PathBuf,
+        attribute: String,
+        id: String,
+    },
+    MissingFromToc {
+        source: PathBuf,
+        id: String,
+    },
+    DuplicateContent {
+        source: PathBuf,
+        original: PathBuf,
+    },
+    RedirectChain {
+        source: PathBuf,
+        hops: Vec<PathBuf>,
+        circular: bool,
+    },
+    IncompleteSrcset {
+        source: PathBuf,
+        srcset: String,
+        variant: String,
+        href: Option<String>,
+    },
+    BrokenSitemapUrl {
+        source: PathBuf,
+        url: String,
+    },
+    MissingFromSitemap {
+        source: PathBuf,
+    },
+    BrokenFeedLink {
+        source: PathBuf,
+        url: String,
+    },
+    BrokenDataLink {
+        source: PathBuf,
+        href: String,
+    },
+    MalformedContactLink {
+        source: PathBuf,
+        href: String,
+        problem: String,
+    },
+    NonPortableLink {
+        source: PathBuf,
+        href: String,
+        problem: String,
+    },
+    MissingReturnLink {
+        source: PathBuf,
+        href: String,
+        hreflang: String,
+    },
+    SourceFileLink {
+        source: PathBuf,
+        href: String,
+        /// The link to the page rendered from the source, if that exists
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        rendered: Option<String>,
+    },
+    BrokenExternalLink {
+        source: PathBuf,
+        href: String,
+        error: String,
+    },
+    BrokenNofollowLink {
+        source: PathBuf,
+        href: String,
+        error: String,
+    },
+    QuestionableExternalLink {
+        source: PathBuf,
+        href: String,
+        error: String,
+    },
+    MissingExternalFragment {
+        source: PathBuf,
+        href: String,
+    },
+    EnvironmentLink {
+        source: PathBuf,
+        href: String,
+        problem: String,
+    },
+    EmptyLink {
+        source: PathBuf,
+        href: String,
+    },
+    HashLink {
+        source: PathBuf,
+        href: String,
+    },
+    JavascriptLink {
+        source: PathBuf,
+        href: String,
+    },
+    UnpublishedUrl {
+        source: PathBuf,
+        url: String,
+        /// A line for a `_redirects` file sending the URL where it most likely went
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        redirect: Option<String>,
+    },
+    VanishedTarget {
+        source: PathBuf,
+        /// The id linked to in the file, if only that has gone
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        fragment: Option<String>,
+        /// The documents of the old build which linked there
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        linked_from: Vec<PathBuf>,
+    },
+}
+
+impl Failure {
+    /// A short description of what kind of failure this is
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Failure::BrokenLink { .. } => "Broken link",
+            Failure::MissingFragment { .. } => "Missing fragment",
+            Failure::LocalServerLink { .. } => "Local server link",
+            Failure::EscapingLink { .. } => "Escaping link",
+            Failure::NoOutput { .. } => "No output",
+            Failure::DroppedLink { .. } => "Dropped link",
+            Failure::ExtraLink { .. } => "Extra link",
+            Failure::ReadError { .. } => "Read error",
+            Failure::FileTooLarge { .. } => "File too large",
+            Failure::DuplicateId { .. } => "Duplicate id",
+            Failure::BrokenIdReference { .. } => "Broken id reference",
+            Failure::MissingFromToc { .. } => "Missing from TOC",
+            Failure::DuplicateContent { .. } => "Duplicate content",
+            Failure::RedirectChain { .. } => "Redirect chain",
+            Failure::IncompleteSrcset { .. } => "Incomplete srcset",
+            Failure::BrokenSitemapUrl { .. } => "Broken sitemap URL",
+            Failure::MissingFromSitemap { .. } => "Missing from sitemap",
+            Failure::BrokenFeedLink { .. } => "Broken feed link",
+            Failure::BrokenDataLink { .. } => "Broken data file link",
+            Failure::MalformedContactLink { .. } => "Malformed contact link",
+            Failure::NonPortableLink { .. } => "Non-portable link",
+            Failure::MissingReturnLink { .. } => "Missing return link",
+            Failure::SourceFileLink { .. } => "Source file link",
+            Failure::BrokenExternalLink { .. } => "Broken external link",
+            Failure::BrokenNofollowLink { .. } => "Broken nofollow link",
+            Failure::QuestionableExternalLink { .. } => "Questionable external link",
+            Failure::MissingExternalFragment { .. } => "Missing external fragment",
+            Failure::EnvironmentLink { .. } => "Environment link",
+            Failure::EmptyLink { .. } => "Empty link",
+            Failure::HashLink { .. } => "Hash link",
+            Failure::JavascriptLink { .. } => "JavaScript link",
+            Failure::UnpublishedUrl { .. } => "Unpublished URL",
+            Failure::VanishedTarget { .. } => "Vanished target",
+        }
+    }
+    /// The document the failure was found in
+    pub fn source(&self) -> &Path {
+        match self {
+            Failure::BrokenLink { source, .. }
+            | Failure::MissingFragment { source, .. }
+            | Failure::LocalServerLink { source, .. }
+            | Failure::EscapingLink { source, .. }
+            | Failure::NoOutput { source, .. }
+            | Failure::DroppedLink { source, .. }
+            | Failure::ExtraLink { source, .. }
+            | Failure::ReadError { source, .. }
+            | Failure::FileTooLarge { source, .. }
+            | Failure::DuplicateId { source, .. }
+            | Failure::BrokenIdReference { source, .. }
+            | Failure::MissingFromToc { source, .. }
+            | Failure::DuplicateContent { source, .. }
+            | Failure::RedirectChain { source, .. }
+            | Failure::IncompleteSrcset { source, .. }
+            | Failure::BrokenSitemapUrl { source, .. }
+            | Failure::MissingFromSitemap { source }
+            | Failure::BrokenFeedLink { source, .. }
+            | Failure::BrokenDataLink { source, .. }
+            | Failure::MalformedContactLink { source, .. }
+            | Failure::NonPortableLink { source, .. }
+            | Failure::MissingReturnLink { source, .. }
+            | Failure::SourceFileLink { source, .. }
+            | Failure::BrokenExternalLink { source, .. }
+            | Failure::BrokenNofollowLink { source, .. }
+            | Failure::QuestionableExternalLink { source, .. }
+            | Failure::MissingExternalFragment { source, .. }
+            | Failure::EnvironmentLink { source, .. }
+            | Failure::EmptyLink { source, .. }
+            | Failure::HashLink { source, .. }
+            | Failure::JavascriptLink { source, .. }
+            | Failure::UnpublishedUrl { source, .. }
+            | Failure::VanishedTarget { source, .. } => source,
+        }
+    }
+    /// The link the failure is about, if it's about one
+    pub fn href(&self) -> Option<&str> {
+        match self {
+            Failure::BrokenLink { href, .. }
+            | Failure::MissingFragment { href, .. }
+            | Failure::LocalServerLink { href, .. }
+            | Failure::EscapingLink { href, .. }
+            | Failure::DroppedLink { href, .. }
+            | Failure::ExtraLink { href, .. }
+            | Failure::IncompleteSrcset {
+                href: Some(href), ..
+            }
+            | Failure::BrokenExternalLink { href, .. }
+            | Failure::BrokenNofollowLink { href, .. }
+            | Failure::QuestionableExternalLink { href, .. }
+            | Failure::MissingExternalFragment { href, .. }
+            | Failure::BrokenSitemapUrl { url: href, .. }
+            | Failure::BrokenFeedLink { url: href, .. }
+            | Failure::BrokenDataLink { href, .. }
+            | Failure::UnpublishedUrl { url: href, .. }
+            | Failure::MalformedContactLink { href, .. }
+            | Failure::NonPortableLink { href, .. }
+            | Failure::MissingReturnLink { href, .. }
+            | Failure::SourceFileLink { href, .. }
+            | Failure::EnvironmentLink { href, .. }
+            | Failure::EmptyLink { href, .. }
+            | Failure::HashLink { href, .. }
+            | Failure::JavascriptLink { href, .. } => Some(href),
+            Failure::NoOutput { .. }
+            | Failure::ReadError { .. }
+            | Failure::FileTooLarge { .. }
+            | Failure::DuplicateId { .. }
+            | Failure::BrokenIdReference { .. }
+            | Failure::MissingFromToc { .. }
+            | Failure::DuplicateContent { .. }
+            | Failure::RedirectChain { .. }
+            | Failure::IncompleteSrcset { href: None, .. }
+            | Failure::MissingFromSitemap { .. }
+            | Failure::VanishedTarget { .. } => None,
+        }
+    }
+    /**
+    Where the link the failure is about leads, which is the same for every document linking
+    there however they write the link, if it's about one.
+    */
+    pub fn target(&self) -> Option<String> {
+        match self {
+            Failure::BrokenLink { link, .. } | Failure::MissingFragment { link, .. } => {
+                let mut target = link.path.to_string_lossy().into_owned();
+                if link.base == LinkBase::Root {
+                    target.insert(0, '/');
+                }
+                if let Some(fragment) = &link.fragment {
+                    target = format!("{target}#{fragment}");
+                }
+                Some(target)
+            }
+            Failure::LocalServerLink { href, .. }
+            | Failure::BrokenExternalLink { href, .. }
+            | Failure::BrokenNofollowLink { href, .. }
+            | Failure::QuestionableExternalLink { href, .. }
+            | Failure::MissingExternalFragment { href, .. }
+            | Failure::BrokenSitemapUrl { url: href, .. }
+            | Failure::BrokenFeedLink { url: href, .. }
+            | Failure::BrokenDataLink { href, .. }
+            | Failure::UnpublishedUrl { url: href, .. }
+            | Failure::EnvironmentLink { href, .. } => Some(href.clone()),
+            _ => None,
+        }
+    }
+    /// The rule which reports this kind of failure
+    pub fn rule(&self) -> Rule {
+        match self {
+            Failure::BrokenLink { .. } => Rule::BrokenLink,
+            Failure::MissingFragment { .. } => Rule::MissingFragment,
+            Failure::LocalServerLink { .. } => Rule::LocalServerLink,
+            Failure::EscapingLink { .. } => Rule::EscapingLink,
+            Failure::NoOutput { .. } => Rule::NoOutput,
+            Failure::DroppedLink { .. } => Rule::DroppedLink,
+            Failure::ExtraLink { .. } => Rule::ExtraLink,
+            Failure::ReadError { .. } => Rule::ReadError,
+            Failure::FileTooLarge { .. } => Rule::FileTooLarge,
+            Failure::DuplicateId { .. } => Rule::DuplicateId,
+            Failure::BrokenIdReference { .. } => Rule::BrokenIdReference,
+            Failure::MissingFromToc { .. } => Rule::MissingFromToc,
+            Failure::DuplicateContent { .. } => Rule::DuplicateContent,
+            Failure::RedirectChain { .. } => Rule::RedirectChain,
+            Failure::IncompleteSrcset { .. } => Rule::IncompleteSrcset,
+            Failure::BrokenSitemapUrl { .. } => Rule::BrokenSitemapUrl,
+            Failure::MissingFromSitemap { .. } => Rule::MissingFromSitemap,
+            Failure::BrokenFeedLink { .. } => Rule::BrokenFeedLink,
+            Failure::BrokenDataLink { .. } => Rule::BrokenDataLink,
+            Failure::MalformedContactLink { .. } => Rule::MalformedContactLink,
+            Failure::NonPortableLink { .. } => Rule::NonPortableLink,
+            Failure::MissingReturnLink { .. } => Rule::MissingReturnLink,
+            Failure::SourceFileLink { .. } => Rule::SourceFileLink,
+            Failure::BrokenExternalLink { .. } => Rule::BrokenExternalLink,
+            Failure::BrokenNofollowLink { .. } => Rule::BrokenNofollowLink,
+            Failure::QuestionableExternalLink { .. } => Rule::QuestionableExternalLink,
+            Failure::MissingExternalFragment { .. } => Rule::MissingExternalFragment,
+            Failure::EnvironmentLink { .. } => Rule::EnvironmentLink,
+            Failure::EmptyLink { .. } => Rule::EmptyLink,
+            Failure::HashLink { .. } => Rule::HashLink,
+            Failure::JavascriptLink { .. } => Rule::JavascriptLink,
+            Failure::UnpublishedUrl { .. } => Rule::UnpublishedUrl,
+            Failure::VanishedTarget { .. } => Rule::VanishedTarget,
+        }
+    }
+}
+
+impl From<BrokenLink> for Failure {
+    fn from(broken: BrokenLink) -> Failure {
+        let BrokenLink {
+            source,
+            href,
+            link,
+            similar_ids,
+            attribute,
+            text,
+            ..
+        } = broken;
+        if broken.missing_fragment {
+            Failure::MissingFragment {
+                source,
+                href,
+                link,
+                similar_ids,
+                attribute,
+                text,
+            }
+        } else {
+            Failure::BrokenLink {
+                source,
+                href,
+                link,
+                attribute,
+                text,
+            }
+        }
+    }
+}
+
+impl From<LoopbackLink> for Failure {
+    fn from(LoopbackLink { source, href }: LoopbackLink) -> Failure {
+        Failure::LocalServerLink { source, href }
+    }
+}
+
+impl From<EscapingLink> for Failure {
+    fn from(EscapingLink { source, href }: EscapingLink) -> Failure {
+        Failure::EscapingLink { source, href }
+    }
+}
+
+impl From<PlaceholderLink> for Failure {
+    fn from(
+        PlaceholderLink {
+            source,
+            href,
+            placeholder,
+        }: PlaceholderLink,
+    ) -> Failure {
+        match placeholder {
+            Placeholder::Empty => Failure::EmptyLink { source, href },
+            Placeholder::Hash => Failure::HashLink { source, href },
+            Placeholder::Javascript => Failure::JavascriptLink { source, href },
+        }
+    }
+}
+
+impl From<VanishedTarget> for Failure {
+    fn from(
+        VanishedTarget {
+            path,
+            fragment,
+            linked_from,
+        }: VanishedTarget,
+    ) -> Failure {
+        Failure::VanishedTarget {
+            source: path,
+            fragment,
+            linked_from,
+        }
+    }
+}
+
+impl From<ContactLink> for Failure {
+    fn from(
+        ContactLink {
+            source,
+            href,
+            problem,
+        }: ContactLink,
+    ) -> Failure {
+        Failure::MalformedContactLink {
+            source,
+            href,
+            problem,
+        }
+    }
+}
+
+impl From<DuplicateId> for Failure {
+    fn from(DuplicateId { source, id, count }: DuplicateId) -> Failure {
+        Failure::DuplicateId { source, id, count }
+    }
+}
+
+impl From<BrokenIdReference> for Failure {
+    fn from(
+        BrokenIdReference {
+            source,
+            attribute,
+            id,
+        }: BrokenIdReference,
+    ) -> Failure {
+        Failure::BrokenIdReference {
+            source,
+            attribute,
+            id,
+        }
+    }
+}
+
+impl From<NonPortableLink> for Failure {
+    fn from(
+        NonPortableLink {
+            source,
+            href,
+            problem,
+        }: NonPortableLink,
+    ) -> Failure {
+        Failure::NonPortableLink {
+            source,
+            href,
+            problem,
+        }
+    }
+}
+
+impl From<TocOmission> for Failure {
+    fn from(TocOmission { source, id }: TocOmission) -> Failure {
+        Failure::MissingFromToc { source, id }
+    }
+}
+
+impl From<DuplicateDocument> for Failure {
+    fn from(DuplicateDocument { source, original }: DuplicateDocument) -> Failure {
+        Failure::DuplicateContent { source, original }
+    }
+}
+
+impl From<RedirectChain> for Failure {
+    fn from(
+        RedirectChain {
+            source,
+            hops,
+            circular,
+        }: RedirectChain,
+    ) -> Failure {
+        Failure::RedirectChain {
+            source,
+            hops,
+            circular,
+        }
+    }
+}
+
+impl From<MissingVariant> for Failure {
+    fn from(
+        MissingVariant {
+            source,
+            srcset,
+            variant,
+            href,
+        }: MissingVariant,
+    ) -> Failure {
+        Failure::IncompleteSrcset {
+            source,
+            srcset,
+            variant,
+            href,
+        }
+    }
+}
+
+impl From<Alternate> for Failure {
+    fn from(
+        Alternate {
+            source,
+            hreflang,
+            href,
+            ..
+        }: Alternate,
+    ) -> Failure {
+        Failure::MissingReturnLink {
+            source,
+            href,
+            hreflang,
+        }
+    }
+}
+
+impl From<SitemapIssue> for Failure {
+    fn from(issue: SitemapIssue) -> Failure {
+        match issue {
+            SitemapIssue::BrokenUrl(url) => Failure::BrokenSitemapUrl {
+                source: PathBuf::from(SITEMAP_PATH),
+                url,
+            },
+            SitemapIssue::Unlisted(source) => Failure::MissingFromSitemap { source },
+        }
+    }
+}
+
+impl From<ReadError> for Failure {
+    fn from(
+        ReadError {
+            path,
+            message,
+            too_large,
+        }: ReadError,
+    ) -> Failure {
+        match too_large {
+            true => Failure::FileTooLarge {
+                source: path,
+                message,
+            },
+            false => Failure::ReadError {
+                source: path,
+                message,
+            },
+        }
+    }
+}
+
+impl From<ConsistencyIssue> for Failure {
+    fn from(issue: ConsistencyIssue) -> Failure {
+        let ConsistencyIssue { source, output, .. } = issue;
+        match issue.inconsistency {
+            Inconsistency::NoOutput => Failure::NoOutput { source, output },
+            Inconsistency::DroppedLink(href) => Failure::DroppedLink {
+                source,
+                output,
+                href,
+            },
+            Inconsistency::ExtraLink(href) => Failure::ExtraLink {
+                source,
+                output,
+                href,
+            },
+        }
+    }
+}
+
+/**
+A link as failures show it: its `href` after the link's text when it has any, like
+`"Installation guide" -> setup/install.html`, which is easier to find on the page.
+*/
+pub fn with_text(text: Option<&str>, href: String) -> String {
+    match text {
+        Some(text) => format!("{text:?} -> {href}"),
+        None => href,
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::BrokenLink {
+                source,
+                href,
+                attribute,
+                text,
+                ..
+            } => write!(
+                f,
+                "Broken {} {} in {source:?}: file not found",
+                LinkAttribute::noun(*attribute),
+                with_text(text.as_deref(), format!("{href:?}"))
+            ),
+            Failure::MissingFragment {
+                source,
+                href,
+                link,
+                similar_ids,
+                text,
+                ..
+            } => write!(
+                f,
+                "Missing fragment {} in {source:?}: {}",
+                with_text(text.as_deref(), format!("{href:?}")),
+                fragment_not_found(link, similar_ids)
+            ),
+            Failure::LocalServerLink { source, href } => write!(
+                f,
+                "Local server link {href:?} in {source:?}, use a relative or production URL instead"
+            ),
+            Failure::EscapingLink { source, href } => write!(
+                f,
+                "Link {href:?} in {source:?} escapes the scanned directory"
+            ),
+            Failure::NoOutput { source, output } => {
+                write!(f, "No output {output:?} for source {source:?}")
+            }
+            Failure::DroppedLink {
+                source,
+                output,
+                href,
+            } => write!(
+                f,
+                "Link {href:?} in source {source:?} is missing from output {output:?}"
+            ),
+            Failure::ExtraLink {
+                source,
+                output,
+                href,
+            } => write!(
+                f,
+                "Link {href:?} in output {output:?} is missing from source {source:?}"
+            ),
+            Failure::ReadError { source, message } => {
+                write!(f, "Could not read {source:?}: {message}")
+            }
+            Failure::FileTooLarge { source, message } => {
+                write!(f, "Skipped {source:?}, which is {message}")
+            }
+            Failure::DuplicateId { source, id, count } => {
+                write!(f, "Id {id:?} appears {count} times in {source:?}")
+            }
+            Failure::BrokenIdReference {
+                source,
+                attribute,
+                id,
+            } => write!(f, "The {attribute} id {id:?} is not in {source:?}"),
+            Failure::MissingFromToc { source, id } => {
+                write!(
+                    f,
+                    "Heading {id:?} is missing from the table of contents in {source:?}"
+                )
+            }
+            Failure::DuplicateContent { source, original } => {
+                write!(f, "{source:?} is an identical copy of {original:?}")
+            }
+            Failure::RedirectChain {
+                source,
+                hops,
+                circular: true,
+            } => write!(
+                f,
+                "Redirect stub {source:?} redirects in a loop through {hops:?}"
+            ),
+            Failure::RedirectChain {
+                source,
+                hops,
+                circular: false,
+            } => write!(
+                f,
+                "Redirect stub {source:?} takes {} redirects to reach {:?}",
+                hops.len(),
+                hops.last().expect("A chain has hops")
+            ),
+            Failure::IncompleteSrcset {
+                source,
+                srcset,
+                variant,
+                href: None,
+            } => write!(
+                f,
+                "Srcset {srcset:?} in {source:?} has no {variant} variant"
+            ),
+            Failure::IncompleteSrcset {
+                source,
+                srcset,
+                variant,
+                href: Some(href),
+            } => write!(
+                f,
+                "The {variant} variant {href:?} of srcset {srcset:?} in {source:?} does not exist"
+            ),
+            Failure::BrokenSitemapUrl { source, url } => {
+                write!(
+                    f,
+                    "Sitemap URL {url:?} in {source:?} does not lead to a file"
+                )
+            }
+            Failure::MissingFromSitemap { source } => {
+                write!(f, "{source:?} is missing from the sitemap")
+            }
+            Failure::BrokenFeedLink { source, url } => {
+                write!(f, "Feed link {url:?} in {source:?} does not lead to a file")
+            }
+            Failure::BrokenDataLink { source, href } => {
+                write!(
+                    f,
+                    "Data file link {href:?} in {source:?} does not lead to a file"
+                )
+            }
+            Failure::MalformedContactLink {
+                source,
+                href,
+                problem,
+            } => write!(
+                f,
+                "Malformed contact link {href:?} in {source:?}: {problem}"
+            ),
+            Failure::NonPortableLink {
+                source,
+                href,
+                problem,
+            } => write!(f, "Non-portable link {href:?} in {source:?}: {problem}"),
+            Failure::MissingReturnLink {
+                source,
+                href,
+                hreflang,
+            } => write!(
+                f,
+                "Alternate {href:?} ({hreflang}) of {source:?} does not link back to it"
+            ),
+            Failure::SourceFileLink {
+                source,
+                href,
+                rendered: Some(rendered),
+            } => write!(
+                f,
+                "Link {href:?} in {source:?} leads to a source file, link to {rendered:?} instead"
+            ),
+            Failure::SourceFileLink {
+                source,
+                href,
+                rendered: None,
+            } => write!(
+                f,
+                "Link {href:?} in {source:?} leads to a source file rather than a rendered page"
+            ),
+            Failure::BrokenExternalLink {
+                source,
+                href,
+                error,
+            } => write!(f, "Broken external link {href:?} in {source:?}: {error}"),
+            Failure::BrokenNofollowLink {
+                source,
+                href,
+                error,
+            } => write!(f, "Broken nofollow link {href:?} in {source:?}: {error}"),
+            Failure::QuestionableExternalLink {
+                source,
+                href,
+                error,
+            } => write!(f, "Questionable external link {href:?} in {source:?}: {error}"),
+            Failure::MissingExternalFragment { source, href } => write!(
+                f,
+                "External link {href:?} in {source:?} leads to a page without its fragment"
+            ),
+            Failure::EnvironmentLink {
+                source,
+                href,
+                problem,
+            } => write!(f, "Environment link {href:?} in {source:?}: {problem}"),
+            Failure::EmptyLink { source, .. } => {
+                write!(f, "Empty link in {source:?} reloads the page")
+            }
+            Failure::HashLink { source, .. } => {
+                write!(f, "Link \"#\" in {source:?} leads nowhere")
+            }
+            Failure::JavascriptLink { source, href } => {
+                write!(f, "Link {href:?} in {source:?} does nothing")
+            }
+            Failure::UnpublishedUrl {
+                source,
+                url,
+                redirect: None,
+            } => write!(
+                f,
+                "Published URL {url:?} in {source:?} no longer leads to a file"
+            ),
+            Failure::UnpublishedUrl {
+                source,
+                url,
+                redirect: Some(redirect),
+            } => write!(
+                f,
+                "Published URL {url:?} in {source:?} no longer leads to a file, redirect it with {redirect:?}"
+            ),
+            Failure::VanishedTarget {
+                source,
+                fragment: None,
+                linked_from,
+            } => write!(
+                f,
+                "{source:?} is gone from the new build, {} documents linked to it",
+                linked_from.len()
+            ),
+            Failure::VanishedTarget {
+                source,
+                fragment: Some(fragment),
+                linked_from,
+            } => write!(
+                f,
+                "Fragment {fragment:?} of {source:?} is gone from the new build, {} documents linked to it",
+                linked_from.len()
+            ),
+        }
+    }
+}
+
+/**
+Explain a missing fragment, like `fragment "install" not found in guide.html, did you mean
+"installing"?`
+*/
+pub fn fragment_not_found(link: &HtmlFileLink, similar_ids: &[String]) -> String {
+    let fragment = link.fragment.as_deref().unwrap_or_default();
+    // An empty path is the root of the scanned directory
+    let document = match link.path.as_os_str().is_empty() {
+        true => Path::new("index.html"),
+        false => link.path.as_path(),
+    };
+    let mut explanation = format!("fragment {fragment:?} not found in {}", document.display());
+    if let Some((last, rest)) = similar_ids.split_last() {
+        let rest: Vec<String> = rest.iter().map(|id| format!("{id:?}")).collect();
+        let similar = match rest.is_empty() {
+            true => format!("{last:?}"),
+            false => format!("{} or {last:?}", rest.join(", ")),
+        };
+        explanation.push_str(&format!(", did you mean {similar}?"));
+    }
+    explanation
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// The failures of each document, then a line counting them
+    #[default]
+    Text,
+    /// A JSON report which can be combined with others by `report merge`
+    Json,
+    /// A standalone web page grouping the failures by document
+    Html,
+    /// A Graphviz graph of the documents and their internal links, with broken links in red
+    Dot,
+}
+
+/**
+Whether a run of the check passed, given explicitly in JSON reports so wrappers don't have to
+infer it from the failures.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    #[default]
+    Passed,
+    Failed,
+}
+
+/**
+All the failures found by a run of the check, and the warnings which don't fail it.
+
+When external links were checked, what came of requesting each URL is kept too, as are the
+`--stats` of the run when they were asked for. Duplicate documents are also gathered into sets
+of identical documents, for scripts cleaning them up. The graph of the documents' links is only kept
+for `--format dot`, and isn't saved in JSON reports, nor is the number of links checked.
+*/
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Report {
+    /// Whether the run passed, as its `--fail-on` says
+    #[serde(default)]
+    pub status: Status,
+    pub failures: Vec<Failure>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<Failure>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub external_urls: BTreeMap<String, UrlStatus>,
+    /// Each set of byte-identical documents, the one kept as the original first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub duplicates: Vec<Vec<PathBuf>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<Stats>,
+    #[serde(skip)]
+    pub graph: Option<LinkGraph>,
+    #[serde(skip)]
+    pub checked_links: Option<usize>,
+}
+
+/**
+Sort failures into a stable order without repeats: by document, then by link, so that every
+format lists them the same way from one run to the next.
+*/
+fn sorted(mut failures: Vec<Failure>) -> Vec<Failure> {
+    failures.sort_by(|a, b| {
+        (a.source(), a.href())
+            .cmp(&(b.source(), b.href()))
+            .then_with(|| a.cmp(b))
+    });
+    failures.dedup();
+    failures
+}
+
+/// The sets of identical documents the `DuplicateContent` failures make up, by their original
+fn duplicate_sets<'a, I: IntoIterator<Item = &'a Failure>>(failures: I) -> Vec<Vec<PathBuf>> {
+    let mut sets: BTreeMap<&Path, Vec<PathBuf>> = BTreeMap::new();
+    for failure in failures {
+        if let Failure::DuplicateContent { source, original } = failure {
+            sets.entry(original).or_default().push(source.clone());
+        }
+    }
+    sets.into_iter()
+        .map(|(original, mut copies)| {
+            copies.sort();
+            copies.dedup();
+            copies.insert(0, original.to_path_buf());
+            copies
+        })
+        .collect()
+}
+
+impl Report {
+    /// A report of `failures`, which fails if there are any until `set_status` says otherwise
+    pub fn new(failures: Vec<Failure>) -> Report {
+        Report {
+            duplicates: duplicate_sets(&failures),
+            status: match failures.is_empty() {
+                true => Status::Passed,
+                false => Status::Failed,
+            },
+            failures: sorted(failures),
+            warnings: vec![],
+            external_urls: BTreeMap::new(),
+            stats: None,
+            graph: None,
+            checked_links: None,
+        }
+    }
+    /// Sort failures into errors and warnings by the severity of their rules
+    pub fn with_rules(failures: Vec<Failure>, rules: &Rules) -> Report {
+        let mut errors = vec![];
+        let mut warnings = vec![];
+        for failure in failures {
+            match rules.severity(failure.rule()) {
+                Severity::Error => errors.push(failure),
+                Severity::Warn => warnings.push(failure),
+                Severity::Off => {}
+            }
+        }
+        let mut report = Report {
+            warnings: sorted(warnings),
+            ..Report::new(errors)
+        };
+        report.duplicates = duplicate_sets(report.failures.iter().chain(&report.warnings));
+        report
+    }
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Report> {
+        let contents = std::fs::read_to_string(path)?;
+        serde_json::from_str(&contents)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+    /// Combine the reports of several shards into a single report, without their stats
+    pub fn merge<I: IntoIterator<Item = Report>>(reports: I) -> Report {
+        let mut failures = vec![];
+        let mut warnings = vec![];
+        let mut external_urls = BTreeMap::new();
+        for report in reports {
+            failures.extend(report.failures);
+            warnings.extend(report.warnings);
+            external_urls.extend(report.external_urls);
+        }
+        let mut report = Report {
+            warnings: sorted(warnings),
+            external_urls,
+            ..Report::new(failures)
+        };
+        report.duplicates = duplicate_sets(report.failures.iter().chain(&report.warnings));
+        report
+    }
+    /// Render the report. Documents are linked from HTML reports if they're found in `roots`,
+    /// and text is grouped as `group_by` says
+    pub fn render(
+        &self,
+        format: Format,
+        roots: &[PathBuf],
+        group_by: GroupBy,
+    ) -> std::io::Result<String> {
+        Ok(match format {
+            Format::Text => crate::text_report::render(self, false, group_by),
+            Format::Json => serde_json::to_string_pretty(self)? + "\n",
+            Format::Html => crate::html_report::render(self, roots),
+            Format::Dot => crate::dot_report::render(self),
+        })
+    }
+    /**
+    Send the report to every sink, in `color` and grouped as `group_by` says if it's printed as
+    text. When it's written to a file but not printed as text, its summary line is printed to
+    stderr for the console log.
+    */
+    pub fn write(
+        &self,
+        sinks: &[Sink],
+        roots: &[PathBuf],
+        color: bool,
+        group_by: GroupBy,
+    ) -> std::io::Result<()> {
+        for sink in sinks {
+            sink.write(self, roots, color, group_by)?;
+        }
+        let to_file = sinks.iter().any(|sink| matches!(sink, Sink::File(..)));
+        if to_file && !sinks.contains(&Sink::Stdout(Format::Text)) {
+            if let Some(summary) = crate::text_report::summary(self) {
+                eprintln!("{summary}");
+            }
+        }
+        Ok(())
+    }
+    /// Whether the check should exit with an error, as `fail_on` says
+    pub fn failed(&self, fail_on: FailOn) -> bool {
+        match fail_on {
+            FailOn::Error => !self.failures.is_empty(),
+            FailOn::Warning => !self.failures.is_empty() || !self.warnings.is_empty(),
+            FailOn::None => false,
+        }
+    }
+    /// Record whether the check passed, as `fail_on` says
+    pub fn set_status(&mut self, fail_on: FailOn) {
+        self.status = match self.failed(fail_on) {
+            true => Status::Failed,
+            false => Status::Passed,
+        };
+    }
+    /// Whether there is nothing to report, not even warnings
+    pub fn is_clean(&self) -> bool {
+        self.failures.is_empty() && self.warnings.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_duplicate_sets() {
+        let duplicate = |source: &str, original: &str| Failure::DuplicateContent {
+            source: source.into(),
+            original: original.into(),
+        };
+        let mut rules = Rules::default();
+        rules.set(Rule::DuplicateContent, Severity::Warn);
+        let report = Report::with_rules(
+            vec![
+                duplicate("c.html", "a.html"),
+                duplicate("b.html", "a.html"),
+                duplicate("e.html", "d.html"),
+            ],
+            &rules,
+        );
+        let sets: Vec<Vec<PathBuf>> = vec![
+            vec!["a.html".into(), "b.html".into(), "c.html".into()],
+            vec!["d.html".into(), "e.html".into()],
+        ];
+        assert_eq!(report.duplicates, sets);
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(
+            json["duplicates"][1],
+            serde_json::json!(["d.html", "e.html"])
+        );
+        assert_eq!(Report::merge([report]).duplicates, sets);
+        assert!(serde_json::to_value(Report::default())
+            .unwrap()
+            .get("duplicates")
+            .is_none());
+    }
+
+    #[test]
+    fn test_report_merge() {
+        let broken = |source: &str, href: &str| Failure::BrokenLink {
+            source: source.into(),
+            href: href.into(),
+            link: HtmlFileLink::new(href),
+            attribute: None,
+            text: None,
+        };
+        let first = Report::new(vec![broken("foo", "bar"), broken("baz", "qux")]);
+        let second = Report::new(vec![broken("foo", "bar")]);
+        let json = serde_json::to_string(&second).unwrap();
+        assert_eq!(
+            json,
+            r#"{"status":"failed","failures":[{"kind":"broken-link","source":"foo","href":"bar","link":{"path":"bar","fragment":null}}]}"#
+        );
+        let second: Report = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            Report::merge([first, second, Report::default()]),
+            Report {
+                status: Status::Failed,
+                failures: vec![broken("baz", "qux"), broken("foo", "bar")],
+                warnings: vec![],
+                external_urls: BTreeMap::new(),
+                duplicates: vec![],
+                stats: None,
+                graph: None,
+                checked_links: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_report_order() {
+        let broken = |source: &str, href: &str| Failure::BrokenLink {
+            source: source.into(),
+            href: href.into(),
+            link: HtmlFileLink::new(href),
+            attribute: None,
+            text: None,
+        };
+        let local = Failure::LocalServerLink {
+            source: "a".into(),
+            href: "http://localhost/".into(),
+        };
+        let read_error = Failure::ReadError {
+            source: "a".into(),
+            message: "nope".into(),
+        };
+        let report = Report::new(vec![
+            broken("b", "x"),
+            read_error,
+            broken("a", "z"),
+            local,
+            broken("a", "a"),
+            broken("b", "x"),
+        ]);
+        let order: Vec<(&Path, Option<&str>)> = report
+            .failures
+            .iter()
+            .map(|failure| (failure.source(), failure.href()))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                (Path::new("a"), None),
+                (Path::new("a"), Some("a")),
+                (Path::new("a"), Some("http://localhost/")),
+                (Path::new("a"), Some("z")),
+                (Path::new("b"), Some("x")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_report_with_rules() {
+        let mut rules = Rules::default();
+        rules.set(Rule::LocalServerLink, Severity::Warn);
+        let local = Failure::LocalServerLink {
+            source: "a".into(),
+            href: "http://localhost/".into(),
+        };
+        let duplicate = Failure::DuplicateId {
+            source: "a".into(),
+            id: "x".into(),
+            count: 2,
+        };
+        let read_error = Failure::ReadError {
+            source: "b".into(),
+            message: "nope".into(),
+        };
+        let report = Report::with_rules(vec![local, duplicate, read_error], &rules);
+        assert!(report.failed(FailOn::Error));
+        assert!(!report.failed(FailOn::None));
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!(
+            report.render(Format::Text, &[], GroupBy::Source).unwrap(),
+            "a\n  warning: local server link http://localhost/, use a relative or production URL instead\nb\n  could not read: nope\n2 files, 1 read error, 1 warning\n"
+        );
+        let json = serde_json::to_string(&report).unwrap();
+        assert_eq!(serde_json::from_str::<Report>(&json).unwrap(), report);
+        let only_warnings = Report {
+            failures: vec![],
+            warnings: report.failures,
+            ..Report::default()
+        };
+        assert!(!only_warnings.failed(FailOn::Error));
+        assert!(only_warnings.failed(FailOn::Warning));
+    }
+}
@@ -0,0 +1,76 @@
+use regex::Regex;
+use serde::Deserialize;
+
+/**
+A `[[rewrite]]` rule of a config file, as written.
+*/
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RewriteConfig {
+    /// A regular expression matched against the `href`, like `^/v2/`
+    pub pattern: String,
+    /// What the match is replaced with, which may refer to groups like `$1`
+    pub replacement: String,
+}
+
+/**
+A rule rewriting the `href` of links before they're resolved, for sites whose URLs are
+post-processed when they're deployed, like by stripping a `/v2` prefix or serving `page.html`
+at `page/`. Failures show the rewritten `href`.
+ */
+#[derive(Debug, Clone)]
+pub struct Rewrite {
+    pattern: Regex,
+    replacement: String,
+}
+
+impl Rewrite {
+    pub fn new(config: &RewriteConfig) -> Result<Rewrite, regex::Error> {
+        Ok(Rewrite {
+            pattern: Regex::new(&config.pattern)?,
+            replacement: config.replacement.clone(),
+        })
+    }
+}
+
+/// Apply each rule in turn to an `href`, replacing the first match of its pattern
+pub fn rewrite_href(rewrites: &[Rewrite], href: &str) -> String {
+    rewrites.iter().fold(href.to_string(), |href, rewrite| {
+        rewrite
+            .pattern
+            .replace(&href, rewrite.replacement.as_str())
+            .into_owned()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rewrite_href() {
+        let rule = |pattern: &str, replacement: &str| {
+            Rewrite::new(&RewriteConfig {
+                pattern: pattern.into(),
+                replacement: replacement.into(),
+            })
+            .unwrap()
+        };
+        let rewrites = [
+            rule("^/v2/", "/"),
+            rule(r"^([^#?]*)\.html([#?].*)?$", "$1/$2"),
+        ];
+        assert_eq!(rewrite_href(&rewrites, "/v2/guide.html"), "/guide/");
+        assert_eq!(
+            rewrite_href(&rewrites, "install.html#linux"),
+            "install/#linux"
+        );
+        assert_eq!(rewrite_href(&rewrites, "/v3/guide/"), "/v3/guide/");
+        assert_eq!(rewrite_href(&[], "/v2/guide.html"), "/v2/guide.html");
+        assert!(Rewrite::new(&RewriteConfig {
+            pattern: "(".into(),
+            replacement: String::new(),
+        })
+        .is_err());
+    }
+}
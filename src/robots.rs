@@ -0,0 +1,150 @@
+use std::time::Duration;
+
+use regex::Regex;
+
+/// The product token external links are requested as, matched against robots.txt user-agents
+pub const USER_AGENT_TOKEN: &str = "relative-link-check";
+
+/**
+The rules of a site's `robots.txt` which apply to this checker.
+
+Rules are taken from the group naming `relative-link-check` as a user-agent if there is one,
+otherwise from the `*` group. The longest matching `Allow` or `Disallow` pattern decides
+whether a path may be requested, with `Allow` winning ties, and patterns may use `*` and a
+trailing `$` as described in RFC 9309.
+ */
+#[derive(Debug, Default)]
+pub struct Robots {
+    /// Each pattern, with whether it allows the paths it matches
+    rules: Vec<(String, bool)>,
+    pub crawl_delay: Option<Duration>,
+}
+
+/// The user-agents a robots.txt group names, and the field and value of each line after them
+type Group = (Vec<String>, Vec<(String, String)>);
+
+/// Whether a robots.txt pattern matches the start of `path`
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut regex = String::from("^");
+    regex.push_str(
+        &pattern
+            .split('*')
+            .map(regex::escape)
+            .collect::<Vec<String>>()
+            .join(".*"),
+    );
+    if anchored {
+        regex.push('$');
+    }
+    Regex::new(&regex).is_ok_and(|regex| regex.is_match(path))
+}
+
+impl Robots {
+    pub fn parse(contents: &str) -> Robots {
+        let mut groups: Vec<Group> = vec![];
+        let mut in_agents = false;
+        for line in contents.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let Some((field, value)) = line.split_once(':') else {
+                continue;
+            };
+            let field = field.trim().to_ascii_lowercase();
+            let value = value.trim().to_string();
+            if field == "user-agent" {
+                if !in_agents {
+                    groups.push((vec![], vec![]));
+                }
+                if let Some((agents, _)) = groups.last_mut() {
+                    agents.push(value.to_ascii_lowercase());
+                }
+                in_agents = true;
+            } else if let Some((_, lines)) = groups.last_mut() {
+                lines.push((field, value));
+                in_agents = false;
+            }
+        }
+        let group = groups
+            .iter()
+            .find(|(agents, _)| agents.iter().any(|agent| agent == USER_AGENT_TOKEN))
+            .or_else(|| {
+                groups
+                    .iter()
+                    .find(|(agents, _)| agents.iter().any(|a| a == "*"))
+            });
+        let mut robots = Robots::default();
+        for (field, value) in group.map(|(_, lines)| lines.as_slice()).unwrap_or_default() {
+            match field.as_str() {
+                // An empty Disallow allows everything, which is the default anyway
+                "allow" | "disallow" if !value.is_empty() => {
+                    robots.rules.push((value.clone(), field == "allow"))
+                }
+                "crawl-delay" => {
+                    robots.crawl_delay = value.parse().ok().map(Duration::from_secs_f64)
+                }
+                _ => {}
+            }
+        }
+        robots
+    }
+    /// Whether the path of a URL (with any query) may be requested
+    pub fn allows(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|(pattern, _)| pattern_matches(pattern, path))
+            .max_by_key(|(pattern, allow)| (pattern.len(), *allow))
+            .is_none_or(|(_, allow)| *allow)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_robots() {
+        let robots = Robots::parse(
+            "
+# Keep out of the drafts
+User-agent: *
+Disallow: /drafts/
+Allow: /drafts/published
+Disallow: /*.pdf$
+Disallow:
+Crawl-delay: 1.5
+
+User-agent: otherbot
+Disallow: /
+",
+        );
+        assert!(robots.allows("/"));
+        assert!(robots.allows("/docs/guide.html"));
+        assert!(!robots.allows("/drafts/"));
+        assert!(!robots.allows("/drafts/secret.html"));
+        assert!(robots.allows("/drafts/published.html"));
+        assert!(!robots.allows("/files/manual.pdf"));
+        assert!(robots.allows("/files/manual.pdf?download=1"));
+        assert_eq!(robots.crawl_delay, Some(Duration::from_millis(1500)));
+    }
+
+    #[test]
+    fn test_robots_own_group() {
+        let robots = Robots::parse(
+            "
+User-agent: Googlebot
+User-agent: relative-link-check
+Disallow: /private
+
+User-agent: *
+Disallow: /
+",
+        );
+        assert!(robots.allows("/public"));
+        assert!(!robots.allows("/private/page.html"));
+        assert!(Robots::parse("").allows("/anything"));
+        assert!(Robots::parse("Disallow: /").allows("/anything"));
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::HashSet;
+
+use regex::RegexSet;
+use serde::Deserialize;
+
+use crate::tree::HtmlFileLink;
+
+/**
+The `[virtual-routes]` section of a config file, as written.
+*/
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RouteConfig {
+    /// Routes matched exactly, like `/app/settings`
+    pub paths: Vec<String>,
+    /// Routes matched by their start, like `/app/`
+    pub prefixes: Vec<String>,
+    /// Regular expressions matched against the whole route, like `^/users/[0-9]+$`
+    pub patterns: Vec<String>,
+}
+
+/**
+Links which a client-side router handles, rather than leading to files, so they are never
+broken. For single page apps embedded in a static site, like `/app/settings`.
+
+Routes are paths from the root of the site, so `../app/settings` and `/app/settings` are the
+same route, and a `#!/app/settings` fragment is the route `/app/settings`.
+ */
+#[derive(Debug, Default, Clone)]
+pub struct VirtualRoutes {
+    paths: HashSet<String>,
+    prefixes: Vec<String>,
+    patterns: RegexSet,
+}
+
+/// A route from the root of the site, with a single leading slash
+fn route(path: &str) -> String {
+    format!("/{}", path.trim_start_matches('/'))
+}
+
+impl VirtualRoutes {
+    pub fn new(config: &RouteConfig) -> Result<VirtualRoutes, regex::Error> {
+        Ok(VirtualRoutes {
+            paths: config.paths.iter().map(|path| route(path)).collect(),
+            prefixes: config.prefixes.iter().map(|prefix| route(prefix)).collect(),
+            patterns: RegexSet::new(&config.patterns)?,
+        })
+    }
+    fn matches(&self, route: &str) -> bool {
+        self.paths.contains(route)
+            || self.prefixes.iter().any(|prefix| route.starts_with(prefix))
+            || self.patterns.is_match(route)
+    }
+    /// Whether a link leads to a virtual route, rather than a file
+    pub fn contains(&self, link: &HtmlFileLink) -> bool {
+        self.matches(&route(&link.path.to_string_lossy()))
+    }
+    /// Whether a link's `#!` fragment is a virtual route, rather than an id
+    pub fn contains_hashbang(&self, link: &HtmlFileLink) -> bool {
+        link.fragment
+            .as_deref()
+            .and_then(|fragment| fragment.strip_prefix('!'))
+            .is_some_and(|path| self.matches(&route(path)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tree::resolve_link;
+
+    #[test]
+    fn test_virtual_routes() {
+        let routes = VirtualRoutes::new(&RouteConfig {
+            paths: vec!["app/settings".into()],
+            prefixes: vec!["/app/admin/".into()],
+            patterns: vec!["^/users/[0-9]+$".into()],
+        })
+        .unwrap();
+        for (href, contained) in [
+            ("/app/settings", true),
+            ("../app/settings", true),
+            ("/app/settings/", false),
+            ("/app/admin/users", true),
+            ("/app/administrator", false),
+            ("/users/42", true),
+            ("/users/42/posts", false),
+            ("/guide.html", false),
+        ] {
+            let link = resolve_link("docs", href);
+            assert_eq!(routes.contains(&link), contained, "{href}");
+        }
+        assert!(routes.contains_hashbang(&HtmlFileLink::new("index.html#!/users/7")));
+        assert!(routes.contains_hashbang(&HtmlFileLink::new("#!app/settings")));
+        assert!(!routes.contains_hashbang(&HtmlFileLink::new("#/users/7")));
+        assert!(!routes.contains_hashbang(&HtmlFileLink::new("/users/7")));
+        assert!(VirtualRoutes::new(&RouteConfig {
+            patterns: vec!["(".into()],
+            ..RouteConfig::default()
+        })
+        .is_err());
+    }
+}
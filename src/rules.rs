@@ -0,0 +1,194 @@
+use std::collections::BTreeMap;
+
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/**
+A check which can fail. Its id is the `kind` of the failures it reports.
+*/
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    ValueEnum,
+    JsonSchema,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rule {
+    /// A link to a file which doesn't exist
+    BrokenLink,
+    /// A link to a document which exists, but has no element with the link's #fragment id
+    MissingFragment,
+    /// A link to a server on the local machine
+    LocalServerLink,
+    /// A link which climbs out of the scanned directory with `..`
+    EscapingLink,
+    /// A source document with no generated output
+    NoOutput,
+    /// A source link missing from the generated output
+    DroppedLink,
+    /// A link in the generated output missing from the source
+    ExtraLink,
+    /// A file or directory which couldn't be read
+    ReadError,
+    /// A file skipped for being larger than --max-file-size
+    FileTooLarge,
+    /// An id used more than once in a document
+    DuplicateId,
+    /// A `<label for>`, `aria-labelledby`, `aria-describedby` or `headers` id not in the document
+    BrokenIdReference,
+    /// A heading left out of a document's table of contents
+    MissingFromToc,
+    /// A document which is a byte-identical copy of another
+    DuplicateContent,
+    /// A redirect stub leading through more than --max-redirect-hops stubs, or in a loop
+    RedirectChain,
+    /// An image `srcset` without one of the --srcset-variants
+    IncompleteSrcset,
+    /// A URL in a sitemap which doesn't lead to a file
+    BrokenSitemapUrl,
+    /// A document which the sitemap doesn't list
+    MissingFromSitemap,
+    /// A link in an RSS or Atom feed to a file of the site which doesn't exist
+    BrokenFeedLink,
+    /// A link in a JSON or YAML data file to a file of the site which doesn't exist
+    BrokenDataLink,
+    /// A `mailto:` or `tel:` link which is malformed
+    MalformedContactLink,
+    /// A link whose path may not lead to the same file on every common filesystem
+    NonPortableLink,
+    /// A `hreflang` translation of a document which doesn't link back to it
+    MissingReturnLink,
+    /// A link to a Markdown, reStructuredText or AsciiDoc source instead of its rendered page
+    SourceFileLink,
+    /// An external link whose URL doesn't answer with a success
+    BrokenExternalLink,
+    /// A broken external link marked `rel="nofollow"`
+    BrokenNofollowLink,
+    /// An external link answering with a status the `[status-codes]` config only warns about
+    QuestionableExternalLink,
+    /// An external link whose `#fragment` isn't an id of the page it leads to
+    MissingExternalFragment,
+    /// An external link to the production site over http, or to a --deny-url-prefix
+    EnvironmentLink,
+    /// An `<a>` with an empty `href`, which reloads the page
+    EmptyLink,
+    /// An `<a href="#">`, which leads nowhere
+    HashLink,
+    /// An `<a href="javascript:void(0)">`, which does nothing
+    JavascriptLink,
+    /// A URL of the --url-manifest which no longer leads to a file
+    UnpublishedUrl,
+    /// A file of the old build given to `rlc diff`, or a #fragment the old build linked to,
+    /// missing from the new build
+    VanishedTarget,
+}
+
+/**
+What becomes of the failures of a rule.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    /// Reported, and fails the check
+    Error,
+    /// Reported, but doesn't fail the check
+    Warn,
+    /// Not reported at all
+    Off,
+}
+
+/**
+Which failures make the check exit with an error, given on the command line as `--fail-on`.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailOn {
+    /// Failures of rules which are errors
+    #[default]
+    Error,
+    /// Warnings too
+    Warning,
+    /// Nothing, so the check only reports
+    None,
+}
+
+/**
+The severity of every rule.
+
+Every rule is an error unless set otherwise, apart from the opt-in checks for duplicate ids,
+id references, tables of contents, duplicate content, redirect chains, documents missing from
+the sitemap, non-portable links, `hreflang` return links, links to source files and placeholder
+links, which are off, and files skipped for their size, which are warnings.
+ */
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Rules(BTreeMap<Rule, Severity>);
+
+impl Rules {
+    /// Parse a `--rule` setting like `duplicate-id=warn`
+    pub fn parse_setting(setting: &str) -> Result<(Rule, Severity), String> {
+        let (rule, severity) = setting
+            .split_once('=')
+            .ok_or_else(|| format!("expected <RULE>=<SEVERITY>, got {setting:?}"))?;
+        Ok((
+            Rule::from_str(rule, false).map_err(|_| format!("unknown rule {rule:?}"))?,
+            Severity::from_str(severity, true).map_err(|_| {
+                format!("unknown severity {severity:?}, expected error, warn or off")
+            })?,
+        ))
+    }
+    pub fn set(&mut self, rule: Rule, severity: Severity) {
+        self.0.insert(rule, severity);
+    }
+    pub fn severity(&self, rule: Rule) -> Severity {
+        match (self.0.get(&rule), rule) {
+            (Some(severity), _) => *severity,
+            (
+                None,
+                Rule::DuplicateId
+                | Rule::BrokenIdReference
+                | Rule::MissingFromToc
+                | Rule::DuplicateContent
+                | Rule::RedirectChain
+                | Rule::MissingFromSitemap
+                | Rule::NonPortableLink
+                | Rule::MissingReturnLink
+                | Rule::SourceFileLink
+                | Rule::EmptyLink
+                | Rule::HashLink
+                | Rule::JavascriptLink,
+            ) => Severity::Off,
+            (None, Rule::FileTooLarge | Rule::QuestionableExternalLink) => Severity::Warn,
+            (None, _) => Severity::Error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rules() {
+        let mut rules = Rules::default();
+        assert_eq!(rules.severity(Rule::BrokenLink), Severity::Error);
+        assert_eq!(rules.severity(Rule::DuplicateId), Severity::Off);
+        rules.set(Rule::BrokenLink, Severity::Warn);
+        assert_eq!(rules.severity(Rule::BrokenLink), Severity::Warn);
+        assert_eq!(
+            Rules::parse_setting("missing-fragment=WARN"),
+            Ok((Rule::MissingFragment, Severity::Warn))
+        );
+        assert!(Rules::parse_setting("missing-fragment").is_err());
+        assert!(Rules::parse_setting("missing-fragments=off").is_err());
+        assert!(Rules::parse_setting("missing-fragment=never").is_err());
+    }
+}
@@ -0,0 +1,60 @@
+use clap::ValueEnum;
+
+use crate::baseline::Baseline;
+use crate::dump::{DumpedLink, LinkTarget};
+use crate::report::Report;
+use crate::sink::Summary;
+
+/**
+A JSON document written or read by the check, whose schema can be printed with `--schema`.
+
+The schemas are generated from the same types the documents are serialized from, so they
+always match the running version.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Schema {
+    /// A report written by --format json and read by `report merge`
+    Report,
+    /// The summary POSTed to --output webhook=<URL>
+    Summary,
+    /// A --baseline file
+    Baseline,
+    /// The links printed by rlc list
+    Links,
+    /// The link targets printed by rlc targets --json
+    Targets,
+}
+
+impl Schema {
+    /// The JSON schema, pretty printed
+    pub fn render(self) -> String {
+        let schema = match self {
+            Schema::Report => schemars::schema_for!(Report),
+            Schema::Summary => schemars::schema_for!(Summary),
+            Schema::Baseline => schemars::schema_for!(Baseline),
+            Schema::Links => schemars::schema_for!(Vec<DumpedLink>),
+            Schema::Targets => schemars::schema_for!(Vec<LinkTarget>),
+        };
+        serde_json::to_string_pretty(&schema).expect("Schemas are always valid JSON") + "\n"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_report_schema() {
+        let schema: serde_json::Value = serde_json::from_str(&Schema::Report.render()).unwrap();
+        assert_eq!(schema["title"], "Report");
+        assert_eq!(schema["required"], serde_json::json!(["failures"]));
+        let kinds: Vec<&str> = schema["$defs"]["Failure"]["oneOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|variant| variant["properties"]["kind"]["const"].as_str().unwrap())
+            .collect();
+        assert!(kinds.contains(&"broken-link"));
+        assert!(kinds.contains(&"incomplete-srcset"));
+    }
+}
@@ -0,0 +1,236 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tiny_http::{Header, Method, Response, Server};
+
+use crate::check::check_scan;
+use crate::filter::FileFilter;
+use crate::html::HtmlInfo;
+use crate::report::Failure;
+use crate::rules::{Rules, Severity};
+use crate::site_path::site_key;
+use crate::streaming::TargetIndex;
+use crate::tree::{HtmlFileLink, Scan};
+use crate::{scanned_path, Args, LinkResolver};
+
+/**
+The body of a `POST /check` request: a document, given by its `content` and where it is in the
+site, or by a file in one of the scanned directories to read it from.
+*/
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CheckRequest {
+    /// With `content`, the document's path relative to the scanned directory, like --stdin
+    /// takes. Without it, a file to read, like --file takes
+    pub path: PathBuf,
+    #[serde(default)]
+    pub content: Option<String>,
+}
+
+/// A failure found in a checked document, with how severe its rule is
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    #[serde(flatten)]
+    pub failure: Failure,
+}
+
+/// The body answering a `POST /check` request
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct CheckResponse {
+    /// The document's path relative to the scanned directory
+    pub path: PathBuf,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/**
+Checks single documents against an index of the site kept in memory, for editors to check a
+document as it's edited without scanning the whole site each time. Documents are checked with
+the `args`, as a whole check would check them.
+*/
+pub struct Checker<'a> {
+    pub index: TargetIndex,
+    pub directories: Vec<PathBuf>,
+    pub filter: FileFilter,
+    pub rules: Rules,
+    pub args: &'a Args,
+    pub resolver: LinkResolver,
+}
+
+impl Checker<'_> {
+    /// The document a request is about, keyed like scanned documents are
+    fn document(&self, request: CheckRequest) -> Result<(PathBuf, HtmlInfo), String> {
+        if let Some(content) = request.content {
+            return Ok((
+                request.path,
+                HtmlInfo::parse_with(&content, &self.filter.parse),
+            ));
+        }
+        let file = request.path;
+        let (directory, path) = scanned_path(&self.directories, &file)
+            .map_err(|err| format!("{file:?}: {err}"))?
+            .ok_or_else(|| format!("{file:?} is not in any of the scanned directories"))?;
+        let info = HtmlInfo::parse_file(&file, &self.filter.parse)
+            .map_err(|err| format!("{file:?}: {err}"))?;
+        Ok((site_key(self.filter.url_prefix(directory), path), info))
+    }
+    /**
+    Check the document of a request. Its links and ids are indexed first, as it may have changed
+    since the site was scanned.
+    */
+    pub fn check(&mut self, request: CheckRequest) -> Result<CheckResponse, String> {
+        let (path, info) = self.document(request)?;
+        self.index.insert(path.clone(), &info);
+        let follow_redirects = self.args.follow_redirects;
+        let contains = |link: &HtmlFileLink| self.index.check(link, follow_redirects);
+        let mut options = self.args.check_options(&self.resolver, contains);
+        let mut scan = Scan::default();
+        scan.add_document(path.clone(), info, &options.hooks, contains);
+        // The translations' return links are in documents which aren't parsed again
+        scan.alternates.clear();
+        let source_links = std::mem::take(&mut scan.source_links);
+        let mut failures = check_scan(&mut scan, None, contains, &self.rules, &mut options)
+            .map_err(|err| err.to_string())?
+            .into_failures();
+        failures.extend(self.resolver.source_file_links(source_links, contains));
+        let diagnostics = failures
+            .into_iter()
+            .map(|failure| Diagnostic {
+                severity: self.rules.severity(failure.rule()),
+                failure,
+            })
+            .filter(|diagnostic| diagnostic.severity != Severity::Off)
+            .collect();
+        Ok(CheckResponse { path, diagnostics })
+    }
+}
+
+/// A JSON response with the given status
+fn json_response<T: Serialize>(status: u16, body: &T) -> Response<std::io::Cursor<Vec<u8>>> {
+    let json = serde_json::to_string(body).expect("Responses are always valid JSON");
+    let header = Header::from_bytes("Content-Type", "application/json").unwrap();
+    Response::from_string(json)
+        .with_status_code(status)
+        .with_header(header)
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+/**
+Answer `POST /check` requests on `address` until the process is stopped, with the diagnostics of
+the document each one gives as JSON.
+*/
+pub fn serve(address: &str, mut checker: Checker) -> std::io::Result<()> {
+    let server = Server::http(address).map_err(std::io::Error::other)?;
+    eprintln!("Listening on http://{}", server.server_addr());
+    for mut request in server.incoming_requests() {
+        let response = match (request.method(), request.url()) {
+            (Method::Post, "/check") => {
+                let mut body = String::new();
+                let parsed = match request.as_reader().read_to_string(&mut body) {
+                    Ok(_) => serde_json::from_str(&body).map_err(|err| err.to_string()),
+                    Err(err) => Err(err.to_string()),
+                };
+                match parsed.and_then(|request| checker.check(request)) {
+                    Ok(response) => json_response(200, &response),
+                    Err(error) => json_response(400, &ErrorResponse { error }),
+                }
+            }
+            (_, "/check") => json_response(
+                405,
+                &ErrorResponse {
+                    error: "only POST is allowed".into(),
+                },
+            ),
+            (_, url) => json_response(
+                404,
+                &ErrorResponse {
+                    error: format!("no such endpoint {url:?}"),
+                },
+            ),
+        };
+        if let Err(err) = request.respond(response) {
+            eprintln!("Couldn't respond: {err}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use clap::Parser;
+
+    use super::*;
+    use crate::fixture::Fixture;
+    use crate::progress::Progress;
+    use crate::rules::Rule;
+    use crate::Cli;
+
+    #[test]
+    fn test_checker() {
+        let fixture = Fixture::new();
+        let directory = &fixture.path;
+        let guide = fixture.write("guide.html", r#"<h1 id="setup">Setup</h1>"#);
+        let filter = FileFilter::default();
+        let directories = vec![directory.clone()];
+        let index = TargetIndex::new(
+            &directories,
+            &filter,
+            1,
+            &Progress::default(),
+            None,
+            Default::default(),
+        )
+        .unwrap();
+        let mut rules = Rules::default();
+        rules.set(Rule::MissingFragment, Severity::Warn);
+        let args = Cli::parse_from([Path::new("rlc"), directory]).args;
+        let resolver = args.link_resolver(directory, &directories).unwrap();
+        let mut checker = Checker {
+            index,
+            directories,
+            filter,
+            rules,
+            args: &args,
+            resolver,
+        };
+        let request: CheckRequest = serde_json::from_str(
+            r##"{"path": "index.html", "content": "<a href='guide.html#setup'></a><a href='guide.html#install'></a><a href='gone.html'></a><a href='#top' id='top'></a>"}"##,
+        )
+        .unwrap();
+        let response = checker.check(request).unwrap();
+        assert_eq!(response.path, PathBuf::from("index.html"));
+        let kinds: Vec<(Severity, &str)> = response
+            .diagnostics
+            .iter()
+            .map(|d| (d.severity, d.failure.href().unwrap()))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (Severity::Warn, "guide.html#install"),
+                (Severity::Error, "gone.html")
+            ]
+        );
+        // A document on disk is read from its file
+        let response = checker
+            .check(CheckRequest {
+                path: guide,
+                content: None,
+            })
+            .unwrap();
+        assert_eq!(response.path, PathBuf::from("guide.html"));
+        assert_eq!(response.diagnostics, vec![]);
+        assert!(checker
+            .check(CheckRequest {
+                path: std::env::temp_dir(),
+                content: None,
+            })
+            .is_err());
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/**
+One of several parallel jobs checking the same site, given on the command line as `N/M`.
+
+Every job still indexes the whole site so that links can be resolved, but only reports the
+failures of its own share of the documents. Documents are sorted by path and split into `M`
+contiguous chunks, so the assignment is the same in every job that sees the same files.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    /// Which shard this is, starting from 1
+    pub index: usize,
+    pub count: usize,
+}
+
+impl Default for Shard {
+    fn default() -> Shard {
+        Shard { index: 1, count: 1 }
+    }
+}
+
+impl Shard {
+    /// Parse a shard like `2/5`
+    pub fn parse(shard: &str) -> Result<Shard, String> {
+        let parsed = shard
+            .split_once('/')
+            .and_then(|(index, count)| Some((index.parse().ok()?, count.parse().ok()?)));
+        match parsed {
+            Some((index, count)) if 0 < index && index <= count => Ok(Shard { index, count }),
+            _ => Err(format!("expected a shard like 2/5, got {shard:?}")),
+        }
+    }
+    /// Pick out the paths belonging to this shard
+    pub fn select<'a, I: IntoIterator<Item = &'a PathBuf>>(&self, paths: I) -> HashSet<PathBuf> {
+        let mut paths: Vec<&PathBuf> = paths.into_iter().collect();
+        paths.sort();
+        let start = paths.len() * (self.index - 1) / self.count;
+        let end = paths.len() * self.index / self.count;
+        paths[start..end]
+            .iter()
+            .map(|path| path.to_path_buf())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_parse() {
+        assert_eq!(Shard::parse("2/5"), Ok(Shard { index: 2, count: 5 }));
+        assert_eq!(Shard::parse("1/1"), Ok(Shard::default()));
+        assert!(Shard::parse("0/5").is_err());
+        assert!(Shard::parse("6/5").is_err());
+        assert!(Shard::parse("2").is_err());
+        assert!(Shard::parse("a/b").is_err());
+    }
+
+    #[test]
+    fn test_select() {
+        let paths: Vec<PathBuf> = ["e", "d", "c", "b", "a"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        let shards: Vec<HashSet<PathBuf>> = (1..=3)
+            .map(|index| Shard { index, count: 3 }.select(&paths))
+            .collect();
+        assert_eq!(shards[0], HashSet::from(["a".into()]));
+        assert_eq!(shards[1], HashSet::from(["b".into(), "c".into()]));
+        assert_eq!(shards[2], HashSet::from(["d".into(), "e".into()]));
+        assert_eq!(Shard::default().select(&paths).len(), 5);
+        assert!(Shard { index: 2, count: 8 }.select(&paths[..1]).is_empty());
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::external::agent_builder;
+use crate::report::{Format, Report, Status};
+use crate::text_report::GroupBy;
+
+/**
+Somewhere to send a report.
+
+Given on the command line as `--output <FORMAT>=<DESTINATION>`, where the destination is a file
+or `-` for stdout, like `--output json=report.json`. A bare `--output report.txt` uses
+`--format`. `--output webhook=<URL>` POSTs a JSON summary of the report to the URL.
+
+Files are replaced atomically, so a CI job collecting them as artifacts never sees a report
+half written.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sink {
+    Stdout(Format),
+    File(Format, PathBuf),
+    Webhook(String),
+}
+
+/**
+The counts of failures in a report, as sent to webhooks.
+*/
+#[derive(Debug, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct Summary {
+    pub status: Status,
+    pub failures: usize,
+    pub warnings: usize,
+    pub documents: usize,
+    pub kinds: BTreeMap<&'static str, usize>,
+}
+
+impl Summary {
+    pub fn new(report: &Report) -> Summary {
+        let mut documents: Vec<_> = report.failures.iter().map(|f| f.source()).collect();
+        documents.sort();
+        documents.dedup();
+        let mut kinds = BTreeMap::new();
+        for failure in report.failures.iter() {
+            *kinds.entry(failure.kind()).or_default() += 1;
+        }
+        Summary {
+            status: report.status,
+            failures: report.failures.len(),
+            warnings: report.warnings.len(),
+            documents: documents.len(),
+            kinds,
+        }
+    }
+}
+
+/**
+Write a file by writing a temporary file next to it and renaming that over it, so it's either
+left as it was or fully written.
+*/
+pub fn write_atomically(path: &Path, contents: &str) -> std::io::Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| std::io::Error::other(format!("{} is not a file", path.display())))?;
+    let mut temporary_name = OsString::from(".");
+    temporary_name.push(name);
+    temporary_name.push(format!(".{}.tmp", std::process::id()));
+    let temporary = path.with_file_name(temporary_name);
+    std::fs::write(&temporary, contents)
+        .and_then(|()| std::fs::rename(&temporary, path))
+        .inspect_err(|_| {
+            let _ = std::fs::remove_file(&temporary);
+        })
+}
+
+impl Sink {
+    /// Parse an `--output` spec, using `format` for destinations which don't name one
+    pub fn parse(spec: &str, format: Format) -> Result<Sink, String> {
+        let (format, destination) = match spec.split_once('=') {
+            Some(("webhook", url)) => return Ok(Sink::Webhook(url.to_string())),
+            Some((name, destination)) => match Format::from_str(name, true) {
+                Ok(format) => (format, destination),
+                // Not a format, so the `=` is part of a file name
+                Err(_) => (format, spec),
+            },
+            None => (format, spec),
+        };
+        match destination {
+            "" => Err(format!("expected a destination in {spec:?}")),
+            "-" => Ok(Sink::Stdout(format)),
+            path => Ok(Sink::File(format, PathBuf::from(path))),
+        }
+    }
+    /// The format the report is sent in, unless it's summarized for a webhook
+    pub fn format(&self) -> Option<Format> {
+        match self {
+            Sink::Stdout(format) | Sink::File(format, _) => Some(*format),
+            Sink::Webhook(_) => None,
+        }
+    }
+    /// The format the report is sent in, if it's meant for people to read
+    pub fn is_human_readable(&self) -> bool {
+        matches!(
+            self,
+            Sink::Stdout(Format::Text) | Sink::File(Format::Text, _)
+        )
+    }
+    /// Send the report. Documents are linked from HTML reports if they're found in `roots`, text
+    /// printed to stdout is in `color`, and all text is grouped as `group_by` says
+    pub fn write(
+        &self,
+        report: &Report,
+        roots: &[PathBuf],
+        color: bool,
+        group_by: GroupBy,
+    ) -> std::io::Result<()> {
+        match self {
+            Sink::Stdout(Format::Text) => {
+                print!("{}", crate::text_report::render(report, color, group_by));
+                Ok(())
+            }
+            Sink::Stdout(format) => {
+                print!("{}", report.render(*format, roots, group_by)?);
+                Ok(())
+            }
+            Sink::File(format, path) => {
+                write_atomically(path, &report.render(*format, roots, group_by)?)
+            }
+            #[cfg(target_arch = "wasm32")]
+            Sink::Webhook(url) => Err(std::io::Error::other(format!(
+                "webhook {url}: requests can't be made from wasm32"
+            ))),
+            #[cfg(not(target_arch = "wasm32"))]
+            Sink::Webhook(url) => {
+                let summary = serde_json::to_string(&Summary::new(report))?;
+                agent_builder()
+                    .build()
+                    .post(url)
+                    .set("Content-Type", "application/json")
+                    .send_string(&summary)
+                    .map_err(|err| std::io::Error::other(format!("webhook {url}: {err}")))?;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixture::Fixture;
+    use crate::report::Failure;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Sink::parse("json=report.json", Format::Text),
+            Ok(Sink::File(Format::Json, "report.json".into()))
+        );
+        assert_eq!(
+            Sink::parse("report.txt", Format::Html),
+            Ok(Sink::File(Format::Html, "report.txt".into()))
+        );
+        assert_eq!(
+            Sink::parse("a=b.txt", Format::Text),
+            Ok(Sink::File(Format::Text, "a=b.txt".into()))
+        );
+        assert_eq!(
+            Sink::parse("TEXT=-", Format::Json),
+            Ok(Sink::Stdout(Format::Text))
+        );
+        assert_eq!(
+            Sink::parse("webhook=https://example.com/hook?a=b", Format::Text),
+            Ok(Sink::Webhook("https://example.com/hook?a=b".into()))
+        );
+        assert!(Sink::parse("json=", Format::Text).is_err());
+    }
+
+    #[test]
+    fn test_write_atomically() {
+        let fixture = Fixture::new();
+        let dir = &fixture.path;
+        let path = fixture.write("report.json", "old");
+        write_atomically(&path, "new").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(std::fs::read_dir(dir).unwrap().count(), 1);
+        assert!(write_atomically(&dir.join("missing/report.json"), "").is_err());
+        assert_eq!(std::fs::read_dir(dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_summary() {
+        let report = Report::new(vec![
+            Failure::LocalServerLink {
+                source: "a".into(),
+                href: "http://localhost/".into(),
+            },
+            Failure::LocalServerLink {
+                source: "b".into(),
+                href: "http://localhost/".into(),
+            },
+            Failure::ReadError {
+                source: "b".into(),
+                message: "nope".into(),
+            },
+        ]);
+        assert_eq!(
+            serde_json::to_string(&Summary::new(&report)).unwrap(),
+            r#"{"status":"failed","failures":3,"warnings":0,"documents":2,"kinds":{"Local server link":2,"Read error":1}}"#
+        );
+    }
+}
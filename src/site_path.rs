@@ -0,0 +1,156 @@
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+/**
+A path on the site, like `docs/guide.html`, with its segments always separated by forward
+slashes as they are in URLs.
+
+Documents are keyed and links resolved as site paths, so they match whatever separator the
+platform's paths use. Converting to and from a file on disk happens at the filesystem boundary
+with `from_file` and `to_file`: on Windows, `docs\guide.html` is the site path
+`docs/guide.html`, and any `\\?\C:\` prefix left by canonicalizing is dropped.
+
+Like browsers, links are split at backslashes as well as forward slashes.
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SitePath(String);
+
+impl SitePath {
+    /**
+    The site path of a file, given relative to the directory it's scanned in. A leading root is
+    kept, as it is for root-relative links, unless it follows a Windows drive or UNC prefix.
+    */
+    pub fn from_file<P: AsRef<Path>>(path: P) -> SitePath {
+        let mut components = path.as_ref().components().peekable();
+        let rooted = components.peek() == Some(&Component::RootDir);
+        let segments: Vec<String> = components
+            .filter_map(|component| match component {
+                Component::Prefix(_) | Component::RootDir | Component::CurDir => None,
+                Component::ParentDir => Some("..".to_string()),
+                Component::Normal(segment) => Some(segment.to_string_lossy().into_owned()),
+            })
+            .collect();
+        let path = segments.join("/");
+        SitePath(if rooted { format!("/{path}") } else { path })
+    }
+    /**
+    Resolve the path of an `href` relative to this directory, dropping `.` segments and the
+    segments `..` climbs out of. A leading slash makes the href relative to the root instead,
+    and is kept. A trailing slash is kept, as are the implied ones of `.` and `..`.
+    */
+    pub fn resolve(&self, href_path: &str) -> SitePath {
+        let rooted = href_path.starts_with(['/', '\\']);
+        let base = if rooted { "" } else { self.as_str() };
+        let rooted = rooted || base.starts_with('/');
+        let mut segments = vec![];
+        for segment in base.split('/').chain(href_path.split(['/', '\\'])) {
+            match segment {
+                "" | "." => {}
+                ".." => {
+                    segments.pop();
+                }
+                segment => segments.push(segment),
+            }
+        }
+        let last = href_path.rsplit(['/', '\\']).next();
+        let is_directory = !href_path.is_empty() && matches!(last, Some("" | "." | ".."));
+        let mut path = segments.join("/");
+        if rooted {
+            path.insert(0, '/');
+        }
+        if is_directory && !path.is_empty() && !path.ends_with('/') {
+            path.push('/');
+        }
+        SitePath(path)
+    }
+    /// The file on disk at this site path under `root`
+    pub fn to_file<P: AsRef<Path>>(&self, root: P) -> PathBuf {
+        self.0
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .fold(root.as_ref().to_path_buf(), |path, segment| {
+                path.join(segment)
+            })
+    }
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// The key of a file found at `path` in a scanned directory mounted at `prefix`
+pub fn site_key<P: AsRef<Path>, Q: AsRef<Path>>(prefix: P, path: Q) -> PathBuf {
+    SitePath::from_file(prefix.as_ref().join(path)).into()
+}
+
+impl From<SitePath> for PathBuf {
+    fn from(path: SitePath) -> PathBuf {
+        PathBuf::from(path.0)
+    }
+}
+
+impl fmt::Display for SitePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let docs = SitePath::from_file("docs/./guide");
+        assert_eq!(docs.as_str(), "docs/guide");
+        for (href, resolved) in [
+            ("setup.html", "docs/guide/setup.html"),
+            ("../index.html", "docs/index.html"),
+            ("..", "docs/"),
+            ("./", "docs/guide/"),
+            ("../../..", ""),
+            ("/blog/post.html", "/blog/post.html"),
+            ("/", "/"),
+            (r"api\index.html", "docs/guide/api/index.html"),
+            (r"..\..\README.html", "README.html"),
+            (r"\blog\", "/blog/"),
+        ] {
+            assert_eq!(docs.resolve(href).as_str(), resolved, "{href}");
+        }
+        assert_eq!(SitePath::default().resolve("").as_str(), "");
+        let root_docs = SitePath::from_file("/docs");
+        assert_eq!(root_docs.resolve(r"..\guide.html").as_str(), "/guide.html");
+        assert_eq!(
+            docs.resolve("setup.html").to_file("site"),
+            Path::new("site")
+                .join("docs")
+                .join("guide")
+                .join("setup.html")
+        );
+    }
+
+    #[test]
+    fn test_site_key() {
+        assert_eq!(
+            site_key("docs", Path::new("guide").join("index.html")),
+            PathBuf::from("docs/guide/index.html")
+        );
+        assert_eq!(site_key("", "index.html"), PathBuf::from("index.html"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_from_windows_file() {
+        assert_eq!(
+            SitePath::from_file(r"docs\guide\index.html").as_str(),
+            "docs/guide/index.html"
+        );
+        assert_eq!(
+            SitePath::from_file(r"\\?\C:\site\index.html").as_str(),
+            "site/index.html"
+        );
+        assert_eq!(
+            site_key(r"docs", r"guide\index.html").to_string_lossy(),
+            "docs/guide/index.html"
+        );
+    }
+}
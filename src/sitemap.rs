@@ -0,0 +1,188 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+/// Where the sitemap is looked for in each scanned directory
+pub const SITEMAP_PATH: &str = "sitemap.xml";
+
+/**
+The URLs listed in a `sitemap.xml`, in the order they appear.
+
+A sitemap index lists other sitemaps instead of pages, read the same way.
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub struct Sitemap {
+    pub urls: Vec<String>,
+    pub is_index: bool,
+}
+
+impl Sitemap {
+    pub fn parse(contents: &str) -> Result<Sitemap, String> {
+        let document = roxmltree::Document::parse(contents).map_err(|err| err.to_string())?;
+        let urls = document
+            .descendants()
+            .filter(|node| node.has_tag_name("loc"))
+            .filter_map(|node| node.text())
+            .map(|url| url.trim().to_string())
+            .collect();
+        let is_index = document.root_element().has_tag_name("sitemapindex");
+        Ok(Sitemap { urls, is_index })
+    }
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Sitemap> {
+        let contents = std::fs::read_to_string(path)?;
+        Sitemap::parse(&contents)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+    /**
+    Load the sitemap of a scanned directory. If it's an index, the pages of the sitemaps it
+    lists are added to its own URLs, which keep the listed sitemaps themselves.
+    */
+    pub fn load_site(directory: &Path) -> std::io::Result<Sitemap> {
+        let mut sitemap = Sitemap::load(directory.join(SITEMAP_PATH))?;
+        if sitemap.is_index {
+            let children: Vec<PathBuf> = sitemap
+                .urls
+                .iter()
+                .filter_map(|url| url_path(url))
+                .map(|path| directory.join(path))
+                .filter(|path| path.is_file())
+                .collect();
+            for child in children {
+                sitemap.urls.extend(Sitemap::load(child)?.urls);
+            }
+        }
+        Ok(sitemap)
+    }
+}
+
+/**
+The file a sitemap URL refers to, relative to the root of the site.
+
+Only the path of the URL is used, so sites are checked the same wherever they're deployed.
+*/
+pub fn url_path(url: &str) -> Option<PathBuf> {
+    let url = Url::parse(url).ok()?;
+    let path = percent_decode_str(url.path()).decode_utf8().ok()?;
+    Some(PathBuf::from(path.trim_start_matches('/')))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SitemapIssue {
+    /// A URL in the sitemap (given as written) which doesn't lead to a file
+    BrokenUrl(String),
+    /// A scanned document which the sitemap doesn't list
+    Unlisted(PathBuf),
+}
+
+/**
+Check that every URL in the sitemap of each scanned directory leads to a document or file in
+it, and find the `documents` which none of the sitemaps list.
+*/
+pub fn check_sitemaps(
+    sitemaps: &[(PathBuf, Sitemap)],
+    documents: &HashSet<&Path>,
+) -> Vec<SitemapIssue> {
+    let mut listed = HashSet::new();
+    let mut issues = vec![];
+    for (directory, url) in sitemaps
+        .iter()
+        .flat_map(|(directory, sitemap)| sitemap.urls.iter().map(move |url| (directory, url)))
+    {
+        let Some(path) = url_path(url) else {
+            issues.push(SitemapIssue::BrokenUrl(url.clone()));
+            continue;
+        };
+        let index = path.join("index.html");
+        if documents.contains(path.as_path()) {
+            listed.insert(path);
+        } else if documents.contains(index.as_path()) {
+            listed.insert(index);
+        } else if !directory.join(&path).is_file() {
+            issues.push(SitemapIssue::BrokenUrl(url.clone()));
+        }
+    }
+    let mut unlisted: Vec<&Path> = documents
+        .iter()
+        .filter(|document| !listed.contains(**document))
+        .copied()
+        .collect();
+    unlisted.sort();
+    issues.extend(
+        unlisted
+            .into_iter()
+            .map(|document| SitemapIssue::Unlisted(document.to_path_buf())),
+    );
+    issues
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let sitemap = Sitemap::parse(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url><loc>https://example.com/</loc><lastmod>2024-01-01</lastmod></url>
+  <url><loc>
+    https://example.com/guide/caf%C3%A9.html
+  </loc></url>
+</urlset>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            sitemap.urls,
+            vec![
+                "https://example.com/",
+                "https://example.com/guide/caf%C3%A9.html"
+            ]
+        );
+        assert_eq!(url_path(&sitemap.urls[0]), Some(PathBuf::new()));
+        assert_eq!(
+            url_path(&sitemap.urls[1]),
+            Some(PathBuf::from("guide/café.html"))
+        );
+        assert!(!sitemap.is_index);
+        assert_eq!(url_path("/relative"), None);
+        let index = Sitemap::parse(
+            "<sitemapindex><sitemap><loc>https://example.com/a.xml</loc></sitemap></sitemapindex>",
+        )
+        .unwrap();
+        assert!(index.is_index);
+        assert_eq!(index.urls, vec!["https://example.com/a.xml"]);
+        assert!(Sitemap::parse("<urlset>").is_err());
+    }
+
+    #[test]
+    fn test_check_sitemap() {
+        let sitemap = Sitemap {
+            urls: vec![
+                "https://example.com/".into(),
+                "https://example.com/guide/".into(),
+                "https://example.com/about.html".into(),
+                "https://example.com/gone.html".into(),
+                "not a url".into(),
+            ],
+            is_index: false,
+        };
+        let documents = HashSet::from([
+            Path::new("index.html"),
+            Path::new("guide/index.html"),
+            Path::new("about.html"),
+            Path::new("unlisted.html"),
+            Path::new("404.html"),
+        ]);
+        assert_eq!(
+            check_sitemaps(&[("/nonexistent".into(), sitemap)], &documents),
+            vec![
+                SitemapIssue::BrokenUrl("https://example.com/gone.html".into()),
+                SitemapIssue::BrokenUrl("not a url".into()),
+                SitemapIssue::Unlisted("404.html".into()),
+                SitemapIssue::Unlisted("unlisted.html".into()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::html::HtmlInfo;
+use crate::intern::InternerStats;
+use crate::report::{Failure, Report};
+use crate::rules::Rule;
+use crate::tree::{resolved_links, HtmlFileLink};
+
+/**
+How many links the scanned documents have, and where the internal ones lead.
+*/
+#[derive(Debug, Default)]
+pub struct LinkCounts {
+    pub internal: usize,
+    pub external: usize,
+    /// Every file and `#fragment` linked to
+    pub targets: HashSet<HtmlFileLink>,
+    /// Every document with each file it links to
+    pub edges: HashSet<(PathBuf, PathBuf)>,
+}
+
+impl LinkCounts {
+    pub fn add(&mut self, file_path: &Path, info: &HtmlInfo) {
+        self.internal += info.relative_hrefs.len();
+        self.external += info.external_hrefs.len();
+        for (_, link) in resolved_links(file_path, info) {
+            self.edges
+                .insert((file_path.to_path_buf(), link.path.clone()));
+            self.targets.insert(link);
+        }
+    }
+}
+
+impl<'a> FromIterator<(&'a PathBuf, &'a HtmlInfo)> for LinkCounts {
+    fn from_iter<I: IntoIterator<Item = (&'a PathBuf, &'a HtmlInfo)>>(documents: I) -> Self {
+        let mut counts = LinkCounts::default();
+        for (file_path, info) in documents {
+            counts.add(file_path, info);
+        }
+        counts
+    }
+}
+
+/**
+How long each phase of a check took, in milliseconds.
+
+Documents are parsed as they're found, so walking the directories is part of parsing. With
+`--low-memory` they're parsed again while checking. External URLs are requested from the start of
+parsing until the last one answers, so that phase overlaps the others.
+*/
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Timings {
+    pub parse_ms: u64,
+    pub check_ms: u64,
+    pub external_ms: u64,
+    pub total_ms: u64,
+}
+
+/// The milliseconds since `start`
+pub fn elapsed_ms(start: Instant) -> u64 {
+    start.elapsed().as_millis() as u64
+}
+
+/**
+Totals for a run of the check, printed by `--stats` and kept in its JSON report so they can be
+tracked over time.
+*/
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Stats {
+    pub documents: usize,
+    pub internal_links: usize,
+    pub external_links: usize,
+    /// How many different files and `#fragment`s the internal links lead to
+    pub unique_targets: usize,
+    /// How many failures each rule reported
+    pub failures: BTreeMap<Rule, usize>,
+    /// How many warnings each rule reported
+    pub warnings: BTreeMap<Rule, usize>,
+    pub timings: Timings,
+    /// How many hrefs and ids were kept once for all the documents they're in, and the memory
+    /// that saved
+    #[serde(default)]
+    pub interning: InternerStats,
+}
+
+impl Stats {
+    pub fn new(
+        documents: usize,
+        links: &LinkCounts,
+        report: &Report,
+        timings: Timings,
+        interning: InternerStats,
+    ) -> Stats {
+        let by_rule = |failures: &[Failure]| {
+            let mut counts = BTreeMap::new();
+            for failure in failures {
+                *counts.entry(failure.rule()).or_default() += 1;
+            }
+            counts
+        };
+        Stats {
+            documents,
+            internal_links: links.internal,
+            external_links: links.external,
+            unique_targets: links.targets.len(),
+            failures: by_rule(&report.failures),
+            warnings: by_rule(&report.warnings),
+            timings,
+            interning,
+        }
+    }
+}
+
+/**
+The totals of `--metrics-file`, in the text format of Prometheus, for its node exporter's
+textfile collector to pick up after scheduled checks.
+
+Every rule is listed, with a count of 0 if it reported nothing, so that graphs and alerts don't
+mistake a rule without failures for a missing one.
+*/
+pub fn render_metrics(stats: &Stats) -> String {
+    let mut metrics = String::new();
+    let mut metric = |name: &str, help: &str, samples: &[(String, String)]| {
+        writeln!(metrics, "# HELP rlc_{name} {help}").unwrap();
+        writeln!(metrics, "# TYPE rlc_{name} gauge").unwrap();
+        for (labels, value) in samples {
+            writeln!(metrics, "rlc_{name}{labels} {value}").unwrap();
+        }
+    };
+    let label = |name: &str, value: &str| format!("{{{name}=\"{value}\"}}");
+    let by_rule = |counts: &BTreeMap<Rule, usize>| {
+        Rule::value_variants()
+            .iter()
+            .map(|rule| {
+                let name = rule.to_possible_value().unwrap().get_name().to_string();
+                let count = counts.get(rule).copied().unwrap_or_default();
+                (label("rule", &name), count.to_string())
+            })
+            .collect::<Vec<_>>()
+    };
+    let seconds = |ms: u64| format!("{:.3}", ms as f64 / 1000.0);
+    metric(
+        "documents",
+        "Documents checked.",
+        &[(String::new(), stats.documents.to_string())],
+    );
+    metric(
+        "links",
+        "Links found in the documents, by kind.",
+        &[
+            (label("kind", "internal"), stats.internal_links.to_string()),
+            (label("kind", "external"), stats.external_links.to_string()),
+        ],
+    );
+    metric(
+        "unique_targets",
+        "Different files and fragments the internal links lead to.",
+        &[(String::new(), stats.unique_targets.to_string())],
+    );
+    metric(
+        "failures",
+        "Failures reported, by rule.",
+        &by_rule(&stats.failures),
+    );
+    metric(
+        "warnings",
+        "Warnings reported, by rule.",
+        &by_rule(&stats.warnings),
+    );
+    let timings = &stats.timings;
+    metric(
+        "duration_seconds",
+        "How long each phase of the check took.",
+        &[
+            (label("phase", "parse"), seconds(timings.parse_ms)),
+            (label("phase", "check"), seconds(timings.check_ms)),
+            (label("phase", "external"), seconds(timings.external_ms)),
+            (label("phase", "total"), seconds(timings.total_ms)),
+        ],
+    );
+    let interning = &stats.interning;
+    metric(
+        "interned_strings",
+        "Different hrefs and ids kept for all the documents.",
+        &[(String::new(), interning.strings.to_string())],
+    );
+    metric(
+        "interned_bytes",
+        "Bytes of the hrefs and ids, as kept and as they would be without interning.",
+        &[
+            (label("copies", "kept"), interning.kept_bytes.to_string()),
+            (label("copies", "all"), interning.interned_bytes.to_string()),
+        ],
+    );
+    metrics
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let documents = [
+            (
+                PathBuf::from("index.html"),
+                HtmlInfo::parse(
+                    r#"<a href="guide.html"/><a href="guide.html#setup"/><a href="https://example.com/"/>"#,
+                ),
+            ),
+            (
+                PathBuf::from("docs/guide.html"),
+                HtmlInfo::parse(r#"<a href="../guide.html"/><a href="../index.html"/>"#),
+            ),
+        ];
+        let links: LinkCounts = documents.iter().map(|(path, info)| (path, info)).collect();
+        let mut report = Report::new(vec![Failure::LocalServerLink {
+            source: "index.html".into(),
+            href: "http://localhost/".into(),
+        }]);
+        report.warnings.push(Failure::DuplicateId {
+            source: "index.html".into(),
+            id: "top".into(),
+            count: 2,
+        });
+        let interning = InternerStats {
+            strings: 3,
+            kept_bytes: 30,
+            interned_bytes: 100,
+        };
+        let stats = Stats::new(
+            documents.len(),
+            &links,
+            &report,
+            Timings::default(),
+            interning,
+        );
+        assert_eq!(
+            (
+                stats.internal_links,
+                stats.external_links,
+                stats.unique_targets
+            ),
+            (4, 1, 3)
+        );
+        assert_eq!(
+            serde_json::to_string(&stats).unwrap(),
+            r#"{"documents":2,"internal_links":4,"external_links":1,"unique_targets":3,"failures":{"local-server-link":1},"warnings":{"duplicate-id":1},"timings":{"parse_ms":0,"check_ms":0,"external_ms":0,"total_ms":0},"interning":{"strings":3,"kept_bytes":30,"interned_bytes":100}}"#
+        );
+        let metrics = render_metrics(&Stats {
+            timings: Timings {
+                parse_ms: 1250,
+                total_ms: 2000,
+                ..Timings::default()
+            },
+            ..stats
+        });
+        for line in [
+            "# HELP rlc_documents Documents checked.",
+            "# TYPE rlc_documents gauge",
+            "rlc_documents 2",
+            r#"rlc_links{kind="internal"} 4"#,
+            r#"rlc_links{kind="external"} 1"#,
+            "rlc_unique_targets 3",
+            r#"rlc_failures{rule="local-server-link"} 1"#,
+            r#"rlc_failures{rule="broken-link"} 0"#,
+            r#"rlc_warnings{rule="duplicate-id"} 1"#,
+            r#"rlc_duration_seconds{phase="parse"} 1.250"#,
+            r#"rlc_duration_seconds{phase="total"} 2.000"#,
+            "rlc_interned_strings 3",
+            r#"rlc_interned_bytes{copies="kept"} 30"#,
+            r#"rlc_interned_bytes{copies="all"} 100"#,
+        ] {
+            assert!(metrics.lines().any(|l| l == line), "{line}\n{metrics}");
+        }
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::filter::FileFilter;
+use crate::html::HtmlInfo;
+use crate::intern::Interned;
+use crate::progress::Progress;
+use crate::tree::{
+    duplicate_documents, redirect_chains, resolve_redirect, walk_documents, FragmentMatch, Hooks,
+    HtmlFileLink, HtmlFiles, Probing, ReadError, Scan, MAX_REDIRECTS,
+};
+
+/**
+Every valid link target of a site, without the rest of the contents of its documents.
+
+Documents and `#fragment` ids are stored as 64 bit hashes, so memory stays small even for sites
+with millions of pages. A hash collision could hide a broken link, but at that size the odds
+are still negligible.
+
+An index can be saved with `--save-index` and read back with `--index`, to check single
+documents without scanning the whole site again. Ids are hashed as the `FragmentMatch` setting
+compares them, so that is saved along with them.
+ */
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TargetIndex {
+    #[serde(serialize_with = "serialize_sorted_set")]
+    targets: HashSet<u64>,
+    /// Where each redirect stub redirects to, keyed by the hash of the stub
+    #[serde(serialize_with = "serialize_sorted_map")]
+    redirects: HashMap<u64, PathBuf>,
+    #[serde(default)]
+    fragment_match: FragmentMatch,
+    /// Not saved, so an index can be checked against with any setting
+    #[serde(skip)]
+    probing: Probing,
+}
+
+/// Saving the same index twice gives the same file, whatever order the hashes are held in
+fn serialize_sorted_set<S: Serializer>(
+    set: &HashSet<u64>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(set.iter().collect::<BTreeSet<_>>())
+}
+
+fn serialize_sorted_map<S: Serializer>(
+    map: &HashMap<u64, PathBuf>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(map.iter().collect::<BTreeMap<_, _>>())
+}
+
+/**
+An index as saved to a file, with the version which saved it. The hashes aren't guaranteed to
+be the same from one build to the next, so other versions' indexes aren't read.
+*/
+#[derive(Serialize, Deserialize)]
+struct SavedIndex<I> {
+    version: String,
+    index: I,
+}
+
+fn target_hash(path: &Path, fragment: Option<&str>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    // Hash the components, since `Path`'s own hash doesn't tell `/foo` apart from `foo`
+    path.components()
+        .for_each(|component| component.hash(&mut hasher));
+    fragment.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl TargetIndex {
+    pub fn new(
+        directories: &[PathBuf],
+        filter: &FileFilter,
+        jobs: usize,
+        progress: &Progress,
+        errors: Option<&mut Vec<ReadError>>,
+        fragment_match: FragmentMatch,
+    ) -> std::io::Result<TargetIndex> {
+        let mut index = TargetIndex {
+            fragment_match,
+            ..TargetIndex::default()
+        };
+        let other_files =
+            walk_documents(directories, filter, jobs, progress, errors, |path, info| {
+                index.insert(path, &info)
+            })?;
+        for (path, ids) in other_files {
+            index.insert_other_file(&path, &ids);
+        }
+        Ok(index)
+    }
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<TargetIndex> {
+        let contents = std::fs::read_to_string(path)?;
+        let saved: SavedIndex<TargetIndex> = serde_json::from_str(&contents)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        if saved.version != env!("CARGO_PKG_VERSION") {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "index was saved by version {}, save it again with this one",
+                    saved.version
+                ),
+            ));
+        }
+        Ok(saved.index)
+    }
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let saved = SavedIndex {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            index: self,
+        };
+        std::fs::write(path, serde_json::to_string(&saved)?)
+    }
+    pub fn insert(&mut self, path: PathBuf, info: &HtmlInfo) {
+        self.targets.insert(target_hash(&path, None));
+        for id in info.ids.iter() {
+            let id = self.fragment_match.normalize(id);
+            self.targets.insert(target_hash(&path, Some(&id)));
+        }
+        if let Some(redirect) = resolve_redirect(&path, info) {
+            self.redirects
+                .insert(target_hash(&path, None), redirect.path);
+        }
+    }
+    /// Index a file which isn't a document, with the ids in it if it's an SVG image
+    fn insert_other_file(&mut self, path: &Path, ids: &[Interned]) {
+        self.targets.insert(target_hash(path, None));
+        for id in ids {
+            let id = self.fragment_match.normalize(id);
+            self.targets.insert(target_hash(path, Some(&id)));
+        }
+    }
+    /// How the ids of the index were hashed
+    pub fn fragment_match(&self) -> FragmentMatch {
+        self.fragment_match
+    }
+    pub fn set_probing(&mut self, probing: Probing) {
+        self.probing = probing;
+    }
+    /// The document a link to `path` points at, if it was indexed
+    fn document(&self, path: &Path) -> Option<PathBuf> {
+        self.probing
+            .candidates(path)
+            .into_iter()
+            .find(|document| self.targets.contains(&target_hash(document, None)))
+    }
+    /// The same check as `HtmlFiles::contains`
+    pub fn contains(&self, HtmlFileLink { path, fragment, .. }: &HtmlFileLink) -> bool {
+        match self.document(path) {
+            Some(document) => {
+                let fragment = fragment
+                    .as_deref()
+                    .map(|fragment| self.fragment_match.normalize(fragment));
+                self.targets
+                    .contains(&target_hash(&document, fragment.as_deref()))
+            }
+            None => false,
+        }
+    }
+    /// Check a link with `contains_following_redirects` or `contains`
+    pub fn check(&self, link: &HtmlFileLink, follow_redirects: bool) -> bool {
+        if follow_redirects {
+            self.contains_following_redirects(link)
+        } else {
+            self.contains(link)
+        }
+    }
+    /// The same check as `HtmlFiles::contains_following_redirects`
+    pub fn contains_following_redirects(&self, link: &HtmlFileLink) -> bool {
+        let Some(fragment) = link.fragment.as_deref() else {
+            return self.contains(link);
+        };
+        let fragment = self.fragment_match.normalize(fragment);
+        let mut path = link.path.clone();
+        for _ in 0..MAX_REDIRECTS {
+            let Some(document) = self.document(&path) else {
+                return false;
+            };
+            let found = self
+                .targets
+                .contains(&target_hash(&document, Some(&fragment)));
+            match self.redirects.get(&target_hash(&document, None)) {
+                Some(redirect) if !found => path = redirect.clone(),
+                _ => return found,
+            }
+        }
+        false
+    }
+}
+
+impl From<&HtmlFiles> for TargetIndex {
+    fn from(files: &HtmlFiles) -> TargetIndex {
+        let mut index = TargetIndex {
+            fragment_match: files.3,
+            ..TargetIndex::default()
+        };
+        for (path, info) in files.0.iter() {
+            index.insert(path.clone(), info);
+        }
+        for (path, ids) in files.1.iter() {
+            index.insert_other_file(path, ids);
+        }
+        index.probing = files.2.clone();
+        index
+    }
+}
+
+/**
+The second pass of checking the documents in two passes, so that only one is held in memory at
+a time.
+
+The first pass indexes the valid link targets with `TargetIndex::new`, this one parses every
+document again to check its links against the index. Anything unreadable was already recorded
+by the first pass, so it's skipped if `skip_unreadable`, rather than aborting the check. Each
+document is passed through the `hooks` as it's parsed.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn check(
+    index: &TargetIndex,
+    directories: &[PathBuf],
+    filter: &FileFilter,
+    jobs: usize,
+    follow_redirects: bool,
+    progress: &Progress,
+    skip_unreadable: bool,
+    hooks: &Hooks,
+) -> std::io::Result<Scan> {
+    let mut scan = Scan::default();
+    let mut content_hashes = vec![];
+    let mut redirects = vec![];
+    let mut recorded = vec![];
+    let errors = skip_unreadable.then_some(&mut recorded);
+    walk_documents(directories, filter, jobs, progress, errors, |path, info| {
+        progress.checked(info.relative_hrefs.len());
+        content_hashes.push((path.clone(), info.content_hash));
+        redirects.extend(resolve_redirect(&path, &info).map(|link| (path.clone(), link)));
+        scan.add_document(path, info, hooks, |link| {
+            index.check(link, follow_redirects)
+        });
+    })?;
+    scan.duplicate_documents = duplicate_documents(
+        content_hashes
+            .iter()
+            .map(|(path, hash)| (path.as_path(), *hash)),
+    );
+    scan.redirect_chains = redirect_chains(
+        redirects
+            .iter()
+            .map(|(path, link)| (path.as_path(), link.path.clone())),
+        &index.probing,
+    );
+    Ok(scan)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixture::Fixture;
+    use crate::html::Ids;
+
+    #[test]
+    fn test_target_index_contains() {
+        let documents = [
+            ("foo", r#"<a href="foo" id="foo" />"#),
+            ("/bar", r#"<a href="/bar" id="bar" />"#),
+            ("/baz/index.html", r#"<a href="/baz" id="baz" />"#),
+            (
+                "old",
+                r#"<meta http-equiv="refresh" content="0; url=/baz/">"#,
+            ),
+        ];
+        let mut index = TargetIndex::default();
+        let mut files = HtmlFiles::default();
+        for (path, document) in documents {
+            index.insert(path.into(), &HtmlInfo::parse(document));
+            files.0.insert(path.into(), HtmlInfo::parse(document));
+        }
+        files.1.insert("logo.png".into(), Ids::default());
+        files
+            .1
+            .insert("icons.svg".into(), ["arrow".into()].into_iter().collect());
+        index.insert_other_file(Path::new("logo.png"), &[]);
+        index.insert_other_file(Path::new("icons.svg"), &["arrow".into()]);
+        let fixture = Fixture::new();
+        let path = fixture.path.join("index.json");
+        TargetIndex::from(&files).save(&path).unwrap();
+        let loaded = TargetIndex::load(&path).unwrap();
+        for href in [
+            "foo",
+            "foooo",
+            "foo#foo",
+            "foo#bar",
+            "/bar",
+            "/bar#bar",
+            "bar",
+            "/baz",
+            "/baz#baz",
+            "/baz/",
+            "foo/",
+            "/baz/#nope",
+            "/baz/index.html#baz",
+            "old",
+            "old#baz",
+            "old#nope",
+            "logo.png",
+            "logo.png#nope",
+            "icons.svg#arrow",
+            "icons.svg#nope",
+        ] {
+            let link = HtmlFileLink::new(href);
+            assert_eq!(index.contains(&link), files.contains(&link), "{href}");
+            assert_eq!(loaded.contains(&link), files.contains(&link), "{href}");
+            assert_eq!(
+                loaded.contains_following_redirects(&link),
+                files.contains_following_redirects(&link),
+                "{href}"
+            );
+            assert_eq!(
+                index.contains_following_redirects(&link),
+                files.contains_following_redirects(&link),
+                "{href}"
+            );
+        }
+    }
+}
@@ -0,0 +1,642 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::io::IsTerminal;
+use std::path::Path;
+
+use clap::ValueEnum;
+
+use crate::html::LinkAttribute;
+use crate::report::{fragment_not_found, with_text, Failure, Report};
+use crate::rules::Rule;
+use crate::stats::Stats;
+use crate::tree::LinkBase;
+
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+const GREEN: &str = "\x1b[32m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/**
+When to color the text report printed to stdout.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Color {
+    /// When stdout is a terminal and `NO_COLOR` isn't set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl Color {
+    pub fn enabled(self) -> bool {
+        match self {
+            Color::Auto => {
+                std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+                    && std::io::stdout().is_terminal()
+            }
+            Color::Always => true,
+            Color::Never => false,
+        }
+    }
+}
+
+/// Wraps text in ANSI escapes, or leaves it be when color is off
+struct Paint(bool);
+
+impl Paint {
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.0 {
+            format!("{style}{text}{RESET}")
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// How to count failures of a rule in the summary line, singular and plural
+fn noun(rule: Rule) -> (&'static str, &'static str) {
+    match rule {
+        Rule::BrokenLink => ("broken link", "broken links"),
+        Rule::MissingFragment => ("missing fragment", "missing fragments"),
+        Rule::LocalServerLink => ("local server link", "local server links"),
+        Rule::EscapingLink => ("escaping link", "escaping links"),
+        Rule::NoOutput => ("source without output", "sources without output"),
+        Rule::DroppedLink => ("dropped link", "dropped links"),
+        Rule::ExtraLink => ("extra link", "extra links"),
+        Rule::ReadError => ("read error", "read errors"),
+        Rule::FileTooLarge => ("file too large", "files too large"),
+        Rule::DuplicateId => ("duplicate id", "duplicate ids"),
+        Rule::BrokenIdReference => ("broken id reference", "broken id references"),
+        Rule::MissingFromToc => (
+            "heading missing from its TOC",
+            "headings missing from their TOC",
+        ),
+        Rule::DuplicateContent => ("duplicate document", "duplicate documents"),
+        Rule::RedirectChain => ("redirect chain", "redirect chains"),
+        Rule::IncompleteSrcset => ("incomplete srcset", "incomplete srcsets"),
+        Rule::BrokenSitemapUrl => ("broken sitemap URL", "broken sitemap URLs"),
+        Rule::MissingFromSitemap => (
+            "document missing from the sitemap",
+            "documents missing from the sitemap",
+        ),
+        Rule::BrokenFeedLink => ("broken feed link", "broken feed links"),
+        Rule::BrokenDataLink => ("broken data file link", "broken data file links"),
+        Rule::MalformedContactLink => ("malformed contact link", "malformed contact links"),
+        Rule::NonPortableLink => ("non-portable link", "non-portable links"),
+        Rule::MissingReturnLink => ("missing return link", "missing return links"),
+        Rule::SourceFileLink => ("link to a source file", "links to source files"),
+        Rule::BrokenExternalLink => ("broken external link", "broken external links"),
+        Rule::BrokenNofollowLink => ("broken nofollow link", "broken nofollow links"),
+        Rule::MissingExternalFragment => {
+            ("missing external fragment", "missing external fragments")
+        }
+        Rule::QuestionableExternalLink => {
+            ("questionable external link", "questionable external links")
+        }
+        Rule::EnvironmentLink => ("environment link", "environment links"),
+        Rule::EmptyLink => ("empty link", "empty links"),
+        Rule::HashLink => ("hash link", "hash links"),
+        Rule::JavascriptLink => ("JavaScript link", "JavaScript links"),
+        Rule::UnpublishedUrl => ("unpublished URL", "unpublished URLs"),
+        Rule::VanishedTarget => ("vanished target", "vanished targets"),
+    }
+}
+
+/// A number with its thousands separated by commas, like `1,234`
+fn thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut separated = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            separated.push(',');
+        }
+        separated.push(digit);
+    }
+    separated
+}
+
+fn count(n: usize, (singular, plural): (&str, &str)) -> String {
+    format!(
+        "{} {}",
+        thousands(n),
+        if n == 1 { singular } else { plural }
+    )
+}
+
+/// The line rendered for a report with nothing in it, like `✓ 1,234 links OK`
+fn success(report: &Report, paint: &Paint) -> String {
+    let line = match report.checked_links {
+        Some(n) => format!("✓ {} OK", count(n, ("link", "links"))),
+        None => "✓ No broken links found".to_string(),
+    };
+    format!("{}\n", paint.paint(GREEN, &line))
+}
+
+/// What is wrong, without the document it's wrong in
+fn describe(failure: &Failure, paint: &Paint) -> String {
+    match failure {
+        Failure::BrokenLink {
+            href,
+            link,
+            attribute,
+            text,
+            ..
+        } => format!(
+            "broken {}{} {}: file not found",
+            match link.base {
+                LinkBase::Root => "root-relative ",
+                LinkBase::Document => "",
+            },
+            LinkAttribute::noun(*attribute),
+            with_text(text.as_deref(), paint.paint(RED, href))
+        ),
+        Failure::MissingFragment {
+            href,
+            link,
+            similar_ids,
+            text,
+            ..
+        } => {
+            let (path, fragment) = href.split_once('#').unwrap_or((href, ""));
+            let href = format!("{path}{}", paint.paint(YELLOW, &format!("#{fragment}")));
+            format!(
+                "missing fragment {}: {}",
+                with_text(text.as_deref(), href),
+                fragment_not_found(link, similar_ids)
+            )
+        }
+        Failure::LocalServerLink { href, .. } => format!(
+            "local server link {}, use a relative or production URL instead",
+            paint.paint(RED, href)
+        ),
+        Failure::EscapingLink { href, .. } => format!(
+            "link {} escapes the scanned directory",
+            paint.paint(RED, href)
+        ),
+        Failure::NoOutput { output, .. } => format!("no output {}", output.display()),
+        Failure::DroppedLink { output, href, .. } => format!(
+            "link {} is missing from output {}",
+            paint.paint(RED, href),
+            output.display()
+        ),
+        Failure::ExtraLink { output, href, .. } => format!(
+            "link {} in output {} is missing from the source",
+            paint.paint(RED, href),
+            output.display()
+        ),
+        Failure::ReadError { message, .. } => format!("could not read: {message}"),
+        Failure::FileTooLarge { message, .. } => format!("skipped, {message}"),
+        Failure::DuplicateId { id, count, .. } => {
+            format!("id {} appears {count} times", paint.paint(YELLOW, id))
+        }
+        Failure::BrokenIdReference { attribute, id, .. } => {
+            format!(
+                "{attribute} id {} is not in the document",
+                paint.paint(RED, id)
+            )
+        }
+        Failure::MissingFromToc { id, .. } => format!(
+            "heading {} is missing from the table of contents",
+            paint.paint(YELLOW, id)
+        ),
+        Failure::DuplicateContent { original, .. } => {
+            format!("identical copy of {}", original.display())
+        }
+        Failure::RedirectChain { hops, circular, .. } => {
+            let hops: Vec<_> = hops.iter().map(|hop| hop.display().to_string()).collect();
+            match circular {
+                true => format!(
+                    "redirects in a loop: {}",
+                    paint.paint(RED, &hops.join(" → "))
+                ),
+                false => format!(
+                    "redirects {} times: {}",
+                    hops.len(),
+                    paint.paint(YELLOW, &hops.join(" → "))
+                ),
+            }
+        }
+        Failure::IncompleteSrcset {
+            srcset,
+            variant,
+            href: None,
+            ..
+        } => format!(
+            "srcset {srcset:?} has no {} variant",
+            paint.paint(RED, variant)
+        ),
+        Failure::IncompleteSrcset {
+            variant,
+            href: Some(href),
+            ..
+        } => format!(
+            "the {variant} variant {} does not exist",
+            paint.paint(RED, href)
+        ),
+        Failure::BrokenSitemapUrl { url, .. } => {
+            format!(
+                "sitemap URL {} does not lead to a file",
+                paint.paint(RED, url)
+            )
+        }
+        Failure::MissingFromSitemap { .. } => "missing from the sitemap".to_string(),
+        Failure::BrokenFeedLink { url, .. } => {
+            format!(
+                "feed link {} does not lead to a file",
+                paint.paint(RED, url)
+            )
+        }
+        Failure::BrokenDataLink { href, .. } => {
+            format!(
+                "data file link {} does not lead to a file",
+                paint.paint(RED, href)
+            )
+        }
+        Failure::MalformedContactLink { href, problem, .. } => format!(
+            "malformed contact link {} ({problem})",
+            paint.paint(RED, href)
+        ),
+        Failure::NonPortableLink { href, problem, .. } => format!(
+            "non-portable link {} ({problem})",
+            paint.paint(YELLOW, href)
+        ),
+        Failure::MissingReturnLink { href, hreflang, .. } => format!(
+            "alternate {} ({hreflang}) does not link back",
+            paint.paint(RED, href)
+        ),
+        Failure::SourceFileLink {
+            href,
+            rendered: Some(rendered),
+            ..
+        } => format!(
+            "link {} leads to a source file, link to {rendered} instead",
+            paint.paint(RED, href)
+        ),
+        Failure::SourceFileLink {
+            href,
+            rendered: None,
+            ..
+        } => format!(
+            "link {} leads to a source file rather than a rendered page",
+            paint.paint(RED, href)
+        ),
+        Failure::BrokenExternalLink { href, error, .. } => {
+            format!("broken external link {} ({error})", paint.paint(RED, href))
+        }
+        Failure::BrokenNofollowLink { href, error, .. } => {
+            format!("broken nofollow link {} ({error})", paint.paint(RED, href))
+        }
+        Failure::MissingExternalFragment { href, .. } => format!(
+            "external link {} leads to a page without its fragment",
+            paint.paint(RED, href)
+        ),
+        Failure::QuestionableExternalLink { href, error, .. } => format!(
+            "questionable external link {} ({error})",
+            paint.paint(YELLOW, href)
+        ),
+        Failure::EnvironmentLink { href, problem, .. } => {
+            format!("environment link {} ({problem})", paint.paint(RED, href))
+        }
+        Failure::EmptyLink { .. } => format!(
+            "empty link {}, which reloads the page",
+            paint.paint(YELLOW, "href=\"\"")
+        ),
+        Failure::HashLink { href, .. } => {
+            format!(
+                "placeholder link {}, which leads nowhere",
+                paint.paint(YELLOW, href)
+            )
+        }
+        Failure::JavascriptLink { href, .. } => {
+            format!(
+                "placeholder link {}, which does nothing",
+                paint.paint(YELLOW, href)
+            )
+        }
+        Failure::UnpublishedUrl { url, redirect, .. } => {
+            let url = paint.paint(RED, url);
+            match redirect {
+                Some(redirect) => format!(
+                    "published URL {url} no longer leads to a file, redirect it with {redirect}"
+                ),
+                None => format!("published URL {url} no longer leads to a file"),
+            }
+        }
+        Failure::VanishedTarget {
+            fragment,
+            linked_from,
+            ..
+        } => {
+            let gone = match fragment {
+                Some(fragment) => format!("#{}", paint.paint(RED, fragment)),
+                None => paint.paint(RED, "the file"),
+            };
+            let mut sources: Vec<_> = (linked_from.iter().take(MAX_LISTED_SOURCES))
+                .map(|source| source.display().to_string())
+                .collect();
+            if linked_from.len() > MAX_LISTED_SOURCES {
+                sources.push(format!("{} more", linked_from.len() - MAX_LISTED_SOURCES));
+            }
+            match sources.is_empty() {
+                true => format!("{gone} is gone from the new build"),
+                false => format!(
+                    "{gone} is gone from the new build, linked from {}",
+                    sources.join(", ")
+                ),
+            }
+        }
+    }
+}
+
+/// What is wrong, without color, for the interactive triage and editors to show
+pub fn describe_plain(failure: &Failure) -> String {
+    describe(failure, &Paint(false))
+}
+
+/**
+The line counting each kind of failure in a report, like
+`3 files, 7 broken links, 2 missing fragments`, or nothing for a report without any.
+*/
+pub fn summary(report: &Report) -> Option<String> {
+    let all = || report.failures.iter().chain(report.warnings.iter());
+    let sources: BTreeSet<&Path> = all().map(Failure::source).collect();
+    if sources.is_empty() {
+        return None;
+    }
+    let mut by_rule: BTreeMap<Rule, usize> = BTreeMap::new();
+    for failure in report.failures.iter() {
+        *by_rule.entry(failure.rule()).or_default() += 1;
+    }
+    let mut summary = vec![count(sources.len(), ("file", "files"))];
+    summary.extend(by_rule.iter().map(|(rule, n)| count(*n, noun(*rule))));
+    if !report.warnings.is_empty() {
+        summary.push(count(report.warnings.len(), ("warning", "warnings")));
+    }
+    Some(summary.join(", "))
+}
+
+/**
+Render the `--stats` of a run, like:
+
+```text
+12 documents, 340 internal links to 85 targets, 20 external links
+failures: 3 broken links, 1 missing fragment
+warnings: none
+parse 120ms, check 30ms, external 0ms, total 160ms
+```
+*/
+pub fn render_stats(stats: &Stats) -> String {
+    let by_rule = |counts: &BTreeMap<Rule, usize>| match counts.is_empty() {
+        true => "none".to_string(),
+        false => counts
+            .iter()
+            .map(|(rule, n)| count(*n, noun(*rule)))
+            .collect::<Vec<_>>()
+            .join(", "),
+    };
+    let timings = &stats.timings;
+    format!(
+        "{}, {} to {}, {}\nfailures: {}\nwarnings: {}\n\
+        parse {}ms, check {}ms, external {}ms, total {}ms\n\
+        {} interned, {} kept, {} saved\n",
+        count(stats.documents, ("document", "documents")),
+        count(stats.internal_links, ("internal link", "internal links")),
+        count(stats.unique_targets, ("target", "targets")),
+        count(stats.external_links, ("external link", "external links")),
+        by_rule(&stats.failures),
+        by_rule(&stats.warnings),
+        timings.parse_ms,
+        timings.check_ms,
+        timings.external_ms,
+        timings.total_ms,
+        count(stats.interning.strings, ("string", "strings")),
+        count(stats.interning.kept_bytes, ("byte", "bytes")),
+        count(stats.interning.saved_bytes(), ("byte", "bytes")),
+    )
+}
+
+/**
+How the text report groups failures.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum GroupBy {
+    /// Under the document each failure was found in
+    #[default]
+    Source,
+    /// Under where each broken link leads, once for all the documents linking there. Failures
+    /// which aren't about a link are still grouped by document
+    Target,
+}
+
+/// How many of the documents linking to a target are listed when grouping by target
+const MAX_LISTED_SOURCES: usize = 10;
+
+/**
+Render a report for people to read in a terminal.
+
+Failures and warnings are grouped as `group_by` says, followed by a summary line counting each
+kind, like `3 files, 7 broken links, 2 missing fragments`. A report without any is a single
+line saying so, like `✓ 1,234 links OK`.
+ */
+pub fn render(report: &Report, color: bool, group_by: GroupBy) -> String {
+    let paint = Paint(color);
+    let Some(summary) = summary(report) else {
+        return success(report, &paint);
+    };
+    let mut by_target: BTreeMap<(String, Rule, bool), BTreeSet<&Path>> = BTreeMap::new();
+    let mut by_source: BTreeMap<&Path, Vec<(&Failure, bool)>> = BTreeMap::new();
+    let failures = report.failures.iter().map(|failure| (failure, false));
+    let warnings = report.warnings.iter().map(|warning| (warning, true));
+    for (failure, warning) in failures.chain(warnings) {
+        match (group_by, failure.target()) {
+            (GroupBy::Target, Some(target)) => {
+                by_target
+                    .entry((target, failure.rule(), warning))
+                    .or_default()
+                    .insert(failure.source());
+            }
+            _ => by_source
+                .entry(failure.source())
+                .or_default()
+                .push((failure, warning)),
+        }
+    }
+
+    let mut text = String::new();
+    for ((target, rule, warning), sources) in by_target.iter() {
+        let _ = writeln!(text, "{}", paint.paint(BOLD, target));
+        let prefix = if *warning {
+            paint.paint(YELLOW, "warning: ")
+        } else {
+            String::new()
+        };
+        let (noun, _) = noun(*rule);
+        let linked_from = count(sources.len(), ("file", "files"));
+        let _ = writeln!(text, "  {prefix}{noun} from {linked_from}:");
+        for source in sources.iter().take(MAX_LISTED_SOURCES) {
+            let _ = writeln!(text, "    {}", source.to_string_lossy());
+        }
+        if sources.len() > MAX_LISTED_SOURCES {
+            let _ = writeln!(text, "    and {} more", sources.len() - MAX_LISTED_SOURCES);
+        }
+    }
+    for (source, failures) in by_source.iter() {
+        let _ = writeln!(text, "{}", paint.paint(BOLD, &source.to_string_lossy()));
+        for (failure, warning) in failures {
+            let prefix = if *warning {
+                paint.paint(YELLOW, "warning: ")
+            } else {
+                String::new()
+            };
+            let _ = writeln!(text, "  {prefix}{}", describe(failure, &paint));
+        }
+    }
+    let _ = writeln!(text, "{summary}");
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tree::HtmlFileLink;
+
+    fn report() -> Report {
+        let mut report = Report::new(vec![
+            Failure::BrokenLink {
+                source: "index.html".into(),
+                href: "gone.html".into(),
+                link: HtmlFileLink::new("gone.html"),
+                attribute: None,
+                text: None,
+            },
+            Failure::MissingFragment {
+                source: "index.html".into(),
+                href: "guide.html#setup".into(),
+                link: HtmlFileLink::new("guide.html#setup"),
+                similar_ids: vec!["set-up".into(), "setup-linux".into()],
+                attribute: None,
+                text: None,
+            },
+            Failure::BrokenLink {
+                source: "guide.html".into(),
+                href: "../up.html".into(),
+                link: HtmlFileLink::new("up.html"),
+                attribute: None,
+                text: Some("Up".into()),
+            },
+        ]);
+        report.warnings.push(Failure::DuplicateId {
+            source: "guide.html".into(),
+            id: "top".into(),
+            count: 2,
+        });
+        report
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            render(&report(), false, GroupBy::Source),
+            "\
+guide.html
+  broken link \"Up\" -> ../up.html: file not found
+  warning: id top appears 2 times
+index.html
+  broken link gone.html: file not found
+  missing fragment guide.html#setup: fragment \"setup\" not found in guide.html, did you mean \"set-up\" or \"setup-linux\"?
+2 files, 2 broken links, 1 missing fragment, 1 warning
+"
+        );
+        assert_eq!(
+            render(&Report::default(), false, GroupBy::Source),
+            "✓ No broken links found\n"
+        );
+        let passed = Report {
+            checked_links: Some(1234),
+            ..Report::default()
+        };
+        assert_eq!(
+            render(&passed, true, GroupBy::Source),
+            "\x1b[32m✓ 1,234 links OK\x1b[0m\n"
+        );
+        assert_eq!(summary(&Report::default()), None);
+        assert_eq!(thousands(999), "999");
+        assert_eq!(thousands(1_000_000), "1,000,000");
+    }
+
+    #[test]
+    fn test_render_by_target() {
+        let mut report = report();
+        report.failures.extend((0..12).map(|i| Failure::BrokenLink {
+            source: format!("posts/{i:02}.html").into(),
+            href: "../gone.html".into(),
+            link: HtmlFileLink::new("gone.html"),
+            attribute: None,
+            text: None,
+        }));
+        assert_eq!(
+            render(&report, false, GroupBy::Target),
+            "\
+gone.html
+  broken link from 13 files:
+    index.html
+    posts/00.html
+    posts/01.html
+    posts/02.html
+    posts/03.html
+    posts/04.html
+    posts/05.html
+    posts/06.html
+    posts/07.html
+    posts/08.html
+    and 3 more
+guide.html#setup
+  missing fragment from 1 file:
+    index.html
+up.html
+  broken link from 1 file:
+    guide.html
+guide.html
+  warning: id top appears 2 times
+14 files, 14 broken links, 1 missing fragment, 1 warning
+"
+        );
+    }
+
+    #[test]
+    fn test_render_stats() {
+        let report = report();
+        let links = crate::stats::LinkCounts {
+            internal: 5,
+            external: 1,
+            targets: [HtmlFileLink::new("guide.html")].into_iter().collect(),
+            ..Default::default()
+        };
+        let interning = crate::intern::InternerStats {
+            strings: 2,
+            kept_bytes: 1500,
+            interned_bytes: 4000,
+        };
+        let stats = Stats::new(2, &links, &report, Default::default(), interning);
+        assert_eq!(
+            render_stats(&stats),
+            "\
+2 documents, 5 internal links to 1 target, 1 external link
+failures: 2 broken links, 1 missing fragment
+warnings: 1 duplicate id
+parse 0ms, check 0ms, external 0ms, total 0ms
+2 strings interned, 1,500 bytes kept, 2,500 bytes saved
+"
+        );
+    }
+
+    #[test]
+    fn test_render_color() {
+        let text = render(&report(), true, GroupBy::Source);
+        assert!(text.contains("\x1b[1mindex.html\x1b[0m\n"));
+        assert!(text.contains("broken link \x1b[31mgone.html\x1b[0m: file not found\n"));
+        assert!(text.contains("missing fragment guide.html\x1b[33m#setup\x1b[0m: fragment"));
+    }
+}
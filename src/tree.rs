@@ -1,118 +1,1815 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
 use std::collections::HashMap;
-use std::ffi::OsStr;
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::HashSet;
 use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, LazyLock, Mutex};
 
+use clap::ValueEnum;
 use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use url::Url;
+#[cfg(not(target_arch = "wasm32"))]
 use walkdir::WalkDir;
 
-use crate::html::HtmlInfo;
+use crate::charset::decode_html;
+use crate::contact::check_contact_link;
+use crate::external::{is_loopback_url, is_web_url};
+use crate::filter::FileFilter;
+use crate::html::{is_svg, parse_srcset, parse_svg_ids, HtmlInfo, Ids, LinkAttribute, Placeholder};
+use crate::intern::Interned;
+use crate::portability::check_href;
+use crate::progress::Progress;
+use crate::provider::FileProvider;
+use crate::report::Failure;
+use crate::site_path::{site_key, SitePath};
+use crate::stats::LinkCounts;
+
+/**
+What the path of a link is relative to.
+*/
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinkBase {
+    /// The document the link is in, or the scanned directory once resolved, like `guide.html`
+    #[default]
+    Document,
+    /// The root of the site, for root-relative links like `/guide.html`
+    Root,
+}
+
+impl LinkBase {
+    pub fn is_document(&self) -> bool {
+        *self == LinkBase::Document
+    }
+}
 
 /**
 A link to an HTML file, with optional fragment.
+
+The path never starts with a slash: a root-relative link has its `base` set to `Root` instead.
 */
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema)]
 pub struct HtmlFileLink {
     pub path: PathBuf,
     pub fragment: Option<String>,
+    #[serde(default, skip_serializing_if = "LinkBase::is_document")]
+    pub base: LinkBase,
 }
 
+/// Splits an href into its path and `#fragment`. Hrefs can span lines, so `.` has to match
+/// newlines too
+static LINK_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new("(?s)^(.*?)(?:#([^#]*))?$").unwrap());
+
 impl HtmlFileLink {
     pub fn new<P: AsRef<Path>>(path: P) -> HtmlFileLink {
         let path = path.as_ref().to_str().expect("Invalid path");
-        let pattern = Regex::new("^(.*?)(?:#([^#]*))?$").unwrap();
-        if let Some(captures) = pattern.captures(path) {
-            let path = PathBuf::from(captures.get(1).unwrap().as_str());
+        if let Some(captures) = LINK_PATTERN.captures(path) {
+            let path = captures.get(1).unwrap().as_str();
+            let base = match path.starts_with(['/', '\\']) {
+                true => LinkBase::Root,
+                false => LinkBase::Document,
+            };
+            let path = PathBuf::from(path.trim_start_matches(['/', '\\']));
             let fragment = captures.get(2).map(|m| m.as_str());
             let fragment = fragment.filter(|s| !s.is_empty()).map(|s| s.to_string());
-            return HtmlFileLink { path, fragment };
+            return HtmlFileLink {
+                path,
+                fragment,
+                base,
+            };
         }
         panic!("Failed to parse path {path:?}")
     }
 }
 
-#[derive(Debug)]
-pub struct HtmlFiles(HashMap<PathBuf, HtmlInfo>);
-impl HtmlFiles {
-    pub fn new(directories: &[PathBuf]) -> std::io::Result<HtmlFiles> {
-        let mut map = HashMap::new();
-        for directory in directories {
-            for result in WalkDir::new(directory) {
-                let entry = result?;
-                let path = entry
-                    .path()
-                    .strip_prefix(directory)
-                    .expect("can't strip the prefix");
-                if path.extension() == Some(OsStr::new("html")) {
-                    let info = HtmlInfo::parse_file(entry.path())?;
-                    map.insert(PathBuf::from(path), info);
+/**
+A link in a scanned document which could not be resolved.
+*/
+#[derive(Debug, PartialEq, Eq)]
+pub struct BrokenLink {
+    /// The document containing the link, relative to the scanned directory
+    pub source: PathBuf,
+    /// The `href` as it was written in the document
+    pub href: String,
+    /// The link after resolving it against the document's location
+    pub link: HtmlFileLink,
+    /// Whether the linked document was found, but without the `#fragment`
+    pub missing_fragment: bool,
+    /// The ids in the linked document most like the missing `#fragment`, best first
+    pub similar_ids: Vec<String>,
+    /// The attribute the link was found in, if it isn't an `href`
+    pub attribute: Option<LinkAttribute>,
+    /// The text of the link, if it's an `<a>` with any
+    pub text: Option<String>,
+}
+
+/**
+A link in a scanned document to a server on the local machine.
+*/
+#[derive(Debug, PartialEq, Eq)]
+pub struct LoopbackLink {
+    /// The document containing the link, relative to the scanned directory
+    pub source: PathBuf,
+    pub href: String,
+}
+
+/**
+A link in a scanned document which climbs out of the scanned directory with `..`.
+*/
+#[derive(Debug, PartialEq, Eq)]
+pub struct EscapingLink {
+    /// The document containing the link, relative to the scanned directory
+    pub source: PathBuf,
+    pub href: String,
+}
+
+/**
+A link in a scanned document to a web page on another site.
+*/
+#[derive(Debug, PartialEq, Eq)]
+pub struct ExternalLink {
+    /// The document containing the link, relative to the scanned directory
+    pub source: PathBuf,
+    pub href: String,
+    /// Whether the link is marked `rel="nofollow"`
+    pub nofollow: bool,
+}
+
+/**
+A file or directory which couldn't be read while walking the scanned directories.
+*/
+#[derive(Debug, PartialEq, Eq)]
+pub struct ReadError {
+    /// The unreadable path, relative to the scanned directory where possible
+    pub path: PathBuf,
+    pub message: String,
+    /// Whether the file was skipped for being larger than the filter's `max_file_size`
+    pub too_large: bool,
+}
+
+impl ReadError {
+    pub fn new(path: PathBuf, err: &std::io::Error) -> ReadError {
+        ReadError {
+            path,
+            message: err.to_string(),
+            too_large: err.kind() == std::io::ErrorKind::FileTooLarge,
+        }
+    }
+    /**
+    Record a path which couldn't be read in `errors`, or without them return the error, which
+    stops the walk. Files skipped for their size never stop it, since one huge file is no reason
+    to give up on the rest, so without `errors` they are only warned about on stderr.
+    */
+    pub fn record(
+        path: PathBuf,
+        err: std::io::Error,
+        errors: Option<&mut Vec<ReadError>>,
+    ) -> std::io::Result<()> {
+        match errors {
+            Some(errors) => errors.push(ReadError::new(path, &err)),
+            None if err.kind() == std::io::ErrorKind::FileTooLarge => {
+                eprintln!("{}", Failure::from(ReadError::new(path, &err)))
+            }
+            None => return Err(err),
+        }
+        Ok(())
+    }
+}
+
+/// A path relative to the scanned directory, paired with something that happened to it
+type Walked<T> = Result<(PathBuf, T), (PathBuf, std::io::Error)>;
+
+/**
+List the files of a provider which pass the filter, sending each document to `send` until it
+returns false, which stops the listing and is returned. Other files are added to `other_files`,
+with the ids in them if they're SVG images.
+*/
+fn list_documents<'a, P: FileProvider>(
+    provider: &'a P,
+    filter: &FileFilter,
+    progress: &Progress,
+    other_files: &mut Vec<(PathBuf, Ids)>,
+    mut send: impl FnMut(Walked<(&'a P, PathBuf)>) -> bool,
+) -> bool {
+    let prefix = filter.url_prefix(provider.root());
+    let mut stopped = false;
+    provider.list(filter, &mut |file| {
+        let document = match file {
+            Ok(path) if !filter.has_extension(&path) => {
+                let ids = match is_svg(&path) {
+                    true => provider
+                        .read_to_string(&path, filter.max_file_size)
+                        .map(|contents| parse_svg_ids(&contents, &filter.parse))
+                        .unwrap_or_default(),
+                    false => Ids::default(),
+                };
+                other_files.push((site_key(prefix, path), ids));
+                return true;
+            }
+            Ok(path) if !filter.is_document(&path) => return true,
+            Ok(path) => Ok((site_key(prefix, &path), (provider, path))),
+            // The provider itself can't be read
+            Err((path, err)) if path == provider.root() => Err((path, err)),
+            Err((path, err)) => Err((site_key(prefix, path), err)),
+        };
+        progress.discovered();
+        stopped = !send(document);
+        !stopped
+    });
+    stopped
+}
+
+/// Read and parse a listed document
+fn parse_document<P: FileProvider>(
+    filter: &FileFilter,
+    document: Walked<(&P, PathBuf)>,
+) -> Walked<HtmlInfo> {
+    document.and_then(|(path, (provider, file_path))| {
+        match provider
+            .read(&file_path, filter.max_file_size)
+            .and_then(|contents| decode_html(&contents))
+        {
+            Ok(contents) => Ok((path, HtmlInfo::parse_with(&contents, &filter.parse))),
+            Err(err) => Err((path, err)),
+        }
+    })
+}
+
+/// Told about each document once it's parsed, with its path, as `Hooks::on_file_parsed` is
+pub type FileParsedHook<'a> = Box<dyn Fn(&Path, &HtmlInfo) + Sync + 'a>;
+
+/// Told whether each link of a document was found, as `Hooks::on_link_checked` is
+pub type LinkCheckedHook<'a> = Box<dyn Fn(&Path, &str, bool) + Sync + 'a>;
+
+/// Tells whether a link of a document is kept at all, as `Hooks::link_filter` does
+pub type LinkFilter<'a> = Box<dyn Fn(&Path, &str) -> bool + Sync + 'a>;
+
+/**
+Closures an integrator can hook into a check, to apply rules of their own without forking the
+crate, like that every image lives under `/assets`. Links are checked on several threads, so
+the hooks have to be `Sync`.
+*/
+#[derive(Default)]
+pub struct Hooks<'a> {
+    /// Called with each document as it's parsed, once its links are filtered
+    pub on_file_parsed: Option<FileParsedHook<'a>>,
+    /// Called with each link of a document which is looked for, and whether it was found
+    pub on_link_checked: Option<LinkCheckedHook<'a>>,
+    /// Links of a document for which this returns false are left out as soon as it's parsed,
+    /// so no rule checks or reports them
+    pub link_filter: Option<LinkFilter<'a>>,
+}
+
+impl Hooks<'_> {
+    /// Leave out the links of a parsed document which the filter doesn't keep, then pass it on
+    pub fn parsed(&self, path: &Path, info: &mut HtmlInfo) {
+        if let Some(link_filter) = &self.link_filter {
+            let keep = |href: &str| link_filter(path, href);
+            info.relative_hrefs.retain(|href| keep(href));
+            info.external_hrefs.retain(|href| keep(href));
+            info.nofollow_hrefs.retain(|href| keep(href));
+            info.placeholder_hrefs.retain(|href| keep(href));
+            info.link_attributes.retain(|href, _| keep(href));
+            info.link_texts.retain(|href, _| keep(href));
+            // A `srcset` goes as a whole, as its variants are only checked together
+            (info.srcsets).retain(|srcset| parse_srcset(srcset).iter().all(|(url, _)| keep(url)));
+            info.alternates.retain(|(_, href)| keep(href));
+            info.redirect = info.redirect.take().filter(|href| keep(href));
+        }
+        if let Some(on_file_parsed) = &self.on_file_parsed {
+            on_file_parsed(path, info);
+        }
+    }
+}
+
+/**
+Parse every HTML document of the providers that passes the filter, passing each to `visit`
+along with its path relative to the directory or archive it was found in, under the URL
+prefix of the provider if it's mounted.
+
+Documents are parsed by `jobs` threads while the providers are listed. The queues between
+listing, parsing and visiting are bounded, so no stage can run far ahead of the others. With a
+single job, documents are parsed as they're listed on the calling thread instead, which is the
+only way to walk them where threads can't be spawned, like in wasm32.
+
+Files and directories which can't be read are recorded in `errors` and skipped. Without
+`errors`, the first one aborts the walk instead. So are files larger than the filter's
+`max_file_size`, though they never abort the walk, and documents skipped for their size are
+returned with the other files.
+
+Documents are counted on `progress` as they are found and as they are visited.
+
+Returns the other files found, which don't have a document extension, so that links to them
+can be checked too. Each comes with the ids in it if it's an SVG image, or none if it isn't or
+can't be read.
+*/
+pub fn walk_documents<P: FileProvider, F: FnMut(PathBuf, HtmlInfo)>(
+    providers: &[P],
+    filter: &FileFilter,
+    jobs: usize,
+    progress: &Progress,
+    errors: Option<&mut Vec<ReadError>>,
+    visit: F,
+) -> std::io::Result<Vec<(PathBuf, Ids)>> {
+    let hooks = Hooks::default();
+    walk_documents_with(providers, filter, jobs, progress, errors, &hooks, visit)
+}
+
+/// Walk the documents as `walk_documents` does, passing each one parsed through the `hooks`
+pub fn walk_documents_with<P: FileProvider, F: FnMut(PathBuf, HtmlInfo)>(
+    providers: &[P],
+    filter: &FileFilter,
+    jobs: usize,
+    progress: &Progress,
+    mut errors: Option<&mut Vec<ReadError>>,
+    hooks: &Hooks,
+    mut visit: F,
+) -> std::io::Result<Vec<(PathBuf, Ids)>> {
+    let mut too_large = vec![];
+    let mut visit_parsed = |result: Walked<HtmlInfo>| -> std::io::Result<()> {
+        match result {
+            Ok((path, mut info)) => {
+                progress.parsed(&path);
+                hooks.parsed(&path, &mut info);
+                visit(path, info);
+                Ok(())
+            }
+            Err((path, err)) => {
+                // Skipped documents still exist, so links to them aren't broken
+                if err.kind() == std::io::ErrorKind::FileTooLarge {
+                    too_large.push((path.clone(), Ids::default()));
+                }
+                ReadError::record(path, err, errors.as_deref_mut())
+            }
+        }
+    };
+    let mut other_files = vec![];
+    if jobs <= 1 {
+        for provider in providers {
+            let mut failed = Ok(());
+            list_documents(provider, filter, progress, &mut other_files, |document| {
+                failed = visit_parsed(parse_document(filter, document));
+                failed.is_ok()
+            });
+            failed?;
+        }
+    } else {
+        let (path_tx, path_rx) = mpsc::sync_channel::<Walked<(&P, PathBuf)>>(jobs * 2);
+        let (info_tx, info_rx) = mpsc::sync_channel::<Walked<HtmlInfo>>(jobs * 2);
+        // Shared by the parsing threads, and dropped once they have all stopped so the walk
+        // stops too
+        let path_rx = Arc::new(Mutex::new(path_rx));
+        other_files = std::thread::scope(|scope| {
+            let walker = scope.spawn(move || {
+                let mut other_files = vec![];
+                for provider in providers {
+                    let send = |document| path_tx.send(document).is_ok();
+                    if list_documents(provider, filter, progress, &mut other_files, send) {
+                        break;
+                    }
+                }
+                other_files
+            });
+            for _ in 0..jobs {
+                let path_rx = path_rx.clone();
+                let info_tx = info_tx.clone();
+                scope.spawn(move || loop {
+                    let Ok(document) = path_rx.lock().unwrap().recv() else {
+                        return;
+                    };
+                    if info_tx.send(parse_document(filter, document)).is_err() {
+                        return;
+                    }
+                });
+            }
+            drop(path_rx);
+            drop(info_tx);
+            for result in info_rx {
+                visit_parsed(result)?;
+            }
+            Ok::<_, std::io::Error>(walker.join().expect("The walker thread panicked"))
+        })?;
+    }
+    other_files.extend(too_large);
+    Ok(other_files)
+}
+
+/**
+The links in a document which `contains` can't find, unless the document is skipped. The
+`hooks` are told whether each link was found.
+*/
+pub fn document_broken_links<F: Fn(&HtmlFileLink) -> bool>(
+    file_path: &Path,
+    info: &HtmlInfo,
+    hooks: &Hooks,
+    contains: F,
+) -> Vec<BrokenLink> {
+    if info.directives.skip {
+        return vec![];
+    }
+    // Links which escape the scanned directory are reported as such, rather than resolving them
+    // to wherever normalizing leaves them
+    resolved_links(file_path, info)
+        .filter(|(href, link)| {
+            let found = contains(link);
+            if let Some(on_link_checked) = &hooks.on_link_checked {
+                on_link_checked(file_path, href, found);
+            }
+            !found && !escapes(file_path, info, href)
+        })
+        .map(|(href, link)| BrokenLink {
+            source: file_path.to_path_buf(),
+            href: href.to_string(),
+            missing_fragment: link.fragment.is_some()
+                && contains(&HtmlFileLink {
+                    path: link.path.clone(),
+                    fragment: None,
+                    base: link.base,
+                }),
+            link,
+            similar_ids: vec![],
+            attribute: info.link_attributes.get(&**href).copied(),
+            text: info.link_texts.get(&**href).cloned(),
+        })
+        .collect()
+}
+
+/// Whether an `href` in a document climbs out of the scanned directory
+fn escapes(file_path: &Path, info: &HtmlInfo, href: &str) -> bool {
+    let link = HtmlFileLink::new(href);
+    match link.base {
+        LinkBase::Root => escapes_root(link.path),
+        LinkBase::Document => escapes_root(link_base(file_path, info).join(link.path)),
+    }
+}
+
+/**
+The links in a document which climb out of the scanned directory, unless the document is
+skipped.
+*/
+pub fn document_escaping_links(file_path: &Path, info: &HtmlInfo) -> Vec<EscapingLink> {
+    if info.directives.skip {
+        return vec![];
+    }
+    info.relative_hrefs
+        .iter()
+        .filter(|href| escapes(file_path, info, href))
+        .map(|href| EscapingLink {
+            source: file_path.to_path_buf(),
+            href: href.to_string(),
+        })
+        .collect()
+}
+
+/**
+The links in a document to local servers, unless the document is skipped.
+*/
+pub fn document_loopback_links(file_path: &Path, info: &HtmlInfo) -> Vec<LoopbackLink> {
+    if info.directives.skip {
+        return vec![];
+    }
+    info.external_hrefs
+        .iter()
+        .filter(|href| is_loopback_url(href))
+        .map(|href| LoopbackLink {
+            source: file_path.to_path_buf(),
+            href: href.to_string(),
+        })
+        .collect()
+}
+
+/**
+The http and https links in a document to other sites, unless the document is skipped. Links
+to local servers are left out, as they're reported already.
+*/
+pub fn document_external_links(file_path: &Path, info: &HtmlInfo) -> Vec<ExternalLink> {
+    if info.directives.skip {
+        return vec![];
+    }
+    info.external_hrefs
+        .iter()
+        .filter(|href| is_web_url(href) && !is_loopback_url(href))
+        .map(|href| ExternalLink {
+            source: file_path.to_path_buf(),
+            href: href.to_string(),
+            nofollow: info.nofollow_hrefs.contains(href),
+        })
+        .collect()
+}
+
+/**
+A `mailto:` or `tel:` link in a scanned document which is malformed.
+*/
+#[derive(Debug, PartialEq, Eq)]
+pub struct ContactLink {
+    /// The document containing the link, relative to the scanned directory
+    pub source: PathBuf,
+    pub href: String,
+    /// What is wrong with the link
+    pub problem: String,
+}
+
+/**
+The malformed `mailto:` and `tel:` links in a document, unless the document is skipped.
+*/
+pub fn document_contact_links(file_path: &Path, info: &HtmlInfo) -> Vec<ContactLink> {
+    if info.directives.skip {
+        return vec![];
+    }
+    info.external_hrefs
+        .iter()
+        .filter_map(|href| match check_contact_link(href)? {
+            Ok(()) => None,
+            Err(problem) => Some(ContactLink {
+                source: file_path.to_path_buf(),
+                href: href.to_string(),
+                problem,
+            }),
+        })
+        .collect()
+}
+
+/**
+An `<a>` in a scanned document which leads nowhere, like `href="#"`.
+*/
+#[derive(Debug, PartialEq, Eq)]
+pub struct PlaceholderLink {
+    /// The document containing the link, relative to the scanned directory
+    pub source: PathBuf,
+    pub href: String,
+    pub placeholder: Placeholder,
+}
+
+/**
+The placeholder links in a document, unless the document is skipped.
+*/
+pub fn document_placeholder_links(file_path: &Path, info: &HtmlInfo) -> Vec<PlaceholderLink> {
+    if info.directives.skip {
+        return vec![];
+    }
+    info.placeholder_hrefs
+        .iter()
+        .filter_map(|href| {
+            Some(PlaceholderLink {
+                source: file_path.to_path_buf(),
+                href: href.to_string(),
+                placeholder: Placeholder::of(href)?,
+            })
+        })
+        .collect()
+}
+
+/**
+A relative link in a scanned document which may not lead to the same file on every common
+filesystem, like one with a backslash or a name Windows doesn't allow.
+*/
+#[derive(Debug, PartialEq, Eq)]
+pub struct NonPortableLink {
+    /// The document containing the link, relative to the scanned directory
+    pub source: PathBuf,
+    pub href: String,
+    /// What is wrong with the link
+    pub problem: String,
+}
+
+/**
+The relative links in a document whose paths aren't portable, unless the document is skipped.
+*/
+pub fn document_non_portable_links(file_path: &Path, info: &HtmlInfo) -> Vec<NonPortableLink> {
+    if info.directives.skip {
+        return vec![];
+    }
+    info.relative_hrefs
+        .iter()
+        .filter_map(|href| {
+            Some(NonPortableLink {
+                source: file_path.to_path_buf(),
+                href: href.to_string(),
+                problem: check_href(href)?,
+            })
+        })
+        .collect()
+}
+
+/**
+An id which appears more than once in a scanned document, so links to it are ambiguous.
+*/
+#[derive(Debug, PartialEq, Eq)]
+pub struct DuplicateId {
+    /// The document containing the id, relative to the scanned directory
+    pub source: PathBuf,
+    pub id: String,
+    pub count: usize,
+}
+
+/**
+The ids which appear more than once in a document, unless the document is skipped.
+*/
+pub fn document_duplicate_ids(file_path: &Path, info: &HtmlInfo) -> Vec<DuplicateId> {
+    if info.directives.skip {
+        return vec![];
+    }
+    info.duplicate_ids()
+        .into_iter()
+        .map(|(id, count)| DuplicateId {
+            source: file_path.to_path_buf(),
+            id: id.to_string(),
+            count,
+        })
+        .collect()
+}
+
+/**
+A `<label for>`, `aria-labelledby`, `aria-describedby` or `headers` attribute referring to an id
+which isn't in the same document.
+*/
+#[derive(Debug, PartialEq, Eq)]
+pub struct BrokenIdReference {
+    /// The document containing the reference, relative to the scanned directory
+    pub source: PathBuf,
+    pub attribute: String,
+    pub id: String,
+}
+
+/**
+The id references of a document to ids it doesn't contain, unless the document is skipped.
+*/
+pub fn document_broken_id_references(file_path: &Path, info: &HtmlInfo) -> Vec<BrokenIdReference> {
+    if info.directives.skip {
+        return vec![];
+    }
+    info.id_references
+        .iter()
+        .filter(|(_, id)| !info.ids.contains(id))
+        .map(|(attribute, id)| BrokenIdReference {
+            source: file_path.to_path_buf(),
+            attribute: attribute.clone(),
+            id: id.clone(),
+        })
+        .collect()
+}
+
+/**
+A heading which a document's table of contents leaves out.
+*/
+#[derive(Debug, PartialEq, Eq)]
+pub struct TocOmission {
+    /// The document containing the heading, relative to the scanned directory
+    pub source: PathBuf,
+    pub id: String,
+}
+
+/**
+The headings left out of a document's table of contents, unless the document is skipped.
+*/
+pub fn document_toc_omissions(file_path: &Path, info: &HtmlInfo) -> Vec<TocOmission> {
+    if info.directives.skip {
+        return vec![];
+    }
+    info.headings_missing_from_toc
+        .iter()
+        .map(|id| TocOmission {
+            source: file_path.to_path_buf(),
+            id: id.clone(),
+        })
+        .collect()
+}
+
+/**
+The images of an `img` tag's `srcset`, resolved like links.
+*/
+#[derive(Debug, PartialEq, Eq)]
+pub struct Srcset {
+    /// The document containing the image, relative to the scanned directory
+    pub source: PathBuf,
+    /// The `srcset` as it was written in the document
+    pub srcset: String,
+    /// Each descriptor, like `2x`, with the image it was given, if that is a relative link
+    pub variants: Vec<(String, Option<HtmlFileLink>)>,
+}
+
+/**
+A variant which a `srcset` should provide, but doesn't.
+*/
+#[derive(Debug, PartialEq, Eq)]
+pub struct MissingVariant {
+    pub source: PathBuf,
+    pub srcset: String,
+    pub variant: String,
+    /// The image given for the variant, if the `srcset` names one but it doesn't exist
+    pub href: Option<String>,
+}
+
+impl Srcset {
+    /**
+    The `required` variants which aren't in the `srcset`, or whose image doesn't `exist`.
+
+    Images given by absolute URLs can't be looked for, so they are assumed to exist.
+    */
+    pub fn missing_variants<F: Fn(&HtmlFileLink) -> bool>(
+        &self,
+        required: &[String],
+        exists: F,
+    ) -> Vec<MissingVariant> {
+        let candidates = parse_srcset(&self.srcset);
+        required
+            .iter()
+            .filter_map(|variant| {
+                let href = match self.variants.iter().position(|(v, _)| v == variant) {
+                    None => None,
+                    Some(i) if self.variants[i].1.as_ref().is_none_or(&exists) => return None,
+                    Some(i) => Some(candidates[i].0.to_string()),
+                };
+                Some(MissingVariant {
+                    source: self.source.clone(),
+                    srcset: self.srcset.clone(),
+                    variant: variant.clone(),
+                    href,
+                })
+            })
+            .collect()
+    }
+}
+
+/**
+The `srcset`s of the images in a document, unless the document is skipped.
+*/
+pub fn document_srcsets(file_path: &Path, info: &HtmlInfo) -> Vec<Srcset> {
+    if info.directives.skip {
+        return vec![];
+    }
+    let base = link_base(file_path, info);
+    info.srcsets
+        .iter()
+        .map(|srcset| Srcset {
+            source: file_path.to_path_buf(),
+            srcset: srcset.clone(),
+            variants: parse_srcset(srcset)
+                .into_iter()
+                .map(|(href, descriptor)| {
+                    let relative = Url::parse(href) == Err(url::ParseError::RelativeUrlWithoutBase);
+                    let link = relative.then(|| resolve_document_link(file_path, &base, href));
+                    (descriptor.to_string(), link)
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/**
+A `<link rel="alternate" hreflang>` to a translation of a document, resolved like a link.
+*/
+#[derive(Debug, PartialEq, Eq)]
+pub struct Alternate {
+    /// The document linking to its translation, relative to the scanned directory
+    pub source: PathBuf,
+    pub hreflang: String,
+    /// The `href` as it was written in the document
+    pub href: String,
+    pub link: HtmlFileLink,
+}
+
+/**
+The relative links of a document to its translations, unless the document is skipped.
+
+Translations given by absolute URLs can't be looked for, so they are left out.
+*/
+pub fn document_alternates(file_path: &Path, info: &HtmlInfo) -> Vec<Alternate> {
+    if info.directives.skip {
+        return vec![];
+    }
+    let base = link_base(file_path, info);
+    info.alternates
+        .iter()
+        .filter(|(_, href)| Url::parse(href) == Err(url::ParseError::RelativeUrlWithoutBase))
+        .map(|(hreflang, href)| Alternate {
+            source: file_path.to_path_buf(),
+            hreflang: hreflang.clone(),
+            href: href.to_string(),
+            link: resolve_document_link(file_path, &base, href),
+        })
+        .collect()
+}
+
+/**
+The alternates whose translation doesn't link back to the document linking to it, so the two
+don't agree on being translations of each other.
+
+Links to the document itself are fine, while translations which don't `exist` are reported as
+broken links already, so neither is reported. Return links given by absolute URLs can't be
+followed, so a translation with only those is reported.
+*/
+pub fn missing_return_links<F: Fn(&HtmlFileLink) -> bool>(
+    alternates: Vec<Alternate>,
+    probing: &Probing,
+    exists: F,
+) -> Vec<Alternate> {
+    let mut translations: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+    for alternate in alternates.iter() {
+        translations.entry(alternate.source.clone()).or_default();
+    }
+    // Resolve each link to the document it leads to, if that has translations of its own
+    let document = |link: &HtmlFileLink| {
+        probing
+            .candidates(&link.path)
+            .into_iter()
+            .find(|path| translations.contains_key(path))
+    };
+    let resolved: Vec<Option<PathBuf>> = alternates
+        .iter()
+        .map(|alternate| document(&alternate.link))
+        .collect();
+    for (alternate, target) in alternates.iter().zip(resolved.iter()) {
+        if let Some(target) = target {
+            translations
+                .get_mut(&alternate.source)
+                .unwrap()
+                .push(target.clone());
+        }
+    }
+    alternates
+        .into_iter()
+        .zip(resolved)
+        .filter(|(alternate, target)| match target {
+            Some(target) => {
+                target != &alternate.source && !translations[target].contains(&alternate.source)
+            }
+            None => exists(&HtmlFileLink {
+                path: alternate.link.path.clone(),
+                fragment: None,
+                base: alternate.link.base,
+            }),
+        })
+        .map(|(alternate, _)| alternate)
+        .collect()
+}
+
+/// Extensions of documentation sources, which generators render to HTML rather than publish
+pub const SOURCE_EXTENSIONS: &[&str] = &["md", "rst", "adoc"];
+
+/// Whether a path is a Markdown, reStructuredText or AsciiDoc source
+pub fn is_source_file<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref()
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            SOURCE_EXTENSIONS
+                .iter()
+                .any(|source| extension.eq_ignore_ascii_case(source))
+        })
+}
+
+/**
+A link to the source of a page, like `guide.md`, which a generator left as it was instead of
+linking to the page rendered from it.
+*/
+#[derive(Debug, PartialEq, Eq)]
+pub struct SourceLink {
+    /// The document containing the link, relative to the scanned directory
+    pub source: PathBuf,
+    pub href: String,
+    pub link: HtmlFileLink,
+}
+
+/**
+The links in a document to source files, unless the document is skipped.
+*/
+pub fn document_source_links(file_path: &Path, info: &HtmlInfo) -> Vec<SourceLink> {
+    if info.directives.skip {
+        return vec![];
+    }
+    resolved_links(file_path, info)
+        .filter(|(_, link)| is_source_file(&link.path))
+        .map(|(href, link)| SourceLink {
+            source: file_path.to_path_buf(),
+            href: href.to_string(),
+            link,
+        })
+        .collect()
+}
+
+/**
+A scanned document which is a byte-identical copy of another.
+*/
+#[derive(Debug, PartialEq, Eq)]
+pub struct DuplicateDocument {
+    /// The copy, relative to the scanned directory
+    pub source: PathBuf,
+    /// The first of the identical documents, by path
+    pub original: PathBuf,
+}
+
+/**
+Group documents by the hash of their contents, reporting all but the first of each group.
+
+Documents sharing the first hash are only identical if they share the second one too, so each
+group is split by the second hash before anything is reported.
+*/
+pub fn duplicate_documents<'a, I: IntoIterator<Item = (&'a Path, (u64, u64))>>(
+    documents: I,
+) -> Vec<DuplicateDocument> {
+    let mut by_hash: HashMap<u64, Vec<(u64, &Path)>> = HashMap::new();
+    for (path, (hash, check)) in documents {
+        by_hash.entry(hash).or_default().push((check, path));
+    }
+    let mut duplicates = vec![];
+    for mut paths in by_hash.into_values().filter(|paths| paths.len() > 1) {
+        paths.sort();
+        for identical in paths.chunk_by(|(a, _), (b, _)| a == b) {
+            let original = identical[0].1;
+            duplicates.extend(identical[1..].iter().map(|(_, path)| DuplicateDocument {
+                source: path.to_path_buf(),
+                original: original.to_path_buf(),
+            }));
+        }
+    }
+    duplicates
+}
+
+/**
+A redirect stub which sends its readers through other stubs before they reach a page, or around
+in a loop.
+*/
+#[derive(Debug, PartialEq, Eq)]
+pub struct RedirectChain {
+    /// The stub, relative to the scanned directory
+    pub source: PathBuf,
+    /// Where each redirect in turn leads
+    pub hops: Vec<PathBuf>,
+    /// Whether the chain leads back to a stub it went through
+    pub circular: bool,
+}
+
+/**
+Follow the redirect of every stub, given with the path it leads to, through the stubs it leads
+to, reporting those which take more than one hop or go around in a loop. The document each
+redirect leads to is found as `probing` says.
+*/
+pub fn redirect_chains<'a, I: IntoIterator<Item = (&'a Path, PathBuf)>>(
+    redirects: I,
+    probing: &Probing,
+) -> Vec<RedirectChain> {
+    let redirects: HashMap<&Path, PathBuf> = redirects.into_iter().collect();
+    let stub = |path: &Path| {
+        probing
+            .candidates(path)
+            .iter()
+            .find_map(|candidate| redirects.get_key_value(candidate.as_path()))
+    };
+    let mut chains = vec![];
+    for (source, target) in redirects.iter() {
+        let mut visited = vec![*source];
+        let mut hops = vec![target.clone()];
+        let mut circular = false;
+        while let Some((stub, target)) = stub(hops.last().unwrap()) {
+            if visited.contains(stub) {
+                circular = true;
+                break;
+            }
+            visited.push(stub);
+            hops.push(target.clone());
+        }
+        if circular || hops.len() > 1 {
+            chains.push(RedirectChain {
+                source: source.to_path_buf(),
+                hops,
+                circular,
+            });
+        }
+    }
+    chains
+}
+
+/**
+Everything found wrong with the scanned documents, before consulting the base directory.
+*/
+#[derive(Debug, Default)]
+pub struct Scan {
+    /// Every scanned document, relative to the directory it was found in
+    pub documents: Vec<PathBuf>,
+    pub broken_links: Vec<BrokenLink>,
+    pub loopback_links: Vec<LoopbackLink>,
+    pub escaping_links: Vec<EscapingLink>,
+    pub external_links: Vec<ExternalLink>,
+    pub contact_links: Vec<ContactLink>,
+    pub placeholder_links: Vec<PlaceholderLink>,
+    pub non_portable_links: Vec<NonPortableLink>,
+    pub duplicate_ids: Vec<DuplicateId>,
+    pub broken_id_references: Vec<BrokenIdReference>,
+    pub toc_omissions: Vec<TocOmission>,
+    pub duplicate_documents: Vec<DuplicateDocument>,
+    pub redirect_chains: Vec<RedirectChain>,
+    pub srcsets: Vec<Srcset>,
+    pub alternates: Vec<Alternate>,
+    pub source_links: Vec<SourceLink>,
+    pub links: LinkCounts,
+}
+
+impl Scan {
+    /**
+    Add what's wrong with a single parsed document, whose links are checked with `contains`.
+    The document is passed through the `hooks` first, so it mustn't have been already.
+    */
+    pub fn add_document<F: Fn(&HtmlFileLink) -> bool>(
+        &mut self,
+        path: PathBuf,
+        mut info: HtmlInfo,
+        hooks: &Hooks,
+        contains: F,
+    ) {
+        hooks.parsed(&path, &mut info);
+        let info = &info;
+        self.broken_links
+            .extend(document_broken_links(&path, info, hooks, contains));
+        self.loopback_links
+            .extend(document_loopback_links(&path, info));
+        self.escaping_links
+            .extend(document_escaping_links(&path, info));
+        self.external_links
+            .extend(document_external_links(&path, info));
+        self.contact_links
+            .extend(document_contact_links(&path, info));
+        self.placeholder_links
+            .extend(document_placeholder_links(&path, info));
+        self.non_portable_links
+            .extend(document_non_portable_links(&path, info));
+        self.duplicate_ids
+            .extend(document_duplicate_ids(&path, info));
+        self.broken_id_references
+            .extend(document_broken_id_references(&path, info));
+        self.toc_omissions
+            .extend(document_toc_omissions(&path, info));
+        self.srcsets.extend(document_srcsets(&path, info));
+        self.alternates.extend(document_alternates(&path, info));
+        self.source_links.extend(document_source_links(&path, info));
+        self.links.add(&path, info);
+        self.documents.push(path);
+    }
+}
+
+/// How many documents a checking thread takes at a time
+const DOCUMENTS_PER_TAKE: usize = 16;
+
+/// How many similar ids are suggested for a missing `#fragment`
+const MAX_SIMILAR_IDS: usize = 3;
+
+/// Redirect stubs are followed at most this many times, in case they redirect in a loop
+pub const MAX_REDIRECTS: usize = 8;
+
+/**
+The parsed documents of the scanned directories, along with every other file in them and the
+ids in those which are SVG images.
+
+Both are keyed by their path relative to the directory they were found in, or under its URL
+prefix if it's mounted. Links are resolved to them as the `Probing` setting says, and their
+`#fragment`s found as the `FragmentMatch` setting says.
+ */
+#[derive(Debug, Default)]
+pub struct HtmlFiles(
+    pub(crate) HashMap<PathBuf, HtmlInfo>,
+    pub(crate) HashMap<PathBuf, Ids>,
+    pub(crate) Probing,
+    pub(crate) FragmentMatch,
+);
+impl HtmlFiles {
+    pub fn new<P: FileProvider>(
+        providers: &[P],
+        filter: &FileFilter,
+        jobs: usize,
+        progress: &Progress,
+        errors: Option<&mut Vec<ReadError>>,
+    ) -> std::io::Result<HtmlFiles> {
+        HtmlFiles::new_with(providers, filter, jobs, progress, errors, &Hooks::default())
+    }
+    /// Parse the documents as `new` does, telling the `hooks` about each one
+    pub fn new_with<P: FileProvider>(
+        providers: &[P],
+        filter: &FileFilter,
+        jobs: usize,
+        progress: &Progress,
+        errors: Option<&mut Vec<ReadError>>,
+        hooks: &Hooks,
+    ) -> std::io::Result<HtmlFiles> {
+        // The documents are all kept, so they share their hrefs and ids
+        let mut filter = filter.clone();
+        filter.parse.intern = true;
+        let mut map = HashMap::new();
+        let other_files = walk_documents_with(
+            providers,
+            &filter,
+            jobs,
+            progress,
+            errors,
+            hooks,
+            |path, info| {
+                map.insert(path, info);
+            },
+        )?;
+        Ok(HtmlFiles(
+            map,
+            other_files.into_iter().collect(),
+            Probing::default(),
+            FragmentMatch::default(),
+        ))
+    }
+    /// Add the files of another scan, like one of archives rather than directories
+    pub fn extend(&mut self, HtmlFiles(documents, other_files, ..): HtmlFiles) {
+        self.0.extend(documents);
+        self.1.extend(other_files);
+    }
+    pub fn set_probing(&mut self, probing: Probing) {
+        self.2 = probing;
+    }
+    pub fn set_fragment_match(&mut self, fragment_match: FragmentMatch) {
+        self.3 = fragment_match;
+    }
+    /// The document a link to `path` leads to, with its path
+    fn document(&self, path: &Path) -> Option<(&PathBuf, &HtmlInfo)> {
+        self.2
+            .candidates(path)
+            .iter()
+            .find_map(|candidate| self.0.get_key_value(candidate))
+    }
+    pub fn contains(&self, HtmlFileLink { path, fragment, .. }: &HtmlFileLink) -> bool {
+        if let Some((_, info)) = self.document(path) {
+            // If a "#fragment" id is present, also check that the document contains the fragment
+            if let Some(fragment) = fragment {
+                self.3.contains(&info.ids, fragment)
+            } else {
+                true
+            }
+        } else {
+            // Other files exist, but only SVG images have ids for a "#fragment" to refer to
+            self.2
+                .candidates(path)
+                .iter()
+                .find_map(|candidate| self.1.get(candidate))
+                .is_some_and(|ids| {
+                    fragment
+                        .as_ref()
+                        .is_none_or(|fragment| self.3.contains(ids, fragment))
+                })
+        }
+    }
+    /**
+    The scanned file a link to `path` only finds on a case-insensitive filesystem, like the
+    default ones of macOS and Windows, because their names differ only by case.
+    */
+    pub fn case_insensitive_match(&self, path: &Path) -> Option<&PathBuf> {
+        let candidates: Vec<String> = self
+            .2
+            .candidates(path)
+            .iter()
+            .map(|candidate| candidate.to_string_lossy().to_lowercase())
+            .collect();
+        self.0
+            .keys()
+            .chain(self.1.keys())
+            .find(|file| candidates.contains(&file.to_string_lossy().to_lowercase()))
+    }
+    /// Like `contains`, but links to redirect stubs have their `#fragment` checked in the
+    /// document the stub redirects to
+    pub fn contains_following_redirects(&self, link: &HtmlFileLink) -> bool {
+        let Some(fragment) = &link.fragment else {
+            return self.contains(link);
+        };
+        let mut path = link.path.clone();
+        for _ in 0..MAX_REDIRECTS {
+            let Some((document, info)) = self.document(&path) else {
+                // Other files don't redirect, but SVG images have ids of their own
+                return self.contains(&HtmlFileLink {
+                    path,
+                    fragment: Some(fragment.clone()),
+                    base: link.base,
+                });
+            };
+            match resolve_redirect(document, info) {
+                Some(redirect) if !self.3.contains(&info.ids, fragment) => path = redirect.path,
+                _ => return self.3.contains(&info.ids, fragment),
+            }
+        }
+        false
+    }
+    /**
+    The ids in the document a link leads to which are most like its `#fragment`, best first.
+    Only a few ids similar enough to be what the link meant are given.
+    */
+    pub fn similar_ids(&self, HtmlFileLink { path, fragment, .. }: &HtmlFileLink) -> Vec<String> {
+        let (Some(fragment), Some(ids)) = (fragment, self.ids(path)) else {
+            return vec![];
+        };
+        let mut similar: Vec<(f64, &Interned)> = ids
+            .iter()
+            .map(|id| (strsim::jaro_winkler(fragment, id), id))
+            .filter(|(similarity, _)| *similarity > 0.7)
+            .collect();
+        similar.sort_by(|(a, a_id), (b, b_id)| b.total_cmp(a).then(a_id.cmp(b_id)));
+        similar.dedup_by_key(|(_, id)| *id);
+        similar
+            .into_iter()
+            .take(MAX_SIMILAR_IDS)
+            .map(|(_, id)| id.to_string())
+            .collect()
+    }
+    /// The ids of the document or SVG image a link to `path` leads to
+    pub fn ids(&self, path: &Path) -> Option<&Ids> {
+        match self.document(path) {
+            Some((_, info)) => Some(&info.ids),
+            None => self
+                .2
+                .candidates(path)
+                .iter()
+                .find_map(|candidate| self.1.get(candidate)),
+        }
+    }
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<&HtmlInfo> {
+        self.0.get(path.as_ref())
+    }
+    /// Add a document, or replace it with what it has become since it was scanned
+    pub fn insert(&mut self, path: PathBuf, info: HtmlInfo) {
+        self.0.insert(path, info);
+    }
+    /// The scanned file a link to `path` leads to, if any
+    pub fn file(&self, path: &Path) -> Option<&PathBuf> {
+        match self.document(path) {
+            Some((file, _)) => Some(file),
+            None => (self.2.candidates(path).iter())
+                .find_map(|candidate| self.1.get_key_value(candidate))
+                .map(|(file, _)| file),
+        }
+    }
+    /// Every scanned file, documents and others alike, in no particular order
+    pub fn paths(&self) -> impl Iterator<Item = &PathBuf> {
+        self.0.keys().chain(self.1.keys())
+    }
+    /**
+    The broken links of every document, checked on `jobs` threads. Each thread takes the next
+    few documents left whenever it's done with its last, so a handful of huge documents don't
+    hold the others up, and remembers where the links it has already looked up lead, since
+    most documents link to the same pages.
+    */
+    pub fn broken_links(
+        &self,
+        follow_redirects: bool,
+        jobs: usize,
+        progress: &Progress,
+    ) -> Vec<BrokenLink> {
+        self.broken_links_with(follow_redirects, jobs, progress, &Hooks::default())
+    }
+    /// The broken links as `broken_links` finds them, with the links checked as the `hooks` say
+    pub fn broken_links_with(
+        &self,
+        follow_redirects: bool,
+        jobs: usize,
+        progress: &Progress,
+        hooks: &Hooks,
+    ) -> Vec<BrokenLink> {
+        let documents: Vec<(&PathBuf, &HtmlInfo)> = self.0.iter().collect();
+        let next = AtomicUsize::new(0);
+        let check = || {
+            let mut broken_links = vec![];
+            let found: RefCell<HashMap<HtmlFileLink, bool>> = RefCell::default();
+            let contains = |link: &HtmlFileLink| {
+                if let Some(found) = found.borrow().get(link) {
+                    return *found;
+                }
+                let contains = match follow_redirects {
+                    true => self.contains_following_redirects(link),
+                    false => self.contains(link),
+                };
+                let link = HtmlFileLink {
+                    path: link.path.clone(),
+                    fragment: link.fragment.clone(),
+                    base: link.base,
+                };
+                found.borrow_mut().insert(link, contains);
+                contains
+            };
+            loop {
+                let start = next.fetch_add(DOCUMENTS_PER_TAKE, Ordering::Relaxed);
+                let end = documents.len().min(start + DOCUMENTS_PER_TAKE);
+                let Some(taken) = documents.get(start..end).filter(|taken| !taken.is_empty())
+                else {
+                    return broken_links;
+                };
+                for (file_path, info) in taken {
+                    progress.checked(info.relative_hrefs.len());
+                    broken_links.extend(document_broken_links(file_path, info, hooks, contains));
+                }
+            }
+        };
+        let jobs = jobs.clamp(1, documents.len().max(1));
+        if jobs == 1 {
+            return check();
+        }
+        std::thread::scope(|scope| {
+            let threads: Vec<_> = (0..jobs).map(|_| scope.spawn(check)).collect();
+            threads
+                .into_iter()
+                .flat_map(|thread| thread.join().expect("A checking thread panicked"))
+                .collect()
+        })
+    }
+    pub fn loopback_links(&self) -> Vec<LoopbackLink> {
+        self.0
+            .iter()
+            .flat_map(|(file_path, info)| document_loopback_links(file_path, info))
+            .collect()
+    }
+    /// Everything wrong with the documents, with their links checked on `jobs` threads
+    pub fn scan(&self, follow_redirects: bool, jobs: usize, progress: &Progress) -> Scan {
+        self.scan_with(follow_redirects, jobs, progress, &Hooks::default())
+    }
+    /// Everything wrong with the documents, with their links checked as the `hooks` say
+    pub fn scan_with(
+        &self,
+        follow_redirects: bool,
+        jobs: usize,
+        progress: &Progress,
+        hooks: &Hooks,
+    ) -> Scan {
+        Scan {
+            documents: self.0.keys().cloned().collect(),
+            broken_links: self.broken_links_with(follow_redirects, jobs, progress, hooks),
+            loopback_links: self.loopback_links(),
+            escaping_links: self
+                .0
+                .iter()
+                .flat_map(|(file_path, info)| document_escaping_links(file_path, info))
+                .collect(),
+            external_links: self
+                .0
+                .iter()
+                .flat_map(|(file_path, info)| document_external_links(file_path, info))
+                .collect(),
+            contact_links: self
+                .0
+                .iter()
+                .flat_map(|(file_path, info)| document_contact_links(file_path, info))
+                .collect(),
+            placeholder_links: self
+                .0
+                .iter()
+                .flat_map(|(file_path, info)| document_placeholder_links(file_path, info))
+                .collect(),
+            non_portable_links: self
+                .0
+                .iter()
+                .flat_map(|(file_path, info)| document_non_portable_links(file_path, info))
+                .collect(),
+            duplicate_ids: self
+                .0
+                .iter()
+                .flat_map(|(file_path, info)| document_duplicate_ids(file_path, info))
+                .collect(),
+            broken_id_references: self
+                .0
+                .iter()
+                .flat_map(|(file_path, info)| document_broken_id_references(file_path, info))
+                .collect(),
+            toc_omissions: self
+                .0
+                .iter()
+                .flat_map(|(file_path, info)| document_toc_omissions(file_path, info))
+                .collect(),
+            duplicate_documents: duplicate_documents(
+                self.0
+                    .iter()
+                    .map(|(file_path, info)| (file_path.as_path(), info.content_hash)),
+            ),
+            redirect_chains: redirect_chains(
+                self.0.iter().filter_map(|(file_path, info)| {
+                    Some((file_path.as_path(), resolve_redirect(file_path, info)?.path))
+                }),
+                &self.2,
+            ),
+            srcsets: self
+                .0
+                .iter()
+                .flat_map(|(file_path, info)| document_srcsets(file_path, info))
+                .collect(),
+            alternates: self
+                .0
+                .iter()
+                .flat_map(|(file_path, info)| document_alternates(file_path, info))
+                .collect(),
+            source_links: self
+                .0
+                .iter()
+                .flat_map(|(file_path, info)| document_source_links(file_path, info))
+                .collect(),
+            links: self.0.iter().collect(),
+        }
+    }
+}
+
+/**
+HTML documents outside the scanned directories, indexed only once something links to them.
+
+Lookups are bounded by the base directory: links which lead anywhere else, or to files which
+aren't HTML documents, are only checked for existence. They're looked for on disk, so the
+wasm32 build, which has none, goes without.
+ */
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug)]
+pub struct LinkedFiles {
+    base_dir: PathBuf,
+    filter: FileFilter,
+    files: HtmlFiles,
+    indexed: HashSet<PathBuf>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl LinkedFiles {
+    pub fn new<P: AsRef<Path>>(base_dir: P, filter: FileFilter) -> LinkedFiles {
+        LinkedFiles {
+            base_dir: base_dir.as_ref().to_path_buf(),
+            filter,
+            files: HtmlFiles::default(),
+            indexed: HashSet::new(),
+        }
+    }
+    pub fn set_probing(&mut self, probing: Probing) {
+        self.files.set_probing(probing);
+    }
+    /**
+    Check a link against the base directory, indexing the directory it points into if needed.
+
+    Documents which can't be read are recorded in `errors` and skipped. Without `errors`, the
+    first one fails the lookup instead.
+    */
+    pub fn contains(
+        &mut self,
+        link: &HtmlFileLink,
+        errors: Option<&mut Vec<ReadError>>,
+    ) -> std::io::Result<bool> {
+        let Some(document) = (self.files.2.candidates(&link.path).into_iter())
+            .find(|candidate| self.base_dir.join(candidate).is_file())
+        else {
+            return Ok(false);
+        };
+        if !self.filter.has_extension(&document) {
+            return Ok(true);
+        }
+        let directory = document.parent().unwrap_or(Path::new("")).to_path_buf();
+        if !self.indexed.contains(&directory) {
+            self.index(&directory, errors)?;
+        }
+        Ok(self.files.contains(link) || !self.within_base_dir(&document))
+    }
+    fn within_base_dir(&self, path: &Path) -> bool {
+        match (
+            self.base_dir.join(path).canonicalize(),
+            self.base_dir.canonicalize(),
+        ) {
+            (Ok(path), Ok(base_dir)) => path.starts_with(base_dir),
+            _ => false,
+        }
+    }
+    /// Parse the documents directly inside `directory`, relative to the base directory
+    fn index(
+        &mut self,
+        directory: &Path,
+        mut errors: Option<&mut Vec<ReadError>>,
+    ) -> std::io::Result<()> {
+        self.indexed.insert(directory.to_path_buf());
+        if !self.within_base_dir(directory) {
+            return Ok(());
+        }
+        for result in WalkDir::new(self.base_dir.join(directory)).max_depth(1) {
+            let parsed = match result {
+                Ok(entry)
+                    if entry.file_type().is_file() && self.filter.has_extension(entry.path()) =>
+                {
+                    let path = site_key(directory, entry.file_name());
+                    match (self.base_dir.read(&path, self.filter.max_file_size))
+                        .and_then(|contents| decode_html(&contents))
+                    {
+                        Ok(contents) => {
+                            Ok((path, HtmlInfo::parse_with(&contents, &self.filter.parse)))
+                        }
+                        Err(err) => Err((path, err)),
+                    }
+                }
+                Ok(_) => continue,
+                Err(err) => {
+                    let path = err.path().unwrap_or(directory);
+                    let path = path
+                        .strip_prefix(&self.base_dir)
+                        .unwrap_or(path)
+                        .to_path_buf();
+                    Err((path, std::io::Error::from(err)))
+                }
+            };
+            match parsed {
+                Ok((path, info)) => {
+                    self.files.0.insert(path, info);
+                }
+                Err((path, err)) => ReadError::record(path, err, errors.as_deref_mut())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/**
+Resolve the relative links of a document, taking its directives into account.
+
+Yields each `href` along with the link it resolves to.
+*/
+pub fn resolved_links<'a>(
+    file_path: &'a Path,
+    info: &'a HtmlInfo,
+) -> impl Iterator<Item = (&'a Interned, HtmlFileLink)> {
+    let base = link_base(file_path, info);
+    info.relative_hrefs.iter().map(move |href| {
+        let mut link = resolve_document_link(file_path, &base, href);
+        if info.directives.no_fragments {
+            link.fragment = None;
+        }
+        (href, link)
+    })
+}
+
+/// The directory the relative links of a document are resolved against
+fn link_base(file_path: &Path, info: &HtmlInfo) -> PathBuf {
+    match &info.directives.base {
+        Some(base) => PathBuf::from(base),
+        None => file_path.parent().expect("No parent").to_path_buf(),
+    }
+}
+
+/**
+Resolve where a redirect stub sends its readers, if it is a stub for a relative link.
+*/
+pub fn resolve_redirect(file_path: &Path, info: &HtmlInfo) -> Option<HtmlFileLink> {
+    let redirect = info.redirect.as_ref()?;
+    if Url::parse(redirect) != Err(url::ParseError::RelativeUrlWithoutBase) {
+        return None;
+    }
+    Some(resolve_document_link(
+        file_path,
+        link_base(file_path, info),
+        redirect,
+    ))
+}
+
+/**
+Resolve an `href` relative to the directory `base`.
+
+The fragment is split off before the path is normalized, so `..#top` and `./#top` both link
+to a directory, which is resolved to its `index.html` document. An empty resolved path is the
+root of the scanned directory. A trailing slash is kept, as are the implied ones of `.` and
+`..`, for `TrailingSlash` to tell `guide/` from `guide`.
+
+Root-relative links, and links resolved against a root-relative `base`, keep the `Root` base.
+*/
+pub fn resolve_link<P: AsRef<Path>>(base: P, href: &str) -> HtmlFileLink {
+    let link = HtmlFileLink::new(href);
+    let directory = match link.base {
+        LinkBase::Root => SitePath::default(),
+        LinkBase::Document => SitePath::from_file(base),
+    };
+    let path = directory.resolve(&link.path.to_string_lossy()).to_string();
+    match path.strip_prefix('/') {
+        Some(path) => HtmlFileLink {
+            path: path.into(),
+            base: LinkBase::Root,
+            ..link
+        },
+        None => HtmlFileLink {
+            path: path.into(),
+            ..link
+        },
+    }
+}
+
+/**
+Whether a link without a trailing slash may lead to a directory's default document, and one with
+a trailing slash to a file, as hosts differ on both.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum TrailingSlash {
+    /// `guide` and `guide/` both lead to `guide/index.html`, and `page.html/` to `page.html`
+    #[default]
+    Lenient,
+    /// `guide` and `guide/` both lead to `guide/index.html`, but `page.html/` is broken
+    Strict,
+    /// Only `guide/` leads to `guide/index.html`, and `page.html/` is broken
+    Require,
+}
+
+/// The documents a link to a directory leads to unless others are configured
+pub const DEFAULT_DOCUMENTS: &[&str] = &["index.html"];
+
+/// The default document `--pretty-urls` adds, so that `about` leads to `about.html`
+pub const PRETTY_URL_DOCUMENT: &str = ".html";
+
+/**
+Which files a link may lead to besides the one it names, as hosts differ on it.
+
+Default documents named like `index.html` are looked for inside the linked directory, while
+those which are only an extension like `.html` are appended to the linked path, for "pretty
+URLs" where `about` serves `about.html`. The `TrailingSlash` setting says which of them a link
+with or without a trailing slash may lead to.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Probing {
+    pub trailing_slash: TrailingSlash,
+    pub default_documents: Vec<String>,
+}
+
+impl Default for Probing {
+    fn default() -> Probing {
+        Probing {
+            trailing_slash: TrailingSlash::default(),
+            default_documents: DEFAULT_DOCUMENTS
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl Probing {
+    /// The files a link to `path` may lead to, in the order they're looked for
+    pub fn candidates(&self, path: &Path) -> Vec<PathBuf> {
+        let has_slash = path.as_os_str().is_empty() || path.to_string_lossy().ends_with('/');
+        let as_file = !has_slash || self.trailing_slash == TrailingSlash::Lenient;
+        let as_directory = has_slash || self.trailing_slash != TrailingSlash::Require;
+        let mut candidates = vec![];
+        if as_file {
+            candidates.push(path.to_path_buf());
+        }
+        for document in self.default_documents.iter() {
+            if document.starts_with('.') {
+                if let (true, Some(name)) = (as_file, path.file_name()) {
+                    let name = format!("{}{document}", name.to_string_lossy());
+                    candidates.push(path.with_file_name(name));
+                }
+            } else if as_directory {
+                candidates.push(path.join(document));
+            }
+        }
+        candidates
+    }
+}
+
+/**
+How a link's `#fragment` is compared with the ids of the document it leads to, to match how
+the site's generator writes ids and the links to them.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum FragmentMatch {
+    /// `#Setup` only finds `id="Setup"`
+    #[default]
+    Exact,
+    /// `#setup` also finds `id="Setup"`
+    IgnoreCase,
+    /// Both are turned into slugs first, so `#getting-started` also finds
+    /// `id="Getting Started"` and `id="getting_started"`
+    Slug,
+}
+
+impl FragmentMatch {
+    /// The form of an id or fragment which is compared
+    pub fn normalize(self, id: &str) -> Cow<'_, str> {
+        match self {
+            FragmentMatch::Exact => Cow::Borrowed(id),
+            FragmentMatch::IgnoreCase => Cow::Owned(id.to_lowercase()),
+            FragmentMatch::Slug => {
+                let mut slug = String::with_capacity(id.len());
+                for c in id.chars().flat_map(char::to_lowercase) {
+                    if c.is_alphanumeric() {
+                        slug.push(c);
+                    } else if (c.is_whitespace() || c == '-' || c == '_') && !slug.ends_with('-') {
+                        slug.push('-');
+                    }
                 }
+                Cow::Owned(slug.trim_matches('-').to_string())
             }
         }
-        Ok(HtmlFiles(map))
     }
-    pub fn contains(&self, HtmlFileLink { path, fragment }: &HtmlFileLink) -> bool {
-        let path_with_index = path.join("index.html");
-        if let Some(info) = self.0.get(path).or_else(|| self.0.get(&path_with_index)) {
-            // If a "#fragment" id is present, also check that the document contains the fragment
-            if let Some(fragment) = fragment {
-                info.ids.contains(&fragment.to_string())
-            } else {
-                true
-            }
-        } else {
-            false
+    /// Whether any of the `ids` is the `fragment`
+    pub fn contains(self, ids: &Ids, fragment: &str) -> bool {
+        if self == FragmentMatch::Exact {
+            return ids.contains(fragment);
         }
+        let fragment = self.normalize(fragment);
+        ids.iter().any(|id| self.normalize(id) == fragment)
     }
-    pub fn missing_file_links(&self) -> Vec<HtmlFileLink> {
-        self.0
-            .iter()
-            .flat_map(|(file_path, info)| {
-                info.relative_hrefs
-                    .iter()
-                    .inspect(|x| println!("a {x:?}"))
-                    .map(|href| file_path.parent().expect("No parent").join(href))
-                    .inspect(|x| println!("b {x:?}"))
-                    .map(normalize_path)
-                    .inspect(|x| println!("c {x:?}"))
-                    .map(HtmlFileLink::new)
-                    .inspect(|x| println!("d {x:?}"))
-                    .filter(|link| !self.contains(link))
-            })
-            .collect()
+}
+
+/**
+Where a root-relative link like `/docs/guide.html` leads, given the site root.
+*/
+#[derive(Debug, PartialEq, Eq)]
+pub enum RootLink {
+    /// Into one of the scanned directories, relative to which the link is given
+    Scanned(HtmlFileLink),
+    /// Elsewhere in the site, relative to the site root
+    Outside(PathBuf),
+}
+
+/**
+Resolve a root-relative link against the site root, given the path of each scanned directory
+within it. Returns nothing for links which aren't root-relative.
+*/
+pub fn resolve_root_link(link: &HtmlFileLink, prefixes: &[PathBuf]) -> Option<RootLink> {
+    if link.base != LinkBase::Root {
+        return None;
     }
+    // Stripped as a string, since stripping a `Path` drops the trailing slash which tells how
+    // the link is probed
+    let path = link.path.to_string_lossy();
+    let scanned = prefixes.iter().find_map(|prefix| {
+        let prefix = prefix.to_string_lossy();
+        if prefix.is_empty() {
+            return Some(&*path);
+        }
+        match path.strip_prefix(&*prefix)? {
+            "" => Some(""),
+            rest => rest.strip_prefix('/'),
+        }
+    });
+    Some(match scanned {
+        Some(path) => RootLink::Scanned(HtmlFileLink {
+            path: path.into(),
+            fragment: link.fragment.clone(),
+            base: LinkBase::Document,
+        }),
+        None => RootLink::Outside(link.path.clone()),
+    })
 }
 
-pub fn normalize_path<P: AsRef<Path>>(path: P) -> PathBuf {
-    let mut components = path.as_ref().components().peekable();
-    let mut ret = if let Some(c @ Component::Prefix(..)) = components.peek().cloned() {
-        components.next();
-        PathBuf::from(c.as_os_str())
+/**
+Resolve an `href` found in the document at `file_path`, relative to the directory `base`.
+
+Links with an empty path like `#section`, `#` or an empty `href` refer to the document itself,
+while `.` and `./#section` refer to the directory containing it, and `/#section` to the root.
+*/
+pub fn resolve_document_link<P: AsRef<Path>>(
+    file_path: &Path,
+    base: P,
+    href: &str,
+) -> HtmlFileLink {
+    let link = HtmlFileLink::new(href);
+    if link.base == LinkBase::Document && link.path.as_os_str().is_empty() {
+        HtmlFileLink {
+            path: file_path.to_path_buf(),
+            ..link
+        }
     } else {
-        PathBuf::new()
-    };
+        resolve_link(base, href)
+    }
+}
 
-    for component in components {
+/**
+Whether a path climbs out of the directory it's relative to, like `../../secrets/config.html`
+does from `docs/`. Normalizing such a path silently drops the `..` components which go too
+far, so it leads somewhere else entirely.
+*/
+pub fn escapes_root<P: AsRef<Path>>(path: P) -> bool {
+    let mut depth = 0;
+    for component in path.as_ref().components() {
         match component {
-            Component::Prefix(..) => unreachable!(),
-            Component::RootDir => {
-                ret.push(component.as_os_str());
-            }
-            Component::CurDir => {}
-            Component::ParentDir => {
-                ret.pop();
-            }
-            Component::Normal(c) => {
-                ret.push(c);
-            }
+            Component::ParentDir if depth == 0 => return true,
+            Component::ParentDir => depth -= 1,
+            Component::Normal(_) => depth += 1,
+            Component::Prefix(..) | Component::RootDir | Component::CurDir => {}
         }
     }
-    // ret.strip_prefix("/").map(Path::to_path_buf).unwrap_or(ret)
-    ret
+    false
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::archive::Archive;
+    use crate::filter::Mount;
+    use crate::fixture::Fixture;
+    use crate::streaming::TargetIndex;
+    use std::collections::BTreeMap;
     #[test]
     fn test_html_file_link_new() {
         macro_rules! assert_link_eq {
@@ -122,6 +1819,7 @@ mod test {
                     HtmlFileLink {
                         path: $path.into(),
                         fragment: None,
+                        base: LinkBase::Document,
                     }
                 );
             };
@@ -130,14 +1828,22 @@ mod test {
                     HtmlFileLink::new($href),
                     HtmlFileLink {
                         path: $path.into(),
-                        fragment: Some($fragment.into())
+                        fragment: Some($fragment.into()),
+                        base: LinkBase::Document,
                     }
                 );
             };
         }
         assert_link_eq!("foo", "foo");
         assert_link_eq!("foo/bar", "foo/bar");
-        assert_link_eq!("/foo/bar", "/foo/bar");
+        assert_eq!(
+            HtmlFileLink::new("/foo/bar#baz"),
+            HtmlFileLink {
+                path: "foo/bar".into(),
+                fragment: Some("baz".into()),
+                base: LinkBase::Root,
+            }
+        );
         assert_link_eq!("foo#bar", "foo", "bar");
         assert_link_eq!("foo#bar#baz", "foo#bar", "baz");
         assert_link_eq!("foo#", "foo");
@@ -151,7 +1857,7 @@ mod test {
             $files.0.insert($key.into(), HtmlInfo::parse($value));
         }};
         ($($key:expr => $value:expr),+) => {{
-            let mut files = HtmlFiles(HashMap::new());
+            let mut files = HtmlFiles::default();
             $(
                 html_files!(files, $key => $value);
             )*
@@ -164,19 +1870,29 @@ mod test {
         };
     }
 
+    fn missing_file_links(files: &HtmlFiles) -> Vec<HtmlFileLink> {
+        files
+            .broken_links(false, 1, &Progress::default())
+            .into_iter()
+            .map(|broken| broken.link)
+            .collect()
+    }
+
     #[test]
     fn test_html_files_contains() {
         let files = html_files!(
             "foo" => r#"<a href="foo" id="foo" />"#,
-            "/bar" => r#"<a href="/bar" id="bar" />"#,
-            "/baz/index.html" => r#"<a href="/baz" id="baz" />"#
+            "bar" => r#"<a href="/bar" id="bar" />"#,
+            "baz/index.html" => r#"<a href="/baz" id="baz" />"#
         );
         assert!(files.contains(&link!("foo")));
         assert!(!files.contains(&link!("foooo")));
         assert!(files.contains(&link!("foo#foo")));
         assert!(files.contains(&link!("/bar")));
         assert!(files.contains(&link!("/bar#bar")));
-        assert!(!files.contains(&link!("bar")));
+        // Without a site root, root-relative links lead into the scanned directory
+        assert!(files.contains(&link!("bar")));
+        assert!(!files.contains(&link!("/foo#bar")));
         assert!(files.contains(&link!("/baz")));
         assert!(files.contains(&link!("/baz#baz")));
         assert!(files.contains(&link!("/baz/")));
@@ -185,15 +1901,1093 @@ mod test {
         assert!(files.contains(&link!("/baz/index.html#baz")));
     }
 
+    #[test]
+    fn test_html_files_trailing_slash() {
+        let mut files = html_files!(
+            "guide/index.html" => r#"<h1 id="top" />"#,
+            "page.html" => r#"<h1 id="top" />"#
+        );
+        files.1.insert("manual.pdf".into(), Ids::default());
+        let found = |files: &HtmlFiles| {
+            [
+                "guide",
+                "guide/#top",
+                "guide/.",
+                "page.html",
+                "page.html/#top",
+                "manual.pdf/",
+            ]
+            .into_iter()
+            .filter(|href| files.contains(&resolve_link("", href)))
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            found(&files),
+            vec![
+                "guide",
+                "guide/#top",
+                "guide/.",
+                "page.html",
+                "page.html/#top",
+                "manual.pdf/"
+            ]
+        );
+        files.2.trailing_slash = TrailingSlash::Strict;
+        assert_eq!(
+            found(&files),
+            vec!["guide", "guide/#top", "guide/.", "page.html"]
+        );
+        files.2.trailing_slash = TrailingSlash::Require;
+        assert_eq!(found(&files), vec!["guide/#top", "guide/.", "page.html"]);
+    }
+
+    #[test]
+    fn test_html_files_probing() {
+        let mut files = html_files!(
+            "guide/index.htm" => r#"<h1 id="top" />"#,
+            "about.html" => r#"<h1 id="top" />"#,
+            "index.html" => ""
+        );
+        let found = |files: &HtmlFiles| {
+            [
+                "guide/#top",
+                "guide",
+                "about",
+                "about#top",
+                "about/",
+                "",
+                "./",
+            ]
+            .into_iter()
+            .filter(|href| files.contains(&resolve_link("", href)))
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(found(&files), vec!["", "./"]);
+        files.2.default_documents = vec!["index.html".into(), "index.htm".into(), ".html".into()];
+        assert_eq!(
+            found(&files),
+            vec![
+                "guide/#top",
+                "guide",
+                "about",
+                "about#top",
+                "about/",
+                "",
+                "./"
+            ]
+        );
+        files.2.trailing_slash = TrailingSlash::Require;
+        assert_eq!(
+            found(&files),
+            vec!["guide/#top", "about", "about#top", "", "./"]
+        );
+        assert_eq!(
+            files.2.candidates(Path::new("about")),
+            vec![PathBuf::from("about"), PathBuf::from("about.html")]
+        );
+    }
+
+    #[test]
+    fn test_html_files_fragment_match() {
+        let mut files = html_files!(
+            "page.html" => r#"<h2 id="Getting Started" /><h2 id="API_reference" /><h2 id="FAQ" />"#
+        );
+        let found = |files: &HtmlFiles| {
+            [
+                "page.html#Getting Started",
+                "page.html#getting started",
+                "page.html#getting-started",
+                "page.html#api-reference",
+                "page.html#faq",
+                "page.html#FAQ",
+                "page.html#fa-q",
+            ]
+            .into_iter()
+            .filter(|href| {
+                let link = link!(href);
+                assert_eq!(
+                    files.contains(&link),
+                    TargetIndex::from(files).contains(&link),
+                    "{href}"
+                );
+                files.contains(&link)
+            })
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            found(&files),
+            vec!["page.html#Getting Started", "page.html#FAQ"]
+        );
+        files.set_fragment_match(FragmentMatch::IgnoreCase);
+        assert_eq!(
+            found(&files),
+            vec![
+                "page.html#Getting Started",
+                "page.html#getting started",
+                "page.html#faq",
+                "page.html#FAQ"
+            ]
+        );
+        files.set_fragment_match(FragmentMatch::Slug);
+        assert_eq!(
+            found(&files),
+            vec![
+                "page.html#Getting Started",
+                "page.html#getting started",
+                "page.html#getting-started",
+                "page.html#api-reference",
+                "page.html#faq",
+                "page.html#FAQ"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_html_files_contains_following_redirects() {
+        let files = html_files!(
+            "old.html" => r#"<meta http-equiv="refresh" content="0; url=new/">"#,
+            "new/index.html" => r#"<meta http-equiv="refresh" content="0; url=../page.html"><p id="new" />"#,
+            "page.html" => r#"<p id="page" />"#,
+            "loop.html" => r#"<meta http-equiv="refresh" content="0; url=loop.html">"#
+        );
+        assert!(files.contains_following_redirects(&link!("old.html")));
+        assert!(files.contains_following_redirects(&link!("old.html#new")));
+        assert!(files.contains_following_redirects(&link!("old.html#page")));
+        assert!(!files.contains_following_redirects(&link!("old.html#nope")));
+        assert!(files.contains_following_redirects(&link!("new#page")));
+        assert!(!files.contains_following_redirects(&link!("loop.html#nope")));
+        assert!(!files.contains(&link!("old.html#page")));
+        assert_eq!(missing_file_links(&files), vec![]);
+    }
+
+    #[test]
+    fn test_srcset_missing_variants() {
+        let info = HtmlInfo::parse(
+            r#"<img srcset="img/a.png, img/a@2x.png 2x, https://cdn.example.com/a@3x.png 3x">"#,
+        );
+        let srcsets = document_srcsets(Path::new("guide/page.html"), &info);
+        assert_eq!(srcsets.len(), 1);
+        assert_eq!(
+            srcsets[0].variants[1],
+            ("2x".to_string(), Some(link!("guide/img/a@2x.png")))
+        );
+        let required = ["1x", "2x", "3x", "4x"].map(String::from);
+        let missing = srcsets[0].missing_variants(&required, |link| {
+            link.path != Path::new("guide/img/a@2x.png")
+        });
+        let missing: Vec<_> = missing
+            .iter()
+            .map(|m| (m.variant.as_str(), m.href.as_deref()))
+            .collect();
+        assert_eq!(missing, vec![("2x", Some("img/a@2x.png")), ("4x", None)]);
+    }
+
+    #[test]
+    fn test_missing_return_links() {
+        let documents = [
+            (
+                "en/index.html",
+                r#"<link rel="alternate" hreflang="en" href="index.html">
+                <link rel="alternate" hreflang="fr" href="../fr/">
+                <link rel="alternate" hreflang="de" href="../de/index.html">
+                <link rel="alternate" hreflang="es" href="../es/index.html">
+                <link rel="alternate" hreflang="it" href="../it/index.html">"#,
+            ),
+            (
+                "fr/index.html",
+                r#"<link rel="alternate" hreflang="en" href="../en/index.html">"#,
+            ),
+            (
+                "de/index.html",
+                r#"<link rel="alternate" hreflang="fr" href="../fr/index.html">"#,
+            ),
+            ("es/index.html", r#"<p>Sin traducciones</p>"#),
+        ];
+        let alternates: Vec<Alternate> = documents
+            .iter()
+            .flat_map(|(path, document)| {
+                document_alternates(Path::new(path), &HtmlInfo::parse(document))
+            })
+            .collect();
+        let exists = |link: &HtmlFileLink| !link.path.starts_with("it");
+        let missing: Vec<_> = missing_return_links(alternates, &Probing::default(), exists)
+            .into_iter()
+            .map(|alternate| (alternate.source, alternate.hreflang))
+            .collect();
+        assert_eq!(
+            missing,
+            vec![
+                ("en/index.html".into(), "de".to_string()),
+                ("en/index.html".into(), "es".to_string()),
+                ("de/index.html".into(), "fr".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_document_source_links() {
+        let info = HtmlInfo::parse(
+            r#"<a href="../guide.md#setup"/><a href="api.RST"/><a href="page.html"/>
+            <a href="https://example.com/README.md"/><a href="notes.adoc/"/><a href="md"/>"#,
+        );
+        let links: Vec<_> = document_source_links(Path::new("docs/index.html"), &info)
+            .into_iter()
+            .map(|source_link| (source_link.href, source_link.link))
+            .collect();
+        assert_eq!(
+            links,
+            vec![
+                ("../guide.md#setup".to_string(), link!("guide.md#setup")),
+                ("api.RST".to_string(), link!("docs/api.RST")),
+                ("notes.adoc/".to_string(), link!("docs/notes.adoc/")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_document_broken_id_references() {
+        let info = HtmlInfo::parse(
+            r#"<label for="name">Name</label><input id="name" aria-describedby="name-hint">
+            <div aria-labelledby="name missing"></div>"#,
+        );
+        assert_eq!(
+            document_broken_id_references(Path::new("form.html"), &info),
+            vec![
+                BrokenIdReference {
+                    source: "form.html".into(),
+                    attribute: "aria-describedby".to_string(),
+                    id: "name-hint".to_string(),
+                },
+                BrokenIdReference {
+                    source: "form.html".into(),
+                    attribute: "aria-labelledby".to_string(),
+                    id: "missing".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_document_contact_links() {
+        let info = HtmlInfo::parse(
+            r#"<a href="mailto:someone@example.com"/><a href="mailto:someone"/>
+            <a href="tel:+1-201-555-0123"/><a href="tel:call-me"/><a href="https://example.com/"/>"#,
+        );
+        let hrefs: Vec<_> = document_contact_links(Path::new("index.html"), &info)
+            .into_iter()
+            .map(|contact_link| contact_link.href)
+            .collect();
+        assert_eq!(hrefs, vec!["mailto:someone", "tel:call-me"]);
+    }
+
+    #[test]
+    fn test_document_placeholder_links() {
+        let info = HtmlInfo::parse(
+            r##"<a href="">Reload</a><a href="#">Top</a><a href="JavaScript:void(0);">Menu</a>
+            <a href="javascript:openMenu()">Menu</a><a href="#top">Top</a><link href="">"##,
+        );
+        let placeholders: Vec<_> = document_placeholder_links(Path::new("index.html"), &info)
+            .into_iter()
+            .map(|link| (link.href, link.placeholder))
+            .collect();
+        assert_eq!(
+            placeholders,
+            vec![
+                (String::new(), Placeholder::Empty),
+                ("#".into(), Placeholder::Hash),
+                ("JavaScript:void(0);".into(), Placeholder::Javascript),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_add_document_hooks() {
+        let document = r##"<a href="kept.html">Kept</a> <a href="../up.html">Up</a>
+            <a href="http://localhost:8000/">Dev</a> <a href="https://example.com/">Example</a>
+            <a href="mailto:someone">Mail</a> <a href="#">Top</a> <a href="guide\setup.html">Setup</a>
+            <a href="readme.md">Source</a> <img srcset="logo.png 2x">
+            <link rel="alternate" hreflang="fr" href="fr/index.html">"##;
+        let add = |hooks: &Hooks| {
+            let mut scan = Scan::default();
+            scan.add_document(
+                "index.html".into(),
+                HtmlInfo::parse(document),
+                hooks,
+                |_| false,
+            );
+            scan
+        };
+        let scan = add(&Hooks::default());
+        assert!(!scan.broken_links.is_empty());
+        assert!(!scan.escaping_links.is_empty());
+        assert!(!scan.loopback_links.is_empty());
+        assert!(!scan.external_links.is_empty());
+        assert!(!scan.contact_links.is_empty());
+        assert!(!scan.placeholder_links.is_empty());
+        assert!(!scan.non_portable_links.is_empty());
+        assert!(!scan.source_links.is_empty());
+        assert!(!scan.srcsets.is_empty());
+        assert!(!scan.alternates.is_empty());
+
+        // Links the filter doesn't keep are left out of every finding
+        let checked = Mutex::new(vec![]);
+        let parsed = Mutex::new(vec![]);
+        let hooks = Hooks {
+            on_file_parsed: Some(Box::new(|path: &Path, info: &HtmlInfo| {
+                let hrefs = info.relative_hrefs.iter().map(|href| href.to_string());
+                parsed
+                    .lock()
+                    .unwrap()
+                    .push((path.to_path_buf(), hrefs.collect::<Vec<_>>()));
+            })),
+            on_link_checked: Some(Box::new(|_: &Path, href: &str, found| {
+                checked.lock().unwrap().push((href.to_string(), found));
+            })),
+            link_filter: Some(Box::new(|_: &Path, href: &str| href == "kept.html")),
+        };
+        let scan = add(&hooks);
+        drop(hooks);
+        let broken: Vec<&str> = scan
+            .broken_links
+            .iter()
+            .map(|broken| &*broken.href)
+            .collect();
+        assert_eq!(broken, vec!["kept.html"]);
+        assert!(scan.escaping_links.is_empty());
+        assert!(scan.loopback_links.is_empty());
+        assert!(scan.external_links.is_empty());
+        assert!(scan.contact_links.is_empty());
+        assert!(scan.placeholder_links.is_empty());
+        assert!(scan.non_portable_links.is_empty());
+        assert!(scan.source_links.is_empty());
+        assert!(scan.srcsets.is_empty());
+        assert!(scan.alternates.is_empty());
+        assert_eq!(
+            parsed.into_inner().unwrap(),
+            vec![("index.html".into(), vec!["kept.html".to_string()])]
+        );
+        assert_eq!(
+            checked.into_inner().unwrap(),
+            vec![("kept.html".to_string(), false)]
+        );
+    }
+
+    #[test]
+    fn test_resolve_root_link() {
+        let prefixes = [PathBuf::from("docs"), PathBuf::from("blog")];
+        assert_eq!(
+            resolve_root_link(&link!("/docs/guide/#setup"), &prefixes),
+            Some(RootLink::Scanned(link!("guide/#setup")))
+        );
+        assert_eq!(
+            resolve_root_link(&link!("/docs/guide/index.html"), &prefixes),
+            Some(RootLink::Scanned(link!("guide/index.html")))
+        );
+        assert_eq!(
+            resolve_root_link(&link!("/docs"), &prefixes),
+            Some(RootLink::Scanned(link!("")))
+        );
+        assert_eq!(
+            resolve_root_link(&link!("/assets/logo.png"), &prefixes),
+            Some(RootLink::Outside("assets/logo.png".into()))
+        );
+        assert_eq!(
+            resolve_root_link(&link!("/docsy/index.html"), &prefixes),
+            Some(RootLink::Outside("docsy/index.html".into()))
+        );
+        assert_eq!(resolve_root_link(&link!("docs/guide"), &prefixes), None);
+        assert_eq!(
+            resolve_root_link(&link!("/docs/guide/"), &[PathBuf::new()]),
+            Some(RootLink::Scanned(link!("docs/guide/")))
+        );
+
+        // The trailing slash is kept, so the link is probed as a directory
+        let probing = Probing {
+            trailing_slash: TrailingSlash::Require,
+            ..Probing::default()
+        };
+        let mut files = html_files!("guide/index.html" => "");
+        files.set_probing(probing);
+        for (href, found) in [("/docs/guide/", true), ("/docs/guide", false)] {
+            let Some(RootLink::Scanned(link)) = resolve_root_link(&link!(href), &prefixes) else {
+                panic!("{href} isn't scanned");
+            };
+            assert_eq!(files.contains(&link), found, "{href}");
+        }
+    }
+
+    #[test]
+    fn test_other_files() {
+        let mut files = html_files!(
+            "index.html" => r#"<a href="manual.pdf"/><a href="manual.pdf#page=2"/>"#
+        );
+        files.1.insert("manual.pdf".into(), Ids::default());
+        files
+            .1
+            .insert("icons.svg".into(), ["arrow".into()].into_iter().collect());
+        assert!(files.contains(&link!("manual.pdf")));
+        assert!(files.contains(&link!("icons.svg#arrow")));
+        assert!(!files.contains(&link!("icons.svg#arow")));
+        assert_eq!(files.similar_ids(&link!("icons.svg#arow")), vec!["arrow"]);
+        assert!(!files.contains(&link!("manual.pdf#page=2")));
+        assert!(!files.contains(&link!("guide.pdf")));
+        let broken = files.broken_links(false, 1, &Progress::default());
+        assert_eq!(broken.len(), 1);
+        assert!(broken[0].missing_fragment);
+    }
+
+    #[test]
+    fn test_broken_links_in_parallel() {
+        let mut files = HtmlFiles::default();
+        for page in 0..100 {
+            html_files!(files, format!("page{page}.html") => &format!(
+                r##"<a href="page{}.html#top"/><a href="missing{}.html"/><a href="#nowhere"/><h1 id="top"/>"##,
+                (page + 1) % 100,
+                page % 7,
+            ));
+        }
+        let check = |jobs| {
+            let mut broken: Vec<_> = files
+                .broken_links(false, jobs, &Progress::default())
+                .into_iter()
+                .map(|broken| (broken.source, broken.href, broken.missing_fragment))
+                .collect();
+            broken.sort();
+            broken
+        };
+        let serial = check(1);
+        assert_eq!(serial.len(), 200);
+        assert_eq!(check(4), serial);
+        assert_eq!(check(1000), serial);
+    }
+
+    #[test]
+    fn test_non_portable_links() {
+        let mut files = html_files!(
+            "index.html" => r#"<a href="Guide/"/><a href="guide\setup.html"/><a href="Logo.PNG"/>"#,
+            "guide/index.html" => "",
+            "guide/setup.html" => ""
+        );
+        files.1.insert("logo.png".into(), Ids::default());
+        assert_eq!(
+            files.case_insensitive_match(Path::new("Guide/")),
+            Some(&PathBuf::from("guide/index.html"))
+        );
+        assert_eq!(
+            files.case_insensitive_match(Path::new("Logo.PNG")),
+            Some(&PathBuf::from("logo.png"))
+        );
+        assert_eq!(
+            files.case_insensitive_match(Path::new("guide/missing.html")),
+            None
+        );
+        let (path, info) = files.0.get_key_value(Path::new("index.html")).unwrap();
+        assert_eq!(
+            document_non_portable_links(path, info),
+            vec![NonPortableLink {
+                source: "index.html".into(),
+                href: "guide\\setup.html".into(),
+                problem: "backslashes are only path separators on Windows".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_html_files_archive() {
+        let archive = Archive {
+            path: "docs.zip".into(),
+            files: BTreeMap::from([
+                ("index.html".into(), br#"<a href="guide/#top"/>"#.to_vec()),
+                ("guide/index.html".into(), br#"<h1 id="top"/>"#.to_vec()),
+                ("guide/logo.png".into(), vec![]),
+                ("node_modules/pkg/index.html".into(), vec![]),
+                ("latin1.html".into(), vec![0xe9]),
+            ]),
+        };
+        let mut filter = FileFilter::new(&[], &[], &["**/node_modules".to_string()]).unwrap();
+        filter.mounts = vec![Mount::new("docs.zip", "/docs")];
+        let mut errors = vec![];
+        let files = HtmlFiles::new(
+            &[archive],
+            &filter,
+            2,
+            &Progress::default(),
+            Some(&mut errors),
+        )
+        .unwrap();
+        assert!(files.contains(&link!("docs/guide#top")));
+        assert!(files.contains(&link!("docs/guide/logo.png")));
+        assert!(!files.contains(&link!("docs/node_modules/pkg/index.html")));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path, PathBuf::from("docs/latin1.html"));
+    }
+
+    #[test]
+    fn test_similar_ids() {
+        let files = html_files!(
+            "guide/index.html" => r#"<h2 id="installing"/><h2 id="install-windows"/><h2 id="usage"/><p id="installing"/>"#
+        );
+        assert_eq!(
+            files.similar_ids(&link!("guide#install")),
+            vec!["installing", "install-windows"]
+        );
+        assert_eq!(files.similar_ids(&link!("guide#zzz")), Vec::<String>::new());
+        assert_eq!(files.similar_ids(&link!("guide")), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_duplicate_documents() {
+        let documents = [
+            (Path::new("b/index.html"), (1, 1)),
+            (Path::new("a/index.html"), (1, 1)),
+            (Path::new("c.html"), (2, 2)),
+            (Path::new("copy/index.html"), (1, 1)),
+            // Shares the first hash by chance, but differs
+            (Path::new("collision.html"), (1, 3)),
+        ];
+        let mut duplicates = duplicate_documents(documents);
+        duplicates.sort_by(|a, b| a.source.cmp(&b.source));
+        assert_eq!(
+            duplicates,
+            vec![
+                DuplicateDocument {
+                    source: "b/index.html".into(),
+                    original: "a/index.html".into(),
+                },
+                DuplicateDocument {
+                    source: "copy/index.html".into(),
+                    original: "a/index.html".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_redirect_chains() {
+        let redirects = [
+            (Path::new("old.html"), PathBuf::from("older/")),
+            (Path::new("older/index.html"), PathBuf::from("new.html")),
+            (Path::new("moved.html"), PathBuf::from("new.html")),
+            (Path::new("ping.html"), PathBuf::from("pong.html")),
+            (Path::new("pong.html"), PathBuf::from("ping.html")),
+            (Path::new("self.html"), PathBuf::from("self.html")),
+        ];
+        let mut chains = redirect_chains(redirects, &Probing::default());
+        chains.sort_by(|a, b| a.source.cmp(&b.source));
+        let chain = |source: &str, hops: &[&str], circular| RedirectChain {
+            source: source.into(),
+            hops: hops.iter().map(PathBuf::from).collect(),
+            circular,
+        };
+        assert_eq!(
+            chains,
+            vec![
+                chain("old.html", &["older/", "new.html"], false),
+                chain("ping.html", &["pong.html", "ping.html"], true),
+                chain("pong.html", &["ping.html", "pong.html"], true),
+                chain("self.html", &["self.html"], true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_walk_documents() {
+        let fixture = Fixture::new();
+        let dir = &fixture.path;
+        for i in 0..20 {
+            fixture.write(format!("sub/{i}.html"), format!(r#"<p id="{i}" />"#));
+        }
+        fixture.write("image.png", "");
+        fixture.write("icons.svg", r#"<svg><symbol id="arrow"/></svg>"#);
+        let mut visited = vec![];
+        let mut errors = vec![];
+        let other_files = walk_documents(
+            std::slice::from_ref(&fixture.path),
+            &FileFilter::default(),
+            3,
+            &Progress::default(),
+            Some(&mut errors),
+            |path, info| visited.push((path, info.ids.to_vec())),
+        )
+        .unwrap();
+        let mut other_files: Vec<_> = (other_files.into_iter())
+            .map(|(path, ids)| (path, ids.to_vec()))
+            .collect();
+        visited.sort();
+        other_files.sort();
+        assert_eq!(errors, vec![]);
+        assert_eq!(
+            other_files,
+            vec![
+                ("icons.svg".into(), vec!["arrow".into()]),
+                ("image.png".into(), vec![])
+            ]
+        );
+        assert_eq!(visited.len(), 20);
+        assert_eq!(visited[0], ("sub/0.html".into(), vec!["0".into()]));
+        let missing = [dir.join("missing")];
+        assert!(walk_documents(
+            &missing,
+            &FileFilter::default(),
+            3,
+            &Progress::default(),
+            None,
+            |_, _| {}
+        )
+        .is_err());
+        walk_documents(
+            &missing,
+            &FileFilter::default(),
+            3,
+            &Progress::default(),
+            Some(&mut errors),
+            |_, _| {},
+        )
+        .unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path, dir.join("missing"));
+    }
+
+    #[test]
+    fn test_walk_documents_ignore_files() {
+        let fixture = Fixture::new();
+        fixture.write(".gitignore", "node_modules\n");
+        fixture.write("guide/.ignore", "draft.html\n");
+        fixture.write("node_modules/pkg/readme.html", "");
+        fixture.write("guide/draft.html", "");
+        fixture.write("guide/index.html", "");
+        fixture.write(".hidden.html", "");
+
+        let walk = |ignore_files| {
+            let mut filter = FileFilter::default();
+            filter.ignore_files = ignore_files;
+            let mut visited = vec![];
+            walk_documents(
+                std::slice::from_ref(&fixture.path),
+                &filter,
+                2,
+                &Progress::default(),
+                None,
+                |path, _| visited.push(path),
+            )
+            .unwrap();
+            visited.sort();
+            visited
+        };
+        assert_eq!(
+            walk(true),
+            vec![
+                PathBuf::from(".hidden.html"),
+                PathBuf::from("guide/index.html")
+            ]
+        );
+        assert_eq!(walk(false).len(), 4);
+    }
+
+    #[test]
+    fn test_walk_documents_max_file_size() {
+        let fixture = Fixture::new();
+        fixture.write("small.html", r#"<p id="a" />"#);
+        fixture.write("dump.html", "x".repeat(100));
+        let mut filter = FileFilter::default();
+        filter.max_file_size = Some(50);
+        let mut visited = vec![];
+        let mut errors = vec![];
+        let other_files = walk_documents(
+            std::slice::from_ref(&fixture.path),
+            &filter,
+            2,
+            &Progress::default(),
+            Some(&mut errors),
+            |path, _| visited.push(path),
+        )
+        .unwrap();
+        assert_eq!(visited, vec![PathBuf::from("small.html")]);
+        assert_eq!(other_files, vec![("dump.html".into(), Ids::default())]);
+        assert_eq!(
+            errors,
+            vec![ReadError {
+                path: "dump.html".into(),
+                message: "larger than the maximum file size of 50 bytes".to_string(),
+                too_large: true,
+            }]
+        );
+        // Without recording errors, files too large are still skipped rather than fatal
+        for jobs in [1, 2] {
+            let other_files = walk_documents(
+                std::slice::from_ref(&fixture.path),
+                &filter,
+                jobs,
+                &Progress::default(),
+                None,
+                |_, _| {},
+            )
+            .unwrap();
+            assert_eq!(other_files, vec![("dump.html".into(), Ids::default())]);
+        }
+    }
+
+    #[test]
+    fn test_walk_documents_max_depth() {
+        let fixture = Fixture::new();
+        fixture.write("index.html", "");
+        fixture.write("api/index.html", "");
+        fixture.write("api/v1/index.html", "");
+        let walk = |max_depth| {
+            let mut filter = FileFilter::default();
+            filter.max_depth = Some(max_depth);
+            let mut visited = vec![];
+            walk_documents(
+                std::slice::from_ref(&fixture.path),
+                &filter,
+                2,
+                &Progress::default(),
+                None,
+                |path, _| visited.push(path),
+            )
+            .unwrap();
+            visited.sort();
+            visited
+        };
+        assert_eq!(walk(0), vec![PathBuf::from("index.html")]);
+        assert_eq!(
+            walk(1),
+            vec![PathBuf::from("api/index.html"), PathBuf::from("index.html")]
+        );
+        assert_eq!(walk(2).len(), 3);
+    }
+
+    #[test]
+    fn test_walk_documents_mounts() {
+        let fixture = Fixture::new();
+        let dir = &fixture.path;
+        fixture.write("docs/index.html", "");
+        fixture.write("blog/index.html", "");
+        fixture.write("blog/photo.png", "");
+        let mut filter = FileFilter::default();
+        filter.mounts = vec![Mount::new(dir.join("blog"), "/news/blog/")];
+        let mut visited = vec![];
+        let other_files = walk_documents(
+            &[dir.join("docs"), dir.join("blog")],
+            &filter,
+            2,
+            &Progress::default(),
+            None,
+            |path, _| visited.push(path),
+        )
+        .unwrap();
+        visited.sort();
+        assert_eq!(
+            visited,
+            vec![
+                PathBuf::from("index.html"),
+                PathBuf::from("news/blog/index.html")
+            ]
+        );
+        assert_eq!(
+            other_files,
+            vec![("news/blog/photo.png".into(), Ids::default())]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_walk_documents_symlinks() {
+        let fixture = Fixture::new();
+        let dir = &fixture.path;
+        fixture.write("real/a.html", "");
+        std::os::unix::fs::symlink(dir.join("real"), dir.join("shared")).unwrap();
+        std::os::unix::fs::symlink(dir, dir.join("real/loop")).unwrap();
+        std::os::unix::fs::symlink(dir.join("real/a.html"), dir.join("b.html")).unwrap();
+
+        let walk = |follow_symlinks| {
+            let mut filter = FileFilter::default();
+            filter.follow_symlinks = follow_symlinks;
+            let mut visited = vec![];
+            let mut errors = vec![];
+            walk_documents(
+                std::slice::from_ref(&fixture.path),
+                &filter,
+                2,
+                &Progress::default(),
+                Some(&mut errors),
+                |path, _| visited.push(path),
+            )
+            .unwrap();
+            assert_eq!(errors, vec![]);
+            visited.sort();
+            visited
+        };
+        assert_eq!(
+            walk(false),
+            vec![PathBuf::from("b.html"), PathBuf::from("real/a.html")]
+        );
+        assert_eq!(
+            walk(true),
+            vec![
+                PathBuf::from("b.html"),
+                PathBuf::from("real/a.html"),
+                PathBuf::from("shared/a.html")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_linked_files_contains() {
+        let fixture = Fixture::new();
+        fixture.write("examples/index.html", r#"<h1 id="top" />"#);
+        fixture.write("examples/nested/a.html", r#"<p id="a" />"#);
+        fixture.write("examples/image.png", "");
+
+        let mut linked = LinkedFiles::new(&fixture.path, FileFilter::default());
+        assert!(linked.contains(&link!("examples/#top"), None).unwrap());
+        assert!(linked
+            .contains(&link!("examples/index.html#top"), None)
+            .unwrap());
+        assert!(!linked.contains(&link!("examples#nope"), None).unwrap());
+        assert!(linked
+            .contains(&link!("examples/nested/a.html#a"), None)
+            .unwrap());
+        assert!(!linked
+            .contains(&link!("examples/nested/a.html#top"), None)
+            .unwrap());
+        assert!(linked.contains(&link!("examples/image.png"), None).unwrap());
+        assert!(!linked
+            .contains(&link!("examples/missing.html"), None)
+            .unwrap());
+        assert_eq!(
+            linked.indexed,
+            HashSet::from(["examples".into(), "examples/nested".into()])
+        );
+        fixture.write("examples/latin1/bad.html", b"caf\xe9");
+        fixture.write("examples/latin1/good.html", "");
+        let mut errors = vec![];
+        assert!(linked
+            .contains(&link!("examples/latin1/good.html"), Some(&mut errors))
+            .unwrap());
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path, PathBuf::from("examples/latin1/bad.html"));
+    }
+
+    #[test]
+    fn test_html_files_loopback_links() {
+        let files = html_files!(
+            "foo" => r#"<a href="http://localhost:8000/foo" /><a href="https://example.com" />"#,
+            "bar" => r#"<meta name="relative-link-check" content="skip"><a href="http://127.0.0.1/" />"#
+        );
+        assert_eq!(
+            files.loopback_links(),
+            vec![LoopbackLink {
+                source: "foo".into(),
+                href: "http://localhost:8000/foo".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_html_files_escaping_links() {
+        let files = html_files!(
+            "index.html" => r#"<a href="../../secrets/config.html" /><a href="/../up.html" /><a href="guide/.." />"#,
+            "guide/intro.html" => r#"<a href="../index.html" /><a href="../../index.html" />"#,
+            "skipped.html" => r#"<meta name="relative-link-check" content="skip"><a href="../nope" />"#
+        );
+        let mut escaping = files.scan(false, 1, &Progress::default()).escaping_links;
+        escaping.sort_by(|a, b| a.href.cmp(&b.href));
+        assert_eq!(
+            escaping,
+            vec![
+                EscapingLink {
+                    source: "guide/intro.html".into(),
+                    href: "../../index.html".into(),
+                },
+                EscapingLink {
+                    source: "index.html".into(),
+                    href: "../../secrets/config.html".into(),
+                },
+                EscapingLink {
+                    source: "index.html".into(),
+                    href: "/../up.html".into(),
+                },
+            ]
+        );
+        // Escaping links aren't also reported as broken
+        assert_eq!(missing_file_links(&files), vec![]);
+        assert!(!escapes_root("guide/../index.html"));
+        assert!(escapes_root("guide/../../index.html"));
+    }
+
     #[test]
     fn test_html_files_missing_file_links() {
         let files = html_files!(
             "foo" => r#"<a href="foo" id="foo" />"#,
-            "/bar" => r#"<a href="/bar" id="bar" />"#,
-            "/baz/index.html" => r#"<a href="/baz" id="baz" />"#
+            "bar" => r#"<a href="/bar" id="bar" />"#,
+            "baz/index.html" => r#"<a href="/baz" id="baz" />"#
+        );
+        assert_eq!(missing_file_links(&files), vec![]);
+
+        let files = html_files!(
+            "foo" => r#"<a href="bar#nope" />"#,
+            "bar" => r#"<a href="foo" />"#
+        );
+        assert_eq!(missing_file_links(&files), vec![link!("bar#nope")]);
+        assert_eq!(
+            files.broken_links(false, 1, &Progress::default())[0].source,
+            PathBuf::from("foo")
+        );
+        assert_eq!(
+            files.broken_links(false, 1, &Progress::default())[0].href,
+            "bar#nope"
+        );
+    }
+
+    #[test]
+    fn test_html_files_missing_file_links_directives() {
+        let files = html_files!(
+            "foo" => r#"<meta name="relative-link-check" content="skip"><a href="nope" />"#,
+            "bar" => r#"<meta name="relative-link-check" content="no-fragments"><a href="baz#nope" />"#,
+            "baz" => r#"<meta name="relative-link-check" content="base=sub"><a href="qux" />"#,
+            "sub/qux" => r#"<a href="../bar" />"#
+        );
+        assert_eq!(missing_file_links(&files), vec![]);
+    }
+
+    #[test]
+    fn test_html_files_missing_file_links_same_page() {
+        let files = html_files!(
+            "guide/intro" => r##"<h2 id="install" /><a href="#install" /><a href="#nope" /><a href="" />"##,
+            "guide/index.html" => r##"<a href="intro#install" />"##
+        );
+        assert_eq!(missing_file_links(&files), vec![link!("guide/intro#nope")]);
+    }
+
+    #[test]
+    fn test_resolve_document_link_matrix() {
+        // (document, href, resolved path, fragment)
+        let cases = [
+            ("guide/intro.html", "", "guide/intro.html", None),
+            ("guide/intro.html", "#", "guide/intro.html", None),
+            (
+                "guide/intro.html",
+                "#setup",
+                "guide/intro.html",
+                Some("setup"),
+            ),
+            ("guide/intro.html", ".", "guide", None),
+            ("guide/intro.html", "./", "guide", None),
+            ("guide/intro.html", "./#setup", "guide", Some("setup")),
+            ("guide/intro.html", ".#setup", "guide", Some("setup")),
+            ("guide/intro.html", "..", "", None),
+            ("guide/intro.html", "../#top", "", Some("top")),
+            ("guide/intro.html", "..#top", "", Some("top")),
+            ("index.html", "", "index.html", None),
+            ("index.html", "#top", "index.html", Some("top")),
+            ("index.html", ".", "", None),
+            ("index.html", "./#top", "", Some("top")),
+        ];
+        for (document, href, path, fragment) in cases {
+            let document = Path::new(document);
+            let link = resolve_document_link(document, document.parent().unwrap(), href);
+            assert_eq!(
+                link,
+                HtmlFileLink {
+                    path: path.into(),
+                    fragment: fragment.map(String::from),
+                    base: LinkBase::Document,
+                },
+                "{href:?} in {document:?}"
+            );
+        }
+        for (href, path, fragment) in [
+            ("/", "", None),
+            ("/#top", "", Some("top")),
+            ("/guide/../index.html", "index.html", None),
+            ("../../../index.html", "index.html", None),
+        ] {
+            let link = resolve_document_link(Path::new("guide/intro.html"), "/guide", href);
+            assert_eq!(
+                link,
+                HtmlFileLink {
+                    path: path.into(),
+                    fragment: fragment.map(String::from),
+                    base: LinkBase::Root,
+                },
+                "{href:?}"
+            );
+        }
+
+        let files = html_files!(
+            "index.html" => r##"<p id="top" /><a href="." /><a href="./#top" /><a href="#top" />"##,
+            "guide/index.html" => r##"<p id="setup" /><a href="." /><a href="#setup" /><a href="" />"##,
+            "guide/intro.html" => r##"<p id="intro" />
+                <a href="" /><a href="#" /><a href="#intro" /><a href="." /><a href="./" />
+                <a href="./#setup" /><a href="..#top" /><a href="../#top" /><a href="./#intro" />"##
+        );
+        assert_eq!(missing_file_links(&files), vec![link!("guide#intro")]);
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Relative paths of documents, like `ab/c/d.html`
+    fn document_path() -> impl Strategy<Value = PathBuf> {
+        (prop::collection::vec("[a-c]{1,2}", 0..3), "[a-c]{1,2}")
+            .prop_map(|(dirs, name)| dirs.iter().collect::<PathBuf>().join(name + ".html"))
+    }
+
+    /// The `href` linking from the document at `from` to the document at `to`
+    fn relative_href(from: &Path, to: &Path) -> String {
+        let from: Vec<_> = from.parent().unwrap().components().collect();
+        let to: Vec<_> = to.components().collect();
+        let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+        let mut href: Vec<String> = vec!["..".to_string(); from.len() - common];
+        href.extend(
+            to[common..]
+                .iter()
+                .map(|c| c.as_os_str().to_string_lossy().to_string()),
         );
-        // TODO make files absolute, don't trim the root off
-        assert_eq!(files.missing_file_links(), vec![]);
-        assert!(false);
+        href.join("/")
+    }
+
+    proptest! {
+        #[test]
+        fn link_parsing_never_panics(href in "(?s)[./#\n]{0,8}|.{0,16}", file_path in document_path()) {
+            let link = HtmlFileLink::new(&href);
+            prop_assert!(link.fragment.as_deref().is_none_or(|f| !f.is_empty()));
+            resolve_document_link(&file_path, file_path.parent().unwrap(), &href);
+        }
+
+        #[test]
+        fn normalization_is_idempotent(path in r"[a-c./\\]{0,12}") {
+            let normalized = SitePath::default().resolve(&path);
+            prop_assert_eq!(SitePath::default().resolve(normalized.as_str()), normalized);
+        }
+
+        #[test]
+        fn links_to_documents_are_never_broken(
+            paths in prop::collection::hash_set(document_path(), 1..8),
+            links in prop::collection::vec((any::<prop::sample::Index>(), any::<prop::sample::Index>(), any::<bool>()), 1..16),
+        ) {
+            let paths: Vec<PathBuf> = paths.into_iter().collect();
+            let mut documents: HashMap<PathBuf, String> =
+                paths.iter().map(|path| (path.clone(), r#"<p id="top">"#.to_string())).collect();
+            for (from, to, fragment) in links {
+                let (from, to) = (from.get(&paths), to.get(&paths));
+                let href = relative_href(from, to) + if fragment { "#top" } else { "" };
+                documents.get_mut(from).unwrap().push_str(&format!(r#"<a href="{href}">"#));
+            }
+            let files = HtmlFiles(
+                documents
+                    .into_iter()
+                    .map(|(path, document)| (path, HtmlInfo::parse(&document)))
+                    .collect(),
+                HashMap::new(),
+                Probing::default(),
+                FragmentMatch::default(),
+            );
+            prop_assert_eq!(files.broken_links(false, 1, &Progress::default()), vec![]);
+        }
     }
 }
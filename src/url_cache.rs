@@ -0,0 +1,162 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::external::UrlStatus;
+
+/// How long cached results are trusted, unless --external-cache-ttl says otherwise
+pub const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Parse a duration like `7d`, `12h`, `30m` or `90s`
+pub fn parse_ttl(ttl: &str) -> Result<Duration, String> {
+    let unit_start = ttl.find(|c: char| !c.is_ascii_digit()).unwrap_or(ttl.len());
+    let (number, unit) = ttl.split_at(unit_start);
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(format!("expected a number of s, m, h, d or w, got {ttl:?}")),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("expected a number of s, m, h, d or w, got {ttl:?}"))?;
+    Ok(Duration::from_secs(number * seconds))
+}
+
+/// Seconds since the Unix epoch
+fn timestamp(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CachedStatus {
+    status: UrlStatus,
+    /// When the URL was requested, in seconds since the Unix epoch
+    checked_at: u64,
+}
+
+/**
+What came of requesting external URLs on earlier runs, kept in an `--external-cache` file so
+that a run only requests the URLs which haven't been checked for `ttl`.
+
+Only URLs which worked are kept, so broken ones are requested again on every run, and are
+reported as fixed as soon as they are. Results older than the `ttl` are dropped when the cache
+is saved.
+ */
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct UrlCache(BTreeMap<String, CachedStatus>);
+
+impl UrlCache {
+    /// Load a cache file. A file that doesn't exist yet is treated as an empty cache.
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<UrlCache> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(UrlCache::default());
+        }
+        let contents = std::fs::read_to_string(path)?;
+        serde_json::from_str(&contents)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let mut contents = serde_json::to_string_pretty(self)?;
+        contents.push('\n');
+        std::fs::write(path, contents)
+    }
+    /// What came of requesting a URL, if it was requested less than `ttl` before `now`
+    pub fn get(&self, url: &str, now: SystemTime, ttl: Duration) -> Option<&UrlStatus> {
+        let cached = self.0.get(url)?;
+        let age = timestamp(now).saturating_sub(cached.checked_at);
+        (age < ttl.as_secs()).then_some(&cached.status)
+    }
+    /// Record what came of requesting URLs at `now`, keeping only those which worked, and
+    /// forget results older than `ttl`
+    pub fn update(
+        &mut self,
+        results: &BTreeMap<String, UrlStatus>,
+        now: SystemTime,
+        ttl: Duration,
+    ) {
+        let checked_at = timestamp(now);
+        for (url, status) in results {
+            if matches!(status, UrlStatus::Ok { .. }) {
+                let status = status.clone();
+                self.0
+                    .insert(url.clone(), CachedStatus { status, checked_at });
+            } else {
+                self.0.remove(url);
+            }
+        }
+        self.0
+            .retain(|_, cached| checked_at.saturating_sub(cached.checked_at) < ttl.as_secs());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixture::Fixture;
+
+    #[test]
+    fn test_parse_ttl() {
+        assert_eq!(parse_ttl("7d"), Ok(Duration::from_secs(7 * 24 * 60 * 60)));
+        assert_eq!(parse_ttl("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_ttl("2w"), Ok(Duration::from_secs(14 * 24 * 60 * 60)));
+        assert!(parse_ttl("7").is_err());
+        assert!(parse_ttl("d").is_err());
+        assert!(parse_ttl("1.5h").is_err());
+    }
+
+    #[test]
+    fn test_url_cache() {
+        let day = Duration::from_secs(24 * 60 * 60);
+        let then = UNIX_EPOCH + day * 1000;
+        let mut cache = UrlCache::default();
+        cache.update(
+            &BTreeMap::from([
+                ("https://a.example/".into(), UrlStatus::Ok { status: 200 }),
+                (
+                    "https://b.example/".into(),
+                    UrlStatus::Broken { status: 404 },
+                ),
+            ]),
+            then,
+            day * 7,
+        );
+        assert_eq!(
+            cache.get("https://a.example/", then + day * 6, day * 7),
+            Some(&UrlStatus::Ok { status: 200 })
+        );
+        assert_eq!(
+            cache.get("https://a.example/", then + day * 7, day * 7),
+            None
+        );
+        assert_eq!(cache.get("https://b.example/", then, day * 7), None);
+
+        let fixture = Fixture::new();
+        let path = fixture.path.join("url-cache.json");
+        assert_eq!(UrlCache::load(&path).unwrap(), UrlCache::default());
+        cache.save(&path).unwrap();
+        let mut loaded = UrlCache::load(&path).unwrap();
+        assert_eq!(loaded, cache);
+        // Once a URL breaks, it's requested again on every run until it works
+        loaded.update(
+            &BTreeMap::from([(
+                "https://a.example/".into(),
+                UrlStatus::Broken { status: 500 },
+            )]),
+            then + day,
+            day * 7,
+        );
+        assert_eq!(loaded, UrlCache::default());
+        // Expired results are forgotten
+        cache.update(&BTreeMap::new(), then + day * 8, day * 7);
+        assert_eq!(cache, UrlCache::default());
+    }
+}
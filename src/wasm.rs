@@ -0,0 +1,27 @@
+use wasm_bindgen::prelude::*;
+
+use crate::archive::Archive;
+use crate::check::{check_site, CheckOptions};
+use crate::filter::FileFilter;
+use crate::report::Format;
+use crate::rules::Rules;
+use crate::text_report::GroupBy;
+
+/**
+Check the site in a zip archive or tarball, told apart by its `name` like `site.zip` or
+`site.tar.gz`, and return its report as JSON, in the format of `--format json`.
+
+This is how pages checking an archive dropped onto them, and Node.js scripts, run the check
+without the `rlc` binary.
+*/
+#[wasm_bindgen(js_name = checkArchive)]
+pub fn check_archive(name: &str, contents: &[u8]) -> Result<String, JsError> {
+    let archive = Archive::from_bytes(name, contents)?;
+    let report = check_site(
+        &[archive],
+        &FileFilter::default(),
+        &Rules::default(),
+        CheckOptions::default(),
+    )?;
+    Ok(report.render(Format::Json, &[], GroupBy::default())?)
+}
@@ -0,0 +1,136 @@
+use std::process::{Command, Output};
+
+use tempfile::TempDir;
+
+/// A site in a temporary directory of its own, removed once it's dropped
+struct Site(TempDir);
+
+impl Site {
+    fn new(files: &[(&str, &[u8])]) -> Site {
+        let root = tempfile::Builder::new().prefix("rlc-").tempdir().unwrap();
+        for (path, contents) in files {
+            let path = root.path().join("site").join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+        Site(root)
+    }
+    /// Run rlc from the directory the site is in
+    fn run(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_rlc"))
+            .current_dir(self.0.path())
+            .args(args)
+            .output()
+            .unwrap()
+    }
+}
+
+#[test]
+fn test_srcset_variants_outside_site() {
+    let site = Site::new(&[
+        (
+            "index.html",
+            b"<img srcset=\"img/a.png 1x, img/a@2x.png 2x\"><img srcset=\"img/b.png 1x\">",
+        ),
+        ("img/a.png", b""),
+        ("img/a@2x.png", b""),
+        ("img/b.png", b""),
+    ]);
+    for low_memory in [&[][..], &["--low-memory"]] {
+        let output = site.run(&[&["site", "--srcset-variants", "1x,2x"][..], low_memory].concat());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(output.status.code(), Some(1), "{stdout}");
+        assert!(
+            !stdout.contains("a.png") && !stdout.contains("a@2x.png"),
+            "{stdout}"
+        );
+        assert!(stdout.contains("2x"), "{stdout}");
+    }
+}
+
+/// Ask `rlc --serve` listening on `address` to check the document at `path`
+fn post_check(address: &str, path: &str) -> serde_json::Value {
+    use std::io::{Read, Write};
+
+    let body = serde_json::json!({ "path": path }).to_string();
+    let mut stream = std::net::TcpStream::connect(address).unwrap();
+    write!(
+        stream,
+        "POST /check HTTP/1.1\r\nHost: {address}\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    serde_json::from_str(body).unwrap()
+}
+
+/// The kind and `href` of each failure, sorted
+fn kinds_and_hrefs(failures: &serde_json::Value) -> Vec<(&str, Option<&str>)> {
+    let mut kinds: Vec<(&str, Option<&str>)> = (failures.as_array().unwrap().iter())
+        .map(|failure| (failure["kind"].as_str().unwrap(), failure["href"].as_str()))
+        .collect();
+    kinds.sort();
+    kinds
+}
+
+#[test]
+fn test_serve_agrees_with_check() {
+    let site = Site::new(&[
+        (
+            "docs/index.html",
+            b"<a href=/docs/guide/>Guide</a> <a href=/docs/gone.html>Gone</a> \
+                  <a href=/assets/logo.png>Logo</a> <a href=/assets/missing.png>Missing</a> \
+                  <a href=guide/setup.md>Setup</a> <a href=guide>Guide</a> \
+                  <img srcset=\"guide/index.html 1x\">",
+        ),
+        ("docs/guide/index.html", b""),
+        ("docs/guide/setup.html", b""),
+        ("assets/logo.png", b""),
+    ]);
+    let args = [
+        "--site-root",
+        "site",
+        "--map-source-links",
+        "--trailing-slash",
+        "require",
+        "--srcset-variants",
+        "1x,2x",
+        "site/docs",
+    ];
+    let output = site.run(&[&args[..], &["--format", "json"]].concat());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let checked = kinds_and_hrefs(&report["failures"]);
+    assert_eq!(
+        checked,
+        vec![
+            ("broken-link", Some("/assets/missing.png")),
+            ("broken-link", Some("/docs/gone.html")),
+            ("broken-link", Some("guide")),
+            ("incomplete-srcset", None),
+        ]
+    );
+
+    let mut server = Command::new(env!("CARGO_BIN_EXE_rlc"))
+        .current_dir(site.0.path())
+        .args([&args[..], &["--serve", "127.0.0.1:0"]].concat())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stderr = std::io::BufReader::new(server.stderr.take().unwrap());
+    let mut line = String::new();
+    while !line.starts_with("Listening on ") {
+        line.clear();
+        assert_ne!(
+            std::io::BufRead::read_line(&mut stderr, &mut line).unwrap(),
+            0
+        );
+    }
+    let address = line.trim().trim_start_matches("Listening on http://");
+    let response = post_check(address, "site/docs/index.html");
+    server.kill().unwrap();
+    server.wait().unwrap();
+    assert_eq!(kinds_and_hrefs(&response["diagnostics"]), checked);
+}
@@ -0,0 +1,53 @@
+//! Going offline can't be undone within a process, so these tests get a test binary of their own
+
+use std::process::Command;
+
+use rlc::external::{agent_builder, go_offline};
+
+#[test]
+fn test_offline_agent() {
+    let agent = agent_builder().build();
+    go_offline();
+    // Not even loopback hosts, or addresses which need no lookup, can be reached
+    for url in ["http://localhost:1/", "http://192.0.2.2:1/"] {
+        let err = agent.get(url).call().unwrap_err();
+        assert!(
+            err.to_string().contains("disabled by --offline"),
+            "{url}: {err}"
+        );
+    }
+}
+
+#[test]
+fn test_offline_conflicts() {
+    let site = tempfile::Builder::new().prefix("rlc-").tempdir().unwrap();
+    std::fs::write(
+        site.path().join("index.html"),
+        "<a href=https://example.com/>",
+    )
+    .unwrap();
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_rlc"))
+            .current_dir(site.path())
+            .args([".", "--offline"])
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    for (args, option) in [
+        (&["--check-external"][..], "check-external"),
+        (&["--serve", "127.0.0.1:0"], "serve"),
+        (
+            &["--output", "webhook=http://192.0.2.2:1/"],
+            "webhook outputs",
+        ),
+    ] {
+        let output = run(args);
+        assert_eq!(output.status.code(), Some(1), "{args:?}");
+        assert_eq!(
+            String::from_utf8_lossy(&output.stderr),
+            format!("offline can't be combined with {option}\n")
+        );
+    }
+    assert!(run(&[]).status.success());
+}